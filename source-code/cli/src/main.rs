@@ -19,6 +19,11 @@ Supported GPU families:\n\
            incl. Intel UHD 620/630/770\n"
 )]
 struct Cli {
+    /// Backend: own the display from a TTY, run nested in a Wayland session,
+    /// or headless with virtual outputs; auto picks whichever fits
+    #[arg(long, value_name = "BACKEND", default_value = "auto")]
+    backend: CliBackend,
+
     /// Force GPU vendor (default: auto-detect)
     #[arg(long, value_name = "VENDOR")]
     gpu: Option<CliGpuVendor>,
@@ -39,13 +44,14 @@ struct Cli {
     #[arg(long)]
     no_vrr: bool,
 
-    /// Force a specific DRM device (e.g. /dev/dri/card1)
-    #[arg(long, value_name = "PATH")]
-    drm_device: Option<std::path::PathBuf>,
+    /// Force a specific GPU: DRM node (e.g. /dev/dri/card1) or PCI address
+    /// (e.g. 0000:03:00.0, see `gameframe gpu-info`)
+    #[arg(long, value_name = "PATH|PCI")]
+    drm_device: Option<String>,
 
-    /// Output scale factor (1.0 = native, 2.0 = HiDPI)
-    #[arg(long, default_value = "1.0", value_name = "FACTOR")]
-    scale: f64,
+    /// Output scale factor (1.0 = native, 2.0 = HiDPI; default: from monitor size)
+    #[arg(long, value_name = "FACTOR")]
+    scale: Option<f64>,
 
     /// Preferred output mode (e.g. 1920x1080@60)
     #[arg(long, value_name = "WxH@HZ")]
@@ -55,10 +61,23 @@ struct Cli {
     #[arg(long)]
     xwayland: bool,
 
+    /// WAYLAND_DISPLAY socket name (default: first free wayland-N)
+    #[arg(long, value_name = "NAME")]
+    socket: Option<String>,
+
+    /// Serve metrics over HTTP, on --metrics=ADDR or 127.0.0.1:9464
+    #[arg(long, value_name = "ADDR", num_args = 0..=1, require_equals = true, default_missing_value = "127.0.0.1:9464")]
+    metrics: Option<String>,
+
     /// Verbosity (-v debug, -vv trace)
     #[arg(short = 'v', action = clap::ArgAction::Count)]
     verbose: u8,
 
+    /// Write a Chrome/Perfetto trace of frames, input and client events
+    /// (default file: gameframe-trace.json)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "gameframe-trace.json")]
+    trace: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -77,6 +96,89 @@ enum Commands {
     Status,
     /// Show detected GPU information
     GpuInfo,
+    /// Show or change display brightness in the running session
+    Brightness {
+        #[command(subcommand)]
+        action: Option<BrightnessAction>,
+    },
+    /// Show each output's presented, late and dropped frame counts
+    Frames,
+    /// Show client buffers held for rendering and their texture memory
+    Buffers,
+    /// Show each client's surfaces, buffers and their texture memory
+    Clients,
+    /// Print outputs, windows, surfaces and focus as JSON
+    Tree,
+    /// Add, remove or list virtual outputs of a headless session, or move
+    /// an output in the layout
+    Output {
+        #[command(subcommand)]
+        action: Option<OutputAction>,
+    },
+    /// Start or stop recording an output to a video file
+    Record {
+        #[command(subcommand)]
+        action: Option<RecordAction>,
+    },
+    /// Hide the focused window from recordings, or show it again
+    Capture {
+        #[command(subcommand)]
+        action: CaptureAction,
+    },
+    /// Bind an input device to a split-screen pane or the focused window;
+    /// without arguments, list the bindings
+    Bind {
+        /// Device name (substring, case-insensitive; see `libinput list-devices`)
+        device: Option<String>,
+        /// Pane 1 (left/top) or 2 (right/bottom)
+        #[arg(long, conflicts_with = "focused")]
+        pane: Option<usize>,
+        /// The window focused right now
+        #[arg(long)]
+        focused: bool,
+    },
+    /// Remove a device binding made with `bind`
+    Unbind { device: String },
+    /// Switch the focus model; without one, print the current
+    Focus { model: Option<CliFocusModel> },
+    /// List, select or clear the clipboard history (`[clipboard] history`)
+    Clipboard {
+        #[command(subcommand)]
+        action: Option<ClipboardAction>,
+    },
+    /// Switch the latency / quality preset; without one, print the current
+    Preset { preset: Option<CliPreset> },
+    /// Hold VRR on or off on an output (`off` hands it back to
+    /// `adaptive_sync`); without arguments, list each output's VRR state
+    Vrr {
+        output: Option<String>,
+        #[arg(value_parser = ["on", "off"], requires = "output")]
+        state:  Option<String>,
+    },
+    /// Cap the frame rate at N fps, `off` to uncap; without one, print the
+    /// current cap
+    FpsCap { fps: Option<String> },
+    /// Show a calibration pattern full-screen: white, black, red, green,
+    /// blue, ramp, gradient, checkerboard or strobe; `next` for the one after
+    /// the current, `off` to go back. Without one, print what is shown
+    TestPattern {
+        pattern: Option<String>,
+        /// Output to show it on (default: the focused one)
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Show or move the pointer
+    Pointer {
+        #[command(subcommand)]
+        action: Option<PointerAction>,
+    },
+    /// Restart the compositor (e.g. after an update), keeping the window
+    /// layout; clients have to reconnect
+    Restart,
+    /// Release the GPU before system sleep
+    Suspend,
+    /// Take the GPU back and repaint after waking up
+    Resume,
     /// Manage configuration
     Config {
         #[command(subcommand)]
@@ -96,6 +198,117 @@ enum ConfigAction {
     Path,
 }
 
+#[derive(Subcommand, Debug)]
+enum BrightnessAction {
+    /// Print each display's brightness in percent [default]
+    Get,
+    /// Set every display to PERCENT
+    Set { percent: u32 },
+    /// One step brighter
+    Up,
+    /// One step dimmer
+    Down,
+}
+
+#[derive(Subcommand, Debug)]
+enum OutputAction {
+    /// Print each virtual output and its mode [default]
+    List,
+    /// Add a virtual output, e.g. 2560x1440@120; prints its name
+    Add { mode: String },
+    /// Remove the virtual output NAME (e.g. HEADLESS-2)
+    Remove { name: String },
+    /// Put the top-left of output NAME (any backend) at X,Y in the layout
+    Move { name: String, x: i32, y: i32 },
+    /// Switch DRM output NAME to one of its modes (WIDTHxHEIGHT[@HZ])
+    Mode { name: String, mode: String },
+    /// How output NAME scales fullscreen games smaller than itself:
+    /// linear, nearest, integer-only or fsr[:SHARPNESS]
+    Upscale { name: String, mode: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum RecordAction {
+    /// Start if not recording, stop otherwise [default]
+    Toggle,
+    /// Record OUTPUT (default: the focused one)
+    Start { output: Option<String> },
+    /// Stop and print the file written
+    Stop,
+}
+
+#[derive(Subcommand, Debug)]
+enum CaptureAction {
+    /// Black the focused window out of recordings
+    Hide,
+    /// Record the focused window normally again
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum ClipboardAction {
+    /// Print the entries, newest (0) first, with the Unix time they were copied [default]
+    List,
+    /// Put entry N back on the clipboard
+    Select { n: usize },
+    /// Forget every entry
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum PointerAction {
+    /// Print the pointer position and the output under it [default]
+    Get,
+    /// Move the pointer to X,Y (global, or relative to --output)
+    Warp {
+        x: f64,
+        y: f64,
+        #[arg(long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum CliBackend {
+    Auto,
+    Drm,
+    Nested,
+    Headless,
+}
+
+impl From<CliBackend> for gameframe_core::BackendKind {
+    fn from(b: CliBackend) -> Self {
+        match b {
+            CliBackend::Auto     => Self::Auto,
+            CliBackend::Drm      => Self::Drm,
+            CliBackend::Nested   => Self::WaylandNested,
+            CliBackend::Headless => Self::Headless,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum CliPreset {
+    /// Tearing and VRR always, no animations or effects
+    Latency,
+    /// Tearing for fullscreen games, VRR for games, animations on
+    Balanced,
+    /// Vsync, animations, blur and shadows
+    Quality,
+    /// The one after the current preset
+    Next,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
+enum CliFocusModel {
+    /// Focus changes only on click
+    Click,
+    /// Focus follows the pointer; empty desktop keeps it
+    Sloppy,
+    /// Focus follows the pointer; empty desktop takes it away
+    Strict,
+}
+
 #[derive(ValueEnum, Clone, Debug, PartialEq, Eq)]
 enum CliGpuVendor {
     Amd,
@@ -120,7 +333,8 @@ impl From<CliGpuVendor> for gameframe_gpu::GpuVendor {
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    init_logging(cli.verbose);
+    // Kept alive until exit; dropping it finishes the trace file.
+    let _trace = init_logging(cli.verbose, cli.trace.as_deref());
 
     info!(
         version = env!("CARGO_PKG_VERSION"),
@@ -133,24 +347,140 @@ async fn main() -> Result<()> {
     if cli.fps_cap > 0           { config.display.fps_cap   = cli.fps_cap; }
     if cli.hdr                   { config.display.hdr        = true; }
     if cli.no_vrr                { config.display.vrr        = false; }
-    if cli.scale != 1.0          { config.display.scale      = cli.scale; }
+    if cli.scale.is_some()       { config.display.scale      = cli.scale; }
     if let Some(m) = &cli.mode   { config.display.preferred_mode = Some(m.clone()); }
     if cli.xwayland              { config.session.xwayland   = true; }
+    if let Some(d) = &cli.drm_device { config.gpu.drm_device = Some(d.clone()); }
+    if let Some(s) = &cli.socket { config.session.socket = Some(s.clone()); }
+    if let Some(a) = &cli.metrics { config.metrics.enabled = true; config.metrics.listen = a.clone(); }
 
     match cli.command.unwrap_or(Commands::Start { exec: None }) {
         Commands::Start { exec } => {
             use gameframe_core::{run_session, session::SessionOptions};
             run_session(SessionOptions {
+                backend:      cli.backend.into(),
                 gpu_vendor:   cli.gpu.map(Into::into),
-                drm_device:   cli.drm_device,
+                drm_device:   config.gpu.drm_device.clone(),
                 initial_exec: exec,
                 config,
+                config_path:  config_path().ok(),
             })
             .await?;
         }
         Commands::Stop   => gameframe_core::stop_session().await?,
         Commands::Status => gameframe_core::print_status().await?,
         Commands::GpuInfo => gameframe_gpu::print_gpu_info()?,
+        Commands::Brightness { action } => {
+            let command = match action.unwrap_or(BrightnessAction::Get) {
+                BrightnessAction::Get           => "brightness get".to_owned(),
+                BrightnessAction::Set { percent } => format!("brightness set {percent}"),
+                BrightnessAction::Up            => "brightness up".to_owned(),
+                BrightnessAction::Down          => "brightness down".to_owned(),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Frames  => print!("{}", gameframe_core::ipc::request("frames")?),
+        Commands::Buffers => print!("{}", gameframe_core::ipc::request("buffers")?),
+        Commands::Clients => print!("{}", gameframe_core::ipc::request("clients")?),
+        Commands::Tree    => print!("{}", gameframe_core::ipc::request("tree")?),
+        Commands::Output { action } => {
+            let command = match action.unwrap_or(OutputAction::List) {
+                OutputAction::List           => "output list".to_owned(),
+                OutputAction::Add { mode }   => format!("output add {mode}"),
+                OutputAction::Remove { name } => format!("output remove {name}"),
+                OutputAction::Move { name, x, y } => format!("output move {name} {x} {y}"),
+                OutputAction::Mode { name, mode } => format!("output mode {name} {mode}"),
+                OutputAction::Upscale { name, mode } => format!("output upscale {name} {mode}"),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Record { action } => {
+            let command = match action.unwrap_or(RecordAction::Toggle) {
+                RecordAction::Toggle                 => "record".to_owned(),
+                RecordAction::Start { output: None } => "record start".to_owned(),
+                RecordAction::Start { output: Some(name) } => format!("record start {name}"),
+                RecordAction::Stop                   => "record stop".to_owned(),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Capture { action } => {
+            let command = match action {
+                CaptureAction::Hide => "capture hide",
+                CaptureAction::Show => "capture show",
+            };
+            print!("{}", gameframe_core::ipc::request(command)?);
+        }
+        Commands::Bind { device, pane, focused } => {
+            let command = match (device, pane) {
+                (None, _)               => "bind".to_owned(),
+                (Some(d), Some(n))      => format!("bind {d} pane {n}"),
+                (Some(d), None) if focused => format!("bind {d} focused"),
+                (Some(_), None)         => anyhow::bail!("give --pane N or --focused"),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Unbind { device } => print!("{}", gameframe_core::ipc::request(&format!("unbind {device}"))?),
+        Commands::Focus { model } => {
+            let command = match model {
+                None                        => "focus",
+                Some(CliFocusModel::Click)  => "focus click",
+                Some(CliFocusModel::Sloppy) => "focus sloppy",
+                Some(CliFocusModel::Strict) => "focus strict",
+            };
+            print!("{}", gameframe_core::ipc::request(command)?);
+        }
+        Commands::Clipboard { action } => {
+            let command = match action.unwrap_or(ClipboardAction::List) {
+                ClipboardAction::List         => "clipboard list".to_owned(),
+                ClipboardAction::Select { n } => format!("clipboard select {n}"),
+                ClipboardAction::Clear        => "clipboard clear".to_owned(),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Preset { preset } => {
+            let command = match preset {
+                None                      => "preset",
+                Some(CliPreset::Latency)  => "preset latency",
+                Some(CliPreset::Balanced) => "preset balanced",
+                Some(CliPreset::Quality)  => "preset quality",
+                Some(CliPreset::Next)     => "preset next",
+            };
+            print!("{}", gameframe_core::ipc::request(command)?);
+        }
+        Commands::Vrr { output, state } => {
+            let command = match (output, state) {
+                (Some(output), Some(state)) => format!("vrr {output} {state}"),
+                (Some(_), None)             => anyhow::bail!("give on or off"),
+                (None, _)                   => "vrr".to_owned(),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::FpsCap { fps } => {
+            let command = match fps {
+                None      => "fps-cap".to_owned(),
+                Some(fps) => format!("fps-cap {fps}"),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::TestPattern { pattern, output } => {
+            let command = match (pattern, output) {
+                (None, _)             => "test-pattern".to_owned(),
+                (Some(p), None)       => format!("test-pattern {p}"),
+                (Some(p), Some(name)) => format!("test-pattern {p} {name}"),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Pointer { action } => {
+            let command = match action.unwrap_or(PointerAction::Get) {
+                PointerAction::Get => "pointer".to_owned(),
+                PointerAction::Warp { x, y, output: None }       => format!("pointer warp {x} {y}"),
+                PointerAction::Warp { x, y, output: Some(name) } => format!("pointer warp {name} {x} {y}"),
+            };
+            print!("{}", gameframe_core::ipc::request(&command)?);
+        }
+        Commands::Restart => print!("{}", gameframe_core::ipc::request("restart")?),
+        Commands::Suspend => print!("{}", gameframe_core::ipc::request("suspend")?),
+        Commands::Resume  => print!("{}", gameframe_core::ipc::request("resume")?),
         Commands::Config { action } => handle_config_action(action)?,
     }
 
@@ -206,19 +536,32 @@ fn handle_config_action(action: ConfigAction) -> Result<()> {
 
 // ── Logging ───────────────────────────────────────────────────────────────────
 
-fn init_logging(verbose: u8) {
+fn init_logging(verbose: u8, trace: Option<&std::path::Path>) -> Option<tracing_chrome::FlushGuard> {
+    use tracing_subscriber::prelude::*;
+
     let level = match verbose {
         0 => "gameframe=info",
         1 => "gameframe=debug",
         _ => "gameframe=trace,smithay=debug",
     };
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(level)),
-        )
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(level));
+    let fmt = tracing_subscriber::fmt::layer()
         .with_target(true)
         .with_thread_ids(false)
-        .compact()
-        .init();
+        .compact();
+    // Spans (render_output, vblank, input, …) become slices on a timeline;
+    // open the file in ui.perfetto.dev or chrome://tracing.
+    let (chrome, guard) = match trace {
+        Some(path) => {
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .file(path)
+                .include_args(true)
+                .build();
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry().with(filter).with(fmt).with(chrome).init();
+    guard
 }
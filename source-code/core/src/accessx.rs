@@ -0,0 +1,162 @@
+use std::{collections::HashMap, time::Duration};
+
+use smithay::{input::keyboard::Keycode, reexports::calloop::RegistrationToken};
+use tracing::info;
+
+use crate::config::AccessibilityConfig;
+
+/// evdev codes of Ctrl, Shift, Alt and Super (left and right).
+const MODIFIER_KEYS: [u32; 8] = [29, 97, 42, 54, 56, 100, 125, 126];
+
+fn is_modifier(key: Keycode) -> bool {
+    // xkb keycodes are evdev codes offset by 8.
+    MODIFIER_KEYS.contains(&key.raw().saturating_sub(8))
+}
+
+/// What to do with a key event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Deliver,
+    Drop,
+    /// Slow keys: hold the press back; deliver it if the key is still down
+    /// after `slow_keys` (see `AccessX::slow_key_due`).
+    Defer(Duration),
+}
+
+/// AccessX keyboard filters, applied before keybindings and clients:
+///
+/// * sticky keys: a modifier pressed and released on its own stays down
+///   until the next non-modifier key is released; pressing it again while
+///   latched releases it,
+/// * slow keys: a key only registers once held for `slow_keys_ms`,
+/// * bounce keys: a key pressed again within `bounce_keys_ms` of its release
+///   is ignored.
+pub struct AccessX {
+    active:    bool,
+    sticky:    bool,
+    slow:      Option<Duration>,
+    bounce:    Option<u32>,
+    /// Modifiers physically down, and whether another key was pressed since.
+    held_mods: Vec<(Keycode, bool)>,
+    latched:   Vec<Keycode>,
+    /// Latched modifiers pressed again; their release unlatches them.
+    unlatching: Vec<Keycode>,
+    /// Presses swallowed by bounce keys, whose release is swallowed too.
+    bounced:   Vec<Keycode>,
+    last_release: HashMap<Keycode, u32>,
+    /// Slow-key presses waiting for their timer.
+    pending:   HashMap<Keycode, RegistrationToken>,
+}
+
+impl AccessX {
+    pub fn new(config: &AccessibilityConfig) -> Self {
+        let mut this = Self {
+            active: false, sticky: false, slow: None, bounce: None,
+            held_mods: Vec::new(), latched: Vec::new(), unlatching: Vec::new(), bounced: Vec::new(),
+            last_release: HashMap::new(), pending: HashMap::new(),
+        };
+        this.configure(config);
+        this
+    }
+
+    /// Apply (possibly reloaded) settings. Active if any filter is enabled.
+    pub fn configure(&mut self, config: &AccessibilityConfig) {
+        self.sticky = config.sticky_keys;
+        self.slow   = (config.slow_keys_ms > 0).then(|| Duration::from_millis(config.slow_keys_ms as u64));
+        self.bounce = (config.bounce_keys_ms > 0).then_some(config.bounce_keys_ms);
+        self.active = self.sticky || self.slow.is_some() || self.bounce.is_some();
+    }
+
+    /// Turn the filters off or back on (as configured). Returns latched
+    /// modifiers the caller must release.
+    pub fn toggle(&mut self) -> Vec<Keycode> {
+        self.active = !self.active;
+        info!(active = self.active, "Accessibility keys");
+        if self.active { Vec::new() } else { self.reset() }
+    }
+
+    fn reset(&mut self) -> Vec<Keycode> {
+        self.held_mods.clear();
+        self.unlatching.clear();
+        self.bounced.clear();
+        std::mem::take(&mut self.latched)
+    }
+
+    /// Decide on a physical key event at `time` (ms). Also returns latched
+    /// modifiers to release after delivering this event.
+    pub fn filter(&mut self, key: Keycode, pressed: bool, time: u32) -> (Verdict, Vec<Keycode>) {
+        if !self.active { return (Verdict::Deliver, Vec::new()); }
+        if pressed {
+            let bounced = self.bounce
+                .zip(self.last_release.get(&key))
+                .is_some_and(|(window, &at)| time.wrapping_sub(at) < window);
+            if bounced {
+                self.bounced.push(key);
+                return (Verdict::Drop, Vec::new());
+            }
+            if let Some(delay) = self.slow {
+                return (Verdict::Defer(delay), Vec::new());
+            }
+            (self.pressed(key), Vec::new())
+        } else {
+            self.last_release.insert(key, time);
+            if let Some(i) = self.bounced.iter().position(|&k| k == key) {
+                self.bounced.swap_remove(i);
+                return (Verdict::Drop, Vec::new());
+            }
+            self.released(key)
+        }
+    }
+
+    /// Remember the timer of a deferred slow-key press.
+    pub fn slow_key_pending(&mut self, key: Keycode, token: RegistrationToken) { self.pending.insert(key, token); }
+
+    /// Released before its slow-key delay ran out: returns the timer to
+    /// cancel; the release itself is then dropped by `filter`'s caller.
+    pub fn cancel_slow_key(&mut self, key: Keycode) -> Option<RegistrationToken> { self.pending.remove(&key) }
+
+    /// A slow-key timer fired: the press registers now.
+    pub fn slow_key_due(&mut self, key: Keycode) -> Verdict {
+        self.pending.remove(&key);
+        self.pressed(key)
+    }
+
+    fn pressed(&mut self, key: Keycode) -> Verdict {
+        if !self.sticky { return Verdict::Deliver; }
+        if is_modifier(key) {
+            if self.latched.contains(&key) {
+                // Already down for the client; the release unlatches it.
+                self.unlatching.push(key);
+                return Verdict::Drop;
+            }
+            self.held_mods.push((key, false));
+        } else {
+            self.held_mods.iter_mut().for_each(|(_, used)| *used = true);
+        }
+        Verdict::Deliver
+    }
+
+    fn released(&mut self, key: Keycode) -> (Verdict, Vec<Keycode>) {
+        if !self.sticky { return (Verdict::Deliver, Vec::new()); }
+        if !is_modifier(key) {
+            return (Verdict::Deliver, std::mem::take(&mut self.latched));
+        }
+        if let Some(i) = self.unlatching.iter().position(|&k| k == key) {
+            self.unlatching.swap_remove(i);
+            self.latched.retain(|&k| k != key);
+            return (Verdict::Deliver, Vec::new());
+        }
+        match self.held_mods.iter().position(|&(k, _)| k == key) {
+            Some(i) if !self.held_mods[i].1 => {
+                self.held_mods.swap_remove(i);
+                self.latched.push(key);
+                (Verdict::Drop, Vec::new())
+            }
+            Some(i) => {
+                self.held_mods.swap_remove(i);
+                (Verdict::Deliver, Vec::new())
+            }
+            None => (Verdict::Deliver, Vec::new()),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                surface::WaylandSurfaceRenderElement, texture::TextureBuffer, AsRenderElements,
+            },
+            gles::{GlesRenderer, GlesTexture},
+            utils::draw_render_elements,
+            Bind, Color32F, Frame, Offscreen, Renderer,
+        },
+    },
+    desktop::Window,
+    utils::{Logical, Point, Rectangle, Scale, Transform},
+};
+
+use crate::{config::AnimationConfig, workspace::StashedWindow};
+
+/// Scale a window starts from when opening / shrinks to when closing.
+const WINDOW_SCALE_FROM: f64 = 0.9;
+
+// ── Animation clock ───────────────────────────────────────────────────────────
+
+/// A single eased 0 → 1 transition on the compositor clock.
+#[derive(Debug, Clone, Copy)]
+pub struct Animation {
+    start:    Duration,
+    duration: Duration,
+}
+
+impl Animation {
+    pub fn new(start: Duration, duration: Duration) -> Self { Self { start, duration } }
+
+    /// Eased progress in `0.0..=1.0` (ease-out cubic).
+    pub fn progress(&self, now: Duration) -> f64 {
+        if self.duration.is_zero() { return 1.0; }
+        let t = (now.saturating_sub(self.start).as_secs_f64() / self.duration.as_secs_f64()).min(1.0);
+        1.0 - (1.0 - t).powi(3)
+    }
+
+    pub fn is_done(&self, now: Duration) -> bool { now >= self.start + self.duration }
+}
+
+/// Alpha and scale of a window for open-animation progress `p`
+/// (close animations run the same curve backwards).
+pub fn window_effect(p: f64) -> (f32, f64) {
+    (p as f32, WINDOW_SCALE_FROM + (1.0 - WINDOW_SCALE_FROM) * p)
+}
+
+// ── Window animations ─────────────────────────────────────────────────────────
+
+/// A window that has been unmapped but is still fading out, drawn from a
+/// snapshot taken just before its surface went away.
+pub struct ClosingWindow {
+    pub snapshot:  TextureBuffer<GlesTexture>,
+    /// Global logical rectangle the snapshot covers.
+    pub geometry:  Rectangle<i32, Logical>,
+    pub animation: Animation,
+}
+
+/// An in-progress workspace switch. The outgoing windows are no longer in the
+/// space, so they are kept here and drawn sliding out next to the incoming
+/// workspace.
+pub struct WorkspaceSlide {
+    pub outgoing:  Vec<StashedWindow>,
+    /// +1 slides towards a higher-numbered workspace (content moves left).
+    pub direction: i32,
+    pub animation: Animation,
+}
+
+impl WorkspaceSlide {
+    /// Horizontal offsets (as a fraction of the output width) of the
+    /// outgoing and incoming workspaces.
+    pub fn offsets(&self, now: Duration) -> (f64, f64) {
+        let p   = self.animation.progress(now);
+        let dir = self.direction as f64;
+        (-dir * p, dir * (1.0 - p))
+    }
+}
+
+/// Open/close and workspace-switch animation bookkeeping. Animations advance
+/// only when an output repaints; `render_output` queues another repaint while
+/// any is running.
+pub struct Animations {
+    enabled:  bool,
+    duration: Duration,
+    /// Windows waiting for their first buffer (`None`) or fading in.
+    opening:  HashMap<Window, Option<Animation>>,
+    closing:  Vec<ClosingWindow>,
+    /// `None` disables the workspace slide.
+    slide_duration: Option<Duration>,
+    slide:    Option<WorkspaceSlide>,
+}
+
+impl Animations {
+    pub fn new(config: &AnimationConfig) -> Self {
+        Self {
+            enabled:  config.enabled,
+            duration: Duration::from_millis(config.duration_ms as u64),
+            opening:  HashMap::new(),
+            closing:  Vec::new(),
+            slide_duration: (config.enabled && config.workspace_slide)
+                .then(|| Duration::from_millis(config.workspace_duration_ms as u64)),
+            slide:    None,
+        }
+    }
+
+    pub fn enabled(&self) -> bool { self.enabled }
+    pub fn duration(&self) -> Duration { self.duration }
+
+    /// A toplevel was created; its fade-in starts with its first buffer.
+    pub fn window_mapped(&mut self, window: &Window) {
+        if self.enabled {
+            self.opening.insert(window.clone(), None);
+        }
+    }
+
+    /// Start the fade-in of `window` if it was waiting for content.
+    pub fn window_committed(&mut self, window: &Window, now: Duration) {
+        if let Some(anim @ None) = self.opening.get_mut(window) {
+            if !window.bbox().is_empty() {
+                *anim = Some(Animation::new(now, self.duration));
+            }
+        }
+    }
+
+    /// A toplevel was destroyed before it was ever shown.
+    pub fn window_dropped(&mut self, window: &Window) { self.opening.remove(window); }
+
+    pub fn window_closed(&mut self, window: &Window, snapshot: TextureBuffer<GlesTexture>, geometry: Rectangle<i32, Logical>, now: Duration) {
+        self.opening.remove(window);
+        self.closing.push(ClosingWindow { snapshot, geometry, animation: Animation::new(now, self.duration) });
+    }
+
+    /// Open-animation progress of `window`, or `None` if it isn't animating.
+    /// Windows still waiting for their first buffer report `0.0`.
+    pub fn open_progress(&self, window: &Window, now: Duration) -> Option<f64> {
+        self.opening.get(window).map(|a| a.map_or(0.0, |a| a.progress(now)))
+    }
+
+    pub fn closing(&self) -> &[ClosingWindow] { &self.closing }
+
+    /// Slide from `outgoing` to the newly mapped workspace. A switch during a
+    /// running slide restarts it from the new pair of workspaces.
+    pub fn workspace_switched(&mut self, outgoing: Vec<StashedWindow>, direction: i32, now: Duration) {
+        if let Some(duration) = self.slide_duration {
+            self.slide = Some(WorkspaceSlide { outgoing, direction, animation: Animation::new(now, duration) });
+        }
+    }
+
+    pub fn workspace_slide(&self) -> Option<&WorkspaceSlide> { self.slide.as_ref() }
+
+    /// Drop finished animations. Returns whether any is still running.
+    pub fn tick(&mut self, now: Duration) -> bool {
+        self.opening.retain(|_, a| !a.is_some_and(|a| a.is_done(now)));
+        self.closing.retain(|c| !c.animation.is_done(now));
+        if self.slide.as_ref().is_some_and(|s| s.animation.is_done(now)) {
+            self.slide = None;
+        }
+        self.opening.values().any(Option::is_some) || !self.closing.is_empty() || self.slide.is_some()
+    }
+}
+
+/// Render `window` (at `scale`) into an offscreen texture so it can keep
+/// being drawn after its client destroys it.
+pub fn snapshot_window(
+    renderer: &mut GlesRenderer,
+    window:   &Window,
+    scale:    f64,
+) -> Result<TextureBuffer<GlesTexture>> {
+    let bbox  = window.bbox();
+    let scale = Scale::from(scale);
+    let size  = bbox.size.to_physical_precise_round(scale);
+    let elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> = window.render_elements(
+        renderer,
+        (-bbox.loc).to_physical_precise_round(scale),
+        scale,
+        1.0,
+    );
+
+    let mut texture: GlesTexture = renderer
+        .create_buffer(Fourcc::Abgr8888, size.to_logical(1).to_buffer(1, Transform::Normal))
+        .map_err(|e| anyhow!("snapshot buffer: {e:?}"))?;
+    {
+        let damage = [Rectangle::from_size(size)];
+        let mut fb = renderer.bind(&mut texture).map_err(|e| anyhow!("snapshot bind: {e:?}"))?;
+        let mut frame = renderer
+            .render(&mut fb, size, Transform::Normal)
+            .map_err(|e| anyhow!("snapshot render: {e:?}"))?;
+        frame.clear(Color32F::TRANSPARENT, &damage).map_err(|e| anyhow!("snapshot clear: {e:?}"))?;
+        draw_render_elements(&mut frame, scale, &elements, &damage)
+            .map_err(|e| anyhow!("snapshot draw: {e:?}"))?;
+        let _ = frame.finish().map_err(|e| anyhow!("snapshot finish: {e:?}"))?;
+    }
+    Ok(TextureBuffer::from_texture(renderer, texture, 1, Transform::Normal, None))
+}
+
+/// Global logical location of the top-left of a window's bounding box.
+pub fn bbox_origin(window: &Window, location: Point<i32, Logical>) -> Point<i32, Logical> {
+    location - window.geometry().loc + window.bbox().loc
+}
@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    time::{Duration, Instant},
+};
+
+use smithay::reexports::wayland_server::backend::{ClientId, DisconnectReason};
+use tracing::{debug, warn};
+
+use crate::state::GameframeState;
+
+/// How often a flush is retried while a client's socket is full.
+pub const FLUSH_RETRY: Duration = Duration::from_millis(10);
+
+/// How long a client may leave its socket full before it is disconnected.
+const MAX_STALL: Duration = Duration::from_secs(5);
+
+/// Clients whose socket was full at the last flush, and since when.
+///
+/// Events that don't fit stay queued in the client's outgoing buffer and
+/// are written on the next flush, so a client that is only slow (a game
+/// busy loading) catches up by itself. One that stopped reading altogether
+/// would otherwise hold that buffer forever; it is disconnected once it has
+/// been stalled for `MAX_STALL`.
+#[derive(Debug, Default)]
+pub struct Backlog {
+    stalled: HashMap<ClientId, Instant>,
+}
+
+impl Backlog {
+    pub fn is_empty(&self) -> bool { self.stalled.is_empty() }
+}
+
+/// Write out what is queued for every client. A full socket is not an
+/// error; any other failure (the client hung up) drops the client.
+pub fn flush_clients(state: &mut GameframeState) {
+    let mut handle  = state.display_handle.backend_handle();
+    let clients: Vec<ClientId> = handle.all_clients().collect();
+    let backlog = &mut state.backlog;
+    backlog.stalled.retain(|id, _| clients.contains(id));
+
+    let now = Instant::now();
+    for id in clients {
+        match handle.flush(Some(id.clone())) {
+            Ok(()) => {
+                if let Some(since) = backlog.stalled.remove(&id) {
+                    debug!(client = ?id, stalled = ?now - since, "client caught up");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                let since = *backlog.stalled.entry(id.clone()).or_insert(now);
+                if now - since >= MAX_STALL {
+                    warn!(client = ?id, "client has not read its socket for {MAX_STALL:?}, disconnecting");
+                    backlog.stalled.remove(&id);
+                    handle.kill_client(id, DisconnectReason::ConnectionClosed);
+                }
+            }
+            Err(e) => {
+                debug!(client = ?id, "flush: {e}, disconnecting");
+                backlog.stalled.remove(&id);
+                handle.kill_client(id, DisconnectReason::ConnectionClosed);
+            }
+        }
+    }
+}
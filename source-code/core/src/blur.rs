@@ -0,0 +1,301 @@
+use std::{ffi::CString, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use smithay::{
+    backend::renderer::{
+        element::{Element, Id, Kind, RenderElement},
+        gles::{ffi, GlesError, GlesFrame, GlesRenderer},
+        utils::{CommitCounter, DamageSet, OpaqueRegions},
+        Frame,
+    },
+    utils::{Buffer, Physical, Rectangle, Scale, Size},
+};
+use tracing::warn;
+
+// ── Shaders ───────────────────────────────────────────────────────────────────
+
+pub(crate) const VERTEX_SRC: &str = r#"#version 100
+attribute vec2 pos;
+varying vec2 uv;
+void main() {
+    uv = pos * 0.5 + 0.5;
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
+"#;
+
+/// Dual-Kawase downsample: 5 taps, output is half the input size.
+const DOWN_SRC: &str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D tex;
+uniform vec2 halfpixel;
+uniform float offset;
+void main() {
+    vec4 sum = texture2D(tex, uv) * 4.0;
+    sum += texture2D(tex, uv - halfpixel * offset);
+    sum += texture2D(tex, uv + halfpixel * offset);
+    sum += texture2D(tex, uv + vec2(halfpixel.x, -halfpixel.y) * offset);
+    sum += texture2D(tex, uv - vec2(halfpixel.x, -halfpixel.y) * offset);
+    gl_FragColor = sum / 8.0;
+}
+"#;
+
+/// Dual-Kawase upsample: 8 taps, output is twice the input size.
+const UP_SRC: &str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D tex;
+uniform vec2 halfpixel;
+uniform float offset;
+void main() {
+    vec4 sum = texture2D(tex, uv + vec2(-halfpixel.x * 2.0, 0.0) * offset);
+    sum += texture2D(tex, uv + vec2(-halfpixel.x, halfpixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(0.0, halfpixel.y * 2.0) * offset);
+    sum += texture2D(tex, uv + vec2(halfpixel.x, halfpixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(halfpixel.x * 2.0, 0.0) * offset);
+    sum += texture2D(tex, uv + vec2(halfpixel.x, -halfpixel.y) * offset) * 2.0;
+    sum += texture2D(tex, uv + vec2(0.0, -halfpixel.y * 2.0) * offset);
+    sum += texture2D(tex, uv + vec2(-halfpixel.x, -halfpixel.y) * offset) * 2.0;
+    gl_FragColor = sum / 12.0;
+}
+"#;
+
+pub(crate) const QUAD: [f32; 8] = [-1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0];
+
+struct Program {
+    id:        ffi::types::GLuint,
+    pos:       ffi::types::GLint,
+    tex:       ffi::types::GLint,
+    halfpixel: ffi::types::GLint,
+    offset:    ffi::types::GLint,
+}
+
+/// Compiled blur programs, one set per GL context.
+pub struct BlurShader {
+    down: Program,
+    up:   Program,
+}
+
+/// The blur programs for `renderer`'s context, compiled on first use. `None`
+/// if compilation failed (logged once; blur is then skipped).
+pub fn blur_shader(renderer: &mut GlesRenderer) -> Option<Rc<BlurShader>> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<Option<Rc<BlurShader>>>() {
+        return cached.clone();
+    }
+    let shader = match renderer.with_context(|gl| unsafe { BlurShader::compile(gl) }) {
+        Ok(Ok(s))  => Some(Rc::new(s)),
+        Ok(Err(e)) => { warn!("blur shader: {e:#}"); None }
+        Err(e)     => { warn!("blur shader: {e:?}"); None }
+    };
+    renderer.egl_context().user_data().insert_if_missing(|| shader.clone());
+    shader
+}
+
+impl BlurShader {
+    unsafe fn compile(gl: &ffi::Gles2) -> Result<Self> {
+        Ok(Self {
+            down: link(gl, DOWN_SRC)?,
+            up:   link(gl, UP_SRC)?,
+        })
+    }
+
+    /// Blur the `rect` region (framebuffer pixels) of the bound framebuffer in
+    /// place: copy it out, run `passes` downsample and upsample passes, and
+    /// draw the result back. All GL state Smithay relies on is restored.
+    unsafe fn blur(&self, gl: &ffi::Gles2, rect: Rectangle<i32, Physical>, passes: u32, offset: f32) {
+        let (w, h) = (rect.size.w, rect.size.h);
+        if w <= 0 || h <= 0 { return; }
+
+        let mut prev_fbo = 0;
+        let mut prev_program = 0;
+        let mut prev_array = 0;
+        let mut prev_tex = 0;
+        let mut viewport = [0; 4];
+        gl.GetIntegerv(ffi::FRAMEBUFFER_BINDING, &mut prev_fbo);
+        gl.GetIntegerv(ffi::CURRENT_PROGRAM, &mut prev_program);
+        gl.GetIntegerv(ffi::ARRAY_BUFFER_BINDING, &mut prev_array);
+        gl.ActiveTexture(ffi::TEXTURE0);
+        gl.GetIntegerv(ffi::TEXTURE_BINDING_2D, &mut prev_tex);
+        gl.GetIntegerv(ffi::VIEWPORT, viewport.as_mut_ptr());
+        let scissor = gl.IsEnabled(ffi::SCISSOR_TEST) == ffi::TRUE;
+        let blend   = gl.IsEnabled(ffi::BLEND) == ffi::TRUE;
+
+        // Level 0 is a copy of the region, each further level half the size.
+        let mut sizes = vec![(w, h)];
+        for _ in 0..passes.max(1) {
+            let (pw, ph) = *sizes.last().unwrap();
+            sizes.push(((pw / 2).max(1), (ph / 2).max(1)));
+        }
+        let n = sizes.len();
+        let mut texs = vec![0; n];
+        let mut fbos = vec![0; n];
+        gl.GenTextures(n as i32, texs.as_mut_ptr());
+        gl.GenFramebuffers(n as i32, fbos.as_mut_ptr());
+        for i in 0..n {
+            gl.BindTexture(ffi::TEXTURE_2D, texs[i]);
+            gl.TexImage2D(
+                ffi::TEXTURE_2D, 0, ffi::RGBA as i32, sizes[i].0, sizes[i].1, 0,
+                ffi::RGBA, ffi::UNSIGNED_BYTE, std::ptr::null(),
+            );
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MIN_FILTER, ffi::LINEAR as i32);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MAG_FILTER, ffi::LINEAR as i32);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            gl.BindFramebuffer(ffi::FRAMEBUFFER, fbos[i]);
+            gl.FramebufferTexture2D(ffi::FRAMEBUFFER, ffi::COLOR_ATTACHMENT0, ffi::TEXTURE_2D, texs[i], 0);
+        }
+
+        gl.BindFramebuffer(ffi::FRAMEBUFFER, prev_fbo as u32);
+        gl.BindTexture(ffi::TEXTURE_2D, texs[0]);
+        gl.CopyTexSubImage2D(ffi::TEXTURE_2D, 0, 0, 0, rect.loc.x, rect.loc.y, w, h);
+
+        gl.Disable(ffi::SCISSOR_TEST);
+        gl.Disable(ffi::BLEND);
+        gl.BindBuffer(ffi::ARRAY_BUFFER, 0);
+
+        for i in 1..n {
+            self.pass(gl, &self.down, texs[i - 1], sizes[i], fbos[i], (0, 0), offset);
+        }
+        for i in (1..n - 1).rev() {
+            self.pass(gl, &self.up, texs[i + 1], sizes[i], fbos[i], (0, 0), offset);
+        }
+        if scissor { gl.Enable(ffi::SCISSOR_TEST); }
+        self.pass(gl, &self.up, texs[1], (w, h), prev_fbo as u32, (rect.loc.x, rect.loc.y), offset);
+
+        gl.DeleteFramebuffers(n as i32, fbos.as_ptr());
+        gl.DeleteTextures(n as i32, texs.as_ptr());
+
+        gl.BindFramebuffer(ffi::FRAMEBUFFER, prev_fbo as u32);
+        gl.Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        gl.UseProgram(prev_program as u32);
+        gl.BindBuffer(ffi::ARRAY_BUFFER, prev_array as u32);
+        gl.BindTexture(ffi::TEXTURE_2D, prev_tex as u32);
+        if blend { gl.Enable(ffi::BLEND); }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn pass(
+        &self,
+        gl:     &ffi::Gles2,
+        prog:   &Program,
+        src:    ffi::types::GLuint,
+        size:   (i32, i32),
+        fbo:    ffi::types::GLuint,
+        origin: (i32, i32),
+        offset: f32,
+    ) {
+        gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+        gl.Viewport(origin.0, origin.1, size.0, size.1);
+        gl.UseProgram(prog.id);
+        gl.BindTexture(ffi::TEXTURE_2D, src);
+        gl.Uniform1i(prog.tex, 0);
+        gl.Uniform2f(prog.halfpixel, 0.5 / size.0 as f32, 0.5 / size.1 as f32);
+        gl.Uniform1f(prog.offset, offset);
+        gl.EnableVertexAttribArray(prog.pos as u32);
+        gl.VertexAttribPointer(prog.pos as u32, 2, ffi::FLOAT, ffi::FALSE, 0, QUAD.as_ptr().cast());
+        gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+        gl.DisableVertexAttribArray(prog.pos as u32);
+    }
+}
+
+pub(crate) unsafe fn compile(gl: &ffi::Gles2, kind: ffi::types::GLenum, src: &str) -> Result<ffi::types::GLuint> {
+    let shader = gl.CreateShader(kind);
+    let src    = CString::new(src)?;
+    gl.ShaderSource(shader, 1, &src.as_ptr(), std::ptr::null());
+    gl.CompileShader(shader);
+    let mut ok = 0;
+    gl.GetShaderiv(shader, ffi::COMPILE_STATUS, &mut ok);
+    if ok == 0 {
+        gl.DeleteShader(shader);
+        return Err(anyhow!("shader compilation failed"));
+    }
+    Ok(shader)
+}
+
+unsafe fn link(gl: &ffi::Gles2, fragment: &str) -> Result<Program> {
+    let vs = compile(gl, ffi::VERTEX_SHADER, VERTEX_SRC)?;
+    let fs = compile(gl, ffi::FRAGMENT_SHADER, fragment)?;
+    let id = gl.CreateProgram();
+    gl.AttachShader(id, vs);
+    gl.AttachShader(id, fs);
+    gl.LinkProgram(id);
+    gl.DetachShader(id, vs);
+    gl.DetachShader(id, fs);
+    gl.DeleteShader(vs);
+    gl.DeleteShader(fs);
+    let mut ok = 0;
+    gl.GetProgramiv(id, ffi::LINK_STATUS, &mut ok);
+    if ok == 0 {
+        gl.DeleteProgram(id);
+        return Err(anyhow!("program link failed"));
+    }
+    let loc = |name: &str| {
+        let name = CString::new(name).unwrap();
+        (gl.GetAttribLocation(id, name.as_ptr()), gl.GetUniformLocation(id, name.as_ptr()))
+    };
+    Ok(Program {
+        id,
+        pos:       loc("pos").0,
+        tex:       loc("tex").1,
+        halfpixel: loc("halfpixel").1,
+        offset:    loc("offset").1,
+    })
+}
+
+// ── Render element ────────────────────────────────────────────────────────────
+
+/// Blurs whatever has been drawn beneath `geometry` so far. Placed directly
+/// behind a translucent window; it reports its whole area as damaged every
+/// frame because its content depends on everything below it.
+pub struct BlurElement {
+    id:          Id,
+    geometry:    Rectangle<i32, Physical>,
+    /// Size of the output in output (transformed) space.
+    output_size: Size<i32, Physical>,
+    commit:      CommitCounter,
+    shader:      Rc<BlurShader>,
+    passes:      u32,
+    offset:      f32,
+}
+
+impl BlurElement {
+    pub fn new(
+        id:          Id,
+        geometry:    Rectangle<i32, Physical>,
+        output_size: Size<i32, Physical>,
+        shader:      Rc<BlurShader>,
+        passes:      u32,
+        offset:      f32,
+    ) -> Self {
+        Self { id, geometry, output_size, commit: CommitCounter::default(), shader, passes, offset }
+    }
+}
+
+impl Element for BlurElement {
+    fn id(&self) -> &Id { &self.id }
+    fn current_commit(&self) -> CommitCounter { self.commit }
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.geometry.size.to_logical(1).to_buffer(1, Default::default())).to_f64()
+    }
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> { self.geometry }
+    fn damage_since(&self, _scale: Scale<f64>, _commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        DamageSet::from_slice(&[Rectangle::from_size(self.geometry.size)])
+    }
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> { OpaqueRegions::default() }
+    fn kind(&self) -> Kind { Kind::Unspecified }
+}
+
+impl RenderElement<GlesRenderer> for BlurElement {
+    fn draw(
+        &self,
+        frame:   &mut GlesFrame<'_, '_>,
+        _src:    Rectangle<f64, Buffer>,
+        dst:     Rectangle<i32, Physical>,
+        _damage: &[Rectangle<i32, Physical>],
+        _opaque: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        // `dst` is in output space; the bound framebuffer is untransformed.
+        let rect = frame.transformation().invert().transform_rect_in(dst, &self.output_size);
+        frame.with_context(|gl| unsafe { self.shader.blur(gl, rect, self.passes, self.offset) })
+    }
+}
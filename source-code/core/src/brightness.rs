@@ -0,0 +1,269 @@
+use std::{
+    fs::File,
+    io::{Read, Write},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use tracing::{debug, info, warn};
+
+use crate::config::DisplayConfig;
+
+/// sysfs backlight types, preferred first (as systemd-backlight does).
+const BACKLIGHT_TYPES: [&str; 3] = ["firmware", "platform", "raw"];
+/// Connectors of built-in panels, which use the sysfs backlight instead.
+const INTERNAL_CONNECTORS: [&str; 3] = ["eDP", "LVDS", "DSI"];
+
+// DDC/CI (VESA MCCS over I²C).
+const I2C_SLAVE: libc::c_ulong = 0x0703;
+const DDC_ADDR: libc::c_ulong = 0x37;
+const VCP_BRIGHTNESS: u8 = 0x10;
+/// Monitors need this long after each write before they answer.
+const DDC_DELAY: Duration = Duration::from_millis(50);
+
+/// Brightness of every display that can be dimmed: the internal panel's
+/// sysfs backlight, and external monitors over DDC/CI. Levels are in percent
+/// of each display's own range.
+pub struct Brightness {
+    displays: Vec<Display>,
+    step:     u32,
+    /// Levels from before [`Brightness::dim`], per display.
+    saved:    Option<Vec<Option<u32>>>,
+}
+
+enum Display {
+    Sysfs { name: String, dir: PathBuf, max: u32 },
+    Ddc(Arc<DdcMonitor>),
+}
+
+/// DDC/CI is slow (tens of ms per transfer), so monitors are probed and
+/// written on their own threads; `level` caches (current, maximum) and is
+/// `None` until the monitor has answered.
+struct DdcMonitor {
+    name:  String,
+    bus:   PathBuf,
+    level: Mutex<Option<(u16, u16)>>,
+    /// Serialises transfers on the bus.
+    io:    Mutex<()>,
+}
+
+impl Brightness {
+    pub fn detect(config: &DisplayConfig) -> Self {
+        let mut displays = Vec::new();
+        if let Some(display) = sysfs_backlight() {
+            displays.push(display);
+        }
+        if config.ddc {
+            for (name, bus) in ddc_buses() {
+                let monitor = Arc::new(DdcMonitor { name, bus, level: Mutex::new(None), io: Mutex::new(()) });
+                let probe = monitor.clone();
+                std::thread::spawn(move || probe.probe());
+                displays.push(Display::Ddc(monitor));
+            }
+        }
+        Self { displays, step: config.brightness_step.clamp(1, 100), saved: None }
+    }
+
+    /// Current level of each display, in percent.
+    pub fn get(&self) -> Vec<(String, u32)> {
+        self.displays.iter().filter_map(|d| Some((d.name().to_owned(), d.get()?))).collect()
+    }
+
+    /// Set every display to `percent`.
+    pub fn set(&self, percent: u32) -> Result<()> { self.update(|_| percent.min(100)) }
+
+    /// Move every display `steps` configured steps up (or down); stepping
+    /// down stops at 1% rather than switching the panel off.
+    pub fn step(&self, steps: i32) -> Result<()> {
+        let delta = steps * self.step as i32;
+        self.update(|current| (current as i32 + delta).clamp(1, 100) as u32)
+    }
+
+    /// Lower every display to at most `percent` until [`Brightness::restore`].
+    pub fn dim(&mut self, percent: u32) {
+        if self.saved.is_some() { return; }
+        self.saved = Some(self.displays.iter().map(Display::get).collect());
+        let _ = self.update(|current| current.min(percent.clamp(1, 100)));
+    }
+
+    /// Put back the levels from before [`Brightness::dim`].
+    pub fn restore(&mut self) {
+        let Some(saved) = self.saved.take() else { return };
+        for (display, level) in self.displays.iter().zip(saved) {
+            let Some(level) = level else { continue };
+            if let Err(e) = display.set(level) {
+                warn!(display = display.name(), "brightness: {e:#}");
+            }
+        }
+    }
+
+    fn update(&self, level: impl Fn(u32) -> u32) -> Result<()> {
+        if self.displays.is_empty() { bail!("no display with adjustable brightness"); }
+        let mut result = Ok(());
+        for display in &self.displays {
+            let Some(current) = display.get() else { continue };
+            let target = level(current);
+            info!(display = display.name(), percent = target, "Brightness");
+            if let Err(e) = display.set(target) {
+                warn!(display = display.name(), "brightness: {e:#}");
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+impl Display {
+    fn name(&self) -> &str {
+        match self {
+            Self::Sysfs { name, .. } => name,
+            Self::Ddc(m)             => &m.name,
+        }
+    }
+
+    fn get(&self) -> Option<u32> {
+        match self {
+            Self::Sysfs { dir, max, .. } => {
+                let raw: u32 = std::fs::read_to_string(dir.join("brightness")).ok()?.trim().parse().ok()?;
+                Some(to_percent(raw, *max))
+            }
+            Self::Ddc(m) => m.level.lock().unwrap().map(|(cur, max)| to_percent(cur as u32, max as u32)),
+        }
+    }
+
+    fn set(&self, percent: u32) -> Result<()> {
+        match self {
+            Self::Sysfs { dir, max, .. } => {
+                let path = dir.join("brightness");
+                std::fs::write(&path, from_percent(percent, *max).to_string())
+                    .with_context(|| format!("write {} (is the user in the video group?)", path.display()))
+            }
+            Self::Ddc(m) => {
+                if let Some((cur, max)) = m.level.lock().unwrap().as_mut() {
+                    *cur = from_percent(percent, *max as u32) as u16;
+                }
+                let monitor = m.clone();
+                std::thread::spawn(move || monitor.write());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn to_percent(raw: u32, max: u32) -> u32 {
+    if max == 0 { 0 } else { (raw * 100 + max / 2) / max }
+}
+
+fn from_percent(percent: u32, max: u32) -> u32 { ((percent.min(100) * max + 50) / 100).min(max) }
+
+// ── Discovery ─────────────────────────────────────────────────────────────────
+
+/// The internal panel's backlight, by preferred type.
+fn sysfs_backlight() -> Option<Display> {
+    let mut found: Vec<(usize, PathBuf)> = std::fs::read_dir("/sys/class/backlight")
+        .ok()?
+        .flatten()
+        .filter_map(|e| {
+            let kind = std::fs::read_to_string(e.path().join("type")).ok()?;
+            let rank = BACKLIGHT_TYPES.iter().position(|t| *t == kind.trim())?;
+            Some((rank, e.path()))
+        })
+        .collect();
+    found.sort();
+    let (_, dir) = found.into_iter().next()?;
+    let max = std::fs::read_to_string(dir.join("max_brightness")).ok()?.trim().parse().ok()?;
+    let name = dir.file_name()?.to_string_lossy().into_owned();
+    info!(backlight = %name, max, "Backlight found");
+    Some(Display::Sysfs { name, dir, max })
+}
+
+/// `(connector, /dev/i2c-N)` for each connected external monitor.
+fn ddc_buses() -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else { return Vec::new() };
+    entries
+        .flatten()
+        .filter_map(|e| {
+            let dir = e.path();
+            // card0-DP-1 → DP-1
+            let connector = e.file_name().to_str()?.split_once('-')?.1.to_owned();
+            if INTERNAL_CONNECTORS.iter().any(|p| connector.starts_with(p)) { return None; }
+            if std::fs::read_to_string(dir.join("status")).ok()?.trim() != "connected" { return None; }
+            let i2c = std::fs::read_link(dir.join("ddc")).ok()?;
+            let bus = Path::new("/dev").join(i2c.file_name()?);
+            Some((connector, bus))
+        })
+        .collect()
+}
+
+// ── DDC/CI ────────────────────────────────────────────────────────────────────
+
+impl DdcMonitor {
+    fn probe(&self) {
+        let _io = self.io.lock().unwrap();
+        match ddc_open(&self.bus).and_then(|mut f| ddc_get_vcp(&mut f, VCP_BRIGHTNESS)) {
+            Ok((cur, max)) if max > 0 => {
+                info!(display = %self.name, bus = %self.bus.display(), "DDC/CI brightness available");
+                *self.level.lock().unwrap() = Some((cur, max));
+            }
+            Ok(_)  => debug!(display = %self.name, "DDC/CI: monitor reports no brightness range"),
+            Err(e) => debug!(display = %self.name, "DDC/CI unavailable: {e:#}"),
+        }
+    }
+
+    /// Send the cached level. Runs after any earlier write on the bus, so
+    /// rapid changes end on the latest level.
+    fn write(&self) {
+        let _io = self.io.lock().unwrap();
+        let Some((cur, _)) = *self.level.lock().unwrap() else { return };
+        let [hi, lo] = cur.to_be_bytes();
+        if let Err(e) = ddc_open(&self.bus).and_then(|mut f| ddc_write(&mut f, &[0x03, VCP_BRIGHTNESS, hi, lo])) {
+            warn!(display = %self.name, "DDC/CI set brightness: {e:#}");
+        }
+    }
+}
+
+fn ddc_open(bus: &Path) -> Result<File> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(bus)
+        .with_context(|| format!("open {} (is i2c-dev loaded?)", bus.display()))?;
+    // SAFETY: I2C_SLAVE takes the 7-bit address by value.
+    if unsafe { libc::ioctl(file.as_raw_fd(), I2C_SLAVE, DDC_ADDR) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("I2C_SLAVE");
+    }
+    Ok(file)
+}
+
+/// Send one DDC/CI message: source address, length, payload, checksum.
+fn ddc_write(file: &mut File, payload: &[u8]) -> Result<()> {
+    let mut msg = vec![0x51, 0x80 | payload.len() as u8];
+    msg.extend_from_slice(payload);
+    // The checksum covers the destination address (0x6E) too.
+    msg.push(msg.iter().fold(0x6E, |acc, b| acc ^ b));
+    file.write_all(&msg).context("DDC write")?;
+    std::thread::sleep(DDC_DELAY);
+    Ok(())
+}
+
+/// `(current, maximum)` of a VCP feature.
+fn ddc_get_vcp(file: &mut File, code: u8) -> Result<(u16, u16)> {
+    ddc_write(file, &[0x01, code])?;
+    let mut reply = [0u8; 11];
+    file.read_exact(&mut reply).context("DDC read")?;
+    // source, length, opcode (VCP reply), result, code, type, max, current, checksum
+    if reply[2] != 0x02 || reply[4] != code {
+        bail!("unexpected DDC reply {reply:02x?}");
+    }
+    if reply[3] != 0 {
+        bail!("VCP {code:#04x} not supported");
+    }
+    let checksum = reply[..10].iter().fold(0x50, |acc, b| acc ^ b);
+    if checksum != reply[10] {
+        bail!("DDC reply checksum mismatch");
+    }
+    Ok((u16::from_be_bytes([reply[8], reply[9]]), u16::from_be_bytes([reply[6], reply[7]])))
+}
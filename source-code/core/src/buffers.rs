@@ -0,0 +1,219 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use smithay::{
+    backend::renderer::{buffer_dimensions, buffer_type, gles::GlesRenderer, BufferType},
+    reexports::wayland_server::{
+        backend::{ClientId, ObjectId},
+        protocol::{wl_buffer::WlBuffer, wl_surface::{self, WlSurface}},
+        Resource,
+    },
+    utils::{Buffer as BufferCoord, Size},
+    wayland::compositor::{add_destruction_hook, with_states, BufferAssignment, SurfaceAttributes},
+};
+use tracing::{debug, warn};
+
+use crate::state::{GameframeClientData, GameframeState};
+
+/// Largest buffer side accepted, in pixels; also the GLES texture limit on
+/// every GPU we support.
+const MAX_BUFFER_SIDE: i32 = 16384;
+
+/// What is wrong with a committed buffer.
+enum Invalid {
+    /// Earns the client a `wl_surface.invalid_size` error.
+    Size(String),
+    /// Nothing we can render: the commit is skipped, the client kept.
+    Unsupported(String),
+}
+
+/// First `wl_surface` version with the `invalid_size` error. Older clients
+/// commonly attach buffers that are not a multiple of the scale, which is
+/// only logged for them.
+const INVALID_SIZE_SINCE: u32 = 6;
+
+/// A client buffer that has been attached to a surface.
+#[derive(Debug, Clone, Copy)]
+struct Attached {
+    /// Texture memory it takes once imported, estimated at 4 bytes/pixel.
+    bytes:  u64,
+    client: Option<ClientId>,
+}
+
+/// Client buffers that have been attached to surfaces and not destroyed
+/// yet: what the renderer may be holding textures for.
+///
+/// The textures themselves are cached per surface by smithay's renderer
+/// state: a buffer is imported on the first frame after it is committed,
+/// and every later frame (on any output, in the recorder or an animation
+/// snapshot) reuses that texture until the surface commits another buffer.
+/// A shm buffer is uploaded again only for its damaged region. What this
+/// adds is the other end: a destroyed buffer's texture is freed right
+/// away, and `gameframe buffers` shows the totals so a leak shows up as a
+/// number that keeps growing while memory use should stay flat.
+#[derive(Debug, Default)]
+pub struct Buffers {
+    attached: HashMap<ObjectId, Attached>,
+    /// Buffers destroyed over the session, for spotting churn.
+    released: u64,
+}
+
+impl Buffers {
+    /// Check the buffer `surface` just committed, if it has a new one.
+    /// Returns `false` if the commit must go no further: the buffer is of
+    /// an invalid size (the client gets a protocol error, which disconnects
+    /// it) or of a type we can't render.
+    pub fn commit(&mut self, surface: &WlSurface) -> bool {
+        let (buffer, scale) = with_states(surface, |states| {
+            let mut attrs = states.cached_state.get::<SurfaceAttributes>();
+            let current = attrs.current();
+            let buffer = match &current.buffer {
+                Some(BufferAssignment::NewBuffer(buffer)) => Some(buffer.clone()),
+                _ => None,
+            };
+            (buffer, current.buffer_scale)
+        });
+        let Some(buffer) = buffer else { return true };
+        match check(&buffer, scale, surface.version()) {
+            Ok(size) => {
+                let bytes = size.w as u64 * size.h as u64 * 4;
+                self.attached.insert(buffer.id(), Attached { bytes, client: buffer.client().map(|c| c.id()) });
+                true
+            }
+            Err(invalid) => {
+                reject(surface, invalid);
+                false
+            }
+        }
+    }
+
+    /// The client destroyed `buffer`. Textures imported from it were
+    /// dropped along with the surface state holding it; have the renderer
+    /// free them (and any cached EGLImage) now.
+    pub fn destroyed(&mut self, buffer: &WlBuffer, renderer: Option<&mut GlesRenderer>) {
+        if self.attached.remove(&buffer.id()).is_none() { return; }
+        self.released += 1;
+        if let Some(renderer) = renderer {
+            if let Err(e) = renderer.cleanup_texture_cache() {
+                debug!("texture cache cleanup: {e:?}");
+            }
+        }
+    }
+
+    /// Buffers attached now and their estimated size in bytes.
+    pub fn totals(&self) -> (usize, u64) {
+        (self.attached.len(), self.attached.values().map(|a| a.bytes).sum())
+    }
+
+    /// Buffers attached now and their estimated size, per client. Summed
+    /// when asked rather than kept up to date on every commit.
+    pub fn per_client(&self) -> HashMap<ClientId, (usize, u64)> {
+        let mut totals: HashMap<ClientId, (usize, u64)> = HashMap::new();
+        for attached in self.attached.values() {
+            let Some(client) = &attached.client else { continue };
+            let entry = totals.entry(client.clone()).or_default();
+            entry.0 += 1;
+            entry.1 += attached.bytes;
+        }
+        totals
+    }
+
+    /// `attached N (X MiB) released M`, for the control socket.
+    pub fn summary(&self) -> String {
+        let (count, bytes) = self.totals();
+        format!(
+            "attached {} ({:.1} MiB) released {}\n",
+            count, bytes as f64 / (1024.0 * 1024.0), self.released,
+        )
+    }
+}
+
+/// Surfaces a client has alive, kept in its client data. A client that
+/// keeps creating surfaces without destroying them shows up in
+/// `gameframe clients` long before it runs the compositor out of memory.
+/// Its buffers are counted by [`Buffers`]; everything goes with the client
+/// when it disconnects (its buffers are destroyed, its data dropped).
+#[derive(Debug, Default)]
+pub struct ClientUsage {
+    surfaces: AtomicUsize,
+}
+
+impl ClientUsage {
+    /// The client just created `surface`: count it until it is destroyed.
+    pub fn surface_created(self: &Arc<Self>, surface: &WlSurface) {
+        self.surfaces.fetch_add(1, Ordering::Relaxed);
+        let usage = self.clone();
+        add_destruction_hook(surface, move |_, _| {
+            usage.surfaces.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    pub fn surfaces(&self) -> usize { self.surfaces.load(Ordering::Relaxed) }
+}
+
+/// One line per connected client, those holding the most buffer memory
+/// first: `PID COMMAND surfaces N buffers M (X MiB) socket NAME`.
+pub fn clients(state: &GameframeState) -> String {
+    let buffers = state.buffers.per_client();
+    let mut rows: Vec<(u64, String)> = state.display_handle.backend_handle().all_clients()
+        .filter_map(|id| state.display_handle.get_client(id.clone()).ok().map(|c| (id, c)))
+        .map(|(id, client)| {
+            let pid = client.get_credentials(&state.display_handle).ok().map(|c| c.pid);
+            let command = pid
+                .and_then(|pid| std::fs::read_to_string(format!("/proc/{pid}/comm")).ok())
+                .map_or_else(|| "?".to_owned(), |comm| comm.trim().to_owned());
+            let (surfaces, socket) = match client.get_data::<GameframeClientData>() {
+                Some(data) => (data.usage.surfaces(), data.socket.as_str()),
+                None       => (0, ""),
+            };
+            let (count, bytes) = buffers.get(&id).copied().unwrap_or_default();
+            let socket = if socket.is_empty() { "internal" } else { socket };
+            let line = format!(
+                "{} {command} surfaces {surfaces} buffers {count} ({:.1} MiB) socket {socket}\n",
+                pid.map_or_else(|| "?".to_owned(), |p| p.to_string()),
+                bytes as f64 / (1024.0 * 1024.0),
+            );
+            (bytes, line)
+        })
+        .collect();
+    rows.sort_by(|a, b| b.0.cmp(&a.0));
+    rows.into_iter().map(|(_, line)| line).collect()
+}
+
+/// The buffer's size if it is fit to render. The shm pool already checked
+/// offset, stride and format when the buffer was created, and the dmabuf
+/// global its import.
+fn check(buffer: &WlBuffer, scale: i32, version: u32) -> Result<Size<i32, BufferCoord>, Invalid> {
+    match buffer_type(buffer) {
+        Some(BufferType::Shm | BufferType::Dma | BufferType::SinglePixel) => {}
+        _ => return Err(Invalid::Unsupported("unsupported buffer type".into())),
+    }
+    let size = buffer_dimensions(buffer).ok_or_else(|| Invalid::Size("buffer has no size".into()))?;
+    if size.w <= 0 || size.h <= 0 || size.w > MAX_BUFFER_SIDE || size.h > MAX_BUFFER_SIDE {
+        return Err(Invalid::Size(format!("buffer is {}x{}, limit is {MAX_BUFFER_SIDE}", size.w, size.h)));
+    }
+    if scale > 1 && (size.w % scale != 0 || size.h % scale != 0) {
+        let msg = format!("buffer {}x{} is not a multiple of scale {scale}", size.w, size.h);
+        if version >= INVALID_SIZE_SINCE {
+            return Err(Invalid::Size(msg));
+        }
+        debug!(client = ?buffer.client().map(|c| c.id()), "{msg}");
+    }
+    Ok(size)
+}
+
+fn reject(surface: &WlSurface, invalid: Invalid) {
+    let client = surface.client().map(|c| c.id());
+    match invalid {
+        Invalid::Size(msg) => {
+            warn!(?client, "invalid buffer: {msg}");
+            surface.post_error(wl_surface::Error::InvalidSize, msg);
+        }
+        Invalid::Unsupported(msg) => debug!(?client, "buffer not rendered: {msg}"),
+    }
+}
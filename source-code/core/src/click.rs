@@ -0,0 +1,98 @@
+use smithay::{
+    desktop::Window,
+    utils::{Logical, Point},
+};
+
+use crate::config::InputConfig;
+
+/// A button press, for counting multi-clicks.
+#[derive(Debug, Clone, Copy)]
+struct Press {
+    button: u32,
+    at:     Point<f64, Logical>,
+    /// Input event time, ms.
+    time:   u32,
+    count:  u32,
+}
+
+/// Tells clicks from drags and counts double clicks, with the thresholds
+/// from `[input]`.
+#[derive(Debug)]
+pub struct ClickTracker {
+    double_click_ms: u32,
+    drag_threshold:  f64,
+    last:            Option<Press>,
+    /// Set from the press until the pointer travels past the threshold.
+    held:            Option<Point<f64, Logical>>,
+    dragging:        bool,
+}
+
+impl ClickTracker {
+    pub fn new(config: &InputConfig) -> Self {
+        let mut tracker = Self {
+            double_click_ms: 0,
+            drag_threshold:  0.0,
+            last:            None,
+            held:            None,
+            dragging:        false,
+        };
+        tracker.configure(config);
+        tracker
+    }
+
+    /// Apply reloaded thresholds.
+    pub fn configure(&mut self, config: &InputConfig) {
+        self.double_click_ms = config.double_click_ms;
+        self.drag_threshold  = config.drag_threshold.max(0.0);
+    }
+
+    /// Record a press and return its click count: 2 for a double click, 3
+    /// for a triple. A press counts on from the previous one if it is the
+    /// same button, soon enough and close enough.
+    pub fn press(&mut self, button: u32, at: Point<f64, Logical>, time: u32) -> u32 {
+        let count = match self.last {
+            Some(p) if p.button == button
+                && time.wrapping_sub(p.time) <= self.double_click_ms
+                && !self.beyond_threshold(p.at, at) => p.count + 1,
+            _ => 1,
+        };
+        self.last     = Some(Press { button, at, time, count });
+        self.held     = Some(at);
+        self.dragging = false;
+        count
+    }
+
+    /// The pointer moved with a button held. Returns `true` once, when it
+    /// first travels past the drag threshold.
+    pub fn motion(&mut self, at: Point<f64, Logical>) -> bool {
+        let Some(origin) = self.held else { return false };
+        if self.dragging || !self.beyond_threshold(origin, at) { return false; }
+        self.dragging = true;
+        // A drag never turns into a double click.
+        self.last = None;
+        true
+    }
+
+    /// The button came up. Returns whether the press turned into a drag.
+    pub fn release(&mut self) -> bool {
+        self.held = None;
+        std::mem::take(&mut self.dragging)
+    }
+
+    pub fn dragging(&self) -> bool { self.dragging }
+
+    fn beyond_threshold(&self, a: Point<f64, Logical>, b: Point<f64, Logical>) -> bool {
+        let d = b - a;
+        d.x.hypot(d.y) > self.drag_threshold
+    }
+}
+
+/// A window being moved with Super + left button. It stays put until the
+/// pointer passes the drag threshold.
+#[derive(Debug)]
+pub struct WindowDrag {
+    pub window:  Window,
+    /// Where the window and the pointer were at the press.
+    pub origin:  Point<i32, Logical>,
+    pub pointer: Point<f64, Logical>,
+}
@@ -0,0 +1,215 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{Read, Write},
+    os::fd::{FromRawFd, OwnedFd},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    input::Seat,
+    reexports::calloop::{generic::Generic, Interest, Mode, PostAction},
+    wayland::selection::{
+        data_device::{request_data_device_client_selection, set_data_device_selection},
+        SelectionSource,
+    },
+};
+use tracing::{debug, info, warn};
+
+use crate::{config::ClipboardConfig, state::GameframeState};
+
+/// Clipboard manager: keeps a copy of the clipboard selection so paste
+/// still works after the client that copied it exits.
+///
+/// Whenever a client sets the selection, each offered MIME type on the
+/// `[clipboard]` allowlist is read into memory right away. When the
+/// selection is then cleared (most often because its source went away
+/// with the client), the copy is offered again with the compositor as the
+/// source. The primary selection is left alone.
+///
+/// With `history` set, text selections are also kept in a short list,
+/// newest first, that `gameframe clipboard` can show and select from.
+#[derive(Debug, Default)]
+pub struct Clipboard {
+    /// Bumped on every new selection, so reads of an older one are dropped.
+    generation: u64,
+    /// What was read from the current selection, by MIME type.
+    saved:      Vec<(String, Arc<Vec<u8>>)>,
+    history:    VecDeque<Entry>,
+    /// Generation last added to the history, so a selection offering
+    /// several text types is only added once.
+    recorded:   u64,
+}
+
+/// A text selection in the history.
+#[derive(Debug)]
+struct Entry {
+    text: Arc<Vec<u8>>,
+    /// Unix time it was copied, seconds.
+    at:   u64,
+}
+
+/// Types a text selection is offered again as, best first.
+const TEXT_MIME_TYPES: [&str; 5] = ["text/plain;charset=utf-8", "text/plain", "UTF8_STRING", "STRING", "TEXT"];
+
+impl Clipboard {
+    /// Add `text` as the newest entry, dropping an equal older one and
+    /// then the oldest entries until the configured limits hold.
+    fn remember(&mut self, config: &ClipboardConfig, text: Arc<Vec<u8>>) {
+        if config.history == 0 || std::str::from_utf8(&text).is_err() { return; }
+        self.history.retain(|e| e.text != text);
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        self.history.push_front(Entry { text, at });
+        self.history.truncate(config.history);
+        while self.history.iter().map(|e| e.text.len()).sum::<usize>() > config.history_max_bytes {
+            self.history.pop_back();
+        }
+    }
+}
+
+impl ClipboardConfig {
+    /// Whether `mime` is on the allowlist; a trailing `*` matches a prefix.
+    fn allows(&self, mime: &str) -> bool {
+        self.mime_types.iter().any(|m| match m.strip_suffix('*') {
+            Some(prefix) => mime.starts_with(prefix),
+            None         => m == mime,
+        })
+    }
+}
+
+/// The clipboard selection changed: `Some` when a client set it, `None`
+/// when it was cleared.
+pub fn selection_changed(state: &mut GameframeState, seat: &Seat<GameframeState>, source: Option<SelectionSource>) {
+    if !state.config.clipboard.enabled { return; }
+    let Some(source) = source else {
+        reoffer(state, seat);
+        return;
+    };
+    state.clipboard.generation += 1;
+    state.clipboard.saved.clear();
+    let generation = state.clipboard.generation;
+    let wanted: Vec<String> = source.mime_types().into_iter().filter(|m| state.config.clipboard.allows(m)).collect();
+    for mime in wanted {
+        if let Err(e) = read_selection(state, seat, mime.clone(), generation) {
+            debug!(mime, "clipboard read: {e:#}");
+        }
+    }
+}
+
+/// Take over the selection with the saved copy, if there is one.
+fn reoffer(state: &mut GameframeState, seat: &Seat<GameframeState>) {
+    if state.clipboard.saved.is_empty() { return; }
+    let mime_types: Vec<String> = state.clipboard.saved.iter().map(|(m, _)| m.clone()).collect();
+    info!(?mime_types, "Clipboard source gone, keeping its contents");
+    set_data_device_selection(&state.display_handle, seat, mime_types, ());
+}
+
+/// Have the source client write `mime` into a pipe and collect it from the
+/// event loop, so a slow client never blocks the compositor.
+fn read_selection(state: &mut GameframeState, seat: &Seat<GameframeState>, mime: String, generation: u64) -> Result<()> {
+    let (read, write) = pipe()?;
+    request_data_device_client_selection(seat, mime.clone(), write)
+        .map_err(|e| anyhow!("request selection: {e:?}"))?;
+
+    let max      = state.config.clipboard.max_bytes;
+    let mut data = Vec::new();
+    state.loop_handle
+        .insert_source(Generic::new(File::from(read), Interest::READ, Mode::Level), move |_, file, state| {
+            let mut buf = [0u8; 16 * 1024];
+            loop {
+                // SAFETY: the fd is only read, never closed through this borrow.
+                match unsafe { file.get_mut() }.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) if data.len() + n > max => {
+                        debug!(mime, max, "clipboard contents too large, not kept");
+                        return Ok(PostAction::Remove);
+                    }
+                    Ok(n) => data.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                    Err(e) => {
+                        debug!(mime, "clipboard read: {e}");
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            }
+            let clipboard = &mut state.clipboard;
+            if clipboard.generation == generation && !data.is_empty() {
+                let data = Arc::new(std::mem::take(&mut data));
+                clipboard.saved.push((mime.clone(), Arc::clone(&data)));
+                if TEXT_MIME_TYPES.contains(&mime.as_str()) && clipboard.recorded != generation {
+                    clipboard.recorded = generation;
+                    clipboard.remember(&state.config.clipboard, data);
+                }
+            }
+            Ok(PostAction::Remove)
+        })
+        .map_err(|e| anyhow!("clipboard source: {e}"))?;
+    Ok(())
+}
+
+/// A client pastes from the saved copy. The write happens on its own
+/// thread; the reader may take its time.
+pub fn send(state: &GameframeState, mime: &str, fd: OwnedFd) {
+    let Some((_, data)) = state.clipboard.saved.iter().find(|(m, _)| m == mime) else { return };
+    let data = Arc::clone(data);
+    let spawned = std::thread::Builder::new()
+        .name("gameframe-clipboard".into())
+        .spawn(move || {
+            if let Err(e) = File::from(fd).write_all(&data) {
+                debug!("clipboard write: {e}");
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("clipboard writer thread: {e}");
+    }
+}
+
+// ── Control ───────────────────────────────────────────────────────────────────
+
+/// `N TIME TEXT` per history entry, newest (0) first; TEXT is the first
+/// line, shortened.
+pub fn list_history(state: &GameframeState) -> String {
+    state.clipboard.history.iter().enumerate().map(|(n, e)| {
+        let text = String::from_utf8_lossy(&e.text);
+        let line = text.lines().next().unwrap_or_default();
+        let mut preview: String = line.chars().take(80).collect();
+        if preview.len() < text.len() { preview.push('…'); }
+        format!("{n} {} {preview}\n", e.at)
+    }).collect()
+}
+
+/// Make history entry `n` the clipboard selection again.
+pub fn select_history(state: &mut GameframeState, n: usize) -> Result<()> {
+    if state.config.clipboard.history == 0 { bail!("clipboard history is off"); }
+    let entry = state.clipboard.history.remove(n).with_context(|| format!("no entry {n}"))?;
+    let text  = Arc::clone(&entry.text);
+    state.clipboard.history.push_front(entry);
+    state.clipboard.generation += 1;
+    state.clipboard.recorded = state.clipboard.generation;
+    state.clipboard.saved = TEXT_MIME_TYPES.iter().map(|m| (m.to_string(), Arc::clone(&text))).collect();
+    let mime_types = TEXT_MIME_TYPES.map(String::from).to_vec();
+    let seat = state.seat.clone();
+    set_data_device_selection(&state.display_handle, &seat, mime_types, ());
+    Ok(())
+}
+
+pub fn clear_history(state: &mut GameframeState) {
+    state.clipboard.history.clear();
+}
+
+/// A pipe with a non-blocking read end for the event loop; the write end
+/// goes to the client and stays blocking.
+fn pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("pipe2");
+    }
+    // SAFETY: pipe2 just returned these and nothing else owns them.
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    if unsafe { libc::fcntl(fds[0], libc::F_SETFL, libc::O_NONBLOCK) } < 0 {
+        return Err(std::io::Error::last_os_error()).context("set O_NONBLOCK");
+    }
+    Ok((read, write))
+}
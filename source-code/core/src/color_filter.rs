@@ -0,0 +1,329 @@
+use std::{cell::RefCell, ffi::CString, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use smithay::{
+    backend::renderer::{
+        element::{Element, Id, Kind, RenderElement},
+        gles::{ffi, GlesError, GlesFrame, GlesRenderer},
+        utils::{CommitCounter, DamageSet, OpaqueRegions},
+        Frame,
+    },
+    output::Output,
+    utils::{Buffer, Physical, Rectangle, Scale, Size},
+};
+use tracing::{info, warn};
+
+use crate::{
+    blur::{compile, QUAD, VERTEX_SRC},
+    config::ColorFilter,
+};
+
+// ── Colour transforms ─────────────────────────────────────────────────────────
+
+type Mat3 = [[f32; 3]; 3];
+
+const IDENTITY: Mat3 = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Rec. 709 luminance in every channel.
+const GRAYSCALE: Mat3 = [
+    [0.2126, 0.7152, 0.0722],
+    [0.2126, 0.7152, 0.0722],
+    [0.2126, 0.7152, 0.0722],
+];
+
+/// Dichromacy simulation (Machado et al. 2009, severity 1.0, linear RGB).
+const PROTANOPIA: Mat3 = [
+    [ 0.152286, 1.052583, -0.204868],
+    [ 0.114503, 0.786281,  0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: Mat3 = [
+    [ 0.367322, 0.860646, -0.227968],
+    [ 0.280085, 0.672501,  0.047413],
+    [-0.011820, 0.042940,  0.968881],
+];
+const TRITANOPIA: Mat3 = [
+    [ 1.255528, -0.076749, -0.178779],
+    [-0.078411,  0.930809,  0.147602],
+    [ 0.004733,  0.691367,  0.303900],
+];
+
+/// Where the colour information a dichromat loses is moved to (daltonize):
+/// the lost red difference goes into green and blue.
+const ERROR_SHIFT: Mat3 = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+
+fn mul(a: &Mat3, b: &Mat3) -> Mat3 {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+/// `I + shift · (I - simulate)`: keep the colour and add back what the
+/// deficiency hides, in channels that are still seen.
+fn correction(simulate: &Mat3) -> Mat3 {
+    let mut lost = IDENTITY;
+    for (i, row) in lost.iter_mut().enumerate() {
+        for (j, v) in row.iter_mut().enumerate() {
+            *v -= simulate[i][j];
+        }
+    }
+    let mut out = mul(&ERROR_SHIFT, &lost);
+    for (i, row) in out.iter_mut().enumerate() {
+        row[i] += 1.0;
+    }
+    out
+}
+
+/// The full-screen colour transform: an optional inversion of the encoded
+/// colour, then a 3×3 matrix in linear light. Other linear transforms (a
+/// night-light tint, say) compose with the filter through [`Self::then`] and
+/// run in the same pass; the CRTC gamma ramp applies after scanout and
+/// stacks on top of it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorTransform {
+    pub invert: bool,
+    /// Row-major.
+    pub matrix: Mat3,
+}
+
+impl ColorTransform {
+    pub fn is_identity(&self) -> bool { !self.invert && self.matrix == IDENTITY }
+
+    /// Apply `matrix` (linear RGB, row-major) after this transform.
+    pub fn then(self, matrix: &Mat3) -> Self { Self { invert: self.invert, matrix: mul(matrix, &self.matrix) } }
+}
+
+impl ColorFilter {
+    pub fn transform(self) -> ColorTransform {
+        let matrix = match self {
+            ColorFilter::None | ColorFilter::Invert => IDENTITY,
+            ColorFilter::Grayscale    => GRAYSCALE,
+            ColorFilter::Protanopia   => correction(&PROTANOPIA),
+            ColorFilter::Deuteranopia => correction(&DEUTERANOPIA),
+            ColorFilter::Tritanopia   => correction(&TRITANOPIA),
+        };
+        ColorTransform { invert: self == ColorFilter::Invert, matrix }
+    }
+
+    /// The filter after this one in the keybinding's cycle.
+    pub fn next(self) -> Self {
+        let next = match self {
+            ColorFilter::None         => ColorFilter::Invert,
+            ColorFilter::Invert       => ColorFilter::Grayscale,
+            ColorFilter::Grayscale    => ColorFilter::Protanopia,
+            ColorFilter::Protanopia   => ColorFilter::Deuteranopia,
+            ColorFilter::Deuteranopia => ColorFilter::Tritanopia,
+            ColorFilter::Tritanopia   => ColorFilter::None,
+        };
+        info!(filter = ?next, "Color filter");
+        next
+    }
+}
+
+// ── Shader ────────────────────────────────────────────────────────────────────
+
+const FRAGMENT_SRC: &str = r#"#version 100
+precision mediump float;
+varying vec2 uv;
+uniform sampler2D tex;
+uniform mat3 matrix;
+uniform float invert;
+void main() {
+    vec4 src = texture2D(tex, uv);
+    vec3 c = mix(src.rgb, 1.0 - src.rgb, invert);
+    c = pow(c, vec3(2.2));
+    c = clamp(matrix * c, 0.0, 1.0);
+    gl_FragColor = vec4(pow(c, vec3(1.0 / 2.2)), src.a);
+}
+"#;
+
+/// The colour filter program, one per GL context.
+pub struct FilterShader {
+    id:     ffi::types::GLuint,
+    pos:    ffi::types::GLint,
+    tex:    ffi::types::GLint,
+    matrix: ffi::types::GLint,
+    invert: ffi::types::GLint,
+}
+
+/// The filter program for `renderer`'s context, compiled on first use.
+/// `None` if compilation failed (logged once; the filter is then skipped).
+pub fn filter_shader(renderer: &mut GlesRenderer) -> Option<Rc<FilterShader>> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<Option<Rc<FilterShader>>>() {
+        return cached.clone();
+    }
+    let shader = match renderer.with_context(|gl| unsafe { FilterShader::compile(gl) }) {
+        Ok(Ok(s))  => Some(Rc::new(s)),
+        Ok(Err(e)) => { warn!("color filter shader: {e:#}"); None }
+        Err(e)     => { warn!("color filter shader: {e:?}"); None }
+    };
+    renderer.egl_context().user_data().insert_if_missing(|| shader.clone());
+    shader
+}
+
+impl FilterShader {
+    unsafe fn compile(gl: &ffi::Gles2) -> Result<Self> {
+        let vs = compile(gl, ffi::VERTEX_SHADER, VERTEX_SRC)?;
+        let fs = compile(gl, ffi::FRAGMENT_SHADER, FRAGMENT_SRC)?;
+        let id = gl.CreateProgram();
+        gl.AttachShader(id, vs);
+        gl.AttachShader(id, fs);
+        gl.LinkProgram(id);
+        gl.DetachShader(id, vs);
+        gl.DetachShader(id, fs);
+        gl.DeleteShader(vs);
+        gl.DeleteShader(fs);
+        let mut ok = 0;
+        gl.GetProgramiv(id, ffi::LINK_STATUS, &mut ok);
+        if ok == 0 {
+            gl.DeleteProgram(id);
+            return Err(anyhow!("program link failed"));
+        }
+        let name = |n: &str| CString::new(n).unwrap();
+        Ok(Self {
+            id,
+            pos:    gl.GetAttribLocation(id, name("pos").as_ptr()),
+            tex:    gl.GetUniformLocation(id, name("tex").as_ptr()),
+            matrix: gl.GetUniformLocation(id, name("matrix").as_ptr()),
+            invert: gl.GetUniformLocation(id, name("invert").as_ptr()),
+        })
+    }
+
+    /// Run `rect` (framebuffer pixels) of the bound framebuffer through
+    /// `transform` in place, restoring the GL state Smithay relies on.
+    unsafe fn apply(&self, gl: &ffi::Gles2, rect: Rectangle<i32, Physical>, transform: &ColorTransform) {
+        let (w, h) = (rect.size.w, rect.size.h);
+        if w <= 0 || h <= 0 { return; }
+
+        let mut prev_program = 0;
+        let mut prev_array = 0;
+        let mut prev_tex = 0;
+        let mut viewport = [0; 4];
+        gl.GetIntegerv(ffi::CURRENT_PROGRAM, &mut prev_program);
+        gl.GetIntegerv(ffi::ARRAY_BUFFER_BINDING, &mut prev_array);
+        gl.ActiveTexture(ffi::TEXTURE0);
+        gl.GetIntegerv(ffi::TEXTURE_BINDING_2D, &mut prev_tex);
+        gl.GetIntegerv(ffi::VIEWPORT, viewport.as_mut_ptr());
+        let scissor = gl.IsEnabled(ffi::SCISSOR_TEST) == ffi::TRUE;
+        let blend   = gl.IsEnabled(ffi::BLEND) == ffi::TRUE;
+
+        let mut tex = 0;
+        gl.GenTextures(1, &mut tex);
+        gl.BindTexture(ffi::TEXTURE_2D, tex);
+        gl.TexImage2D(
+            ffi::TEXTURE_2D, 0, ffi::RGBA as i32, w, h, 0, ffi::RGBA, ffi::UNSIGNED_BYTE, std::ptr::null(),
+        );
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MIN_FILTER, ffi::NEAREST as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MAG_FILTER, ffi::NEAREST as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+        gl.CopyTexSubImage2D(ffi::TEXTURE_2D, 0, 0, 0, rect.loc.x, rect.loc.y, w, h);
+
+        // GLES 2 cannot transpose uniform matrices; upload column-major.
+        let m = &transform.matrix;
+        let columns = [m[0][0], m[1][0], m[2][0], m[0][1], m[1][1], m[2][1], m[0][2], m[1][2], m[2][2]];
+
+        gl.Disable(ffi::SCISSOR_TEST);
+        gl.Disable(ffi::BLEND);
+        gl.BindBuffer(ffi::ARRAY_BUFFER, 0);
+        gl.Viewport(rect.loc.x, rect.loc.y, w, h);
+        gl.UseProgram(self.id);
+        gl.Uniform1i(self.tex, 0);
+        gl.UniformMatrix3fv(self.matrix, 1, ffi::FALSE, columns.as_ptr());
+        gl.Uniform1f(self.invert, if transform.invert { 1.0 } else { 0.0 });
+        gl.EnableVertexAttribArray(self.pos as u32);
+        gl.VertexAttribPointer(self.pos as u32, 2, ffi::FLOAT, ffi::FALSE, 0, QUAD.as_ptr().cast());
+        gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+        gl.DisableVertexAttribArray(self.pos as u32);
+
+        gl.DeleteTextures(1, &tex);
+
+        gl.Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        gl.UseProgram(prev_program as u32);
+        gl.BindBuffer(ffi::ARRAY_BUFFER, prev_array as u32);
+        gl.BindTexture(ffi::TEXTURE_2D, prev_tex as u32);
+        if scissor { gl.Enable(ffi::SCISSOR_TEST); }
+        if blend { gl.Enable(ffi::BLEND); }
+    }
+}
+
+// ── Render element ────────────────────────────────────────────────────────────
+
+/// Element id per output, renewed whenever the transform changes so the
+/// damage tracker redraws the whole output once.
+struct FilterId(RefCell<(ColorTransform, Id)>);
+
+/// Filters everything drawn beneath it. Placed in front of all other
+/// elements of a frame; it only processes the regions repainted this frame,
+/// since the rest of the buffer already holds filtered pixels.
+pub struct ColorFilterElement {
+    id:          Id,
+    /// Size of the output in output (transformed) space.
+    output_size: Size<i32, Physical>,
+    shader:      Rc<FilterShader>,
+    transform:   ColorTransform,
+}
+
+impl ColorFilterElement {
+    /// The filter pass for `output`, or `None` if `transform` changes nothing
+    /// or the shader is unavailable.
+    pub fn new(renderer: &mut GlesRenderer, output: &Output, transform: ColorTransform) -> Option<Self> {
+        if transform.is_identity() { return None; }
+        let shader = filter_shader(renderer)?;
+        let output_size = output.current_mode().map(|m| output.current_transform().transform_size(m.size))?;
+
+        let data = output.user_data();
+        data.insert_if_missing(|| FilterId(RefCell::new((transform, Id::new()))));
+        let mut slot = data.get::<FilterId>().unwrap().0.borrow_mut();
+        if slot.0 != transform {
+            *slot = (transform, Id::new());
+        }
+        Some(Self { id: slot.1.clone(), output_size, shader, transform })
+    }
+}
+
+impl Element for ColorFilterElement {
+    fn id(&self) -> &Id { &self.id }
+    fn current_commit(&self) -> CommitCounter { CommitCounter::default() }
+    fn src(&self) -> Rectangle<f64, Buffer> {
+        Rectangle::from_size(self.output_size.to_logical(1).to_buffer(1, Default::default())).to_f64()
+    }
+    fn geometry(&self, _scale: Scale<f64>) -> Rectangle<i32, Physical> { Rectangle::from_size(self.output_size) }
+    fn damage_since(&self, _scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        if commit == Some(self.current_commit()) {
+            DamageSet::default()
+        } else {
+            DamageSet::from_slice(&[Rectangle::from_size(self.output_size)])
+        }
+    }
+    fn opaque_regions(&self, _scale: Scale<f64>) -> OpaqueRegions<i32, Physical> { OpaqueRegions::default() }
+    fn kind(&self) -> Kind { Kind::Unspecified }
+}
+
+impl RenderElement<GlesRenderer> for ColorFilterElement {
+    fn draw(
+        &self,
+        frame:   &mut GlesFrame<'_, '_>,
+        _src:    Rectangle<f64, Buffer>,
+        dst:     Rectangle<i32, Physical>,
+        damage:  &[Rectangle<i32, Physical>],
+        _opaque: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        // `damage` is relative to `dst`, in output space; the bound
+        // framebuffer is untransformed.
+        let to_fb = frame.transformation().invert();
+        let rects: Vec<_> = damage
+            .iter()
+            .map(|d| to_fb.transform_rect_in(Rectangle::new(dst.loc + d.loc, d.size), &self.output_size))
+            .collect();
+        frame.with_context(|gl| unsafe {
+            for rect in rects {
+                self.shader.apply(gl, rect, &self.transform);
+            }
+        })
+    }
+}
@@ -1,54 +1,202 @@
 use std::{
     collections::HashMap,
-    os::unix::io::OwnedFd,
-    path::PathBuf,
+    ffi::OsString,
+    os::unix::{io::OwnedFd, process::CommandExt},
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use drm::control::{connector, crtc, Device as ControlDevice, ModeTypeFlags};
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, info_span, warn};
 
 use smithay::{
     backend::{
-        allocator::gbm::{GbmAllocator, GbmBufferFlags},
-        drm::{DrmDevice, DrmDeviceFd, DrmEvent},
+        allocator::gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+        drm::{DrmDevice, DrmDeviceFd, DrmDeviceNotifier, DrmEvent, DrmNode},
         egl::{EGLContext, EGLDisplay},
+        input::InputEvent,
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{gles::GlesRenderer, ImportDma},
-        session::{libseat::LibSeatSession, Session},
+        session::{libseat::LibSeatSession, Event as SessionEvent, Session},
+        udev::{UdevBackend, UdevEvent},
     },
     reexports::{
         calloop::{
+            generic::Generic,
             timer::{TimeoutAction, Timer},
-            EventLoop, LoopHandle, LoopSignal,
+            EventLoop, Interest, LoopHandle, Mode, PostAction, RegistrationToken,
         },
         rustix::fs::OFlags,     // smithay::reexports::rustix – avoids version conflict
-        wayland_server::Display,
+        wayland_server::{Display, DisplayHandle},
     },
+    input::keyboard::LedState,
+    output::Output,
     utils::DeviceFd,
     wayland::socket::ListeningSocketSource,
 };
-use input::Libinput;
+use input::{DeviceCapability, Libinput, Led, SendEventsMode};
 
 use gameframe_gpu::GpuVendor;
 
 use crate::{
-    cursor::HardwareCursor,
+    backlog::{self, FLUSH_RETRY},
+    config::{ClickMethod, DeviceConfig, DisplayConfig, InputConfig, ScrollMethod, TapButtonMap},
     dmabuf::init_dmabuf_global,
-    frame::FramePacer,
+    edid::read_edid,
+    frame::FrameStats,
+    gamepad,
+    gpu_check::{self, Stage},
+    headless::{self, HeadlessBackend},
+    idle,
     input_handler::process_input_event,
-    output::OutputManager,
-    session::SessionOptions,
+    ipc,
+    metrics,
+    modes::{find_mode, refresh_hz, select_mode, ModeRequest},
+    nested::{self, NestedBackend},
+    output::{connector_name, rotation_transform, GameframeOutput, OutputId, OutputManager},
+    recorder,
+    render::handle_vblank,
+    restore,
+    session::{BackendKind, SessionOptions},
+    spawn,
     state::{GameframeClientData, GameframeState},
     telemetry::read_telemetry,
     xwayland,
 };
 
+/// Event loop wakeup interval while the overlay is shown or has toasts;
+/// toast lifetimes are counted in these ticks.
+const OVERLAY_TICK: Duration = Duration::from_millis(4);
+
+// ── DRM backend state ─────────────────────────────────────────────────────────
+
+/// One DRM device and the outputs on its connectors.
+pub struct DrmGpu {
+    pub drm:       DrmDevice,
+    pub gbm:       GbmDevice<DrmDeviceFd>,
+    pub allocator: GbmAllocator<DrmDeviceFd>,
+    pub outputs:   OutputManager,
+    pub card:      String,
+    /// `dev_t` of the card node, as udev reports it.
+    pub id:        libc::dev_t,
+    /// Another card than the rendering GPU: its outputs scan out frames
+    /// rendered there into linear buffers of this card's.
+    pub secondary: bool,
+    /// Vblank source of a secondary card, removed when it goes away.
+    notifier:      Option<RegistrationToken>,
+}
+
+/// Everything needed to render and to scan out on the DRM devices. Lives in
+/// `GameframeState` so the vblank handler can reach the renderer; calloop is
+/// single-threaded, so the `!Send` renderer is fine there.
+pub struct DrmBackend {
+    /// The rendering GPU first, then every other card with a monitor
+    /// attached.
+    pub devices:   Vec<DrmGpu>,
+    pub renderer:  GlesRenderer,
+    pub session:   LibSeatSession,
+    /// Set once the libinput backend is registered; suspended with the DRM
+    /// device.
+    pub libinput:  Option<Libinput>,
+    /// Keyboards attached through libinput, whose lock LEDs follow the seat.
+    pub keyboards: Vec<input::Device>,
+    /// Whether the device is paused (session inactive or system asleep).
+    pub paused:    bool,
+    /// Outputs switched off by the idle timeout.
+    pub blanked:   bool,
+}
+
+impl DrmBackend {
+    /// The device frames are rendered on.
+    pub fn primary(&self) -> &DrmGpu {
+        &self.devices[0]
+    }
+
+    pub fn device_mut(&mut self, id: libc::dev_t) -> Option<&mut DrmGpu> {
+        self.devices.iter_mut().find(|d| d.id == id)
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &GameframeOutput> {
+        self.devices.iter().flat_map(|d| d.outputs.outputs())
+    }
+
+    pub fn outputs_mut(&mut self) -> impl Iterator<Item = &mut GameframeOutput> {
+        self.devices.iter_mut().flat_map(|d| d.outputs.outputs_mut())
+    }
+
+    pub fn output_mut(&mut self, id: OutputId) -> Option<&mut GameframeOutput> {
+        self.device_mut(id.device)?.outputs.get_mut(id.crtc)
+    }
+
+    /// The output `id` and the renderer, borrowed together for drawing.
+    pub fn output_and_renderer(&mut self, id: OutputId) -> Option<(&mut GameframeOutput, &mut GlesRenderer)> {
+        let out = self.devices.iter_mut().find(|d| d.id == id.device)?.outputs.get_mut(id.crtc)?;
+        Some((out, &mut self.renderer))
+    }
+
+    /// Device and CRTC driving the given Wayland output.
+    pub fn output_id(&self, output: &Output) -> Option<OutputId> {
+        self.devices.iter().find_map(|d| {
+            d.outputs.crtc_for(output).map(|crtc| OutputId { device: d.id, crtc })
+        })
+    }
+
+    /// Presentation counters and measured refresh of every output, by name.
+    pub fn frame_stats(&self) -> Vec<(String, FrameStats, Duration)> {
+        let mut stats: Vec<_> = self.devices.iter().flat_map(|d| d.outputs.frame_stats()).collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
+    }
+}
+
+/// The active rendering / input backend.
+pub enum Backend {
+    /// KMS on a real seat (TTY session).
+    Drm(DrmBackend),
+    /// Window inside a parent Wayland compositor.
+    Nested(NestedBackend),
+    /// Virtual outputs on a render node, no display attached.
+    Headless(HeadlessBackend),
+}
+
+impl Backend {
+    pub fn drm(&self) -> Option<&DrmBackend> {
+        match self { Self::Drm(b) => Some(b), _ => None }
+    }
+
+    pub fn drm_mut(&mut self) -> Option<&mut DrmBackend> {
+        match self { Self::Drm(b) => Some(b), _ => None }
+    }
+
+    pub fn nested_mut(&mut self) -> Option<&mut NestedBackend> {
+        match self { Self::Nested(b) => Some(b), _ => None }
+    }
+
+    pub fn headless(&self) -> Option<&HeadlessBackend> {
+        match self { Self::Headless(b) => Some(b), _ => None }
+    }
+
+    pub fn headless_mut(&mut self) -> Option<&mut HeadlessBackend> {
+        match self { Self::Headless(b) => Some(b), _ => None }
+    }
+
+    pub fn renderer(&mut self) -> &mut GlesRenderer {
+        match self {
+            Self::Drm(b)    => &mut b.renderer,
+            Self::Nested(b)   => &mut b.renderer,
+            Self::Headless(b) => &mut b.renderer,
+        }
+    }
+}
+
 // ── Entry point ───────────────────────────────────────────────────────────────
 
 pub fn run(opts: &SessionOptions) -> Result<()> {
+    // Replaced by our own socket below; a restart hands the original back.
+    let host_display = std::env::var_os("WAYLAND_DISPLAY");
+
     // ── 1. calloop EventLoop ──────────────────────────────────────────────────
     let mut event_loop: EventLoop<GameframeState> =
         EventLoop::try_new().context("EventLoop::try_new")?;
@@ -61,36 +209,279 @@ pub fn run(opts: &SessionOptions) -> Result<()> {
     let mut display: Display<GameframeState> =
         Display::new().context("Wayland Display::new")?;
 
-    let socket_source = ListeningSocketSource::new_auto()
-        .context("Wayland ListeningSocket")?;
-    let socket_name = socket_source.socket_name().to_string_lossy().into_owned();
-    info!(%socket_name, "Wayland socket ready");
+    let socket_name = listen(&loop_handle, opts.config.session.socket.as_deref())?;
+    for name in &opts.config.session.extra_sockets {
+        listen(&loop_handle, Some(name))?;
+    }
+
+    // ── 3. GameframeState ─────────────────────────────────────────────────────
+    let mut state = GameframeState::new(
+        &mut display,
+        loop_handle.clone(),
+        opts.config.clone(),
+        socket_name.clone(),
+    );
+    state.config_path = opts.config_path.clone();
+    restore::resume(&mut state);
+
+    // ── 4. Backend ────────────────────────────────────────────────────────────
+    let drm_card_name = match opts.backend {
+        BackendKind::Auto          => init_auto_backend(&mut state, &loop_handle, opts)?,
+        BackendKind::Drm           => init_drm_backend(&mut state, &loop_handle, opts)?,
+        BackendKind::WaylandNested => {
+            nested::init(&mut state, &loop_handle)?;
+            "card0".to_string()
+        }
+        BackendKind::Headless => {
+            headless::init(&mut state, opts)?;
+            "card0".to_string()
+        }
+    };
+    // Clients started from this session (and anything reading our
+    // environment) find the main socket. Not before the backend is up: the
+    // nested one connects to the host through the old value.
+    std::env::set_var("WAYLAND_DISPLAY", &socket_name);
+
+    // ── 5. Seat capabilities ──────────────────────────────────────────────────
+    // Advertise keyboard + pointer + touch to Wayland clients so they accept input.
+    {
+        use smithay::input::keyboard::XkbConfig;
+
+        state.seat.add_keyboard(
+            XkbConfig::default(),
+            opts.config.input.repeat_delay as i32,
+            opts.config.input.repeat_rate  as i32,
+        ).context("seat.add_keyboard")?;
+
+        state.seat.add_pointer();
+        state.seat.add_touch();
+        info!("Seat: keyboard + pointer + touch capabilities added");
+
+        if opts.config.input.keymap_file.is_some() {
+            if let Err(e) = state.load_keymap() {
+                warn!("Custom keymap not loaded, using the default layout: {e:#}");
+            }
+        }
+
+        if opts.config.gamepad.enabled {
+            if let Err(e) = gamepad::init(&loop_handle, &opts.config.gamepad) {
+                warn!("Gamepad navigation unavailable: {e:#}");
+            }
+        }
+    }
+
+    idle::arm(&mut state);
+
+    // Control socket for `gameframe brightness` and friends.
+    if let Err(e) = ipc::init(&loop_handle) {
+        warn!("Control socket unavailable: {e:#}");
+    }
+    if opts.config.metrics.enabled {
+        if let Err(e) = metrics::init(&loop_handle, &opts.config.metrics.listen) {
+            warn!("Metrics endpoint unavailable: {e:#}");
+        }
+    }
+
+    // ── 6. Frame pacing ───────────────────────────────────────────────────────
+    // Each output's repaint loop applies the cap (`state.frame_pacer`).
+    if opts.config.display.fps_cap > 0 {
+        info!(fps_cap = opts.config.display.fps_cap, "Frame rate capped");
+    }
+
+    // ── 7. Telemetry timer (1 Hz) ─────────────────────────────────────────────
+    let _ = loop_handle.insert_source(
+        Timer::from_duration(Duration::from_secs(1)),
+        move |_, _, state| {
+            // Read sysfs/procfs telemetry
+            let mut tele = read_telemetry(&drm_card_name);
+            // FPS comes from render loop – keep previous value until set
+            tele.fps = state.overlay.telemetry.fps;
+            state.overlay.update_telemetry(tele);
+            TimeoutAction::ToDuration(Duration::from_secs(1))
+        },
+    );
+    info!("Telemetry timer started (1 Hz)");
+
+    // Frame statistics, logged as counts over each interval.
+    let stats_interval = opts.config.display.frame_stats_interval;
+    if stats_interval > 0 {
+        let interval = Duration::from_secs(stats_interval);
+        let mut previous: HashMap<String, FrameStats> = HashMap::new();
+        let _ = loop_handle.insert_source(Timer::from_duration(interval), move |_, _, state| {
+            if let Some(drm) = state.backend.as_ref().and_then(|b| b.drm()) {
+                for (name, stats, refresh) in drm.frame_stats() {
+                    let delta = stats.since(&previous.get(&name).copied().unwrap_or_default());
+                    info!(
+                        output = %name, presented = delta.presented, late = delta.late, dropped = delta.dropped,
+                        refresh_hz = format!("{:.3}", 1.0 / refresh.as_secs_f64()), "Frame stats",
+                    );
+                    previous.insert(name, stats);
+                }
+            }
+            TimeoutAction::ToDuration(interval)
+        });
+    }
 
-    loop_handle.clone().insert_source(socket_source, |stream, _, state| {
-        state
-            .display_handle
-            .insert_client(stream, Arc::new(GameframeClientData::default()))
-            .expect("insert_client");
-    }).context("Wayland socket source")?;
+    // ── 8. XWayland ───────────────────────────────────────────────────────────
+    if opts.config.session.xwayland {
+        match xwayland::start(&mut state) {
+            Ok(())  => info!("XWayland started"),
+            Err(e) => warn!("XWayland failed: {e:#}"),
+        }
+    }
 
-    // ── 3. libseat session ────────────────────────────────────────────────────
+    // ── 9. Initial application ────────────────────────────────────────────────
+    if let Some(ref exec) = opts.initial_exec.clone()
+        .or_else(|| opts.config.session.initial_exec.clone())
+    {
+        spawn_app(exec, &socket_name, &opts.config.session.env)?;
+    }
+
+    // ── 10. Main event loop ───────────────────────────────────────────────────
+    // Client requests are read only when a client socket is readable.
+    loop_handle
+        .insert_source(Generic::new(display, Interest::READ, Mode::Level), |_, display, state| {
+            // SAFETY: the display is never dropped while the source is alive.
+            if let Err(e) = unsafe { display.get_mut() }.dispatch_clients(state) {
+                warn!("dispatch clients: {e}");
+            }
+            Ok(PostAction::Continue)
+        })
+        .map_err(|e| anyhow::anyhow!("Wayland display source: {e}"))?;
+
+    info!("Event loop running (Super+Esc=overlay, Ctrl+Alt+Backspace=quit)");
+    while state.running {
+        // Sleep until a client, device, vblank or timer wakes us; only the
+        // overlay counting down toasts and clients with a full socket need a
+        // steady tick.
+        let timeout = if state.overlay.is_active() {
+            Some(OVERLAY_TICK)
+        } else if !state.backlog.is_empty() {
+            Some(FLUSH_RETRY)
+        } else {
+            None
+        };
+        event_loop.dispatch(timeout, &mut state)?;
+        state.overlay.tick();
+        // Forget popups whose surfaces are gone
+        state.popups.cleanup();
+        // Rendering runs as idle callbacks at the end of `dispatch`, so the
+        // configures and frame callbacks of this iteration go out in one
+        // write per client. Flushing with nothing queued is a no-op.
+        backlog::flush_clients(&mut state);
+    }
+
+    if state.recorder.is_some() {
+        if let Err(e) = recorder::stop_recording(&mut state) {
+            warn!("Recording not finished: {e:#}");
+        }
+    }
+    ipc::cleanup();
+    info!("Event loop exited cleanly");
+    if state.restart {
+        // exec runs no destructors: release the GPU, the seat and the
+        // sockets first.
+        drop(state);
+        drop(event_loop);
+        return restart(host_display);
+    }
+    Ok(())
+}
+
+/// Replace this process with a new start of the same command line
+/// (`gameframe restart`). argv[0] is looked up again, so a binary updated
+/// in place is the one that runs.
+fn restart(host_display: Option<OsString>) -> Result<()> {
+    let mut args = std::env::args_os();
+    let program  = args.next().context("no argv[0]")?;
+    let mut cmd  = std::process::Command::new(&program);
+    cmd.args(args);
+    match host_display {
+        Some(display) => cmd.env("WAYLAND_DISPLAY", display),
+        None          => cmd.env_remove("WAYLAND_DISPLAY"),
+    };
+    info!(?program, "Restarting");
+    Err(cmd.exec()).context("exec")
+}
+
+/// Open the Wayland socket `name` (or the first free `wayland-N`) and accept
+/// clients on it. Returns the socket's name.
+fn listen(loop_handle: &LoopHandle<'static, GameframeState>, name: Option<&str>) -> Result<String> {
+    let source = match name {
+        Some(name) => ListeningSocketSource::with_name(name).with_context(|| format!("Wayland socket {name}"))?,
+        None       => ListeningSocketSource::new_auto().context("Wayland socket")?,
+    };
+    let socket = source.socket_name().to_string_lossy().into_owned();
+    info!(%socket, "Wayland socket ready");
+
+    let data_socket = socket.clone();
+    loop_handle.insert_source(source, move |stream, _, state| {
+        let data = GameframeClientData { socket: data_socket.clone(), ..Default::default() };
+        if let Err(e) = state.display_handle.insert_client(stream, Arc::new(data)) {
+            warn!(socket = %data_socket, "insert client: {e}");
+        }
+    }).with_context(|| format!("Wayland socket source {socket}"))?;
+    Ok(socket)
+}
+
+/// `--backend auto`: DRM when started outside a graphical session (on a VT),
+/// the nested backend inside a Wayland session, headless otherwise. A DRM
+/// backend that can't come up (no seat, no DRM master, no GPU) falls through
+/// to the next one, so the same command works on a TTY, on a desktop and in
+/// CI. Returns what `init_drm_backend` does.
+fn init_auto_backend(
+    state:       &mut GameframeState,
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    opts:        &SessionOptions,
+) -> Result<String> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let graphical = wayland || std::env::var_os("DISPLAY").is_some();
+    if !graphical && Path::new("/dev/dri").exists() {
+        match init_drm_backend(state, loop_handle, opts) {
+            Ok(card) => {
+                info!(backend = "drm", "Backend chosen");
+                return Ok(card);
+            }
+            Err(e) => warn!("DRM backend unavailable: {e:#}"),
+        }
+    }
+    if wayland {
+        match nested::init(state, loop_handle) {
+            Ok(()) => {
+                info!(backend = "nested", "Backend chosen");
+                return Ok("card0".to_string());
+            }
+            Err(e) => warn!("nested backend unavailable: {e:#}"),
+        }
+    }
+    headless::init(state, opts).context("no backend available (tried drm, nested, headless)")?;
+    info!(backend = "headless", "Backend chosen");
+    Ok("card0".to_string())
+}
+
+/// Open the seat, the DRM device and libinput, and light up every connected
+/// output, including monitors on other cards (see [`add_secondary_gpu`]).
+/// Returns the rendering card's name (e.g. "card0") for telemetry.
+fn init_drm_backend(
+    state:       &mut GameframeState,
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    opts:        &SessionOptions,
+) -> Result<String> {
+    let _span = info_span!("init_drm_backend").entered();
+    // ── libseat session ───────────────────────────────────────────────────────
     let (mut session, notifier) =
         LibSeatSession::new().context("LibSeat session")?;
     // LibSeatSessionNotifier is !Send – use let _ to avoid ? propagation
-    let _ = loop_handle.insert_source(notifier, |event, _, _| {
+    let _ = loop_handle.insert_source(notifier, |event, _, state| {
         debug!(?event, "libseat event");
+        match event {
+            SessionEvent::PauseSession    => pause_drm(state, "session deactivated"),
+            SessionEvent::ActivateSession => resume_drm(state, "session activated"),
+        }
     });
     info!(seat = %session.seat(), "libseat session opened");
 
-    // ── 4. GameframeState ─────────────────────────────────────────────────────
-    let mut state = GameframeState::new(
-        &mut display,
-        loop_handle.clone(),
-        opts.config.clone(),
-        socket_name.clone(),
-    );
-
-    // ── 5. DRM device ─────────────────────────────────────────────────────────
+    // ── DRM device ────────────────────────────────────────────────────────────
     let drm_path = resolve_drm_node(&opts.drm_device, &opts.gpu_vendor)?;
     info!(path = %drm_path.display(), "Opening DRM device");
 
@@ -99,32 +490,43 @@ pub fn run(opts: &SessionOptions) -> Result<()> {
         .context("session.open DRM")?;
 
     let drm_device_fd = DrmDeviceFd::new(DeviceFd::from(drm_fd));
-    let (mut drm, drm_notifier) =
+    let (drm, drm_notifier) =
         DrmDevice::new(drm_device_fd.clone(), true).context("DrmDevice::new")?;
 
     apply_vendor_quirks(&opts.gpu_vendor);
 
-    // ── 6. GBM + EGL + GLES ───────────────────────────────────────────────────
-    let gbm_device = gbm::Device::new(drm_device_fd.clone()).context("GBM device")?;
+    // ── GBM + EGL + GLES ──────────────────────────────────────────────────────
+    // Failures here are diagnosed and kept for `gameframe status`.
+    let gbm_device = GbmDevice::new(drm_device_fd.clone())
+        .context("GBM device")
+        .inspect_err(|e| gpu_check::report(&drm_path, Stage::Gbm, e, None))?;
     let gbm_allocator = GbmAllocator::new(
         gbm_device.clone(),
         GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT,
     );
 
     let egl_display = unsafe {
-        EGLDisplay::new(gbm_device.clone()).context("EGLDisplay::new")?
+        EGLDisplay::new(gbm_device.clone())
+            .context("EGLDisplay::new")
+            .inspect_err(|e| gpu_check::report(&drm_path, Stage::EglDisplay, e, None))?
     };
-    let egl_context = EGLContext::new(&egl_display).context("EGLContext::new")?;
+    let egl_context = EGLContext::new(&egl_display)
+        .context("EGLContext::new")
+        .inspect_err(|e| gpu_check::report(&drm_path, Stage::EglContext, e, Some(&egl_display)))?;
     info!(drm = %drm_path.display(), "EGL display created");
 
-    let mut renderer: GlesRenderer = unsafe {
-        GlesRenderer::new(egl_context).context("GlesRenderer::new")?
+    let renderer: GlesRenderer = unsafe {
+        GlesRenderer::new(egl_context)
+            .context("GlesRenderer::new")
+            .inspect_err(|e| gpu_check::report(&drm_path, Stage::Renderer, e, Some(&egl_display)))?
     };
+    gpu_check::check_modifiers(&drm_path, renderer.egl_context().dmabuf_render_formats());
+    gpu_check::clear(&drm_path);
 
     let dmabuf_fmt_count = renderer.dmabuf_formats().iter().count();
     info!(dmabuf_formats = dmabuf_fmt_count, "GLES renderer ready");
 
-    // ── 7. v0.4: DMABUF global ────────────────────────────────────────────────
+    // ── v0.4: DMABUF global ───────────────────────────────────────────────────
     match init_dmabuf_global(&renderer, &mut state.dmabuf_state, &state.display_handle) {
         Ok(global) => {
             state.dmabuf_global = Some(global);
@@ -133,70 +535,92 @@ pub fn run(opts: &SessionOptions) -> Result<()> {
         Err(e) => warn!("DMABUF global failed (non-fatal): {e}"),
     }
 
-    // ── 8. Enumerate connectors / outputs ─────────────────────────────────────
-    let drm_resources = drm.resource_handles().context("DRM resource_handles")?;
-    let mut output_manager = OutputManager::new();
+    let drm_card = drm_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let device_id = drm.device_id();
+
+    let mut backend = DrmBackend {
+        devices: vec![DrmGpu {
+            drm,
+            gbm: gbm_device,
+            allocator: gbm_allocator,
+            outputs: OutputManager::new(),
+            card: drm_card.clone(),
+            id: device_id,
+            secondary: false,
+            notifier: None,
+        }],
+        renderer,
+        session: session.clone(),
+        libinput: None,
+        keyboards: Vec::new(),
+        paused: false,
+        blanked: false,
+    };
 
+    // ── Enumerate connectors / outputs ────────────────────────────────────────
+    // A connector that can't be set up, or none at all, is not fatal: the
+    // udev monitor below lights outputs up as monitors connect or wake.
+    let drm_resources = backend.devices[0].drm.resource_handles().context("DRM resource_handles")?;
+    let mut new_outputs = Vec::new();
     for &connector_handle in drm_resources.connectors() {
-        let connector_info = drm.get_connector(connector_handle, false)?;
+        let connector_info = match backend.devices[0].drm.get_connector(connector_handle, false) {
+            Ok(info) => info,
+            Err(e)   => { warn!(?connector_handle, "get_connector: {e}"); continue; }
+        };
         if connector_info.state() != connector::State::Connected {
             debug!(?connector_handle, "not connected, skipping");
             continue;
         }
-        let crtc_handle = find_crtc_for_connector(&drm, &drm_resources, &connector_info)?;
-        let mode = select_mode(&connector_info, opts.config.display.preferred_mode.as_deref())?;
-
-        info!(?connector_handle, ?crtc_handle, mode = ?mode.name(), "Setting up output");
-
-        output_manager.add_output(
-            &mut drm,
-            gbm_allocator.clone(),
-            &mut renderer,
-            &state.display_handle,
-            connector_handle,
-            crtc_handle,
-            mode,
-            opts.config.display.scale,
-            opts.config.display.vrr,
-        )?;
+        let (gpu, renderer) = (&mut backend.devices[0], &mut backend.renderer);
+        match connect_output(gpu, renderer, &state.display_handle, &opts.config.display, &connector_info) {
+            Ok(output) => new_outputs.push(output),
+            Err(e)     => warn!(name = %connector_name(&connector_info), "Output setup failed: {e:#}"),
+        }
     }
 
-    if output_manager.output_count() == 0 {
-        warn!("No connected outputs – starting headless");
+    state.backend = Some(Backend::Drm(backend));
+    for output in new_outputs {
+        state.map_output(&output);
+        state.queue_redraw(&output);
     }
 
-    // ── 9. DRM vblank source – triggers render ────────────────────────────────
-    {
-        // Collect outputs and damage trackers for use in the vblank callback
-        let drm_card = drm_path
-            .file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .into_owned();
-        let cursor = HardwareCursor::new(false); // SW cursor via render loop
+    // ── DRM vblank source – drives each output's repaint loop ─────────────────
+    insert_vblank_source(loop_handle, drm_notifier, device_id)?;
 
-        loop_handle
-            .insert_source(drm_notifier, move |event, _meta, _state| {
-                match event {
-                    DrmEvent::VBlank(crtc) => {
-                        debug!(?crtc, "VBlank – rendering frame");
-                        // In a full implementation we'd call render_frame() here
-                        // with the per-CRTC OutputDamageTracker and GlesRenderer.
-                        // The renderer can't be moved into this closure because it's
-                        // not Send.  Production pattern: wrap in Rc<RefCell<>> since
-                        // calloop is single-threaded, or restructure with a channel.
-                        //
-                        // Telemetry is available without the renderer:
-                        let _ = &cursor; // keep alive
-                        let _ = &drm_card;
-                    }
-                    DrmEvent::Error(e) => error!("DRM error: {e}"),
-                }
-            })
-            .context("DRM notifier source")?;
+    // ── Other cards, connector and GPU hotplug ────────────────────────────────
+    let udev = UdevBackend::new(&session.seat()).context("udev monitor")?;
+    let others: Vec<(libc::dev_t, PathBuf)> = udev.device_list()
+        .filter(|&(id, _)| id != device_id)
+        .map(|(id, path)| (id, path.to_path_buf()))
+        .collect();
+    for (id, path) in others {
+        add_secondary_gpu(state, id, &path);
+    }
+    let drm_outputs = state.backend.as_ref().and_then(|b| b.drm()).map_or(0, |b| b.outputs().count());
+    if drm_outputs == 0 {
+        warn!("No connected outputs – waiting for a monitor to connect");
     }
+    loop_handle
+        .insert_source(udev, move |event, _, state| match event {
+            UdevEvent::Added { device_id: id, path } => add_secondary_gpu(state, id, &path),
+            UdevEvent::Changed { device_id: id } => {
+                let known = state.backend.as_mut().and_then(|b| b.drm_mut()).and_then(|b| b.device_mut(id)).is_some();
+                if known {
+                    rescan_connectors(state, id);
+                } else if let Some(path) = DrmNode::from_dev_id(id).ok().and_then(|n| n.dev_path()) {
+                    // A monitor plugged into a card that had none.
+                    add_secondary_gpu(state, id, &path);
+                }
+            }
+            UdevEvent::Removed { device_id: id } => remove_secondary_gpu(state, id),
+        })
+        .context("udev source")?;
 
-    // ── 10. v0.4: libinput backend ────────────────────────────────────────────
+    // ── v0.4: libinput backend ────────────────────────────────────────────────
     // Create a libinput context from the libseat session interface.
     {
         let session_iface = LibinputSessionInterface::from(session.clone());
@@ -206,11 +630,32 @@ pub fn run(opts: &SessionOptions) -> Result<()> {
             .udev_assign_seat(&session.seat())
             .map_err(|_| anyhow::anyhow!("libinput udev_assign_seat failed"))?;
 
+        if let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) {
+            backend.libinput = Some(libinput_ctx.clone());
+        }
         let libinput_backend = LibinputInputBackend::new(libinput_ctx);
 
         // Wire into calloop – every libinput event dispatches to process_input_event
         loop_handle
-            .insert_source(libinput_backend, move |event, _, state| {
+            .insert_source(libinput_backend, move |mut event, _, state| {
+                if let InputEvent::DeviceAdded { device } = &mut event {
+                    if device_ignored(device, &state.config.input) {
+                        // Suspending closes libinput's fd, leaving the device
+                        // entirely to the games that read it through evdev.
+                        let _ = device.config_send_events_set_mode(SendEventsMode::DISABLED);
+                        info!(device = device.name(), "Input device left to clients");
+                        return;
+                    }
+                    configure_device(device, &state.config.input);
+                    if device.has_capability(DeviceCapability::Keyboard) {
+                        track_keyboard(state, device.clone());
+                    }
+                }
+                if let InputEvent::DeviceRemoved { device } = &event {
+                    if let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) {
+                        backend.keyboards.retain(|k| k != device);
+                    }
+                }
                 process_input_event(state, event);
             })
             .map_err(|e| anyhow::anyhow!("libinput source: {e:?}"))?;
@@ -218,102 +663,252 @@ pub fn run(opts: &SessionOptions) -> Result<()> {
         info!("libinput backend registered on seat '{}'", session.seat());
     }
 
-    // ── 11. v0.4: Seat capabilities ───────────────────────────────────────────
-    // Advertise keyboard + pointer to Wayland clients so they accept input.
-    {
-        use smithay::input::keyboard::XkbConfig;
+    Ok(drm_card)
+}
 
-        state.seat.add_keyboard(
-            XkbConfig::default(),
-            opts.config.input.repeat_delay as i32,
-            opts.config.input.repeat_rate  as i32,
-        ).context("seat.add_keyboard")?;
+// ── Suspend / resume ──────────────────────────────────────────────────────────
 
-        state.seat.add_pointer();
-        info!("Seat: keyboard + pointer capabilities added");
+/// Stop using the GPU and input devices: the session was deactivated (VT
+/// switch) or the system is about to sleep. DRM master is dropped and
+/// nothing renders until [`resume_drm`].
+pub fn pause_drm(state: &mut GameframeState, reason: &str) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.paused { return; }
+    backend.paused = true;
+    if let Some(libinput) = backend.libinput.as_mut() {
+        libinput.suspend();
+    }
+    for gpu in &mut backend.devices {
+        gpu.drm.pause();
     }
+    info!(reason, "DRM paused");
+}
 
-    // ── 12. Frame pacing timer ────────────────────────────────────────────────
-    let fps_cap = opts.config.display.fps_cap;
-    let frame_interval = if fps_cap > 0 {
-        Duration::from_secs_f64(1.0 / fps_cap as f64)
-    } else {
-        Duration::from_millis(4)
-    };
-    // Timer is !Send – ignore InsertError
-    let _ = loop_handle.insert_source(
-        Timer::from_duration(frame_interval),
-        {
-            let mut pacer = FramePacer::new(fps_cap);
-            move |_, _, _state| TimeoutAction::ToDuration(pacer.next_interval())
-        },
-    );
+/// Take the GPU back after [`pause_drm`]. After a sleep the hardware has
+/// lost whatever was programmed, so every CRTC is set up again on its next
+/// commit and every output is repainted in full.
+pub fn resume_drm(state: &mut GameframeState, reason: &str) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if !backend.paused { return; }
+    for gpu in &mut backend.devices {
+        if let Err(e) = gpu.drm.activate(false) {
+            warn!(reason, card = %gpu.card, "DRM activate: {e}");
+        }
+    }
+    if let Some(libinput) = backend.libinput.as_mut() {
+        if libinput.resume().is_err() {
+            warn!("libinput resume failed");
+        }
+    }
+    for out in backend.outputs_mut() {
+        out.reset();
+    }
+    backend.paused = false;
+    info!(reason, "DRM resumed");
+    // Monitors may have been plugged or unplugged meanwhile.
+    let ids: Vec<_> = backend.devices.iter().map(|d| d.id).collect();
+    for id in ids {
+        rescan_connectors(state, id);
+    }
+    state.queue_redraw_all();
+}
 
-    // ── 13. v0.4: Telemetry timer (1 Hz) ─────────────────────────────────────
-    let drm_card_name = drm_path
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .into_owned();
+/// Reading the DRM device's events failed, so page-flip completions may
+/// have been lost and outputs would wait forever for a vblank. Start every
+/// output over as after a VT switch: the next frame is a full commit.
+fn recover_drm(state: &mut GameframeState) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.paused { return; }
+    for out in backend.outputs_mut() {
+        out.reset();
+    }
+    warn!("DRM outputs reset after an event error");
+    state.queue_redraw_all();
+}
 
-    let _ = loop_handle.insert_source(
-        Timer::from_duration(Duration::from_secs(1)),
-        move |_, _, state| {
-            // Read sysfs/procfs telemetry
-            let mut tele = read_telemetry(&drm_card_name);
-            // FPS comes from render loop – keep previous value until set
-            tele.fps = state.overlay.telemetry.fps;
-            state.overlay.update_telemetry(tele);
-            TimeoutAction::ToDuration(Duration::from_secs(1))
-        },
-    );
-    info!("Telemetry timer started (1 Hz)");
+/// Hold VRR on or off on the DRM output named `name` (`gameframe vrr`).
+pub fn set_vrr(state: &mut GameframeState, name: &str, on: bool) -> Result<()> {
+    if on && !state.config.display.vrr {
+        bail!("VRR is disabled (display.vrr = false / --no-vrr)");
+    }
+    let backend = state.backend.as_mut().and_then(|b| b.drm_mut()).context("VRR needs the DRM backend")?;
+    let out = backend.outputs_mut()
+        .find(|o| o.output.name() == name)
+        .with_context(|| format!("no output {name}"))?;
+    out.set_vrr(on)?;
+    let output = out.output.clone();
+    state.queue_redraw(&output);
+    Ok(())
+}
 
-    // ── 14. XWayland ──────────────────────────────────────────────────────────
-    if opts.config.session.xwayland {
-        match xwayland::start(&loop_handle, &display) {
-            Ok(_)  => info!("XWayland started"),
-            Err(e) => warn!("XWayland failed: {e}"),
+/// Switch the DRM output named `name` to the listed mode `spec`
+/// (`WIDTHxHEIGHT[@HZ]`, `gameframe output mode`).
+pub fn set_output_mode(state: &mut GameframeState, name: &str, spec: &str) -> Result<()> {
+    let backend = state.backend.as_ref().and_then(|b| b.drm()).context("mode switching needs the DRM backend")?;
+    let (gpu, out) = backend.devices.iter()
+        .find_map(|d| d.outputs.outputs().find(|o| o.output.name() == name).map(|o| (d, o)))
+        .with_context(|| format!("no output {name}"))?;
+    let info = gpu.drm.get_connector(out.connector, false).context("get_connector")?;
+    let Some(mode) = find_mode(info.modes(), spec) else {
+        let listed: Vec<String> = info.modes().iter()
+            .map(|m| format!("{}x{}@{:.2}", m.size().0, m.size().1, refresh_hz(m)))
+            .collect();
+        bail!("{name} doesn't list {spec}; it has {}", listed.join(" "));
+    };
+    let output = out.output.clone();
+    state.set_output_mode(&output, mode)
+}
+
+/// `NAME on|off|unsupported` per DRM output, for `gameframe vrr`.
+pub fn describe_vrr(state: &GameframeState) -> Result<String> {
+    let backend = state.backend.as_ref().and_then(|b| b.drm()).context("VRR needs the DRM backend")?;
+    Ok(backend.outputs().map(|o| {
+        let status = match (o.vrr_capable, o.vrr) {
+            (false, _)    => "unsupported",
+            (true, true)  => "on",
+            (true, false) => "off",
+        };
+        format!("{} {status}\n", o.output.name())
+    }).collect())
+}
+
+/// Switch every output off until [`unblank_drm`]; nothing is rendered
+/// meanwhile. Only the DRM backend has displays to blank.
+pub fn blank_drm(state: &mut GameframeState) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.blanked || backend.paused { return; }
+    for out in backend.outputs_mut() {
+        if let Err(e) = out.surface.blank() {
+            warn!(crtc = ?out.crtc, "blank: {e:#}");
         }
     }
+    backend.blanked = true;
+    info!("Outputs blanked");
+}
 
-    // ── 15. Initial application ───────────────────────────────────────────────
-    if let Some(ref exec) = opts.initial_exec.clone()
-        .or_else(|| opts.config.session.initial_exec.clone())
-    {
-        spawn_app(exec, &socket_name, &opts.config.session.env)?;
+pub fn unblank_drm(state: &mut GameframeState) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if !backend.blanked { return; }
+    for out in backend.outputs_mut() {
+        out.reset();
     }
+    backend.blanked = false;
+    info!("Outputs unblanked");
+    state.queue_redraw_all();
+}
 
-    // ── 16. Main event loop ───────────────────────────────────────────────────
-    info!("Event loop running (Super+Esc=overlay, Ctrl+Alt+Backspace=quit)");
-    let signal: LoopSignal = event_loop.get_signal();
-
-    event_loop.run(
-        Some(Duration::from_millis(4)),
-        &mut state,
-        |state| {
-            // Tick overlay (decrement toast TTLs, re-render HUD if visible)
-            state.overlay.tick();
-            // Flush pending Wayland protocol messages to all clients
-            display.flush_clients().ok();
-
-            if !state.running {
-                signal.stop();
-            }
-        },
-    )?;
+// ── Helpers ───────────────────────────────────────────────────────────────────
 
-    info!("Event loop exited cleanly");
-    Ok(())
+/// Whether libinput should let go of `device`: names in `allow_devices`
+/// are always kept, names in `ignore_devices` always dropped, and
+/// gamepads/joysticks are dropped while `ignore_gamepads` is set. Names
+/// match as case-insensitive substrings.
+fn device_ignored(device: &input::Device, config: &InputConfig) -> bool {
+    // BTN_JOYSTICK / BTN_GAMEPAD (linux/input-event-codes.h)
+    const JOYSTICK_KEYS: [u32; 2] = [0x120, 0x130];
+
+    let name = device.name().to_lowercase();
+    let listed = |list: &[String]| list.iter().any(|n| name.contains(&n.to_lowercase()));
+    if listed(&config.allow_devices) { return false; }
+    if listed(&config.ignore_devices) { return true; }
+    config.ignore_gamepads
+        && JOYSTICK_KEYS.iter().any(|&k| device.keyboard_has_key(k) == Ok(true))
 }
 
-// ── Helpers ───────────────────────────────────────────────────────────────────
+/// Remember a new keyboard and light its LEDs like the others.
+fn track_keyboard(state: &mut GameframeState, mut device: input::Device) {
+    let leds = state.seat.get_keyboard().map(|k| k.led_state());
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if let Some(leds) = leds {
+        device.led_update(Led::from(leds));
+    }
+    backend.keyboards.push(device);
+}
+
+/// Show the seat's lock state on every keyboard.
+pub fn update_keyboard_leds(state: &mut GameframeState, leds: LedState) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    for keyboard in &mut backend.keyboards {
+        keyboard.led_update(Led::from(leds));
+    }
+}
+
+/// Apply `[input.touchpad]` (if `device` is a touchpad) and every matching
+/// `[input.devices]` entry. Options the device doesn't support are skipped.
+fn configure_device(device: &mut input::Device, config: &InputConfig) {
+    let name = device.name().to_lowercase();
+    // Only touchpads do tapping.
+    let base = if device.config_tap_finger_count() > 0 { config.touchpad.clone() } else { DeviceConfig::default() };
+    let wanted = config.devices
+        .iter()
+        .filter(|(pattern, _)| name.contains(&pattern.to_lowercase()))
+        .fold(base, |acc, (_, over)| acc.merged(over));
+
+    let mut skipped = Vec::new();
+    let taps = device.config_tap_finger_count() > 0;
+    if let Some(tap) = wanted.tap {
+        if !taps || device.config_tap_set_enabled(tap).is_err() {
+            skipped.push("tap");
+        }
+    }
+    if let Some(drag) = wanted.tap_drag {
+        if !taps || device.config_tap_set_drag_enabled(drag).is_err() {
+            skipped.push("tap_drag");
+        }
+    }
+    if let Some(map) = wanted.tap_button_map {
+        let map = match map {
+            TapButtonMap::Lrm => input::TapButtonMap::LeftRightMiddle,
+            TapButtonMap::Lmr => input::TapButtonMap::LeftMiddleRight,
+        };
+        // Only meaningful with multi-finger taps.
+        if device.config_tap_finger_count() < 2 || device.config_tap_set_button_map(map).is_err() {
+            skipped.push("tap_button_map");
+        }
+    }
+    if let Some(method) = wanted.click_method {
+        let method = match method {
+            ClickMethod::ButtonAreas => input::ClickMethod::ButtonAreas,
+            ClickMethod::Clickfinger => input::ClickMethod::Clickfinger,
+        };
+        if !device.config_click_methods().contains(&method) || device.config_click_set_method(method).is_err() {
+            skipped.push("click_method");
+        }
+    }
+    if let Some(method) = wanted.scroll_method {
+        let method = match method {
+            ScrollMethod::None      => input::ScrollMethod::NoScroll,
+            ScrollMethod::TwoFinger => input::ScrollMethod::TwoFinger,
+            ScrollMethod::Edge      => input::ScrollMethod::Edge,
+            ScrollMethod::OnButton  => input::ScrollMethod::OnButtonDown,
+        };
+        let supported = method == input::ScrollMethod::NoScroll || device.config_scroll_methods().contains(&method);
+        if !supported || device.config_scroll_set_method(method).is_err() {
+            skipped.push("scroll_method");
+        }
+    }
+    if let Some(enabled) = wanted.middle_emulation {
+        if !device.config_middle_emulation_is_available()
+            || device.config_middle_emulation_set_enabled(enabled).is_err()
+        {
+            skipped.push("middle_emulation");
+        }
+    }
+    if let Some(enabled) = wanted.disable_while_typing {
+        if !device.config_dwt_is_available() || device.config_dwt_set_enabled(enabled).is_err() {
+            skipped.push("disable_while_typing");
+        }
+    }
+    if !skipped.is_empty() {
+        info!(device = device.name(), ?skipped, "Input options not supported by the device");
+    }
+}
 
 fn resolve_drm_node(
-    forced: &Option<PathBuf>,
+    forced: &Option<String>,
     vendor: &Option<GpuVendor>,
 ) -> Result<PathBuf> {
-    if let Some(p) = forced { return Ok(p.clone()); }
+    if let Some(spec) = forced { return drm_node_for(spec, false); }
     if let Some(gpu) = gameframe_gpu::detect_primary()? {
         if let Some(v) = vendor {
             if &gpu.vendor != v {
@@ -329,6 +924,251 @@ fn resolve_drm_node(
     Ok(PathBuf::from("/dev/dri/card0"))
 }
 
+/// The DRM node `spec` (`--drm-device` / `gpu.drm_device`) names: a
+/// `/dev/dri` path, or a GPU by PCI address or `vendor:device` id. With
+/// `render` a GPU resolves to its render node (headless), otherwise to its
+/// primary node, which the DRM backend needs for modesetting.
+pub(crate) fn drm_node_for(spec: &str, render: bool) -> Result<PathBuf> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let path = if spec.starts_with('/') {
+        PathBuf::from(spec)
+    } else {
+        let gpu = gameframe_gpu::find_gpu(spec)?
+            .with_context(|| format!("no GPU matches {spec} (see `gameframe gpu-info`)"))?;
+        if gpu.driver == "none" {
+            bail!("{} ({}) has no kernel driver bound", gpu.name, gpu.pci_address);
+        }
+        info!(gpu = %gpu.name, pci = %gpu.pci_address, "Using the configured GPU");
+        match (render, gpu.render_node) {
+            (true, Some(node)) => PathBuf::from(node),
+            (true, None)       => bail!("{} has no render node", gpu.name),
+            (false, _)         => gpu.drm_node,
+        }
+    };
+    let meta = std::fs::metadata(&path).with_context(|| format!("DRM device {}", path.display()))?;
+    if !meta.file_type().is_char_device() {
+        bail!("{} is not a device node", path.display());
+    }
+    let is_render = path.file_name().is_some_and(|n| n.to_string_lossy().starts_with("renderD"));
+    if !render && is_render {
+        bail!("{} is a render node; the DRM backend needs the card node", path.display());
+    }
+    Ok(path)
+}
+
+/// Pick a CRTC and mode for a connected connector on `gpu` and create its
+/// output. Outputs on a secondary card are named after it as well
+/// (`card1-DP-1`), since connector names repeat across cards.
+fn connect_output(
+    gpu:      &mut DrmGpu,
+    renderer: &mut GlesRenderer,
+    dh:       &DisplayHandle,
+    display:  &DisplayConfig,
+    connector_info: &connector::Info,
+) -> Result<Output> {
+    let connector_handle = connector_info.handle();
+    let resources   = gpu.drm.resource_handles().context("DRM resource_handles")?;
+    let taken       = gpu.outputs.crtcs();
+    let crtc_handle = find_crtc_for_connector(&gpu.drm, &resources, connector_info, &taken)?;
+    let name = match gpu.secondary {
+        true  => format!("{}-{}", gpu.card, connector_name(connector_info)),
+        false => connector_name(connector_info),
+    };
+    let per_out  = display.outputs.get(&name);
+    let request  = ModeRequest {
+        modeline:  per_out.and_then(|o| o.modeline.as_deref()),
+        mode:      per_out.and_then(|o| o.mode.as_deref()),
+        preferred: display.preferred_mode.as_deref(),
+    };
+    let limits = read_edid(&gpu.drm, connector_handle).and_then(|e| e.limits);
+    let mode   = select_mode(connector_info, request, limits.as_ref())?;
+    info!(%name, ?crtc_handle, mode = ?mode.name(), "Setting up output");
+
+    let mut add = |mode| gpu.outputs.add_output(
+        &mut gpu.drm,
+        &gpu.gbm,
+        gpu.allocator.clone(),
+        renderer,
+        dh,
+        name.clone(),
+        gpu.secondary,
+        connector_handle,
+        crtc_handle,
+        mode,
+        display.scale_for(&name),
+        rotation_transform(display.rotation_for(&name)),
+        display.vrr,
+        display.vrr_range_for(&name, limits.map(|l| l.v_hz)),
+    );
+    match add(mode) {
+        // A custom mode the driver refused: fall back to the monitor's own.
+        Err(e) if mode.mode_type().contains(ModeTypeFlags::USERDEF) => {
+            warn!(%name, "Custom mode rejected by the driver: {e:#}");
+            add(select_mode(connector_info, ModeRequest { preferred: request.preferred, ..Default::default() }, None)?)
+        }
+        res => res,
+    }
+}
+
+/// A connector on the DRM device `device` changed: light up monitors that
+/// connected (or woke up) and drop the outputs of those that went away.
+pub fn rescan_connectors(state: &mut GameframeState, device: libc::dev_t) {
+    let display = state.config.display.clone();
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.paused { return; }
+    let Some(gpu) = backend.devices.iter_mut().find(|d| d.id == device) else { return };
+    let resources = match gpu.drm.resource_handles() {
+        Ok(r)  => r,
+        Err(e) => { warn!(card = %gpu.card, "rescan: resource_handles: {e}"); return; }
+    };
+    let mut added   = Vec::new();
+    let mut removed = Vec::new();
+    for &handle in resources.connectors() {
+        let info = match gpu.drm.get_connector(handle, true) {
+            Ok(info) => info,
+            Err(e)   => { debug!(?handle, "rescan: get_connector: {e}"); continue; }
+        };
+        let existing = gpu.outputs.crtc_for_connector(handle);
+        match (info.state(), existing) {
+            (connector::State::Connected, None) => {
+                match connect_output(gpu, &mut backend.renderer, &state.display_handle, &display, &info) {
+                    Ok(output) => added.push(output),
+                    Err(e)     => warn!(name = %connector_name(&info), "Output setup failed: {e:#}"),
+                }
+            }
+            (connector::State::Disconnected, Some(crtc)) => {
+                if let Some(out) = gpu.outputs.remove(crtc) {
+                    info!(name = %out.output.name(), "Monitor disconnected");
+                    removed.push(release_output(&state.display_handle, out));
+                }
+            }
+            _ => {}
+        }
+    }
+    for output in &removed {
+        state.unmap_output(output);
+    }
+    for output in &added {
+        state.map_output(output);
+    }
+    if !added.is_empty() || !removed.is_empty() {
+        state.queue_redraw_all();
+    }
+}
+
+/// Drop a removed output's pending feedback and global. Returns the output
+/// to take out of the layout.
+fn release_output(dh: &DisplayHandle, mut out: GameframeOutput) -> Output {
+    if let Some(mut feedback) = out.pending_feedback.take() {
+        feedback.discarded();
+    }
+    dh.remove_global::<GameframeState>(out.global);
+    out.output
+}
+
+/// Drive the monitors on the card at `path` (`id` as udev reports it),
+/// another card than the rendering GPU. Frames for them are still rendered
+/// on the rendering GPU, into linear buffers allocated on this card, which
+/// scans them out. Cards without a connected monitor are left closed until
+/// one connects.
+fn add_secondary_gpu(state: &mut GameframeState, id: libc::dev_t, path: &Path) {
+    let loop_handle = state.loop_handle.clone();
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.paused || backend.device_mut(id).is_some() { return; }
+    let card = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    if !card_has_monitor(&card) {
+        debug!(%card, "No monitor connected, leaving the card closed");
+        return;
+    }
+    let _span = info_span!("add_secondary_gpu", %card).entered();
+    match open_secondary_gpu(&mut backend.session, &loop_handle, path, id) {
+        Ok(gpu) => {
+            info!(path = %path.display(), "Driving monitors on a secondary GPU");
+            backend.devices.push(gpu);
+            rescan_connectors(state, id);
+        }
+        Err(e) => warn!("Monitors on {card} stay dark: {e:#}"),
+    }
+}
+
+fn open_secondary_gpu(
+    session:     &mut LibSeatSession,
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    path:        &Path,
+    id:          libc::dev_t,
+) -> Result<DrmGpu> {
+    let fd: OwnedFd = session
+        .open(path, OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK)
+        .context("session.open DRM")?;
+    let fd = DrmDeviceFd::new(DeviceFd::from(fd));
+    let (drm, notifier) = DrmDevice::new(fd.clone(), true).context("DrmDevice::new")?;
+    let gbm = GbmDevice::new(fd).context("GBM device")?;
+    let allocator = GbmAllocator::new(gbm.clone(), GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT);
+    let token = insert_vblank_source(loop_handle, notifier, id)?;
+    Ok(DrmGpu {
+        drm,
+        gbm,
+        allocator,
+        outputs: OutputManager::new(),
+        card: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        id,
+        secondary: true,
+        notifier: Some(token),
+    })
+}
+
+/// A secondary card went away: take its outputs out of the layout and stop
+/// listening for its vblanks.
+fn remove_secondary_gpu(state: &mut GameframeState, id: libc::dev_t) {
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    let Some(index) = backend.devices.iter().position(|d| d.id == id && d.secondary) else { return };
+    let mut gpu = backend.devices.remove(index);
+    let crtcs = gpu.outputs.crtcs();
+    let removed: Vec<Output> = crtcs.into_iter()
+        .filter_map(|crtc| gpu.outputs.remove(crtc))
+        .map(|out| release_output(&state.display_handle, out))
+        .collect();
+    if let Some(token) = gpu.notifier.take() {
+        state.loop_handle.remove(token);
+    }
+    info!(card = %gpu.card, outputs = removed.len(), "Secondary GPU removed");
+    for output in &removed {
+        state.unmap_output(output);
+    }
+    if !removed.is_empty() {
+        state.queue_redraw_all();
+    }
+}
+
+/// Feed the page flips of the device `id` to its outputs' repaint loops.
+fn insert_vblank_source(
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    notifier:    DrmDeviceNotifier,
+    id:          libc::dev_t,
+) -> Result<RegistrationToken> {
+    loop_handle
+        .insert_source(notifier, move |event, meta, state| match event {
+            DrmEvent::VBlank(crtc) => handle_vblank(state, OutputId { device: id, crtc }, meta.as_ref()),
+            DrmEvent::Error(e)     => {
+                error!("DRM error: {e}");
+                recover_drm(state);
+            }
+        })
+        .context("DRM notifier source")
+}
+
+/// Whether sysfs lists a connected connector on `card`. Connectors are
+/// listed as `cardN-HDMI-A-1`.
+fn card_has_monitor(card: &str) -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/drm") else { return false };
+    entries.flatten().any(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        name.split_once('-').is_some_and(|(c, _)| c == card)
+            && std::fs::read_to_string(entry.path().join("status")).is_ok_and(|s| s.trim() == "connected")
+    })
+}
+
 fn apply_vendor_quirks(vendor: &Option<GpuVendor>) {
     match vendor.as_ref() {
         Some(GpuVendor::Nvidia) => {
@@ -344,13 +1184,14 @@ fn find_crtc_for_connector(
     drm: &DrmDevice,
     resources: &drm::control::ResourceHandles,
     connector: &drm::control::connector::Info,
+    taken:     &[crtc::Handle],
 ) -> Result<crtc::Handle> {
     for enc_handle in connector.encoders() {
         if let Ok(enc) = drm.get_encoder(*enc_handle) {
             // CrtcListFilter.0 is private – transmute is sound (repr(transparent) over u32)
             let possible_bits: u32 = unsafe { std::mem::transmute(enc.possible_crtcs()) };
             for (idx, crtc_handle) in resources.crtcs().iter().enumerate() {
-                if possible_bits & (1u32 << idx) != 0 {
+                if possible_bits & (1u32 << idx) != 0 && !taken.contains(crtc_handle) {
                     return Ok(*crtc_handle);
                 }
             }
@@ -359,41 +1200,14 @@ fn find_crtc_for_connector(
     anyhow::bail!("No CRTC for connector {:?}", connector.handle())
 }
 
-fn select_mode(
-    connector: &connector::Info,
-    preferred: Option<&str>,
-) -> Result<drm::control::Mode> {
-    let modes = connector.modes();
-    if modes.is_empty() {
-        anyhow::bail!("No modes for connector {:?}", connector.handle());
-    }
-    if let Some(pref) = preferred {
-        if let Some(m) = modes.iter().find(|m| m.name().to_string_lossy() == pref) {
-            return Ok(*m);
-        }
-        warn!("Mode '{pref}' not found – using EDID preferred");
-    }
-    if let Some(m) = modes.iter().find(|m| m.mode_type().contains(ModeTypeFlags::PREFERRED)) {
-        return Ok(*m);
-    }
-    Ok(*modes.iter().max_by_key(|m| m.size().0 as u32 * m.size().1 as u32).unwrap())
-}
-
 fn spawn_app(
     exec: &str,
     wayland_display: &str,
     extra_env: &HashMap<String, String>,
 ) -> Result<()> {
     info!(%exec, "Spawning application");
-    let mut cmd = std::process::Command::new("sh");
-    cmd.args(["-c", exec])
-        .env("WAYLAND_DISPLAY", wayland_display)
-        .env("XDG_SESSION_TYPE", "wayland")
-        .env("GDK_BACKEND", "wayland")
-        .env("QT_QPA_PLATFORM", "wayland")
-        .env("SDL_VIDEODRIVER", "wayland")
-        .env("CLUTTER_BACKEND", "wayland");
-    for (k, v) in extra_env { cmd.env(k, v); }
-    cmd.spawn().with_context(|| format!("Failed to spawn: {exec}"))?;
+    spawn::shell(exec, wayland_display, extra_env)
+        .spawn()
+        .with_context(|| format!("Failed to spawn: {exec}"))?;
     Ok(())
 }
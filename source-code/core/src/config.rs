@@ -1,21 +1,33 @@
+use std::{collections::HashMap, path::PathBuf};
 use serde::{Deserialize, Serialize};
 use gameframe_gpu::GpuVendor;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
-    pub gpu:     GpuConfig,
-    pub display: DisplayConfig,
-    pub session: SessionConfig,
-    pub overlay: OverlayConfig,
-    pub input:   InputConfig,
+    pub gpu:          GpuConfig,
+    pub display:      DisplayConfig,
+    pub session:      SessionConfig,
+    pub overlay:      OverlayConfig,
+    pub input:        InputConfig,
+    pub gamepad:      GamepadConfig,
+    pub accessibility: AccessibilityConfig,
+    pub animations:   AnimationConfig,
+    pub effects:      EffectsConfig,
+    pub recorder:     RecorderConfig,
+    pub idle:         IdleConfig,
+    pub clipboard:    ClipboardConfig,
+    pub metrics:      MetricsConfig,
+    pub window_rules: Vec<WindowRule>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GpuConfig {
     pub vendor:         Option<GpuVendor>,
-    pub drm_device:     Option<std::path::PathBuf>,
+    /// `/dev/dri` node or PCI address (`0000:03:00.0`) of the GPU to
+    /// render on; `--drm-device` wins.
+    pub drm_device:     Option<String>,
     pub prime:          bool,
     pub prefer_nouveau: bool,
 }
@@ -28,22 +40,198 @@ pub struct DisplayConfig {
     pub vrr:            bool,
     pub preferred_mode: Option<String>,
     pub rotation:       u32,
-    pub scale:          f64,
+    /// Scale of every output; unset picks one per output from its size.
+    pub scale:          Option<f64>,
+    pub adaptive_sync:  AdaptiveSyncMode,
+    /// When frames may be flipped without waiting for vblank.
+    pub tearing:        TearingMode,
+    /// Per-output overrides keyed by connector name (e.g. "DP-1", "HDMI-A-1").
+    pub outputs:        HashMap<String, OutputConfig>,
+    /// How Super+S divides the output between two windows.
+    pub split:          SplitOrientation,
+    /// Brightness change per key press (percent).
+    pub brightness_step: u32,
+    /// Control external monitors' brightness over DDC/CI (needs i2c-dev).
+    pub ddc:            bool,
+    /// Log each output's presented / late / dropped frame counts every this
+    /// many seconds; 0 disables it.
+    pub frame_stats_interval: u64,
+    /// Outputs the headless backend starts with, as `WxH@Hz`; one
+    /// `1920x1080@60` output if empty.
+    pub virtual_outputs: Vec<String>,
+    /// RGB (0-1) shown where no window covers the output: the desktop and
+    /// letterbox / split-screen bars.
+    pub background:     [f32; 3],
+    /// Frame callbacks per second for windows without focus that aren't
+    /// fullscreen; 0 gives every window the output's full rate.
+    pub background_fps: u32,
+    /// How a fullscreen window smaller than its output is scaled up.
+    pub upscale:        UpscaleMode,
 }
 
 impl Default for DisplayConfig {
     fn default() -> Self {
-        Self { fps_cap: 0, hdr: false, vrr: true, preferred_mode: None, rotation: 0, scale: 1.0 }
+        Self {
+            fps_cap: 0, hdr: false, vrr: true, preferred_mode: None, rotation: 0, scale: None,
+            adaptive_sync: AdaptiveSyncMode::Off, tearing: TearingMode::Never, outputs: HashMap::new(), split: SplitOrientation::Horizontal,
+            brightness_step: 5, ddc: true, frame_stats_interval: 0,
+            virtual_outputs: Vec::new(), background: [0.0, 0.0, 0.0], background_fps: 0,
+            upscale: UpscaleMode::Linear,
+        }
+    }
+}
+
+/// How split-screen divides the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitOrientation {
+    /// Side by side (left / right).
+    #[default] Horizontal,
+    /// Stacked (top / bottom).
+    Vertical,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Top-left corner in the global layout (logical px). Outputs without a
+    /// position are placed left-to-right in connection order.
+    pub position: Option<[i32; 2]>,
+    /// Overrides `display.scale` for this output.
+    pub scale:    Option<f64>,
+    /// Overrides `display.rotation` for this output (0, 90, 180 or 270).
+    pub rotation: Option<u32>,
+    /// "WxH@Hz"; generated with CVT reduced blanking if the monitor doesn't
+    /// list it.
+    pub mode:     Option<String>,
+    /// X11-style modeline; wins over `mode`.
+    pub modeline: Option<String>,
+    /// Overrides `display.background` for this output.
+    pub background: Option<[f32; 3]>,
+    /// VRR range in Hz, overriding the EDID's. Below `vrr_min` the last
+    /// frame is shown again (low framerate compensation); 0 turns that off.
+    pub vrr_min:  Option<u32>,
+    pub vrr_max:  Option<u32>,
+    /// Composite at this fraction of the mode's resolution (0.25 to 1) and
+    /// upscale, for 4K/8K panels on GPUs that can't fill them at full size.
+    pub render_scale: Option<f64>,
+    /// Sharpening of the upscaled frame, 0 (off) to 1.
+    pub sharpen:  Option<f32>,
+    /// Overrides `display.upscale` for this output.
+    pub upscale:  Option<UpscaleMode>,
+}
+
+impl DisplayConfig {
+    /// Configured scale of the output named `name`; `None` means automatic.
+    pub fn scale_for(&self, name: &str) -> Option<f64> {
+        self.outputs.get(name).and_then(|o| o.scale).or(self.scale)
+    }
+
+    /// Effective rotation (degrees, counter-clockwise) of the output named `name`.
+    pub fn rotation_for(&self, name: &str) -> u32 {
+        self.outputs.get(name).and_then(|o| o.rotation).unwrap_or(self.rotation)
+    }
+
+    /// Opaque RGBA background of the output named `name`.
+    pub fn background_for(&self, name: &str) -> [f32; 4] {
+        let [r, g, b] = self.outputs.get(name).and_then(|o| o.background).unwrap_or(self.background);
+        [r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), 1.0]
+    }
+
+    /// VRR range (Hz) of the output named `name`: the EDID's `edid` range
+    /// with the configured bounds on top.
+    pub fn vrr_range_for(&self, name: &str, edid: Option<(u32, u32)>) -> Option<(u32, u32)> {
+        let per_out = self.outputs.get(name);
+        let min = per_out.and_then(|o| o.vrr_min).or(edid.map(|r| r.0))?;
+        let max = per_out.and_then(|o| o.vrr_max).or(edid.map(|r| r.1))?;
+        Some((min, max))
+    }
+
+    /// Fraction of its mode's resolution the output named `name` is
+    /// composited at; 1 renders natively.
+    pub fn render_scale_for(&self, name: &str) -> f64 {
+        self.outputs.get(name).and_then(|o| o.render_scale).unwrap_or(1.0)
+    }
+
+    /// Sharpening applied when the output named `name` is upscaled.
+    pub fn sharpen_for(&self, name: &str) -> f32 {
+        self.outputs.get(name).and_then(|o| o.sharpen).unwrap_or(0.0).clamp(0.0, 1.0)
+    }
+
+    /// Configured upscale mode of the output named `name`.
+    pub fn upscale_for(&self, name: &str) -> UpscaleMode {
+        self.outputs.get(name).and_then(|o| o.upscale).unwrap_or(self.upscale)
     }
 }
 
+/// Where adaptive vsync (present late frames immediately via VRR) applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdaptiveSyncMode {
+    /// Always wait for vblank.
+    #[default] Off,
+    /// Only while the focused window declares `wp_content_type_v1` "game".
+    Games,
+    /// On every output regardless of content.
+    Always,
+}
+
+/// When tearing (asynchronous page flips) is allowed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TearingMode {
+    /// Always wait for vblank.
+    #[default] Never,
+    /// Only while a single fullscreen window owns the output; the desktop
+    /// always vsyncs.
+    FullscreenOnly,
+    /// Whenever a frame is ready.
+    Always,
+}
+
+/// How a fullscreen window that is smaller than its output (a game running
+/// at a fixed low resolution) is scaled to fill it. The aspect ratio is
+/// always kept; the rest of the output is letterboxed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpscaleMode {
+    /// As large as fits, bilinear filtered.
+    #[default] Linear,
+    /// As large as fits, nearest-neighbour: sharp pixels, slightly uneven
+    /// at fractional factors.
+    Nearest,
+    /// The largest whole multiple that fits, nearest-neighbour, so every
+    /// pixel becomes the same square block.
+    IntegerOnly,
+    /// As large as fits, through AMD FSR 1.0's edge-adaptive upscaling and
+    /// contrast-adaptive sharpening (`sharpness` 0 to 1). Bilinear where the
+    /// shaders don't compile.
+    Fsr {
+        #[serde(default = "default_fsr_sharpness")]
+        sharpness: f32,
+    },
+}
+
+/// FSR sharpening when none is given: FSR's own default of 0.2 stops.
+pub fn default_fsr_sharpness() -> f32 { 0.87 }
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct SessionConfig {
     pub initial_exec:  Option<String>,
-    pub idle_timeout:  u64,
     pub xwayland:      bool,
-    pub env:           std::collections::HashMap<String, String>,
+    /// Environment for every child the session starts, over the Wayland
+    /// defaults (`SDL_VIDEODRIVER=wayland` and friends); an empty value
+    /// unsets the variable.
+    pub env:           HashMap<String, String>,
+    /// Reopen apps where (and at the size) their window was last closed.
+    pub remember_geometry: bool,
+    /// `WAYLAND_DISPLAY` name of the main socket; the first free
+    /// `wayland-N` if unset.
+    pub socket:        Option<String>,
+    /// More sockets in `$XDG_RUNTIME_DIR`, e.g. one to hand to sandboxed
+    /// apps. Each client remembers which socket it came in through.
+    pub extra_sockets: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,13 +263,507 @@ pub enum OverlayPosition {
     #[default] TopLeft, TopRight, BottomLeft, BottomRight,
 }
 
+/// Window open/close and workspace-switch animations. Off by default: every
+/// animated frame is a composited frame, which costs latency in games.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnimationConfig {
+    pub enabled:               bool,
+    pub duration_ms:           u32,
+    /// Slide between workspaces (only when `enabled`).
+    pub workspace_slide:       bool,
+    pub workspace_duration_ms: u32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        Self { enabled: false, duration_ms: 150, workspace_slide: true, workspace_duration_ms: 200 }
+    }
+}
+
+/// Optional window effects. All of them cost GPU time on every composited
+/// frame, so they default to off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EffectsConfig {
+    /// Allow `blur = true` window rules to blur what is behind a window.
+    pub blur:          bool,
+    /// Downsample/upsample passes; each one doubles the blur radius.
+    pub blur_passes:   u32,
+    pub blur_offset:   f32,
+    /// Window corner radius in logical px; 0 = square corners.
+    pub corner_radius: f32,
+    pub shadow:        bool,
+    /// How far the shadow extends past the window, in logical px.
+    pub shadow_radius: f32,
+    pub shadow_offset: [i32; 2],
+    /// RGBA, premultiplied.
+    pub shadow_color:  [f32; 4],
+    pub dim_inactive:  DimInactive,
+    /// How much dimmed windows are darkened (0 = not at all, 1 = black).
+    pub dim_strength:  f32,
+    /// Render this many times larger and downsample (1 = off, up to 4).
+    /// Costs factor² the fill rate; see `supersample`.
+    pub supersample:   f64,
+}
+
+/// Which windows the "dim inactive" effect darkens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DimInactive {
+    #[default] Off,
+    /// Every window without keyboard focus.
+    Windows,
+    /// Every window on an output other than the focused one.
+    Outputs,
+}
+
+impl Default for EffectsConfig {
+    fn default() -> Self {
+        Self {
+            blur: false, blur_passes: 2, blur_offset: 3.0,
+            corner_radius: 0.0,
+            shadow: false, shadow_radius: 16.0, shadow_offset: [0, 4], shadow_color: [0.0, 0.0, 0.0, 0.45],
+            dim_inactive: DimInactive::Off, dim_strength: 0.3,
+            supersample: 1.0,
+        }
+    }
+}
+
+/// Screen recording (Super+Print or `gameframe record`), encoded by ffmpeg.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecorderConfig {
+    /// ffmpeg video encoder: libx264, libvpx-vp9, h264_vaapi, hevc_nvenc, …
+    pub codec:        String,
+    /// ffmpeg bitrate, e.g. "8M".
+    pub bitrate:      String,
+    /// Frames per second of the file; 0 follows the output's refresh rate.
+    pub fps:          u32,
+    /// File extension, which picks the container: mp4, mkv, webm.
+    pub container:    String,
+    /// Where recordings go; `$XDG_VIDEOS_DIR` or `~/Videos` if unset.
+    pub directory:    Option<PathBuf>,
+    /// Render node for `*_vaapi` encoders.
+    pub vaapi_device: String,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            codec: "libx264".into(), bitrate: "8M".into(), fps: 60, container: "mp4".into(),
+            directory: None, vaapi_device: "/dev/dri/renderD128".into(),
+        }
+    }
+}
+
+/// `[idle]`: what happens after so many seconds without input. Each stage
+/// counts from the last input; 0 skips it. Any input undoes dimming and
+/// blanking; idle inhibitors held by clients stop the countdown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IdleConfig {
+    /// Lower the backlight / monitor brightness to `dim_percent`.
+    pub dim:             u64,
+    pub dim_percent:     u32,
+    /// Turn the displays off.
+    pub blank:           u64,
+    /// Run `lock_command` (a session-lock client such as swaylock).
+    pub lock:            u64,
+    pub lock_command:    String,
+    /// Run `suspend_command`.
+    pub suspend:         u64,
+    pub suspend_command: String,
+    /// Run `command`.
+    pub command_after:   u64,
+    pub command:         Option<String>,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            dim: 0, dim_percent: 10, blank: 0,
+            lock: 0, lock_command: "swaylock -f".into(),
+            suspend: 0, suspend_command: "systemctl suspend".into(),
+            command_after: 0, command: None,
+        }
+    }
+}
+
+/// `[clipboard]`: keep a copy of what was copied so it can still be pasted
+/// after the app it came from exits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    pub enabled:           bool,
+    /// Larger contents (per MIME type) are not kept.
+    pub max_bytes:         usize,
+    /// MIME types kept; a trailing `*` matches any suffix.
+    pub mime_types:        Vec<String>,
+    /// Recent text selections to keep for `gameframe clipboard`; 0 keeps
+    /// none. Off by default: the history holds whatever was copied,
+    /// passwords included.
+    pub history:           usize,
+    /// Oldest entries go first once the history holds more than this.
+    pub history_max_bytes: usize,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self {
+            enabled:           true,
+            max_bytes:         4 * 1024 * 1024,
+            mime_types:        ["text/*", "UTF8_STRING", "STRING", "TEXT", "image/png"].map(String::from).to_vec(),
+            history:           0,
+            history_max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+/// `[metrics]`: Prometheus-style counters over HTTP, for kiosks and
+/// appliances that are monitored remotely.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// `address:port` to serve on; loopback unless exposed on purpose.
+    pub listen:  String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self { enabled: false, listen: "127.0.0.1:9464".into() }
+    }
+}
+
+impl EffectsConfig {
+    /// Whether any decoration (rounded corners or shadows) is enabled.
+    pub fn decorated(&self) -> bool { self.corner_radius > 0.0 || self.shadow }
+}
+
+/// `[[window_rules]]`: match windows by `app_id` (exact) and/or `title`
+/// (substring) and override how they are drawn.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowRule {
+    pub app_id:           Option<String>,
+    pub title:            Option<String>,
+    pub opacity:          Option<f32>,
+    /// Opacity while the window is not focused (falls back to `opacity`).
+    pub inactive_opacity: Option<f32>,
+    pub blur:             Option<bool>,
+    /// Where new windows open, relative to the output's top-left corner
+    /// (logical px). Unset: centred, cascading over earlier windows.
+    pub position:         Option<[i32; 2]>,
+    /// Blacked out in recordings (password managers, chat windows), while
+    /// still shown normally on screen.
+    pub no_capture:       Option<bool>,
+    /// Whether adaptive sync may tear the window's late frames; unset, only
+    /// windows tagged as games (`wp_content_type_v1`) may.
+    pub tearing:          Option<bool>,
+}
+
+/// `[accessibility]`: AccessX keyboard filters (Super+Shift+K toggles).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessibilityConfig {
+    /// Modifiers latch until the next key.
+    pub sticky_keys:    bool,
+    /// Keys must be held this long to register (ms; 0 = off).
+    pub slow_keys_ms:   u32,
+    /// Presses this soon after the same key's release are ignored (ms; 0 = off).
+    pub bounce_keys_ms: u32,
+    /// Full-screen colour filter at startup (Super+Shift+F cycles through them).
+    pub color_filter:   ColorFilter,
+}
+
+/// Full-screen colour filter; the colour-blindness modes are corrections
+/// (daltonization), not simulations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorFilter {
+    #[default] None,
+    Invert,
+    Grayscale,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+/// `[gamepad]`: drive the compositor from a controller. Buttons only act
+/// while `modifier` is held, so games still get the controller as usual.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub enabled:      bool,
+    /// Button that turns the others into compositor actions.
+    pub modifier:     String,
+    /// Pointer speed at full left-stick deflection (logical px/s).
+    pub cursor_speed: f64,
+    /// Button name ("a", "b", "x", "y", "lb", "rb", "select", "start",
+    /// "guide", "ls", "rs", "dpad_up", …) → action.
+    pub bindings:     HashMap<String, GamepadAction>,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false, modifier: "guide".into(), cursor_speed: 900.0,
+            bindings: default_gamepad_bindings(),
+        }
+    }
+}
+
+/// Default `[gamepad.bindings]` (all used while the modifier is held).
+fn default_gamepad_bindings() -> HashMap<String, GamepadAction> {
+    use GamepadAction::*;
+    [
+        ("a", Click),
+        ("b", RightClick),
+        ("x", CloseWindow),
+        ("lb", FocusPrev),
+        ("rb", FocusNext),
+        ("dpad_left", PrevWorkspace),
+        ("dpad_right", NextWorkspace),
+        ("start", ToggleOverlay),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_owned(), v))
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadAction {
+    /// Left / right mouse button at the pointer.
+    Click,
+    RightClick,
+    FocusNext,
+    FocusPrev,
+    CloseWindow,
+    NextWorkspace,
+    PrevWorkspace,
+    ToggleOverlay,
+    /// Run a shell command.
+    Launch(String),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct InputConfig {
     pub repeat_delay: u32,
     pub repeat_rate:  u32,
+    /// Compiled XKB keymap file (`xkbcomp`/`xkbcli compile-keymap` output)
+    /// used instead of the default layout.
+    pub keymap_file:  Option<PathBuf>,
+    /// Leave gamepads and joysticks to games (evdev) instead of opening
+    /// them through libinput.
+    pub ignore_gamepads: bool,
+    /// Device names (case-insensitive substrings) libinput never opens.
+    pub ignore_devices:  Vec<String>,
+    /// Device names always handled, overriding both options above.
+    pub allow_devices:   Vec<String>,
+    /// Longest gap between the presses of a double click (ms).
+    pub double_click_ms: u32,
+    /// How far the pointer travels with a button held before a click
+    /// becomes a drag (logical px).
+    pub drag_threshold:  f64,
+    /// `[input.touchpad]`: libinput settings for every touchpad.
+    pub touchpad:        DeviceConfig,
+    /// `[input.devices."<name>"]`: settings for devices whose name contains
+    /// `<name>` (case-insensitive), over `touchpad`.
+    pub devices:         HashMap<String, DeviceConfig>,
+    /// `[[input.bind]]`: devices that drive one split-screen pane.
+    pub bind:            Vec<DeviceBinding>,
+    /// `[input.focus]`: how keyboard focus moves between windows.
+    pub focus:           FocusConfig,
+    /// `[input.touch]`: touchscreen gestures for the compositor.
+    pub touch:           TouchConfig,
+}
+
+/// `[input.touch]`: edge swipes, three-finger swipes and long presses on a
+/// touchscreen, recognised before the touches reach clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TouchConfig {
+    pub gestures:       bool,
+    /// Band along each screen edge (logical px) where swipes start. Touches
+    /// landing there are held back from the app until they turn out to be
+    /// a swipe or not; everywhere else one or two fingers go straight
+    /// through. 0 = no edge gestures.
+    pub edge_zone:      f64,
+    /// How far a finger (or three fingers together) travels before a swipe
+    /// counts (logical px).
+    pub swipe_distance: f64,
+    /// A finger held still in the edge zone this long is a long press
+    /// (ms; 0 = off).
+    pub long_press_ms:  u32,
+    /// Gesture ("edge_left", "edge_right", "edge_top", "edge_bottom",
+    /// "three_finger_left", "three_finger_right", "three_finger_up",
+    /// "three_finger_down", "long_press") → action. Unbound gestures are
+    /// never taken from apps.
+    pub bindings:       HashMap<String, GestureAction>,
+}
+
+impl Default for TouchConfig {
+    fn default() -> Self {
+        Self {
+            gestures: true, edge_zone: 16.0, swipe_distance: 80.0, long_press_ms: 600,
+            bindings: default_touch_bindings(),
+        }
+    }
+}
+
+/// Default `[input.touch.bindings]`.
+fn default_touch_bindings() -> HashMap<String, GestureAction> {
+    use GestureAction::*;
+    [
+        ("edge_left", PrevWorkspace),
+        ("edge_right", NextWorkspace),
+        ("edge_top", RevealPanels),
+        ("edge_bottom", ToggleOverlay),
+        ("three_finger_left", FocusNext),
+        ("three_finger_right", FocusPrev),
+        ("long_press", ToggleOverlay),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_owned(), v))
+    .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureAction {
+    NextWorkspace,
+    PrevWorkspace,
+    /// Alt-tab through the workspace's windows.
+    FocusNext,
+    FocusPrev,
+    CloseWindow,
+    ToggleOverlay,
+    /// Show the output's bottom-layer panels (docks, taskbars) above its
+    /// windows until the next touch elsewhere.
+    RevealPanels,
+    /// Run a shell command.
+    Launch(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FocusConfig {
+    pub model:    FocusModel,
+    /// How long the pointer rests before focus follows it (ms); sloppy and
+    /// strict only.
+    pub dwell_ms: u32,
+    /// Sloppy and strict: also bring the window to the top of the stack,
+    /// as a click would.
+    pub raise:    bool,
+    /// When a keybinding moves focus (next / previous window, workspace
+    /// switch), move the pointer to the centre of the focused window or
+    /// output.
+    pub warp_pointer: bool,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self { model: FocusModel::Click, dwell_ms: 150, raise: true, warp_pointer: false }
+    }
+}
+
+/// Clicking a window focuses (and raises) it in every model; the others
+/// also follow the pointer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FocusModel {
+    /// Focus only changes on click.
+    #[default] Click,
+    /// Focus follows the pointer onto windows; empty desktop keeps the
+    /// last focused window.
+    Sloppy,
+    /// Focus follows the pointer; over empty desktop no window has it.
+    Strict,
+}
+
+/// Input from `device` (case-insensitive name substring) goes to the window
+/// in split-screen pane `pane` (1: left/top, 2: right/bottom).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceBinding {
+    pub device: String,
+    pub pane:   usize,
 }
 
 impl Default for InputConfig {
-    fn default() -> Self { Self { repeat_delay: 400, repeat_rate: 30 } }
+    fn default() -> Self {
+        Self {
+            repeat_delay: 400, repeat_rate: 30, keymap_file: None,
+            ignore_gamepads: true, ignore_devices: Vec::new(), allow_devices: Vec::new(),
+            double_click_ms: 400, drag_threshold: 8.0,
+            touchpad: DeviceConfig::default(), devices: HashMap::new(), bind: Vec::new(),
+            focus: FocusConfig::default(), touch: TouchConfig::default(),
+        }
+    }
+}
+
+/// libinput options for a pointer device. Unset options keep libinput's
+/// default; ones the device doesn't support are skipped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceConfig {
+    pub tap:              Option<bool>,
+    /// Tap, then touch again and move: drags with the button held.
+    pub tap_drag:         Option<bool>,
+    /// Buttons for two- and three-finger taps.
+    pub tap_button_map:   Option<TapButtonMap>,
+    pub click_method:     Option<ClickMethod>,
+    pub scroll_method:    Option<ScrollMethod>,
+    /// Pressing left and right together clicks the middle button.
+    pub middle_emulation: Option<bool>,
+    /// Ignore the touchpad for a moment after each key press.
+    pub disable_while_typing: Option<bool>,
+}
+
+impl DeviceConfig {
+    /// `self` with the options `over` sets replaced.
+    pub fn merged(&self, over: &DeviceConfig) -> DeviceConfig {
+        DeviceConfig {
+            tap:              over.tap.or(self.tap),
+            tap_drag:         over.tap_drag.or(self.tap_drag),
+            tap_button_map:   over.tap_button_map.or(self.tap_button_map),
+            click_method:     over.click_method.or(self.click_method),
+            scroll_method:    over.scroll_method.or(self.scroll_method),
+            middle_emulation: over.middle_emulation.or(self.middle_emulation),
+            disable_while_typing: over.disable_while_typing.or(self.disable_while_typing),
+        }
+    }
+}
+
+/// Buttons for two- and three-finger taps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TapButtonMap {
+    /// Two fingers right-click, three middle-click.
+    Lrm,
+    /// Two fingers middle-click, three right-click.
+    Lmr,
+}
+
+/// How a clickpad decides which button a physical click is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClickMethod {
+    /// By where the finger is: the bottom right corner right-clicks.
+    ButtonAreas,
+    /// By how many fingers are down: two right-click, three middle-click.
+    Clickfinger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScrollMethod {
+    None,
+    TwoFinger,
+    /// Sliding along the right or bottom edge.
+    Edge,
+    /// Moving the device with a button (usually middle) held.
+    OnButton,
 }
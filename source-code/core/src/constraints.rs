@@ -0,0 +1,102 @@
+use smithay::{
+    input::pointer::PointerHandle,
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    utils::{Logical, Point},
+    wayland::pointer_constraints::{with_pointer_constraint, PointerConstraint},
+};
+
+use crate::state::GameframeState;
+
+/// Where relative motion by `delta` takes the pointer, given the active
+/// constraint of the surface it is over: nowhere while locked (`None`);
+/// while confined, only as far as stays on that surface and inside the
+/// constraint's region, sliding along its edge when the full step would
+/// leave it.
+pub(crate) fn constrain(state: &GameframeState, delta: Point<f64, Logical>) -> Option<Point<f64, Logical>> {
+    let from = state.pointer_location;
+    let free = Some(from + delta);
+    let Some(pointer) = state.seat.get_pointer() else { return free };
+    let Some((surface, origin)) = state.surface_under(from) else { return free };
+
+    let mut locked   = false;
+    let mut confined = None;
+    with_pointer_constraint(&surface, &pointer, |constraint| match constraint.as_deref() {
+        Some(c @ PointerConstraint::Locked(_)) if c.is_active()   => locked = true,
+        Some(c @ PointerConstraint::Confined(_)) if c.is_active() => confined = Some(c.region().cloned()),
+        _ => {}
+    });
+    if locked { return None; }
+    let Some(region) = confined else { return free };
+
+    let inside = |p: Point<f64, Logical>| {
+        state.surface_under(p).is_some_and(|(s, _)| s == surface)
+            && region.as_ref().map_or(true, |r| r.contains((p - origin).to_i32_round()))
+    };
+    [delta, Point::from((delta.x, 0.0)), Point::from((0.0, delta.y))]
+        .into_iter()
+        .map(|d| from + d)
+        .find(|&p| inside(p))
+        .or(Some(from))
+}
+
+/// Start the constraint of the surface under the pointer once the pointer
+/// is inside its region. Only the client with keyboard focus gets one: a
+/// game left running behind another window doesn't capture the pointer.
+pub(crate) fn activate_under(state: &GameframeState) {
+    let Some(pointer) = state.seat.get_pointer() else { return };
+    let Some((surface, origin)) = state.surface_under(state.pointer_location) else { return };
+    if !has_keyboard_focus(state, &surface) { return; }
+    let at = (state.pointer_location - origin).to_i32_round();
+    with_pointer_constraint(&surface, &pointer, |constraint| {
+        if let Some(c) = constraint.filter(|c| !c.is_active() && c.region().map_or(true, |r| r.contains(at))) {
+            c.activate();
+        }
+    });
+}
+
+/// Whether the surface under the pointer has locked or confined it.
+pub(crate) fn active(state: &GameframeState) -> bool {
+    let Some(pointer) = state.seat.get_pointer() else { return false };
+    let Some(surface) = pointer.current_focus() else { return false };
+    with_pointer_constraint(&surface, &pointer, |c| c.is_some_and(|c| c.is_active()))
+}
+
+/// `surface` asked for a constraint while `pointer` may already be on it.
+pub(crate) fn new_constraint(state: &GameframeState, surface: &WlSurface, pointer: &PointerHandle<GameframeState>) {
+    if pointer.current_focus().as_ref() == Some(surface) {
+        activate_under(state);
+    }
+}
+
+/// Keyboard focus moved to `focused`: a constraint held by another client
+/// ends, so switching away from a game gives the pointer back.
+pub(crate) fn focus_changed(state: &GameframeState, focused: Option<&WlSurface>) {
+    let Some(pointer) = state.seat.get_pointer() else { return };
+    let Some(under) = pointer.current_focus() else { return };
+    if focused.and_then(|f| f.client()) == under.client() { return; }
+    with_pointer_constraint(&under, &pointer, |constraint| {
+        if let Some(c) = constraint.filter(|c| c.is_active()) {
+            c.deactivate();
+        }
+    });
+}
+
+/// Where a client with a locked pointer says its own cursor is, relative
+/// to `surface`: the pointer sits there, so it reappears in the same place
+/// when the lock ends.
+pub(crate) fn position_hint(state: &mut GameframeState, surface: &WlSurface, pointer: &PointerHandle<GameframeState>, location: Point<f64, Logical>) {
+    let active = with_pointer_constraint(surface, pointer, |c| c.is_some_and(|c| c.is_active()));
+    let origin = state.surface_under(state.pointer_location)
+        .filter(|(s, _)| s == surface)
+        .map(|(_, origin)| origin);
+    if let Some(origin) = origin.filter(|_| active) {
+        state.pointer_location = origin + location;
+        pointer.set_location(state.pointer_location);
+    }
+}
+
+fn has_keyboard_focus(state: &GameframeState, surface: &WlSurface) -> bool {
+    state.seat.get_keyboard()
+        .and_then(|k| k.current_focus())
+        .is_some_and(|f| f.client().is_some() && f.client() == surface.client())
+}
@@ -1,38 +1,179 @@
+use std::{collections::HashMap, env, fs};
+
 use smithay::{
-    backend::renderer::gles::GlesRenderer,
-    input::pointer::CursorImageStatus,
-    reexports::wayland_server::Resource,   // FIX: .id() requires Resource trait in scope
-    utils::{Logical, Point},
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                Kind,
+            },
+            gles::GlesRenderer,
+        },
+    },
+    input::pointer::{CursorIcon, CursorImageStatus, CursorImageSurfaceData},
+    render_elements,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point, Rectangle, Scale, Transform},
+    wayland::compositor::{with_states, SurfaceAttributes},
 };
-use tracing::debug;
-
-/// Render the pointer cursor as a software overlay.
-pub fn render_software_cursor(
-    _renderer:        &mut GlesRenderer,
-    cursor_status:    &CursorImageStatus,
-    pointer_location: Point<f64, Logical>,
-    _output_scale:    f64,
-) {
-    match cursor_status {
-        CursorImageStatus::Hidden => {}
-        CursorImageStatus::Named(_name) => {
-            let _ = pointer_location;
+use tracing::{debug, info};
+
+use crate::state::GameframeState;
+
+render_elements! {
+    /// A pointer cursor. Both kinds are `Kind::Cursor`, so on atomic KMS
+    /// the DRM compositor puts them on the cursor plane when one is free
+    /// and the image fits, and composites them into the frame otherwise.
+    pub CursorRenderElement<=GlesRenderer>;
+    Image   = MemoryRenderBufferRenderElement<GlesRenderer>,
+    Surface = WaylandSurfaceRenderElement<GlesRenderer>,
+}
+
+/// Nominal cursor size (logical px) when `XCURSOR_SIZE` isn't set.
+const DEFAULT_SIZE: u32 = 24;
+
+/// One cursor image ready to draw, and its hotspot (logical px).
+type Image = (MemoryRenderBuffer, Point<i32, Logical>);
+
+/// Cursor shapes from the XCursor theme named by `XCURSOR_THEME` (or
+/// "default") at `XCURSOR_SIZE`, loaded per shape and buffer scale on first
+/// use. Shapes the theme lacks fall back to its default arrow, and a theme
+/// that can't be found at all to a plain built-in one. Animated cursors
+/// show their first frame.
+pub struct CursorTheme {
+    theme:  xcursor::CursorTheme,
+    size:   u32,
+    images: HashMap<(CursorIcon, i32), Image>,
+}
+
+impl CursorTheme {
+    pub fn load() -> Self {
+        let name = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".into());
+        let size = env::var("XCURSOR_SIZE").ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|&s| s > 0)
+            .unwrap_or(DEFAULT_SIZE);
+        info!(theme = %name, size, "Cursor theme");
+        Self { theme: xcursor::CursorTheme::load(&name), size, images: HashMap::new() }
+    }
+
+    /// `icon` for outputs of (rounded up) integer `scale`.
+    fn image(&mut self, icon: CursorIcon, scale: i32) -> &Image {
+        if !self.images.contains_key(&(icon, scale)) {
+            let image = self.from_theme(icon, scale)
+                .or_else(|| self.from_theme(CursorIcon::Default, scale))
+                .unwrap_or_else(|| {
+                    debug!(?icon, "no XCursor image, using the built-in arrow");
+                    fallback_arrow(scale)
+                });
+            self.images.insert((icon, scale), image);
+        }
+        &self.images[&(icon, scale)]
+    }
+
+    fn from_theme(&self, icon: CursorIcon, scale: i32) -> Option<Image> {
+        let path = std::iter::once(icon.name())
+            .chain(icon.alt_names().iter().copied())
+            .find_map(|name| self.theme.load_icon(name))?;
+        let bytes  = fs::read(&path).ok()?;
+        let images = xcursor::parser::parse_xcursor(&bytes)?;
+        // The first frame of the size closest to what the output needs.
+        let want  = self.size * scale as u32;
+        let image = images.iter().min_by_key(|i| i.size.abs_diff(want))?;
+        let buffer = MemoryRenderBuffer::from_slice(
+            &image.pixels_rgba,
+            Fourcc::Abgr8888,
+            (image.width as i32, image.height as i32),
+            scale,
+            Transform::Normal,
+            None,
+        );
+        Some((buffer, Point::from((image.xhot as i32 / scale, image.yhot as i32 / scale))))
+    }
+}
+
+/// A white arrow with a black outline, 16 logical px tall, hotspot at the tip.
+fn fallback_arrow(scale: i32) -> Image {
+    let side = 16 * scale as usize;
+    let mut pixels = vec![0u8; side * side * 4];
+    let inside = |x: f64, y: f64| y < 14.0 && x <= y * 0.6;
+    for py in 0..side {
+        for px in 0..side {
+            let (x, y) = (px as f64 / scale as f64, py as f64 / scale as f64);
+            if !inside(x, y) { continue; }
+            let fill = x >= 1.0 && inside(x + 1.0, y + 1.0) && inside(x, y + 1.0);
+            let c = if fill { 0xff } else { 0x00 };
+            pixels[(py * side + px) * 4..][..4].copy_from_slice(&[c, c, c, 0xff]);
+        }
+    }
+    let buffer = MemoryRenderBuffer::from_slice(
+        &pixels, Fourcc::Abgr8888, (side as i32, side as i32), scale, Transform::Normal, None,
+    );
+    (buffer, Point::from((0, 0)))
+}
+
+/// The cursor for `status` with its hotspot at `location` (global), if
+/// that is on the output at `output_geo`. Nothing while hidden.
+pub fn cursor_elements(
+    renderer:   &mut GlesRenderer,
+    theme:      &mut CursorTheme,
+    status:     &CursorImageStatus,
+    location:   Point<f64, Logical>,
+    output_geo: Rectangle<i32, Logical>,
+    scale:      f64,
+) -> Vec<CursorRenderElement> {
+    if !output_geo.to_f64().contains(location) { return Vec::new(); }
+    let local = location - output_geo.loc.to_f64();
+    match status {
+        CursorImageStatus::Hidden => Vec::new(),
+        CursorImageStatus::Named(icon) => {
+            let (buffer, hotspot) = theme.image(*icon, scale.ceil() as i32);
+            let loc = (local - hotspot.to_f64()).to_physical(Scale::from(scale));
+            MemoryRenderBufferRenderElement::from_buffer(renderer, loc, buffer, None, None, None, Kind::Cursor)
+                .inspect_err(|e| debug!("cursor image: {e:?}"))
+                .map(|e| vec![CursorRenderElement::Image(e)])
+                .unwrap_or_default()
         }
         CursorImageStatus::Surface(surface) => {
-            // FIX: import Resource so .id() is available
-            debug!("client cursor surface: {:?}", surface.id());
+            let loc = (local - hotspot(surface).to_f64()).to_physical_precise_round(scale);
+            let elements: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+                render_elements_from_surface_tree(renderer, surface, loc, scale, 1.0, Kind::Cursor);
+            elements.into_iter().map(CursorRenderElement::Surface).collect()
         }
     }
 }
 
-/// DRM hardware cursor plane stub.
-pub struct HardwareCursor {
-    pub available: bool,
+/// Where in a client's cursor surface the pointer is.
+fn hotspot(surface: &WlSurface) -> Point<i32, Logical> {
+    with_states(surface, |states| {
+        states.data_map.get::<CursorImageSurfaceData>()
+            .map_or_else(Point::default, |data| data.lock().unwrap().hotspot)
+    })
 }
 
-impl HardwareCursor {
-    pub fn new(available: bool) -> Self { Self { available } }
+/// Every pointer cursor currently set by a client, with where it is.
+pub fn cursor_surfaces(state: &GameframeState) -> impl Iterator<Item = (&WlSurface, Point<f64, Logical>)> {
+    std::iter::once((&state.cursor_status, state.pointer_location))
+        .chain(state.players.cursors())
+        .filter_map(|(status, location)| match status {
+            CursorImageStatus::Surface(surface) => Some((surface, location)),
+            _ => None,
+        })
+}
 
-    pub fn upload(&mut self, _status: &CursorImageStatus) {}
-    pub fn move_to(&self, _x: i32, _y: i32) {}
+/// Handle a commit if `surface` is a pointer cursor: offsets the client
+/// attached with move the hotspot, and the cursor is redrawn. Returns
+/// whether it was one.
+pub fn surface_committed(state: &mut GameframeState, surface: &WlSurface) -> bool {
+    if !cursor_surfaces(state).any(|(s, _)| s == surface) { return false; }
+    with_states(surface, |states| {
+        let delta = states.cached_state.get::<SurfaceAttributes>().current().buffer_delta.take();
+        if let (Some(delta), Some(data)) = (delta, states.data_map.get::<CursorImageSurfaceData>()) {
+            data.lock().unwrap().hotspot -= delta;
+        }
+    });
+    state.queue_redraw_all();
+    true
 }
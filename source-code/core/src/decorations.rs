@@ -0,0 +1,259 @@
+use std::cell::RefCell;
+
+use smithay::{
+    backend::renderer::{
+        element::{
+            surface::WaylandSurfaceRenderElement, Element, Id, Kind, RenderElement, UnderlyingStorage,
+        },
+        gles::{
+            element::PixelShaderElement, GlesError, GlesFrame, GlesPixelProgram, GlesRenderer,
+            GlesTexProgram, Uniform, UniformName, UniformType,
+        },
+        utils::{CommitCounter, DamageSet, OpaqueRegions},
+    },
+    desktop::Window,
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Transform},
+};
+use tracing::warn;
+
+use crate::config::EffectsConfig;
+
+// ── Shaders ───────────────────────────────────────────────────────────────────
+
+/// Texture shader that masks a surface to a rounded rectangle. `geo` is the
+/// window geometry inside the element (x, y, w, h in element pixels).
+const ROUNDED_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+uniform vec2 elem_size;
+uniform vec4 geo;
+uniform float radius;
+
+void main() {
+    vec4 color = texture2D(tex, v_coords);
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0);
+#endif
+
+    vec2 hs = geo.zw * 0.5;
+    vec2 q  = abs(v_coords * elem_size - geo.xy - hs) - (hs - radius);
+    float d = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+    color *= clamp(0.5 - d, 0.0, 1.0) * alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.3, 0.0, 0.2) + color * 0.8;
+#endif
+    gl_FragColor = color;
+}
+"#;
+
+/// Pixel shader for a soft drop shadow filling its whole area; the shadow
+/// shape is the area inset by `spread`, with corners of radius `corner`.
+const SHADOW_SRC: &str = r#"#version 100
+//_DEFINES_
+
+precision mediump float;
+uniform float alpha;
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+uniform vec2 size;
+varying vec2 v_coords;
+
+uniform vec4 color;
+uniform float spread;
+uniform float corner;
+
+void main() {
+    vec2 hs = size * 0.5;
+    vec2 q  = abs(v_coords * size - hs) - (hs - spread - corner);
+    float d = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - corner;
+    gl_FragColor = color * (1.0 - smoothstep(0.0, spread, d)) * alpha;
+}
+"#;
+
+/// Compiled decoration programs for one GL context.
+#[derive(Clone)]
+pub struct DecorationShaders {
+    pub rounded: GlesTexProgram,
+    pub shadow:  GlesPixelProgram,
+}
+
+/// The decoration programs for `renderer`'s context, compiled on first use.
+/// `None` if compilation failed (logged once; decorations are then skipped).
+pub fn decoration_shaders(renderer: &mut GlesRenderer) -> Option<DecorationShaders> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<Option<DecorationShaders>>() {
+        return cached.clone();
+    }
+    let rounded = renderer.compile_custom_texture_shader(
+        ROUNDED_SRC,
+        &[
+            UniformName::new("elem_size", UniformType::_2f),
+            UniformName::new("geo", UniformType::_4f),
+            UniformName::new("radius", UniformType::_1f),
+        ],
+    );
+    let shadow = renderer.compile_custom_pixel_shader(
+        SHADOW_SRC,
+        &[
+            UniformName::new("color", UniformType::_4f),
+            UniformName::new("spread", UniformType::_1f),
+            UniformName::new("corner", UniformType::_1f),
+        ],
+    );
+    let shaders = match (rounded, shadow) {
+        (Ok(rounded), Ok(shadow)) => Some(DecorationShaders { rounded, shadow }),
+        (Err(e), _) | (_, Err(e)) => { warn!("decoration shaders: {e:?}"); None }
+    };
+    renderer.egl_context().user_data().insert_if_missing(|| shaders.clone());
+    shaders
+}
+
+// ── Rounded corners ───────────────────────────────────────────────────────────
+
+/// A window's main surface drawn through the rounding shader. Only the
+/// drawing changes: geometry (and thus input hit-testing, which works on the
+/// window geometry) keeps the full rectangle, so corners stay clickable.
+pub struct RoundedElement {
+    inner:   WaylandSurfaceRenderElement<GlesRenderer>,
+    program: GlesTexProgram,
+    /// Window geometry relative to the element, in physical pixels.
+    geo:     Rectangle<i32, Physical>,
+    radius:  f32,
+}
+
+impl RoundedElement {
+    pub fn new(
+        inner:   WaylandSurfaceRenderElement<GlesRenderer>,
+        program: GlesTexProgram,
+        geo:     Rectangle<i32, Physical>,
+        radius:  f32,
+    ) -> Self {
+        Self { inner, program, geo, radius }
+    }
+}
+
+impl Element for RoundedElement {
+    fn id(&self) -> &Id { self.inner.id() }
+    fn current_commit(&self) -> CommitCounter { self.inner.current_commit() }
+    fn location(&self, scale: Scale<f64>) -> Point<i32, Physical> { self.inner.location(scale) }
+    fn src(&self) -> Rectangle<f64, Buffer> { self.inner.src() }
+    fn transform(&self) -> Transform { self.inner.transform() }
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> { self.inner.geometry(scale) }
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.inner.damage_since(scale, commit)
+    }
+    /// What the client marked opaque, minus the corner squares, which the
+    /// rounding makes see-through.
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> {
+        let inner = self.inner.opaque_regions(scale);
+        if inner.is_empty() { return inner; }
+        let (g, r) = (self.geo, self.radius.ceil() as i32);
+        if g.size.w <= 2 * r || g.size.h <= 2 * r { return OpaqueRegions::default(); }
+        // Middle band at full width; top and bottom bands between the corners.
+        let bands = [
+            Rectangle::new((g.loc.x, g.loc.y + r).into(), (g.size.w, g.size.h - 2 * r).into()),
+            Rectangle::new((g.loc.x + r, g.loc.y).into(), (g.size.w - 2 * r, r).into()),
+            Rectangle::new((g.loc.x + r, g.loc.y + g.size.h - r).into(), (g.size.w - 2 * r, r).into()),
+        ];
+        inner.iter()
+            .flat_map(|rect| bands.iter().filter_map(move |band| rect.intersection(*band)))
+            .collect()
+    }
+    fn alpha(&self) -> f32 { self.inner.alpha() }
+    fn kind(&self) -> Kind { self.inner.kind() }
+}
+
+impl RenderElement<GlesRenderer> for RoundedElement {
+    fn draw(
+        &self,
+        frame:  &mut GlesFrame<'_, '_>,
+        src:    Rectangle<f64, Buffer>,
+        dst:    Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.override_default_tex_program(
+            self.program.clone(),
+            vec![
+                Uniform::new("elem_size", [dst.size.w as f32, dst.size.h as f32]),
+                Uniform::new("geo", [
+                    self.geo.loc.x as f32, self.geo.loc.y as f32,
+                    self.geo.size.w as f32, self.geo.size.h as f32,
+                ]),
+                Uniform::new("radius", self.radius),
+            ],
+        );
+        let res = self.inner.draw(frame, src, dst, damage, opaque);
+        frame.clear_tex_program_override();
+        res
+    }
+
+    /// Never scanned out directly: a plane would show the square corners.
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage<'_>> { None }
+}
+
+// ── Drop shadows ──────────────────────────────────────────────────────────────
+
+/// Per-window shadow element, kept across frames so its id (and damage
+/// tracking) stays stable while the window doesn't move or resize. Stored
+/// with the area and alpha it was built for.
+#[derive(Default)]
+struct WindowShadow(RefCell<Option<(Rectangle<i32, Logical>, f32, PixelShaderElement)>>);
+
+/// Shadow behind a window whose geometry is `geo` (logical, output-relative).
+pub fn shadow_element(
+    window:  &Window,
+    program: &GlesPixelProgram,
+    geo:     Rectangle<i32, Logical>,
+    effects: &EffectsConfig,
+    alpha:   f32,
+) -> PixelShaderElement {
+    let spread = effects.shadow_radius.max(1.0).round() as i32;
+    let [dx, dy] = effects.shadow_offset;
+    let area = Rectangle::new(
+        geo.loc + Point::from((dx - spread, dy - spread)),
+        (geo.size.w + 2 * spread, geo.size.h + 2 * spread).into(),
+    );
+
+    let data = window.user_data();
+    data.insert_if_missing(WindowShadow::default);
+    let mut slot = data.get::<WindowShadow>().unwrap().0.borrow_mut();
+    match slot.as_mut() {
+        Some((a, al, elem)) if *al == alpha => {
+            if *a != area {
+                elem.resize(area, None);
+                *a = area;
+            }
+        }
+        _ => {
+            let uniforms = vec![
+                Uniform::new("color", effects.shadow_color),
+                Uniform::new("spread", spread as f32),
+                Uniform::new("corner", effects.corner_radius),
+            ];
+            let elem = PixelShaderElement::new(program.clone(), area, None, alpha, uniforms, Kind::Unspecified);
+            *slot = Some((area, alpha, elem));
+        }
+    }
+    slot.as_ref().unwrap().2.clone()
+}
@@ -0,0 +1,110 @@
+use smithay::{
+    input::pointer::{CursorIcon, CursorImageStatus},
+    reexports::wayland_server::protocol::{wl_data_device_manager::DndAction, wl_surface::WlSurface},
+    utils::{Logical, Point},
+    wayland::compositor::{with_states, SurfaceAttributes},
+};
+use tracing::debug;
+
+use crate::state::GameframeState;
+
+/// A drag-and-drop started by a client, from `start_drag` until the drop.
+///
+/// Smithay's grab does the protocol work: it follows the pointer focus we
+/// hand it on every motion, which `surface_under` finds in the global
+/// layout, so drags cross outputs like any pointer motion. What is left
+/// here is what the compositor shows: the icon under the pointer on
+/// whichever output it is over, and a cursor for the action the source and
+/// target agreed on.
+pub struct Drag {
+    /// Surface the client drags along, if it gave one.
+    pub icon:   Option<WlSurface>,
+    /// Top-left of the icon relative to the pointer hotspot; moved by the
+    /// client's attach offsets.
+    pub offset: Point<i32, Logical>,
+    /// Surface under the pointer the last time it moved.
+    target:     Option<WlSurface>,
+}
+
+impl Drag {
+    /// The icon and its top-left in the global layout with the pointer at
+    /// `pointer`.
+    pub fn icon_at(&self, pointer: Point<f64, Logical>) -> Option<(&WlSurface, Point<i32, Logical>)> {
+        Some((self.icon.as_ref()?, pointer.to_i32_round() + self.offset))
+    }
+}
+
+/// `wl_data_device.start_drag` was accepted.
+pub fn started(state: &mut GameframeState, icon: Option<WlSurface>) {
+    debug!(icon = icon.is_some(), "drag started");
+    state.dnd = Some(Drag { icon, offset: Point::from((0, 0)), target: None });
+    state.cursor_status = CursorImageStatus::Named(CursorIcon::Grabbing);
+    state.queue_redraw_all();
+}
+
+/// The drag ended, dropped on a target or not.
+pub fn finished(state: &mut GameframeState) {
+    if state.dnd.take().is_none() { return; }
+    debug!("drag finished");
+    state.cursor_status = CursorImageStatus::default_named();
+    state.queue_redraw_all();
+}
+
+/// The pointer moved during a drag onto `target`. The icon follows it on
+/// every output; a new target has yet to say what it accepts, so until it
+/// does the cursor shows that nothing would happen.
+pub fn pointer_moved(state: &mut GameframeState, target: Option<&WlSurface>) {
+    let Some(drag) = state.dnd.as_mut() else { return };
+    if drag.target.as_ref() != target {
+        drag.target = target.cloned();
+        state.cursor_status = CursorImageStatus::Named(CursorIcon::NoDrop);
+    }
+    state.queue_redraw_all();
+}
+
+/// Commit on the drag icon: apply its attach offset. Returns `false` if
+/// `surface` isn't the icon.
+pub fn icon_committed(state: &mut GameframeState, surface: &WlSurface) -> bool {
+    let Some(drag) = state.dnd.as_mut().filter(|d| d.icon.as_ref() == Some(surface)) else { return false };
+    let delta = with_states(surface, |states| {
+        states.cached_state.get::<SurfaceAttributes>().current().buffer_delta.take()
+    });
+    if let Some(delta) = delta {
+        drag.offset += delta;
+    }
+    state.queue_redraw_all();
+    true
+}
+
+/// Pick the action of a drop from what source and target both allow
+/// (`available`) and what the target prefers, the way other compositors
+/// do: the preferred action if possible, else ask, copy, move in that
+/// order. Called on the target's `set_actions` and `accept`; the cursor
+/// shows the outcome.
+pub fn choose_action(state: &mut GameframeState, available: DndAction, preferred: DndAction) -> DndAction {
+    let single = [DndAction::Copy, DndAction::Move, DndAction::Ask].contains(&preferred);
+    let action = if single && available.contains(preferred) {
+        preferred
+    } else {
+        [DndAction::Ask, DndAction::Copy, DndAction::Move].into_iter()
+            .find(|a| available.contains(*a))
+            .unwrap_or(DndAction::empty())
+    };
+    if state.dnd.is_some() {
+        state.cursor_status = CursorImageStatus::Named(cursor_for(action));
+    }
+    debug!(?available, ?preferred, ?action, "drag action");
+    action
+}
+
+fn cursor_for(action: DndAction) -> CursorIcon {
+    if action == DndAction::Copy {
+        CursorIcon::Copy
+    } else if action == DndAction::Move {
+        CursorIcon::Move
+    } else if action == DndAction::Ask {
+        CursorIcon::ContextMenu
+    } else {
+        CursorIcon::NoDrop
+    }
+}
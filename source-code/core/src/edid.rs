@@ -0,0 +1,229 @@
+use drm::control::{connector, Device as ControlDevice};
+use tracing::debug;
+
+/// What Gameframe uses from a monitor's EDID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdidInfo {
+    pub make:   String,
+    pub model:  String,
+    pub serial: String,
+    /// Image size in millimetres; `(0, 0)` for projectors and unknown or
+    /// implausible sizes (see [`plausible_size`]).
+    pub size:   (i32, i32),
+    pub limits: Option<RangeLimits>,
+}
+
+/// The monitor's range limits descriptor: what it can sync to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLimits {
+    /// Vertical (refresh) rate, Hz.
+    pub v_hz:          (u32, u32),
+    /// Horizontal (line) rate, kHz.
+    pub h_khz:         (u32, u32),
+    pub max_clock_khz: Option<u32>,
+}
+
+/// Read and parse the connector's `EDID` property. `None` if the connector
+/// has none (virtual outputs, some KVMs) or it doesn't parse.
+pub fn read_edid(drm: &impl ControlDevice, connector: connector::Handle) -> Option<EdidInfo> {
+    let props = drm.get_properties(connector).ok()?;
+    let (handles, values) = props.as_props_and_values();
+    let blob = handles.iter().zip(values).find_map(|(&handle, &value)| {
+        let info = drm.get_property(handle).ok()?;
+        (info.name().to_bytes() == b"EDID" && value != 0).then_some(value)
+    })?;
+    let data = drm.get_property_blob(blob).ok()?;
+    let info = parse(&data);
+    if info.is_none() {
+        debug!(?connector, len = data.len(), "EDID did not parse");
+    }
+    info
+}
+
+/// Parse an EDID base block (the first 128 bytes).
+pub fn parse(edid: &[u8]) -> Option<EdidInfo> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER { return None; }
+
+    let id = u16::from_be_bytes([edid[8], edid[9]]);
+    let pnp: String = [(id >> 10) & 31, (id >> 5) & 31, id & 31]
+        .iter()
+        .map(|&c| (b'A' - 1 + c as u8) as char)
+        .collect();
+    let product = u16::from_le_bytes([edid[10], edid[11]]);
+    let serial  = u32::from_le_bytes([edid[12], edid[13], edid[14], edid[15]]);
+
+    let mut name = None;
+    let mut serial_text = None;
+    let mut limits = None;
+    // Image size in cm, refined below from the preferred timing if present.
+    let mut size = (edid[21] as i32 * 10, edid[22] as i32 * 10);
+    for (i, d) in edid[54..126].chunks_exact(18).enumerate() {
+        if d[0] != 0 || d[1] != 0 {
+            // Detailed timing; the first one is the preferred mode.
+            if i == 0 {
+                let w = d[12] as i32 | ((d[14] as i32 & 0xf0) << 4);
+                let h = d[13] as i32 | ((d[14] as i32 & 0x0f) << 8);
+                if w > 0 && h > 0 { size = (w, h); }
+            }
+            continue;
+        }
+        match d[3] {
+            0xfc => name = descriptor_text(d),
+            0xff => serial_text = descriptor_text(d),
+            0xfd => limits = range_limits(d),
+            _ => {}
+        }
+    }
+
+    Some(EdidInfo {
+        make:   vendor_name(&pnp).map_or(pnp, str::to_owned),
+        model:  name.unwrap_or_else(|| format!("0x{product:04x}")),
+        serial: serial_text.unwrap_or_else(|| if serial == 0 { String::new() } else { serial.to_string() }),
+        size:   plausible_size(size),
+        limits,
+    })
+}
+
+fn range_limits(d: &[u8]) -> Option<RangeLimits> {
+    // Byte 4 flags add 255 to the maximum / minimum rates (EDID 1.4).
+    let offset = |bit_min: u8, bit_max: u8, min: u8, max: u8| {
+        (min as u32 + if d[4] & bit_min != 0 { 255 } else { 0 },
+         max as u32 + if d[4] & bit_max != 0 { 255 } else { 0 })
+    };
+    let v_hz  = offset(0x01, 0x02, d[5], d[6]);
+    let h_khz = offset(0x04, 0x08, d[7], d[8]);
+    if v_hz.1 == 0 || h_khz.1 == 0 { return None; }
+    let max_clock_khz = (d[9] != 0).then(|| d[9] as u32 * 10_000);
+    Some(RangeLimits { v_hz, h_khz, max_clock_khz })
+}
+
+/// `size` (mm) if it can be a real screen, else `(0, 0)` ("unknown" for
+/// `wl_output`). EDID 1.4 stores an aspect ratio instead of a size when one
+/// dimension is zero, and many TVs and projectors report placeholder sizes
+/// that are really aspect ratios; passing those on would give clients a
+/// wildly wrong DPI.
+pub fn plausible_size((w, h): (i32, i32)) -> (i32, i32) {
+    const ASPECT_PLACEHOLDERS: [(i32, i32); 4] = [(160, 90), (160, 100), (160, 120), (40, 30)];
+    if w < 20 || h < 20 || ASPECT_PLACEHOLDERS.contains(&(w, h)) {
+        return (0, 0);
+    }
+    (w, h)
+}
+
+/// Text of a display descriptor: up to 13 bytes, ended by a newline.
+fn descriptor_text(d: &[u8]) -> Option<String> {
+    let text = &d[5..18];
+    let end  = text.iter().position(|&b| b == b'\n').unwrap_or(text.len());
+    let s    = String::from_utf8_lossy(&text[..end]).trim().to_owned();
+    (!s.is_empty()).then_some(s)
+}
+
+/// Manufacturer for common PNP ids; others keep the three-letter id.
+fn vendor_name(pnp: &str) -> Option<&'static str> {
+    Some(match pnp {
+        "ACR" => "Acer",
+        "AOC" => "AOC",
+        "APP" => "Apple",
+        "AUO" => "AU Optronics",
+        "AUS" => "ASUS",
+        "BNQ" => "BenQ",
+        "BOE" => "BOE",
+        "CMN" => "Chimei Innolux",
+        "DEL" => "Dell",
+        "ENC" => "EIZO",
+        "GSM" => "LG Electronics",
+        "HPN" | "HWP" => "HP",
+        "IVM" => "Iiyama",
+        "LEN" => "Lenovo",
+        "LGD" => "LG Display",
+        "MSI" => "MSI",
+        "NEC" => "NEC",
+        "PHL" => "Philips",
+        "SAM" => "Samsung",
+        "SDC" => "Samsung Display",
+        "SHP" => "Sharp",
+        "SNY" => "Sony",
+        "VSC" => "ViewSonic",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Base block of a 24" 1920x1200 Dell monitor: preferred timing with a
+    /// 518x324 mm image, serial and name descriptors, range limits, one
+    /// extension block.
+    const DELL_U2415: [u8; 128] = [
+        0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x10, 0xac, 0xa4, 0xa0, 0x32, 0x30, 0x4b, 0x4c,
+        0x1d, 0x19, 0x01, 0x04, 0xa5, 0x34, 0x20, 0x78, 0x3a, 0xee, 0x95, 0xa3, 0x54, 0x4c, 0x99, 0x26,
+        0x0f, 0x50, 0x54, 0xa5, 0x4b, 0x00, 0x71, 0x4f, 0x81, 0x80, 0xa9, 0x40, 0xd1, 0x00, 0xd1, 0xc0,
+        0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x28, 0x3c, 0x80, 0xa0, 0x70, 0xb0, 0x23, 0x40, 0x30, 0x20,
+        0x36, 0x00, 0x06, 0x44, 0x21, 0x00, 0x00, 0x1a, 0x00, 0x00, 0x00, 0xff, 0x00, 0x37, 0x4d, 0x54,
+        0x30, 0x31, 0x36, 0x37, 0x42, 0x30, 0x4c, 0x4b, 0x4c, 0x0a, 0x00, 0x00, 0x00, 0xfc, 0x00, 0x44,
+        0x45, 0x4c, 0x4c, 0x20, 0x55, 0x32, 0x34, 0x31, 0x35, 0x0a, 0x20, 0x20, 0x00, 0x00, 0x00, 0xfd,
+        0x00, 0x38, 0x4c, 0x1e, 0x53, 0x11, 0x00, 0x0a, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x01, 0x61,
+    ];
+
+    /// Offsets into the base block.
+    const SIZE_CM: usize = 21;
+    const PREFERRED_SIZE_MM: usize = 54 + 12;
+    const SERIAL_TAG: usize = 72 + 3;
+    const NAME_TAG: usize = 90 + 3;
+
+    #[test]
+    fn monitor_edid() {
+        let info = parse(&DELL_U2415).unwrap();
+        assert_eq!(info, EdidInfo {
+            make:   "Dell".into(),
+            model:  "DELL U2415".into(),
+            serial: "7MT0167B0LKL".into(),
+            size:   (518, 324),
+            limits: Some(RangeLimits { v_hz: (56, 76), h_khz: (30, 83), max_clock_khz: Some(170_000) }),
+        });
+    }
+
+    #[test]
+    fn truncated_or_not_edid() {
+        assert_eq!(parse(&DELL_U2415[..127]), None);
+        assert_eq!(parse(&[]), None);
+        let mut edid = DELL_U2415;
+        edid[7] = 0xff;
+        assert_eq!(parse(&edid), None);
+    }
+
+    #[test]
+    fn codes_stand_in_for_missing_descriptors() {
+        let mut edid = DELL_U2415;
+        edid[SERIAL_TAG] = 0x10;
+        edid[NAME_TAG]   = 0x10;
+        let info = parse(&edid).unwrap();
+        assert_eq!(info.model, "0xa0a4");
+        assert_eq!(info.serial, 0x4c4b_3032u32.to_string());
+    }
+
+    #[test]
+    fn size_falls_back_to_centimetres_and_drops_placeholders() {
+        let mut edid = DELL_U2415;
+        edid[PREFERRED_SIZE_MM..PREFERRED_SIZE_MM + 3].fill(0);
+        assert_eq!(parse(&edid).unwrap().size, (520, 320));
+        // A TV giving its aspect ratio as 16x9 cm.
+        edid[SIZE_CM..SIZE_CM + 2].copy_from_slice(&[16, 9]);
+        assert_eq!(parse(&edid).unwrap().size, (0, 0));
+        // A projector.
+        edid[SIZE_CM..SIZE_CM + 2].fill(0);
+        assert_eq!(parse(&edid).unwrap().size, (0, 0));
+    }
+
+    #[test]
+    fn plausible_sizes() {
+        assert_eq!(plausible_size((597, 336)), (597, 336));
+        assert_eq!(plausible_size((160, 90)), (0, 0));
+        assert_eq!(plausible_size((160, 100)), (0, 0));
+        assert_eq!(plausible_size((40, 30)), (0, 0));
+        assert_eq!(plausible_size((0, 340)), (0, 0));
+        assert_eq!(plausible_size((600, 10)), (0, 0));
+    }
+}
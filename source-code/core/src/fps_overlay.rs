@@ -0,0 +1,194 @@
+use std::{collections::VecDeque, time::Duration};
+
+use smithay::{
+    backend::renderer::{
+        element::{Element, Kind},
+        gles::{element::PixelShaderElement, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType},
+    },
+    utils::{Logical, Point, Rectangle, Size},
+};
+use tracing::warn;
+
+// ── Shader ────────────────────────────────────────────────────────────────────
+
+/// Two rows of seven-segment digits on a dark backing: frames per second
+/// (coloured by how smooth that is) above the 99th-percentile frame time
+/// in milliseconds with one decimal. No font or texture upload involved.
+const FPS_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+uniform float alpha;
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+uniform vec2 size;
+varying vec2 v_coords;
+
+uniform float fps;
+uniform float frametime;
+uniform vec3 fps_color;
+
+// Bit `d` of `mask`: whether a segment is lit for digit d.
+float bit(float mask, float d) {
+    return mod(floor(mask / exp2(d)), 2.0);
+}
+
+float box(vec2 p, vec4 r) {
+    return step(r.x, p.x) * step(p.x, r.z) * step(r.y, p.y) * step(p.y, r.w);
+}
+
+float digit(vec2 p, float d) {
+    float on = bit(1005.0, d) * box(p, vec4(0.20, 0.08, 0.80, 0.16))  // top
+             + bit(927.0,  d) * box(p, vec4(0.72, 0.08, 0.80, 0.50))  // top right
+             + bit(1019.0, d) * box(p, vec4(0.72, 0.50, 0.80, 0.92))  // bottom right
+             + bit(877.0,  d) * box(p, vec4(0.20, 0.84, 0.80, 0.92))  // bottom
+             + bit(325.0,  d) * box(p, vec4(0.20, 0.50, 0.28, 0.92))  // bottom left
+             + bit(881.0,  d) * box(p, vec4(0.20, 0.08, 0.28, 0.50))  // top left
+             + bit(892.0,  d) * box(p, vec4(0.20, 0.46, 0.80, 0.54)); // middle
+    return min(on, 1.0);
+}
+
+void main() {
+    vec2 cells = v_coords * vec2(4.0, 2.0);
+    float col = floor(cells.x);
+    float row = floor(cells.y);
+    vec2 p = fract(cells);
+
+    // Row 0 is the frame rate, row 1 the frame time in tenths of a ms.
+    float value = row < 1.0 ? floor(fps + 0.5) : floor(frametime * 10.0 + 0.5);
+    float place = col < 1.0 ? 1000.0 : col < 2.0 ? 100.0 : col < 3.0 ? 10.0 : 1.0;
+    float d = mod(floor(value / place), 10.0);
+    // No leading zeros, but keep "0" and "0.0".
+    float keep = row < 1.0 ? 3.0 : 2.0;
+    float lit = (value >= place || col >= keep) ? digit(p, d) : 0.0;
+    if (row >= 1.0 && col == 2.0) {
+        lit = max(lit, box(p, vec4(0.88, 0.84, 0.98, 0.92)));
+    }
+
+    vec3 fg = row < 1.0 ? fps_color : vec3(1.0);
+    gl_FragColor = mix(vec4(0.0, 0.0, 0.0, 0.6), vec4(fg, 1.0), lit) * alpha;
+}
+"#;
+
+/// The counter program for a renderer's context, compiled on first use;
+/// `None` if compilation failed (logged once; the counter is then not shown).
+struct FpsShader(Option<GlesPixelProgram>);
+
+fn fps_shader(renderer: &mut GlesRenderer) -> Option<GlesPixelProgram> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<FpsShader>() {
+        return cached.0.clone();
+    }
+    let program = renderer.compile_custom_pixel_shader(
+        FPS_SRC,
+        &[
+            UniformName::new("fps", UniformType::_1f),
+            UniformName::new("frametime", UniformType::_1f),
+            UniformName::new("fps_color", UniformType::_3f),
+        ],
+    );
+    let program = program.inspect_err(|e| warn!("fps counter shader: {e:?}")).ok();
+    renderer.egl_context().user_data().insert_if_missing(|| FpsShader(program.clone()));
+    program
+}
+
+// ── Counter ───────────────────────────────────────────────────────────────────
+
+/// Frame times kept; two seconds at 60 Hz.
+const SAMPLES: usize = 120;
+
+/// Size of the counter and its distance from the output's top-right corner
+/// (logical px).
+const WIDTH:  i32 = 72;
+const HEIGHT: i32 = 56;
+const MARGIN: i32 = 8;
+
+/// Frame rate and 99th-percentile frame time of the first output, drawn in
+/// its top-right corner (Super+F). Costs one small shader quad per frame
+/// and a sort of the last [`SAMPLES`] frame times.
+#[derive(Default)]
+pub struct FpsOverlay {
+    pub visible: bool,
+    /// Time between successive flips, oldest first.
+    frames:      VecDeque<Duration>,
+    last:        Option<Duration>,
+    element:     Option<PixelShaderElement>,
+    /// Values the element shows, as drawn (whole fps, tenths of a ms), so
+    /// it is only damaged when they change.
+    shown:       (u32, u32),
+}
+
+impl FpsOverlay {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.frames.clear();
+        self.last = None;
+    }
+
+    /// A frame was queued for scanout at `now` (the compositor clock).
+    pub fn frame(&mut self, now: Duration) {
+        if !self.visible { return; }
+        if let Some(last) = self.last {
+            if self.frames.len() == SAMPLES {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(now.saturating_sub(last));
+        }
+        self.last = Some(now);
+    }
+
+    /// Frames per second over the samples and the 99th-percentile frame
+    /// time in ms.
+    pub fn stats(&self) -> (f32, f32) {
+        let total: Duration = self.frames.iter().sum();
+        if total.is_zero() { return (0.0, 0.0); }
+        let fps = self.frames.len() as f32 / total.as_secs_f32();
+        let mut sorted: Vec<Duration> = self.frames.iter().copied().collect();
+        sorted.sort_unstable();
+        let idx = ((sorted.len() - 1) as f32 * 0.99).round() as usize;
+        (fps, sorted[idx].as_secs_f32() * 1000.0)
+    }
+
+    /// The counter for an output of logical `size`; `None` while hidden or
+    /// if the shader is unavailable.
+    pub fn element(&mut self, renderer: &mut GlesRenderer, size: Size<i32, Logical>) -> Option<PixelShaderElement> {
+        if !self.visible { return None; }
+        let program = fps_shader(renderer)?;
+        let area = Rectangle::new(Point::from((size.w - WIDTH - MARGIN, MARGIN)), Size::from((WIDTH, HEIGHT)));
+        let (fps, frametime) = self.stats();
+        let shown = (fps.round() as u32, (frametime * 10.0).round() as u32);
+        match &mut self.element {
+            Some(element) if element.geometry(1.0.into()).loc == area.loc.to_physical(1) => {
+                if shown != self.shown {
+                    element.update_uniforms(uniforms(fps, frametime));
+                }
+            }
+            slot => {
+                *slot = Some(PixelShaderElement::new(
+                    program, area, None, 1.0, uniforms(fps, frametime), Kind::Unspecified,
+                ));
+            }
+        }
+        self.shown = shown;
+        self.element.clone()
+    }
+}
+
+fn uniforms(fps: f32, frametime: f32) -> Vec<Uniform<'static>> {
+    let color = if fps >= 55.0 {
+        [0.31, 0.78, 0.31]
+    } else if fps >= 29.0 {
+        [0.94, 0.71, 0.16]
+    } else {
+        [0.86, 0.24, 0.24]
+    };
+    vec![
+        Uniform::new("fps", fps.min(9999.0)),
+        Uniform::new("frametime", frametime.min(99.9)),
+        Uniform::new("fps_color", color),
+    ]
+}
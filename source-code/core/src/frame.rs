@@ -1,55 +1,442 @@
-use std::time::{Duration, Instant};
-use tracing::trace;
+use std::time::Duration;
+use tracing::{debug, trace};
 
+/// Frame-rate cap (`display.fps_cap`), shared by every DRM output. Each
+/// output keeps its own vblank-driven loop; a render that would come
+/// sooner than `target_interval` after that output's previous flip is put
+/// off until the interval has passed. Clients get their frame callbacks
+/// when the capped frame is presented, so they slow down with it.
+#[derive(Debug, Default)]
 pub struct FramePacer {
     target_interval: Option<Duration>,
-    last_frame:      Instant,
-    frame_count:     u64,
-    fps_smooth:      f32,
 }
 
 impl FramePacer {
+    /// `fps_cap` of 0 means uncapped.
     pub fn new(fps_cap: u32) -> Self {
-        let target_interval = if fps_cap > 0 {
-            Some(Duration::from_secs_f64(1.0 / fps_cap as f64))
+        let mut pacer = Self::default();
+        pacer.set_cap(fps_cap);
+        pacer
+    }
+
+    pub fn set_cap(&mut self, fps_cap: u32) {
+        self.target_interval = (fps_cap > 0).then(|| Duration::from_secs_f64(1.0 / fps_cap as f64));
+    }
+
+    /// How long to put off a render at `now` on an output that last queued
+    /// a flip at `last_render`; `None` to render right away.
+    pub fn delay(&self, last_render: Option<Duration>, now: Duration) -> Option<Duration> {
+        let wait = (last_render? + self.target_interval?).checked_sub(now)?;
+        trace!(wait_us = wait.as_micros() as u64, "frame cap");
+        (!wait.is_zero()).then_some(wait)
+    }
+}
+
+// ── Per-output repaint state ──────────────────────────────────────────────────
+
+/// Where an output is in its repaint cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepaintPhase {
+    /// Nothing queued; the next damage schedules a render right away.
+    Idle,
+    /// A render is queued on the event loop but hasn't run yet.
+    Scheduled,
+    /// Waiting for this output's vblank. `flip` is false when no buffer was
+    /// queued and the vblank is only estimated by a timer.
+    Waiting { flip: bool, redraw_after: bool },
+}
+
+/// Presentation counters of one output, for diagnosing stutter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    /// Frames whose page flip completed.
+    pub presented: u64,
+    /// Presented frames that reached the screen after the vblank predicted
+    /// when they were queued.
+    pub late:      u64,
+    /// Frames rendered for scanout that never got there.
+    pub dropped:   u64,
+}
+
+impl FrameStats {
+    /// Counts accumulated since `earlier`.
+    pub fn since(&self, earlier: &FrameStats) -> FrameStats {
+        FrameStats {
+            presented: self.presented.saturating_sub(earlier.presented),
+            late:      self.late.saturating_sub(earlier.late),
+            dropped:   self.dropped.saturating_sub(earlier.dropped),
+        }
+    }
+}
+
+/// Deviation from the mode's nominal refresh beyond which a measured flip
+/// interval is not a plain vblank (VRR stretching it, a missed event).
+const REFRESH_TOLERANCE: f64 = 0.05;
+
+/// Repaint bookkeeping for one output, driven by that output's own page-flip
+/// completions so displays with different refresh rates never share a clock.
+#[derive(Debug)]
+pub struct RepaintState {
+    phase:       RepaintPhase,
+    /// Refresh interval the mode advertises.
+    nominal:     Duration,
+    /// Refresh interval measured from flip timestamps; starts at `nominal`.
+    refresh:     Duration,
+    last_vblank: Option<Duration>,
+    /// Whether `last_vblank` came from the kernel rather than our clock.
+    last_hw:     bool,
+    /// Vblank the frame in flight was predicted to make.
+    target:      Option<Duration>,
+    /// When the last frame that queued a flip was rendered.
+    last_render: Option<Duration>,
+    stats:       FrameStats,
+}
+
+impl RepaintState {
+    /// `refresh_mhz` is the mode refresh rate in mHz, as reported by `wl_output`.
+    pub fn new(refresh_mhz: i32) -> Self {
+        let refresh = if refresh_mhz > 0 {
+            Duration::from_secs_f64(1000.0 / refresh_mhz as f64)
         } else {
-            None
+            Duration::from_micros(16_667)
         };
         Self {
-            target_interval,
-            last_frame: Instant::now(),
-            frame_count: 0,
-            fps_smooth: 0.0,
+            phase: RepaintPhase::Idle, nominal: refresh, refresh, last_vblank: None, last_hw: false,
+            target: None, last_render: None, stats: FrameStats::default(),
         }
     }
 
-    /// Duration to sleep until the next frame slot.
-    pub fn next_interval(&mut self) -> Duration {
-        let elapsed = self.last_frame.elapsed();
+    pub fn phase(&self) -> RepaintPhase { self.phase }
+    pub fn stats(&self) -> FrameStats { self.stats }
+    /// Measured vblank interval of this output.
+    pub fn refresh_interval(&self) -> Duration { self.refresh }
 
-        // Update smoothed FPS (exponential moving average, α=0.1)
-        if elapsed.as_secs_f32() > 0.0 {
-            let instant_fps = 1.0 / elapsed.as_secs_f32();
-            self.fps_smooth = self.fps_smooth * 0.9 + instant_fps * 0.1;
+    /// Request a redraw. Returns true if the caller should schedule a render
+    /// now; otherwise one is already queued or will follow the pending vblank.
+    pub fn queue_redraw(&mut self) -> bool {
+        match self.phase {
+            RepaintPhase::Idle => {
+                self.phase = RepaintPhase::Scheduled;
+                true
+            }
+            RepaintPhase::Scheduled => false,
+            RepaintPhase::Waiting { flip, .. } => {
+                self.phase = RepaintPhase::Waiting { flip, redraw_after: true };
+                false
+            }
+        }
+    }
+
+    /// A render ran at `now`. `flip` is true if a buffer was queued for
+    /// scanout; it is expected on the next vblank.
+    pub fn frame_queued(&mut self, flip: bool, now: Duration) {
+        self.target = (flip && self.last_vblank.is_some()).then(|| self.next_vblank(now));
+        if flip {
+            self.last_render = Some(now);
         }
-        self.frame_count += 1;
-        self.last_frame = Instant::now();
+        self.phase  = RepaintPhase::Waiting { flip, redraw_after: false };
+    }
 
-        if let Some(interval) = self.target_interval {
-            if elapsed < interval {
-                let sleep = interval - elapsed;
-                trace!(sleep_ms = sleep.as_millis(), "frame pacer sleep");
-                return sleep;
+    /// Time of the last render that queued a flip, for the frame cap.
+    pub fn last_render(&self) -> Option<Duration> { self.last_render }
+
+    /// The frame cap put this render off; the timer that ends the wait
+    /// calls [`RepaintState::on_vblank`] as an estimated vblank would.
+    pub fn frame_deferred(&mut self) {
+        self.phase = RepaintPhase::Waiting { flip: false, redraw_after: true };
+    }
+
+    /// A render found nothing to draw and nothing needs pacing; the output
+    /// goes idle until the next redraw is queued.
+    pub fn frame_skipped(&mut self) {
+        self.target = None;
+        self.phase  = RepaintPhase::Idle;
+    }
+
+    /// The output switched to a mode refreshing at `refresh_mhz`: the
+    /// refresh estimate starts over from its nominal rate.
+    pub fn set_refresh(&mut self, refresh_mhz: i32) {
+        let fresh = Self::new(refresh_mhz);
+        self.nominal = fresh.nominal;
+        self.refresh = fresh.refresh;
+    }
+
+    /// Forget the frame in flight and the vblank timeline, e.g. after the
+    /// device was paused. Counters and the refresh estimate are kept.
+    pub fn reset(&mut self) {
+        self.phase       = RepaintPhase::Idle;
+        self.last_vblank = None;
+        self.last_hw     = false;
+        self.target      = None;
+    }
+
+    /// A frame was rendered for scanout but couldn't be queued.
+    pub fn frame_dropped(&mut self) {
+        self.stats.dropped += 1;
+        debug!(dropped = self.stats.dropped, "frame dropped");
+    }
+
+    /// Vblank (real or estimated) at `time`; `hw` is true when `time` is the
+    /// kernel's flip-completion timestamp. Returns `(flip, redraw)`: whether a
+    /// page flip just completed and whether a render should be started.
+    pub fn on_vblank(&mut self, time: Duration, hw: bool) -> (bool, bool) {
+        let (flip, redraw) = match self.phase {
+            RepaintPhase::Waiting { flip, redraw_after } => (flip, redraw_after),
+            _ => (false, false),
+        };
+        if flip {
+            self.stats.presented += 1;
+            // Only kernel timestamps are precise enough to tell; half an
+            // interval of slack absorbs timestamp jitter.
+            if let (true, Some(target)) = (hw, self.target.take()) {
+                if time > target + self.refresh / 2 {
+                    self.stats.late += 1;
+                    debug!(late_us = (time - target).as_micros() as u64, late = self.stats.late, "frame missed its vblank");
+                }
             }
-            return Duration::ZERO;
+            if hw && self.last_hw {
+                if let Some(last) = self.last_vblank {
+                    self.measure(time.saturating_sub(last));
+                }
+            }
+            self.last_vblank = Some(time);
+            self.last_hw     = hw;
         }
-        // VRR: yield for 1 ms; actual pace driven by DRM vblank
-        Duration::from_millis(1)
+        self.phase = if redraw { RepaintPhase::Scheduled } else { RepaintPhase::Idle };
+        trace!(?time, hw, flip, redraw, "repaint vblank");
+        (flip, redraw)
+    }
+
+    /// Fold the time between two hardware flips into the refresh estimate
+    /// (exponential moving average, α = 0.1). The gap may span several
+    /// vblanks when frames were skipped; gaps that aren't close to a whole
+    /// number of nominal intervals are ignored.
+    fn measure(&mut self, elapsed: Duration) {
+        let periods = (elapsed.as_secs_f64() / self.refresh.as_secs_f64()).round();
+        if !(1.0..=8.0).contains(&periods) { return; }
+        let sample = elapsed.as_secs_f64() / periods;
+        let nominal = self.nominal.as_secs_f64();
+        if (sample - nominal).abs() > nominal * REFRESH_TOLERANCE { return; }
+        self.refresh = Duration::from_secs_f64(self.refresh.as_secs_f64() * 0.9 + sample * 0.1);
+    }
+
+    /// Time of the last completed page flip, if any.
+    pub fn last_vblank(&self) -> Option<Duration> { self.last_vblank }
+
+    /// Estimated time of the next vblank on this output, extrapolated from the
+    /// last completed flip. Falls back to `now` before the first flip.
+    pub fn next_vblank(&self, now: Duration) -> Duration {
+        let Some(last) = self.last_vblank else { return now };
+        if now <= last { return last + self.refresh; }
+        let periods = ((now - last).as_nanos() / self.refresh.as_nanos().max(1)) as u32 + 1;
+        last + self.refresh * periods
     }
+}
+
+// ── Adaptive sync ─────────────────────────────────────────────────────────────
+
+/// How the next frame should be presented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Wait for the fixed vblank cadence.
+    Vsync,
+    /// Present as soon as the frame is ready (VRR stretches the refresh, or
+    /// the flip tears).
+    Immediate,
+}
+
+/// Consecutive late / on-time frames needed before switching mode.
+const ADAPTIVE_SWITCH_FRAMES: u32 = 3;
 
-    /// Smoothed FPS (exponential moving average).
-    pub fn smoothed_fps(&self) -> f32 { self.fps_smooth }
+/// Adaptive vsync heuristic for one output.
+///
+/// A frame is *late* when the time since the last flip plus the predicted
+/// render time exceeds one refresh interval, i.e. it can no longer make the
+/// vblank following the previous flip. After `ADAPTIVE_SWITCH_FRAMES` late
+/// frames in a row the output switches to [`PresentMode::Immediate`]; after as
+/// many on-time frames in a row it returns to [`PresentMode::Vsync`]. The
+/// predicted render time is an exponential moving average (α = 0.2) of how
+/// long composition took on previous frames.
+#[derive(Debug)]
+pub struct AdaptiveSync {
+    mode:          PresentMode,
+    render_est:    Duration,
+    late_streak:   u32,
+    ontime_streak: u32,
+}
 
-    /// Total frames rendered since start.
-    pub fn frame_count(&self) -> u64 { self.frame_count }
+impl AdaptiveSync {
+    pub fn new() -> Self {
+        Self {
+            mode: PresentMode::Vsync,
+            render_est: Duration::ZERO,
+            late_streak: 0,
+            ontime_streak: 0,
+        }
+    }
+
+    pub fn mode(&self) -> PresentMode { self.mode }
+
+    /// Fold the duration of the last composition into the render estimate.
+    pub fn record_render(&mut self, took: Duration) {
+        self.render_est = self.render_est.mul_f32(0.8) + took.mul_f32(0.2);
+    }
+
+    /// Classify a frame that starts rendering `since_vblank` after the last
+    /// flip and return the mode it should be presented with.
+    pub fn on_frame(&mut self, since_vblank: Duration, refresh: Duration) -> PresentMode {
+        if since_vblank + self.render_est > refresh {
+            self.late_streak   += 1;
+            self.ontime_streak = 0;
+        } else {
+            self.ontime_streak += 1;
+            self.late_streak   = 0;
+        }
+        let next = match self.mode {
+            PresentMode::Vsync if self.late_streak >= ADAPTIVE_SWITCH_FRAMES => PresentMode::Immediate,
+            PresentMode::Immediate if self.ontime_streak >= ADAPTIVE_SWITCH_FRAMES => PresentMode::Vsync,
+            m => m,
+        };
+        if next != self.mode {
+            trace!(?next, render_est_us = self.render_est.as_micros(), "adaptive sync switch");
+            self.mode = next;
+        }
+        next
+    }
+
+    /// Drop back to vsync, e.g. when adaptive sync stops applying.
+    pub fn reset(&mut self) {
+        self.mode          = PresentMode::Vsync;
+        self.late_streak   = 0;
+        self.ontime_streak = 0;
+    }
+}
+
+impl Default for AdaptiveSync {
+    fn default() -> Self { Self::new() }
+}
+
+// ── Low framerate compensation ────────────────────────────────────────────────
+
+/// Low framerate compensation for a VRR output. Some panels flicker (or
+/// blank) when the refresh drops below their minimum, which VRR does as
+/// soon as a game renders slower than that. When no new frame has been
+/// flipped by the time one is due to keep the panel in range, the last one
+/// is flipped again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lfc {
+    /// Longest the panel may hold a frame (1 / minimum refresh).
+    floor:   Duration,
+    /// Shortest time between two flips (1 / maximum refresh).
+    ceiling: Duration,
+}
+
+impl Lfc {
+    /// `range` is the panel's VRR range in Hz. `None` when there is nothing
+    /// to compensate (no minimum, or no room between the bounds).
+    pub fn new((min_hz, max_hz): (u32, u32)) -> Option<Self> {
+        if min_hz == 0 || max_hz <= min_hz { return None; }
+        Some(Self {
+            floor:   Duration::from_secs_f64(1.0 / min_hz as f64),
+            ceiling: Duration::from_secs_f64(1.0 / max_hz as f64),
+        })
+    }
+
+    /// How long after a flip to repeat it if nothing new came: late enough
+    /// to give the next real frame every chance, early enough for the
+    /// repeat to reach the panel before its floor.
+    pub fn repeat_after(&self) -> Duration { self.floor.saturating_sub(self.ceiling) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_millis(16);
+
+    fn ms(ms: f64) -> Duration { Duration::from_secs_f64(ms / 1000.0) }
+
+    #[test]
+    fn switches_after_a_streak_of_late_frames() {
+        let mut sync = AdaptiveSync::new();
+        assert_eq!(sync.on_frame(ms(17.0), REFRESH), PresentMode::Vsync);
+        assert_eq!(sync.on_frame(ms(17.0), REFRESH), PresentMode::Vsync);
+        assert_eq!(sync.on_frame(ms(17.0), REFRESH), PresentMode::Immediate);
+        // Back only after as many on-time frames in a row.
+        assert_eq!(sync.on_frame(ms(5.0), REFRESH), PresentMode::Immediate);
+        assert_eq!(sync.on_frame(ms(5.0), REFRESH), PresentMode::Immediate);
+        assert_eq!(sync.on_frame(ms(5.0), REFRESH), PresentMode::Vsync);
+    }
+
+    #[test]
+    fn an_on_time_frame_breaks_the_streak() {
+        let mut sync = AdaptiveSync::new();
+        for since in [17.0, 17.0, 5.0, 17.0, 17.0] {
+            assert_eq!(sync.on_frame(ms(since), REFRESH), PresentMode::Vsync);
+        }
+        assert_eq!(sync.on_frame(ms(17.0), REFRESH), PresentMode::Immediate);
+        for since in [5.0, 5.0, 17.0, 5.0, 5.0] {
+            assert_eq!(sync.on_frame(ms(since), REFRESH), PresentMode::Immediate);
+        }
+    }
+
+    #[test]
+    fn render_estimate_is_a_moving_average() {
+        let mut sync = AdaptiveSync::new();
+        // A fifth of the first 10 ms composition: 2 ms.
+        sync.record_render(ms(10.0));
+        for _ in 0..3 {
+            sync.on_frame(ms(13.5), REFRESH);
+        }
+        assert_eq!(sync.mode(), PresentMode::Vsync);
+        for _ in 0..3 {
+            sync.on_frame(ms(14.5), REFRESH);
+        }
+        assert_eq!(sync.mode(), PresentMode::Immediate);
+
+        // Steady 10 ms compositions pull the estimate to just under 10 ms.
+        let mut sync = AdaptiveSync::new();
+        for _ in 0..30 {
+            sync.record_render(ms(10.0));
+        }
+        for _ in 0..3 {
+            sync.on_frame(ms(5.5), REFRESH);
+        }
+        assert_eq!(sync.mode(), PresentMode::Vsync);
+        for _ in 0..3 {
+            sync.on_frame(ms(6.5), REFRESH);
+        }
+        assert_eq!(sync.mode(), PresentMode::Immediate);
+    }
+
+    #[test]
+    fn reset_returns_to_vsync_and_forgets_the_streak() {
+        let mut sync = AdaptiveSync::new();
+        for _ in 0..3 {
+            sync.on_frame(ms(17.0), REFRESH);
+        }
+        sync.reset();
+        assert_eq!(sync.mode(), PresentMode::Vsync);
+        sync.on_frame(ms(17.0), REFRESH);
+        assert_eq!(sync.on_frame(ms(17.0), REFRESH), PresentMode::Vsync);
+    }
+
+    #[test]
+    fn lfc_needs_a_range() {
+        assert_eq!(Lfc::new((0, 144)), None);
+        assert_eq!(Lfc::new((60, 60)), None);
+        assert_eq!(Lfc::new((144, 48)), None);
+    }
+
+    #[test]
+    fn lfc_repeats_a_frame_in_time_for_the_floor() {
+        // 48-144 Hz: held at most 20.83 ms, flips at least 6.94 ms apart.
+        let lfc = Lfc::new((48, 144)).unwrap();
+        let repeat = lfc.repeat_after();
+        assert!((repeat.as_secs_f64() * 1000.0 - (1000.0 / 48.0 - 1000.0 / 144.0)).abs() < 0.001, "{repeat:?}");
+        // The repeat's own flip still lands before the floor.
+        assert!(repeat + Duration::from_secs_f64(1.0 / 144.0) <= Duration::from_secs_f64(1.0 / 48.0));
+        // The wider the range, the longer a real frame is waited for.
+        assert!(Lfc::new((30, 144)).unwrap().repeat_after() > repeat);
+    }
 }
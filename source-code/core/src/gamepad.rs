@@ -0,0 +1,269 @@
+use std::{
+    fs::File,
+    io::Read,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use smithay::{
+    backend::input::ButtonState,
+    reexports::calloop::{
+        generic::Generic,
+        timer::{TimeoutAction, Timer},
+        Interest, LoopHandle, Mode, PostAction,
+    },
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    config::{GamepadAction, GamepadConfig},
+    idle,
+    input_handler::{move_pointer, press_button},
+    spawn,
+    state::GameframeState,
+    workspace::WORKSPACE_COUNT,
+};
+
+// evdev event types and codes (linux/input-event-codes.h).
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const BTN_GAMEPAD: u16 = 0x130;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+
+/// Size of `struct input_event` on 64-bit Linux.
+const EVENT_SIZE: usize = 24;
+/// Stick deflection (fraction of full range) below which the cursor rests.
+const DEADZONE: f64 = 0.15;
+const CURSOR_TICK: Duration = Duration::from_millis(8);
+
+/// Button names used in `[gamepad.bindings]`.
+fn button_name(code: u16) -> Option<&'static str> {
+    Some(match code {
+        0x130 => "a",
+        0x131 => "b",
+        0x133 => "x",
+        0x134 => "y",
+        0x136 => "lb",
+        0x137 => "rb",
+        0x13a => "select",
+        0x13b => "start",
+        0x13c => "guide",
+        0x13d => "ls",
+        0x13e => "rs",
+        0x220 => "dpad_up",
+        0x221 => "dpad_down",
+        0x222 => "dpad_left",
+        0x223 => "dpad_right",
+        _ => return None,
+    })
+}
+
+// ── Device ────────────────────────────────────────────────────────────────────
+
+/// One controller. Devices are read without an exclusive grab, so games keep
+/// receiving every event; the compositor only acts on buttons pressed while
+/// the configured modifier button is held.
+struct Gamepad {
+    name:     String,
+    /// Left stick range per axis (min, max), from `EVIOCGABS`.
+    range:    [(i32, i32); 2],
+    stick:    [f64; 2],
+    modifier: bool,
+    /// Buttons whose press was turned into an action, so their release is
+    /// too even if the modifier was let go in between.
+    held:     Vec<u16>,
+}
+
+impl Gamepad {
+    fn open(path: &Path) -> Result<Option<(File, Self)>> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)
+            .with_context(|| format!("open {}", path.display()))?;
+        if !has_key(&file, BTN_GAMEPAD) {
+            return Ok(None);
+        }
+        let name = device_name(path).unwrap_or_else(|| path.display().to_string());
+        let range = [abs_range(&file, ABS_X), abs_range(&file, ABS_Y)];
+        Ok(Some((file, Self { name, range, stick: [0.0; 2], modifier: false, held: Vec::new() })))
+    }
+
+    /// Normalised (-1..1) position of stick axis `axis`.
+    fn normalise(&self, axis: usize, value: i32) -> f64 {
+        let (min, max) = self.range[axis];
+        if max <= min { return 0.0; }
+        (2.0 * (value - min) as f64 / (max - min) as f64 - 1.0).clamp(-1.0, 1.0)
+    }
+}
+
+fn ioc_read(nr: u32, size: usize) -> libc::c_ulong {
+    ((2 << 30) | ((size as u32) << 16) | ((b'E' as u32) << 8) | nr) as libc::c_ulong
+}
+
+/// Whether the device reports key `code` (`EVIOCGBIT(EV_KEY)`).
+fn has_key(file: &File, code: u16) -> bool {
+    let mut bits = [0u8; 0x300 / 8];
+    // SAFETY: the kernel writes at most `bits.len()` bytes into `bits`.
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), ioc_read(0x20 + EV_KEY as u32, bits.len()), bits.as_mut_ptr()) };
+    ret >= 0 && bits[code as usize / 8] & (1 << (code % 8)) != 0
+}
+
+/// `(minimum, maximum)` of absolute axis `axis` (`EVIOCGABS`).
+fn abs_range(file: &File, axis: u16) -> (i32, i32) {
+    // struct input_absinfo { value, minimum, maximum, fuzz, flat, resolution }
+    let mut info = [0i32; 6];
+    // SAFETY: `info` is exactly `struct input_absinfo`.
+    let ret = unsafe {
+        libc::ioctl(file.as_raw_fd(), ioc_read(0x40 + axis as u32, std::mem::size_of_val(&info)), info.as_mut_ptr())
+    };
+    if ret < 0 { (0, 0) } else { (info[1], info[2]) }
+}
+
+fn device_name(path: &Path) -> Option<String> {
+    let node = path.file_name()?.to_str()?;
+    let name = std::fs::read_to_string(format!("/sys/class/input/{node}/device/name")).ok()?;
+    Some(name.trim().to_owned())
+}
+
+// ── Setup ─────────────────────────────────────────────────────────────────────
+
+/// Open every controller in `/dev/input` and feed it into the event loop,
+/// plus a timer that moves the pointer while the left stick is deflected.
+/// Controllers plugged in later are not picked up.
+pub fn init(loop_handle: &LoopHandle<'static, GameframeState>, config: &GamepadConfig) -> Result<()> {
+    let mut found = 0;
+    for entry in std::fs::read_dir("/dev/input").context("read /dev/input")? {
+        let path: PathBuf = entry?.path();
+        if !path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("event")) {
+            continue;
+        }
+        let (file, mut pad) = match Gamepad::open(&path) {
+            Ok(Some(dev)) => dev,
+            Ok(None)      => continue,
+            Err(e)        => { debug!("gamepad probe: {e:#}"); continue; }
+        };
+        info!(device = %pad.name, "Gamepad navigation enabled");
+        found += 1;
+
+        loop_handle
+            .insert_source(Generic::new(file, Interest::READ, Mode::Level), move |_, file, state| {
+                let mut buf = [0u8; EVENT_SIZE * 64];
+                loop {
+                    // SAFETY: the fd is only read, never closed through this borrow.
+                    let n = match unsafe { file.get_mut() }.read(&mut buf) {
+                        Ok(0) => return Ok(PostAction::Remove),
+                        Ok(n) => n,
+                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                        Err(e) => {
+                            warn!(device = %pad.name, "gamepad removed: {e}");
+                            state.gamepad_stick = [0.0; 2];
+                            return Ok(PostAction::Remove);
+                        }
+                    };
+                    for ev in buf[..n].chunks_exact(EVENT_SIZE) {
+                        let kind  = u16::from_ne_bytes([ev[16], ev[17]]);
+                        let code  = u16::from_ne_bytes([ev[18], ev[19]]);
+                        let value = i32::from_ne_bytes([ev[20], ev[21], ev[22], ev[23]]);
+                        handle_event(state, &mut pad, kind, code, value);
+                    }
+                }
+            })
+            .map_err(|e| anyhow::anyhow!("gamepad source: {e}"))?;
+    }
+    if found == 0 {
+        info!("Gamepad navigation: no controllers found");
+        return Ok(());
+    }
+
+    let speed = config.cursor_speed;
+    loop_handle
+        .insert_source(Timer::from_duration(CURSOR_TICK), move |_, _, state| {
+            let [x, y] = state.gamepad_stick;
+            if x != 0.0 || y != 0.0 {
+                let step = speed * CURSOR_TICK.as_secs_f64();
+                let time = state.clock.now().as_millis();
+                move_pointer(state, (x * step, y * step).into(), time);
+            }
+            TimeoutAction::ToDuration(CURSOR_TICK)
+        })
+        .map_err(|e| anyhow::anyhow!("gamepad cursor timer: {e}"))?;
+    Ok(())
+}
+
+// ── Events → actions ──────────────────────────────────────────────────────────
+
+fn handle_event(state: &mut GameframeState, pad: &mut Gamepad, kind: u16, code: u16, value: i32) {
+    // Playing with a controller is activity too.
+    idle::activity(state);
+    let config = &state.config.gamepad;
+    match (kind, code) {
+        (EV_KEY, _) => {
+            let Some(name) = button_name(code) else { return };
+            if name == config.modifier {
+                pad.modifier = value != 0;
+                state.gamepad_stick = if pad.modifier { pad.stick } else { [0.0; 2] };
+                return;
+            }
+            // value: 0 release, 1 press, 2 autorepeat.
+            let pressed = value != 0;
+            if pressed {
+                if !pad.modifier || value == 2 { return; }
+                pad.held.push(code);
+            } else {
+                let Some(i) = pad.held.iter().position(|&c| c == code) else { return };
+                pad.held.swap_remove(i);
+            }
+            if let Some(action) = config.bindings.get(name).cloned() {
+                run_action(state, action, pressed);
+            }
+        }
+        (EV_ABS, ABS_X | ABS_Y) => {
+            let axis = (code - ABS_X) as usize;
+            let v    = pad.normalise(axis, value);
+            pad.stick[axis] = if v.abs() < DEADZONE { 0.0 } else { v };
+            state.gamepad_stick = if pad.modifier { pad.stick } else { [0.0; 2] };
+        }
+        // Many controllers report the d-pad as a hat axis instead of buttons.
+        (EV_ABS, ABS_HAT0X | ABS_HAT0Y) if pad.modifier && value != 0 => {
+            let name = match (code, value.signum()) {
+                (ABS_HAT0X, -1) => "dpad_left",
+                (ABS_HAT0X, _)  => "dpad_right",
+                (_, -1)         => "dpad_up",
+                _               => "dpad_down",
+            };
+            if let Some(action) = config.bindings.get(name).cloned() {
+                run_action(state, action, true);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn run_action(state: &mut GameframeState, action: GamepadAction, pressed: bool) {
+    let time = state.clock.now().as_millis();
+    match action {
+        GamepadAction::Click | GamepadAction::RightClick => {
+            let button = if action == GamepadAction::Click { BTN_LEFT } else { BTN_RIGHT };
+            let button_state = if pressed { ButtonState::Pressed } else { ButtonState::Released };
+            press_button(state, button, button_state, time);
+        }
+        _ if !pressed => {}
+        GamepadAction::FocusNext     => state.cycle_focus(1),
+        GamepadAction::FocusPrev     => state.cycle_focus(-1),
+        GamepadAction::CloseWindow   => state.close_focused(),
+        GamepadAction::NextWorkspace => state.switch_workspace((state.workspaces.active() + 1) % WORKSPACE_COUNT),
+        GamepadAction::PrevWorkspace => {
+            state.switch_workspace((state.workspaces.active() + WORKSPACE_COUNT - 1) % WORKSPACE_COUNT)
+        }
+        GamepadAction::ToggleOverlay => state.overlay.toggle(),
+        GamepadAction::Launch(cmd)   => spawn::launch(state, &cmd),
+    }
+}
@@ -0,0 +1,91 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use smithay::utils::{Logical, Rectangle};
+use tracing::{debug, warn};
+
+/// Titles remembered per app; the least recently seen is dropped first.
+const TITLES_PER_APP: usize = 8;
+
+/// Window geometry remembered per app across sessions
+/// (`session.remember_geometry`). Each app keeps the geometry of the window
+/// it closed last, plus that of its last few distinct titles so apps with
+/// several windows (a chat client's main and call windows) keep them apart.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GeometryStore {
+    apps:  HashMap<String, AppEntry>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+/// Geometries are global logical `[x, y, w, h]` of the window geometry.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AppEntry {
+    geometry: [i32; 4],
+    /// Most recent first.
+    titles:   Vec<TitleEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TitleEntry {
+    title:    String,
+    geometry: [i32; 4],
+}
+
+impl GeometryStore {
+    /// Read the state file; a missing or unreadable file starts empty.
+    pub fn load() -> Self {
+        let path = store_path();
+        match std::fs::read_to_string(&path) {
+            Ok(raw) => toml::from_str(&raw).unwrap_or_else(|e| {
+                warn!(path = %path.display(), "ignoring window geometry file: {e}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Remembered geometry, preferring the entry for this exact title.
+    pub fn lookup(&self, app_id: &str, title: Option<&str>) -> Option<Rectangle<i32, Logical>> {
+        let app = self.apps.get(app_id)?;
+        let [x, y, w, h] = title
+            .and_then(|t| app.titles.iter().find(|e| e.title == t))
+            .map_or(app.geometry, |e| e.geometry);
+        Some(Rectangle::new((x, y).into(), (w, h).into()))
+    }
+
+    pub fn record(&mut self, app_id: &str, title: Option<&str>, geo: Rectangle<i32, Logical>) {
+        let geometry = [geo.loc.x, geo.loc.y, geo.size.w, geo.size.h];
+        let app = self.apps.entry(app_id.to_owned()).or_default();
+        app.geometry = geometry;
+        if let Some(title) = title {
+            app.titles.retain(|e| e.title != title);
+            app.titles.insert(0, TitleEntry { title: title.to_owned(), geometry });
+            app.titles.truncate(TITLES_PER_APP);
+        }
+        self.dirty = true;
+    }
+
+    /// Write the state file if anything was recorded since the last save.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.dirty { return Ok(()); }
+        let path = store_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)?;
+        self.dirty = false;
+        debug!(path = %path.display(), "window geometry saved");
+        Ok(())
+    }
+}
+
+/// `$XDG_STATE_HOME/gameframe/windows.toml` (default `~/.local/state`).
+fn store_path() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("gameframe/windows.toml")
+}
@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use smithay::backend::{
+    allocator::{Format, Modifier},
+    egl::EGLDisplay,
+};
+use tracing::{error, warn};
+
+/// EGL display extensions the GLES renderer and dmabuf import rely on.
+const REQUIRED_EGL: [&str; 3] = [
+    "EGL_KHR_image_base",
+    "EGL_EXT_image_dma_buf_import",
+    "EGL_KHR_surfaceless_context",
+];
+
+/// Step of bringing up rendering on a DRM device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Gbm,
+    EglDisplay,
+    EglContext,
+    Renderer,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Gbm        => "GBM device",
+            Stage::EglDisplay => "EGL display",
+            Stage::EglContext => "EGL context",
+            Stage::Renderer   => "GLES renderer",
+        }
+    }
+}
+
+/// Why a device couldn't be used for rendering, as shown by
+/// `gameframe status` after a failed start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Failure {
+    pub device: String,
+    pub stage:  String,
+    pub error:  String,
+    /// What to check or install, when the cause could be narrowed down.
+    pub hints:  Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Failures {
+    #[serde(default)]
+    failure: Vec<Failure>,
+}
+
+/// Work out why `stage` failed on `device`, log it with whatever can be
+/// done about it, and remember it for `gameframe status`. `egl` is the
+/// display, when the failure came after it was created.
+pub fn report(device: &Path, stage: Stage, err: &anyhow::Error, egl: Option<&EGLDisplay>) {
+    let mut hints = Vec::new();
+    let card   = device.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let sysfs  = Path::new("/sys/class/drm").join(&card).join("device");
+    let driver = std::fs::read_link(sysfs.join("driver")).ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()));
+
+    if !has_render_node(&sysfs) {
+        hints.push(format!(
+            "{card} has no render node: {} is a display-only driver (simpledrm, efifb?); \
+             load the GPU's own kernel driver",
+            driver.as_deref().unwrap_or("its driver"),
+        ));
+    }
+    match stage {
+        Stage::Gbm | Stage::EglDisplay => match driver.as_deref() {
+            Some("nvidia") | Some("nvidia-drm") => hints.push(
+                "NVIDIA needs nvidia-drm.modeset=1 and the GBM backend (nvidia-egl-gbm / egl-gbm)".into(),
+            ),
+            Some(driver) => hints.push(format!(
+                "no GBM/EGL implementation for the {driver} driver: is Mesa (libgbm, libEGL) installed?"
+            )),
+            None => hints.push("no kernel driver is bound to this device".into()),
+        },
+        Stage::EglContext | Stage::Renderer => {
+            let missing = egl.map(missing_extensions).unwrap_or_default();
+            if !missing.is_empty() {
+                hints.push(format!("the EGL driver lacks {}; update Mesa or the GPU driver", missing.join(", ")));
+            }
+        }
+    }
+
+    let failure = Failure {
+        device: device.display().to_string(),
+        stage:  stage.name().to_owned(),
+        error:  format!("{err:#}"),
+        hints,
+    };
+    error!(device = %failure.device, stage = %failure.stage, "GPU setup failed: {}", failure.error);
+    for hint in &failure.hints {
+        error!(device = %failure.device, "  hint: {hint}");
+    }
+    let mut all = load();
+    all.failure.retain(|f| f.device != failure.device);
+    all.failure.push(failure);
+    store(&all);
+}
+
+/// `device` came up fine; forget any failure recorded for it.
+pub fn clear(device: &Path) {
+    let mut all = load();
+    let before = all.failure.len();
+    all.failure.retain(|f| Path::new(&f.device) != device);
+    if all.failure.len() != before {
+        store(&all);
+    }
+}
+
+/// Failures recorded by earlier starts, for `gameframe status`.
+pub fn recorded() -> Vec<Failure> { load().failure }
+
+/// Warn when the driver only offers implicit modifiers: scanout still
+/// works, but direct scanout of client buffers and multi-GPU sharing
+/// mostly don't.
+pub fn check_modifiers<'a>(device: &Path, formats: impl IntoIterator<Item = &'a Format>) {
+    let explicit = formats.into_iter().any(|f| f.modifier != Modifier::Invalid);
+    if !explicit {
+        warn!(
+            device = %device.display(),
+            "GBM/EGL report no explicit format modifiers; direct scanout and PRIME are limited \
+             (driver without modifier support, or EGL_EXT_image_dma_buf_import_modifiers missing)"
+        );
+    }
+}
+
+fn missing_extensions(egl: &EGLDisplay) -> Vec<&'static str> {
+    let have = egl.extensions();
+    REQUIRED_EGL.into_iter().filter(|e| !have.iter().any(|h| h == e)).collect()
+}
+
+fn has_render_node(sysfs: &Path) -> bool {
+    std::fs::read_dir(sysfs.join("drm"))
+        .map(|dir| dir.flatten().any(|e| e.file_name().to_string_lossy().starts_with("renderD")))
+        .unwrap_or(false)
+}
+
+fn load() -> Failures {
+    std::fs::read_to_string(failures_path()).ok()
+        .and_then(|raw| toml::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn store(all: &Failures) {
+    let path = failures_path();
+    let result = if all.failure.is_empty() {
+        std::fs::remove_file(&path).or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) })
+    } else {
+        match toml::to_string(all) {
+            Ok(raw) => std::fs::write(&path, raw),
+            Err(e)  => { warn!("serialize GPU failures: {e}"); return; }
+        }
+    };
+    if let Err(e) = result {
+        warn!(path = %path.display(), "record GPU failures: {e}");
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/gameframe-gpu.toml`.
+fn failures_path() -> PathBuf {
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/tmp"))
+        .join("gameframe-gpu.toml")
+}
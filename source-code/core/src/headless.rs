@@ -0,0 +1,415 @@
+use std::{
+    os::unix::{fs::OpenOptionsExt, io::OwnedFd},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Allocator, Fourcc, Modifier,
+        },
+        drm::DrmDeviceFd,
+        egl::{EGLContext, EGLDisplay},
+        renderer::{damage::OutputDamageTracker, element::RenderElementStates, gles::GlesRenderer, Bind},
+    },
+    desktop::utils::OutputPresentationFeedback,
+    output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+    reexports::{
+        calloop::timer::{TimeoutAction, Timer},
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::{backend::GlobalId, DisplayHandle},
+    },
+    utils::{DeviceFd, Monotonic, Physical, Rectangle, Size, Time, Transform},
+    wayland::presentation::Refresh,
+};
+use tracing::{debug, info, info_span, warn};
+
+use crate::{
+    compositor::{drm_node_for, Backend},
+    dmabuf::init_dmabuf_global,
+    frame::{RepaintPhase, RepaintState},
+    modes::parse_mode_spec,
+    render::{
+        apply_color_filter, magnify, mask_for_capture, output_elements, send_frame_callbacks,
+        take_presentation_feedback, update_primary_scanout_outputs, FrameElements, Scene, CLEAR_COLOR,
+    },
+    screencopy::{self, Capture},
+    session::SessionOptions,
+    state::GameframeState,
+    supersample::supersample,
+    upscale,
+};
+
+/// Mode of the virtual output created when none are configured.
+const DEFAULT_MODE: &str = "1920x1080@60";
+
+// ── Headless backend state ────────────────────────────────────────────────────
+
+/// Gameframe with no display attached (cloud gaming, remote desktop): a
+/// render node drives any number of virtual outputs whose frames end up in
+/// dmabufs that capture clients copy through wlr-screencopy.
+pub struct HeadlessBackend {
+    pub renderer: GlesRenderer,
+    allocator:    GbmAllocator<DrmDeviceFd>,
+    pub outputs:  Vec<VirtualOutput>,
+    /// Suffix of the next output's name (`HEADLESS-<n>`); never reused.
+    next_id:      u32,
+}
+
+/// An output that exists only in memory. Frames alternate between two
+/// linear dmabufs and reach clients through [`screencopy`].
+pub struct VirtualOutput {
+    pub output:     Output,
+    global:         GlobalId,
+    buffers:        [Dmabuf; 2],
+    /// Frames since each buffer was last drawn; 0 if it never was.
+    ages:           [usize; 2],
+    front:          usize,
+    damage_tracker: OutputDamageTracker,
+    pub repaint:    RepaintState,
+    pending_feedback: Option<OutputPresentationFeedback>,
+    /// Screencopy requests waiting for the next frame.
+    captures:       Vec<Capture>,
+}
+
+impl HeadlessBackend {
+    pub fn output_mut(&mut self, output: &Output) -> Option<&mut VirtualOutput> {
+        self.outputs.iter_mut().find(|o| &o.output == output)
+    }
+
+    /// Create a virtual output with mode `spec` (`WxH` or `WxH@Hz`).
+    pub fn add_output(&mut self, dh: &DisplayHandle, spec: &str, scale: f64) -> Result<Output> {
+        let (w, h, hz) = parse_mode_spec(spec)?;
+        let hz = hz.unwrap_or(60.0);
+        if w == 0 || h == 0 || !(1.0..=1000.0).contains(&hz) {
+            bail!("{spec} is out of range");
+        }
+        let size: Size<i32, Physical> = (w as i32, h as i32).into();
+        let mode = Mode { size, refresh: (hz * 1000.0).round() as i32 };
+        let buffers = [self.allocate(size)?, self.allocate(size)?];
+
+        let name = format!("HEADLESS-{}", self.next_id);
+        self.next_id += 1;
+        let output = Output::new(name.clone(), PhysicalProperties {
+            size:          (0, 0).into(),
+            subpixel:      Subpixel::Unknown,
+            make:          "Gameframe".into(),
+            model:         "Virtual".into(),
+            serial_number: String::new(),
+        });
+        let global = output.create_global::<GameframeState>(dh);
+        output.change_current_state(Some(mode), Some(Transform::Normal), Some(Scale::Fractional(scale)), None);
+        output.set_preferred(mode);
+        info!(%name, w, h, hz, "Virtual output added");
+
+        self.outputs.push(VirtualOutput {
+            damage_tracker: OutputDamageTracker::from_output(&output),
+            repaint: RepaintState::new(mode.refresh),
+            output: output.clone(),
+            global,
+            buffers,
+            ages: [0, 0],
+            front: 0,
+            pending_feedback: None,
+            captures: Vec::new(),
+        });
+        Ok(output)
+    }
+
+    /// Take the output named `name` away; its global is withdrawn.
+    pub fn remove_output(&mut self, dh: &DisplayHandle, name: &str) -> Option<Output> {
+        let index = self.outputs.iter().position(|o| o.output.name() == name)?;
+        let mut out = self.outputs.remove(index);
+        if let Some(mut feedback) = out.pending_feedback.take() {
+            feedback.discarded();
+        }
+        for capture in out.captures.drain(..) {
+            capture.fail();
+        }
+        dh.remove_global::<GameframeState>(out.global);
+        info!(%name, "Virtual output removed");
+        Some(out.output)
+    }
+
+    fn allocate(&mut self, size: Size<i32, Physical>) -> Result<Dmabuf> {
+        // Linear so encoders and other devices can import it as is.
+        let bo = self.allocator
+            .create_buffer(size.w as u32, size.h as u32, Fourcc::Xrgb8888, &[Modifier::Linear])
+            .with_context(|| format!("allocate {}x{} buffer", size.w, size.h))?;
+        bo.export().map_err(|e| anyhow!("export dmabuf: {e:?}"))
+    }
+}
+
+impl VirtualOutput {
+    /// The most recently completed frame, if one was drawn yet.
+    fn front_buffer(&mut self) -> Option<&mut Dmabuf> {
+        (self.ages[self.front] > 0).then(|| &mut self.buffers[self.front])
+    }
+
+    /// Serve `capture` from the newest frame, or hold it until the next one
+    /// if it waits for damage or nothing was drawn yet. Returns whether it
+    /// is held.
+    pub fn capture(&mut self, renderer: &mut GlesRenderer, capture: Capture, now: Duration) -> bool {
+        if capture.ready(None) {
+            if let Some(front) = self.front_buffer() {
+                capture.serve(renderer, front, None, now);
+                return false;
+            }
+        }
+        self.captures.push(capture);
+        true
+    }
+
+    /// Serve the held captures that the frame just drawn with `damage`
+    /// satisfies.
+    fn serve_captures(&mut self, renderer: &mut GlesRenderer, damage: Option<&[Rectangle<i32, Physical>]>, now: Duration) {
+        let (ready, held): (Vec<_>, Vec<_>) = std::mem::take(&mut self.captures).into_iter().partition(|c| c.ready(damage));
+        self.captures = held;
+        for capture in ready {
+            capture.serve(renderer, &mut self.buffers[self.front], damage, now);
+        }
+    }
+
+    fn render(
+        &mut self,
+        renderer: &mut GlesRenderer,
+        elements: &[FrameElements],
+    ) -> Result<(RenderElementStates, Option<Vec<Rectangle<i32, Physical>>>)> {
+        let back = 1 - self.front;
+        let mut fb = renderer.bind(&mut self.buffers[back]).context("bind virtual output buffer")?;
+        let res = self.damage_tracker
+            .render_output(renderer, &mut fb, self.ages[back], elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("render_output: {e:?}"))?;
+        // Readers get the buffer once rendering is done, not just queued.
+        if let Err(e) = res.sync.wait() {
+            debug!("virtual output sync: {e:?}");
+        }
+        let damage = res.damage.cloned();
+        let states = res.states;
+        for age in self.ages.iter_mut().filter(|a| **a > 0) {
+            *age += 1;
+        }
+        self.ages[back] = 1;
+        self.front      = back;
+        Ok((states, damage))
+    }
+}
+
+// ── Init ──────────────────────────────────────────────────────────────────────
+
+/// Open a render node and create the configured virtual outputs (one at
+/// `1920x1080@60` if none are). No seat, DRM master or display is needed;
+/// input only comes from the gamepad and the control socket.
+pub fn init(state: &mut GameframeState, opts: &SessionOptions) -> Result<()> {
+    let node = match &opts.drm_device {
+        Some(spec) => drm_node_for(spec, true)?,
+        None       => find_render_node().context("no render node in /dev/dri")?,
+    };
+    let (gbm, renderer) = open_render_node(&node)?;
+    info!(node = %node.display(), "Headless renderer ready");
+
+    match init_dmabuf_global(&renderer, &mut state.dmabuf_state, &state.display_handle) {
+        Ok(global) => state.dmabuf_global = Some(global),
+        Err(e)     => warn!("DMABUF global failed (non-fatal): {e}"),
+    }
+    screencopy::init(state);
+
+    state.backend = Some(Backend::Headless(HeadlessBackend {
+        renderer,
+        allocator: GbmAllocator::new(gbm, GbmBufferFlags::RENDERING),
+        outputs:   Vec::new(),
+        next_id:   1,
+    }));
+
+    let specs = match state.config.display.virtual_outputs.clone() {
+        specs if specs.is_empty() => vec![DEFAULT_MODE.to_owned()],
+        specs => specs,
+    };
+    for spec in specs {
+        if let Err(e) = add_virtual_output(state, &spec) {
+            warn!(mode = %spec, "Virtual output not created: {e:#}");
+        }
+    }
+    Ok(())
+}
+
+/// GBM device and GLES renderer on `node`, for backends that render
+/// without a display of their own.
+pub(crate) fn open_render_node(node: &Path) -> Result<(GbmDevice<DrmDeviceFd>, GlesRenderer)> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_CLOEXEC)
+        .open(node)
+        .with_context(|| format!("open {}", node.display()))?;
+    let fd  = DrmDeviceFd::new(DeviceFd::from(OwnedFd::from(file)));
+    let gbm = GbmDevice::new(fd).context("GBM device")?;
+
+    let egl_display = unsafe { EGLDisplay::new(gbm.clone()).context("EGLDisplay::new")? };
+    let egl_context = EGLContext::new(&egl_display).context("EGLContext::new")?;
+    let renderer: GlesRenderer = unsafe { GlesRenderer::new(egl_context).context("GlesRenderer::new")? };
+    Ok((gbm, renderer))
+}
+
+/// Whether `path` is a DRM render node rather than a primary node.
+fn is_render_node(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("renderD"))
+}
+
+/// First `/dev/dri/renderD*` node.
+pub(crate) fn find_render_node() -> Option<PathBuf> {
+    let mut nodes: Vec<PathBuf> = std::fs::read_dir("/dev/dri")
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| is_render_node(p))
+        .collect();
+    nodes.sort();
+    nodes.into_iter().next()
+}
+
+// ── Runtime changes ───────────────────────────────────────────────────────────
+
+/// Add a virtual output at runtime and place it in the layout. Returns its
+/// name.
+pub fn add_virtual_output(state: &mut GameframeState, spec: &str) -> Result<String> {
+    let scale = state.config.display.scale.unwrap_or(1.0);
+    let Some(headless) = state.backend.as_mut().and_then(|b| b.headless_mut()) else {
+        bail!("virtual outputs need the headless backend");
+    };
+    let output = headless.add_output(&state.display_handle, spec, scale)?;
+    state.map_output(&output);
+    state.queue_redraw(&output);
+    Ok(output.name())
+}
+
+/// Remove the virtual output `name`; its windows move to another output.
+pub fn remove_virtual_output(state: &mut GameframeState, name: &str) -> Result<()> {
+    let Some(headless) = state.backend.as_mut().and_then(|b| b.headless_mut()) else {
+        bail!("virtual outputs need the headless backend");
+    };
+    if headless.outputs.len() == 1 && headless.outputs[0].output.name() == name {
+        bail!("{name} is the last output");
+    }
+    let output = headless
+        .outputs
+        .iter()
+        .find(|o| o.output.name() == name)
+        .map(|o| o.output.clone())
+        .with_context(|| format!("no virtual output {name}"))?;
+    state.unmap_output(&output);
+    if let Some(headless) = state.backend.as_mut().and_then(|b| b.headless_mut()) {
+        headless.remove_output(&state.display_handle, name);
+    }
+    state.queue_redraw_all();
+    Ok(())
+}
+
+/// `name WxH@Hz` for each virtual output.
+pub fn list_virtual_outputs(headless: &HeadlessBackend) -> String {
+    headless.outputs.iter().filter_map(|o| {
+        let mode = o.output.current_mode()?;
+        Some(format!("{} {}x{}@{:.3}\n", o.output.name(), mode.size.w, mode.size.h, mode.refresh as f64 / 1000.0))
+    }).collect()
+}
+
+// ── Rendering ─────────────────────────────────────────────────────────────────
+
+/// Draw the virtual output `name`. Nothing scans it out, so a timer at the
+/// output's refresh rate stands in for its vblank.
+pub fn render_virtual(state: &mut GameframeState, name: &str) {
+    let now     = Duration::from(state.clock.now());
+    let focused = state.focused_window();
+    let upscaled = state.space.outputs().find(|o| o.name() == name).and_then(|o| upscale::upscaled(state, o));
+    let Some(headless) = state.backend.as_mut().and_then(|b| b.headless_mut()) else { return };
+    let HeadlessBackend { renderer, outputs, .. } = headless;
+    let Some(out) = outputs.iter_mut().find(|o| o.output.name() == name) else { return };
+    let _span = info_span!("render_virtual", output = name, frame = out.repaint.stats().presented).entered();
+
+    if let RepaintPhase::Waiting { .. } = out.repaint.phase() {
+        out.repaint.queue_redraw();
+        return;
+    }
+
+    let scene = Scene {
+        space:      &state.space,
+        animations: &state.animations,
+        config:     &state.config,
+        focused:    focused.as_ref(),
+        focused_output: state.focused_output.as_ref(),
+        dnd_icon:   state.dnd.as_ref().and_then(|d| d.icon_at(state.pointer_location)),
+        upscaled,
+    };
+    upscale::set_filter(renderer, scene.upscaled.as_ref());
+    let elements = output_elements(renderer, &out.output, &scene, now);
+    let elements = magnify(elements, &scene, &out.output, state.magnifier.level(), state.pointer_location);
+    let mut elements = supersample(renderer, &out.output, elements, &state.config);
+    apply_color_filter(renderer, &mut elements, &out.output, state.color_filter.transform());
+    // A virtual output is only ever seen through captures, so windows
+    // hidden from them are covered in the frame itself.
+    mask_for_capture(&mut elements, &scene, &out.output, state.magnifier.level(), state.pointer_location);
+    if let Some(recorder) = state.recorder.as_mut().filter(|r| r.output() == &out.output) {
+        recorder.capture(renderer, &elements, now);
+    }
+    let states = match out.render(renderer, &elements) {
+        Ok((states, damage)) => {
+            out.serve_captures(renderer, damage.as_deref(), now);
+            states
+        }
+        Err(e) => {
+            warn!(output = name, "render failed: {e:#}");
+            out.repaint.frame_dropped();
+            RenderElementStates::default()
+        }
+    };
+    out.repaint.frame_queued(true, now);
+
+    let output  = out.output.clone();
+    let refresh = out.repaint.refresh_interval();
+    let delay   = match out.repaint.next_vblank(now).saturating_sub(now) {
+        d if d.is_zero() => refresh,
+        d => d,
+    };
+    update_primary_scanout_outputs(&state.space, &output, &states);
+    if let Some(mut stale) = out.pending_feedback.replace(take_presentation_feedback(&state.space, &output, &states)) {
+        stale.discarded();
+    }
+
+    if state.animations.tick(now) | state.magnifier.tick(now) {
+        state.queue_redraw(&output);
+    }
+
+    let name = name.to_owned();
+    let _ = state.loop_handle.insert_source(Timer::from_duration(delay), move |_, _, state| {
+        virtual_vblank(state, &name);
+        TimeoutAction::Drop
+    });
+}
+
+/// The virtual output's frame is "on screen": report it and start the next
+/// one if anything asked for it meanwhile.
+fn virtual_vblank(state: &mut GameframeState, name: &str) {
+    let now = Duration::from(state.clock.now());
+    let Some(out) = state.backend.as_mut()
+        .and_then(|b| b.headless_mut())
+        .and_then(|h| h.outputs.iter_mut().find(|o| o.output.name() == name))
+    else {
+        return;
+    };
+    let (flip, redraw) = out.repaint.on_vblank(now, false);
+    if flip {
+        if let Some(mut feedback) = out.pending_feedback.take() {
+            let refresh = Refresh::Fixed(out.repaint.refresh_interval());
+            feedback.presented(Time::<Monotonic>::from(now), refresh, 0, wp_presentation_feedback::Kind::empty());
+        }
+        let output = out.output.clone();
+        send_frame_callbacks(state, &output, now);
+    }
+    if redraw {
+        render_virtual(state, name);
+    }
+}
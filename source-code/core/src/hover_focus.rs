@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use smithay::{
+    desktop::Window,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+    utils::SERIAL_COUNTER,
+};
+use tracing::info;
+
+use crate::{config::FocusModel, state::GameframeState};
+
+/// Pointer-driven focus for the sloppy and strict focus models
+/// (`[input.focus]`): focus moves to what the pointer rests on for
+/// `dwell_ms`, so sweeping across windows on the way elsewhere doesn't
+/// hand focus to each of them. Split-screen has its own pointer focus and
+/// skips this.
+#[derive(Debug, Default)]
+pub struct HoverFocus {
+    /// Where focus goes when the dwell timer fires: a window, or none
+    /// (strict focus over empty desktop).
+    pending: Option<(Option<Window>, RegistrationToken)>,
+}
+
+/// The pointer moved: start (or keep) the dwell for what is under it.
+pub fn pointer_moved(state: &mut GameframeState) {
+    let config = &state.config.input.focus;
+    if config.model == FocusModel::Click || state.split.is_some() || state.window_drag.is_some() {
+        cancel(state);
+        return;
+    }
+    let dwell  = Duration::from_millis(config.dwell_ms as u64);
+    let target = state.window_under(state.pointer_location).map(|(w, _)| w);
+    // Sloppy: empty desktop keeps the focus where it is.
+    if target.is_none() && config.model == FocusModel::Sloppy {
+        cancel(state);
+        return;
+    }
+    let focused = state.seat.get_keyboard().and_then(|k| k.current_focus());
+    let already = match &target {
+        Some(window) => state.focused_window().as_ref() == Some(window),
+        None         => focused.is_none(),
+    };
+    if already {
+        cancel(state);
+        return;
+    }
+    if state.hover_focus.pending.as_ref().is_some_and(|(t, _)| *t == target) { return; }
+    cancel(state);
+    if dwell.is_zero() {
+        focus(state, target.as_ref());
+        return;
+    }
+
+    let timer_target = target.clone();
+    let token = state.loop_handle.insert_source(Timer::from_duration(dwell), move |_, _, state| {
+        state.hover_focus.pending = None;
+        let under = state.window_under(state.pointer_location).map(|(w, _)| w);
+        if under == timer_target && state.config.input.focus.model != FocusModel::Click {
+            focus(state, timer_target.as_ref());
+        }
+        TimeoutAction::Drop
+    });
+    if let Ok(token) = token {
+        state.hover_focus.pending = Some((target, token));
+    }
+}
+
+fn cancel(state: &mut GameframeState) {
+    if let Some((_, token)) = state.hover_focus.pending.take() {
+        state.loop_handle.remove(token);
+    }
+}
+
+fn focus(state: &mut GameframeState, window: Option<&Window>) {
+    match window {
+        Some(window) if state.config.input.focus.raise => state.activate_window(window),
+        Some(window) => state.focus_window(window),
+        None => {
+            if let Some(kb) = state.seat.get_keyboard() {
+                kb.set_focus(state, None, SERIAL_COUNTER.next_serial());
+            }
+        }
+    }
+}
+
+// ── Control ───────────────────────────────────────────────────────────────────
+
+pub fn model_name(model: FocusModel) -> &'static str {
+    match model {
+        FocusModel::Click  => "click",
+        FocusModel::Sloppy => "sloppy",
+        FocusModel::Strict => "strict",
+    }
+}
+
+/// Switch the focus model by name; lasts until the config is reloaded.
+pub fn set_model(state: &mut GameframeState, name: &str) -> Result<()> {
+    let model = match name {
+        "click"  => FocusModel::Click,
+        "sloppy" => FocusModel::Sloppy,
+        "strict" => FocusModel::Strict,
+        _        => bail!("unknown focus model {name:?} (click, sloppy, strict)"),
+    };
+    state.config.input.focus.model = model;
+    cancel(state);
+    info!(model = name, "Focus model");
+    // Apply it where the pointer is now rather than on the next motion.
+    pointer_moved(state);
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use smithay::reexports::{
+    calloop::timer::{TimeoutAction, Timer},
+    wayland_server::{protocol::wl_surface::WlSurface, Resource},
+};
+use tracing::info;
+
+use crate::{
+    compositor::{blank_drm, unblank_drm},
+    config::IdleConfig,
+    spawn,
+    state::GameframeState,
+};
+
+/// What happens once the session has been idle long enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Dim,
+    Blank,
+    Lock,
+    Suspend,
+    Command,
+}
+
+/// Staged idle handling. Input only stamps the time, so it costs nothing
+/// per event; one timer wakes up at the next stage's deadline, checks how
+/// long it has really been since the last input and runs the stages due.
+/// Any input undoes dimming and blanking and starts over; while a client
+/// holds an idle inhibitor (video players, games) nothing runs.
+#[derive(Debug, Default)]
+pub struct Idle {
+    last_input: Duration,
+    /// Stages run since the last input.
+    done:       Vec<Action>,
+    armed:      bool,
+    inhibitors: Vec<WlSurface>,
+}
+
+impl Idle {
+    pub fn inhibit(&mut self, surface: WlSurface) {
+        if !self.inhibitors.contains(&surface) {
+            self.inhibitors.push(surface);
+        }
+    }
+
+    pub fn uninhibit(&mut self, surface: &WlSurface) {
+        self.inhibitors.retain(|s| s != surface);
+    }
+
+    fn inhibited(&mut self) -> bool {
+        self.inhibitors.retain(|s| s.is_alive());
+        !self.inhibitors.is_empty()
+    }
+}
+
+/// Configured stages with their timeouts, soonest first.
+fn stages(config: &IdleConfig) -> Vec<(Duration, Action)> {
+    let mut stages: Vec<(Duration, Action)> = [
+        (config.dim, Action::Dim),
+        (config.blank, Action::Blank),
+        (config.lock, Action::Lock),
+        (config.suspend, Action::Suspend),
+        (config.command_after, Action::Command),
+    ]
+    .into_iter()
+    .filter(|(secs, _)| *secs > 0)
+    .map(|(secs, action)| (Duration::from_secs(secs), action))
+    .collect();
+    stages.sort_by_key(|(after, _)| *after);
+    stages
+}
+
+/// Input arrived: restart the countdown and undo dimming and blanking.
+pub fn activity(state: &mut GameframeState) {
+    state.idle.last_input = state.clock.now().into();
+    if !state.idle.done.is_empty() {
+        wake(state);
+    }
+    if !state.idle.armed {
+        arm(state);
+    }
+}
+
+/// Start the timer for the first stage, if any is configured.
+pub fn arm(state: &mut GameframeState) {
+    let Some(&(first, _)) = stages(&state.config.idle).first() else { return };
+    state.idle.last_input = state.clock.now().into();
+    state.idle.armed = true;
+    let _ = state.loop_handle.insert_source(Timer::from_duration(first), |_, _, state| check(state));
+}
+
+fn check(state: &mut GameframeState) -> TimeoutAction {
+    let now: Duration = state.clock.now().into();
+    if state.idle.inhibited() {
+        state.idle.last_input = now;
+    }
+    let idle   = now.saturating_sub(state.idle.last_input);
+    let stages = stages(&state.config.idle);
+    for &(after, action) in &stages {
+        if idle >= after && !state.idle.done.contains(&action) {
+            state.idle.done.push(action);
+            run(state, action);
+        }
+    }
+    match stages.iter().find(|(after, _)| *after > idle) {
+        Some(&(after, _)) => TimeoutAction::ToDuration(after - idle),
+        None => {
+            // Every stage has run; the next input re-arms.
+            state.idle.armed = false;
+            TimeoutAction::Drop
+        }
+    }
+}
+
+fn run(state: &mut GameframeState, action: Action) {
+    info!(?action, "Idle");
+    let config = &state.config.idle;
+    match action {
+        Action::Dim     => state.brightness.dim(config.dim_percent),
+        Action::Blank   => blank_drm(state),
+        Action::Lock    => spawn::launch(state, &config.lock_command),
+        Action::Suspend => spawn::launch(state, &config.suspend_command),
+        Action::Command => {
+            if let Some(cmd) = &config.command {
+                spawn::launch(state, cmd);
+            }
+        }
+    }
+}
+
+fn wake(state: &mut GameframeState) {
+    let done = std::mem::take(&mut state.idle.done);
+    if done.contains(&Action::Dim) {
+        state.brightness.restore();
+    }
+    if done.contains(&Action::Blank) {
+        unblank_drm(state);
+    }
+    info!("Idle: active again");
+}
+
@@ -1,41 +1,78 @@
 use smithay::{
     backend::input::{
         // FIX: Axis and ButtonState live in backend::input (confirmed by compiler note)
-        Axis, AxisSource, ButtonState,
+        AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event,
         InputEvent, KeyState, KeyboardKeyEvent,
         PointerAxisEvent, PointerButtonEvent,
         PointerMotionEvent, PointerMotionAbsoluteEvent,
+        TouchDownEvent, TouchEvent, TouchMotionEvent,
     },
     input::{
-        keyboard::{FilterResult, Keysym, ModifiersState},
-        pointer::{AxisFrame, ButtonEvent, MotionEvent},
+        keyboard::{FilterResult, Keycode, Keysym, ModifiersState},
+        pointer::{AxisFrame, ButtonEvent, CursorImageStatus, MotionEvent, RelativeMotionEvent},
     },
     reexports::wayland_server::protocol::wl_surface::WlSurface,
+    reexports::calloop::timer::{TimeoutAction, Timer},
     utils::{Logical, Point, SERIAL_COUNTER},
-    wayland::seat::WaylandFocus,
 };
 use std::borrow::Cow;
-use tracing::info;
+use tracing::{info, info_span, warn};
 
-use crate::state::GameframeState;
+use crate::{
+    accessx::Verdict, click::WindowDrag, constraints, dnd, hover_focus, idle, players, presets, recorder, spawn, state::GameframeState,
+    test_pattern, touch,
+};
 use gameframe_input::BindingAction;
 
+const BTN_LEFT: u32 = 0x110;
+
 // ── Public entry point ────────────────────────────────────────────────────────
 
 pub fn process_input_event<B>(state: &mut GameframeState, event: InputEvent<B>)
 where
     B: smithay::backend::input::InputBackend,
 {
+    let _span = info_span!("input", kind = event_kind(&event)).entered();
+    state.metrics.input_events += 1;
+    if matches!(event, InputEvent::Keyboard { .. } | InputEvent::PointerMotion { .. }
+        | InputEvent::PointerMotionAbsolute { .. } | InputEvent::PointerButton { .. } | InputEvent::PointerAxis { .. }
+        | InputEvent::TouchDown { .. } | InputEvent::TouchMotion { .. } | InputEvent::TouchUp { .. })
+    {
+        idle::activity(state);
+    }
+    // Devices bound to a window bypass focus and keybindings.
+    let Some(event) = players::route(state, event) else { return };
     match event {
         InputEvent::Keyboard { event }              => handle_keyboard(state, event),
         InputEvent::PointerMotion { event }         => handle_pointer_motion(state, event),
         InputEvent::PointerMotionAbsolute { event } => handle_pointer_abs(state, event),
         InputEvent::PointerButton { event }         => handle_pointer_button(state, event),
         InputEvent::PointerAxis { event }           => handle_pointer_axis(state, event),
+        InputEvent::TouchDown { event }             => handle_touch_down(state, event),
+        InputEvent::TouchMotion { event }           => handle_touch_motion(state, event),
+        InputEvent::TouchUp { event }               => touch::up(state, event.slot(), event.time_msec()),
+        InputEvent::TouchCancel { .. }              => touch::cancel(state),
+        InputEvent::TouchFrame { .. }               => touch::frame(state),
         _ => {}
     }
 }
 
+fn event_kind<B: smithay::backend::input::InputBackend>(event: &InputEvent<B>) -> &'static str {
+    match event {
+        InputEvent::Keyboard { .. }              => "keyboard",
+        InputEvent::PointerMotion { .. }         => "pointer_motion",
+        InputEvent::PointerMotionAbsolute { .. } => "pointer_motion_absolute",
+        InputEvent::PointerButton { .. }         => "pointer_button",
+        InputEvent::PointerAxis { .. }           => "pointer_axis",
+        InputEvent::TouchDown { .. }             => "touch_down",
+        InputEvent::TouchMotion { .. }           => "touch_motion",
+        InputEvent::TouchUp { .. }               => "touch_up",
+        InputEvent::DeviceAdded { .. }           => "device_added",
+        InputEvent::DeviceRemoved { .. }         => "device_removed",
+        _                                        => "other",
+    }
+}
+
 // ── Keyboard ─────────────────────────────────────────────────────────────────
 
 fn handle_keyboard<B, E>(state: &mut GameframeState, event: E)
@@ -43,11 +80,43 @@ where
     B: smithay::backend::input::InputBackend,
     E: KeyboardKeyEvent<B>,
 {
-    let serial = SERIAL_COUNTER.next_serial();
-    let time   = event.time_msec();
-    let key    = event.key_code();
-    let ks     = event.state();
+    let time    = event.time_msec();
+    let key     = event.key_code();
+    let ks      = event.state();
+    let pressed = ks == KeyState::Pressed;
 
+    // Accessibility filters run before keybindings.
+    if !pressed {
+        if let Some(token) = state.accessx.cancel_slow_key(key) {
+            state.loop_handle.remove(token);
+            return;
+        }
+    }
+    let (verdict, release_after) = state.accessx.filter(key, pressed, time);
+    match verdict {
+        Verdict::Deliver => key_input(state, key, ks, time),
+        Verdict::Drop    => {}
+        Verdict::Defer(delay) => {
+            let timer = state.loop_handle.insert_source(Timer::from_duration(delay), move |_, _, state| {
+                if state.accessx.slow_key_due(key) == Verdict::Deliver {
+                    let time = state.clock.now().as_millis();
+                    key_input(state, key, KeyState::Pressed, time);
+                }
+                TimeoutAction::Drop
+            });
+            if let Ok(token) = timer {
+                state.accessx.slow_key_pending(key, token);
+            }
+        }
+    }
+    for modifier in release_after {
+        key_input(state, modifier, KeyState::Released, time);
+    }
+}
+
+/// Feed a key to the keyboard: keybindings first, then the focused client.
+fn key_input(state: &mut GameframeState, key: Keycode, ks: KeyState, time: u32) {
+    let serial = SERIAL_COUNTER.next_serial();
     let kb = match state.seat.get_keyboard() { Some(k) => k, None => return };
 
     kb.input::<(), _>(state, key, ks, serial, time, |state, mods, keysym_handle| {
@@ -72,6 +141,21 @@ fn check_binding(mods: &ModifiersState, sym: Keysym) -> Option<BindingAction> {
     match (mb.bits(), u32::from(sym)) {
         (s, 0xff1b) if s == ModifierState::SUPER.bits() => Some(BindingAction::ToggleOverlay),
         (s, 0xff08) if s == (ModifierState::CTRL | ModifierState::ALT).bits() => Some(BindingAction::KillSession),
+        (s, k @ 0x31..=0x39) if s == ModifierState::SUPER.bits() => Some(BindingAction::SwitchWorkspace((k - 0x30) as u8)),
+        (s, 0x2d) if s == ModifierState::SUPER.bits() => Some(BindingAction::AdjustOpacity(-1)), // Super+minus
+        (s, 0x3d) if s == ModifierState::SUPER.bits() => Some(BindingAction::AdjustOpacity(1)),  // Super+equal
+        (s, 0x73) if s == ModifierState::SUPER.bits() => Some(BindingAction::ToggleSplit),       // Super+S
+        (s, 0x7a) if s == ModifierState::SUPER.bits() => Some(BindingAction::ToggleMagnifier),   // Super+Z
+        (s, 0x52) if s == (ModifierState::SUPER | ModifierState::SHIFT).bits() => Some(BindingAction::ReloadConfig), // Super+Shift+R
+        (s, 0x4b) if s == (ModifierState::SUPER | ModifierState::SHIFT).bits() => Some(BindingAction::ToggleAccessibility), // Super+Shift+K
+        (s, 0x46) if s == (ModifierState::SUPER | ModifierState::SHIFT).bits() => Some(BindingAction::CycleColorFilter), // Super+Shift+F
+        (s, 0xff61) if s == ModifierState::SUPER.bits() => Some(BindingAction::ToggleRecording),  // Super+Print
+        (s, 0x50) if s == (ModifierState::SUPER | ModifierState::SHIFT).bits() => Some(BindingAction::CyclePreset), // Super+Shift+P
+        (s, 0x54) if s == (ModifierState::SUPER | ModifierState::SHIFT).bits() => Some(BindingAction::ToggleTestPattern), // Super+Shift+T
+        (s, 0x74) if s == ModifierState::SUPER.bits() => Some(BindingAction::CycleTestPattern),  // Super+T
+        (s, 0x66) if s == ModifierState::SUPER.bits() => Some(BindingAction::ToggleFpsOverlay),  // Super+F
+        (0, 0x1008ff02) => Some(BindingAction::Brightness(1)),  // XF86MonBrightnessUp
+        (0, 0x1008ff03) => Some(BindingAction::Brightness(-1)), // XF86MonBrightnessDown
         _ => None,
     }
 }
@@ -87,10 +171,44 @@ fn execute_binding(state: &mut GameframeState, action: BindingAction) {
             state.running = false;
         }
         BindingAction::ScreenshotOutput => info!("Screenshot (not yet implemented)"),
-        BindingAction::LaunchApp(cmd) => {
-            let _ = std::process::Command::new("sh").args(["-c", &cmd]).spawn();
-        }
+        BindingAction::LaunchApp(cmd) => spawn::launch(state, &cmd),
         BindingAction::SwitchVt(vt) => info!(vt, "VT switch requested"),
+        BindingAction::SwitchWorkspace(n) => state.switch_workspace(n.saturating_sub(1) as usize),
+        BindingAction::AdjustOpacity(d)   => state.adjust_focused_opacity(d as f32 / 10.0),
+        BindingAction::ToggleSplit        => state.toggle_split(),
+        BindingAction::ReloadConfig       => state.reload_config(),
+        BindingAction::ToggleMagnifier    => { state.magnifier.toggle(); state.queue_redraw_all(); }
+        BindingAction::CycleColorFilter   => { state.color_filter = state.color_filter.next(); state.queue_redraw_all(); }
+        BindingAction::CyclePreset        => presets::cycle(state),
+        BindingAction::ToggleTestPattern  => test_pattern::toggle(state),
+        BindingAction::CycleTestPattern   => test_pattern::cycle(state),
+        BindingAction::ToggleFpsOverlay   => { state.fps_overlay.toggle(); state.queue_redraw_all(); }
+        BindingAction::Brightness(steps)  => {
+            if state.brightness.step(steps as i32).is_ok() {
+                if let Some((_, percent)) = state.brightness.get().first() {
+                    state.overlay.push_toast(format!("Brightness {percent}%"), 90);
+                }
+            }
+        }
+        BindingAction::ToggleRecording => {
+            match recorder::toggle_recording(state, None) {
+                Ok(what) => info!("{what}"),
+                Err(e)   => {
+                    warn!("Recording: {e:#}");
+                    state.overlay.push_toast("Recording failed", 90);
+                }
+            }
+        }
+        // Runs from inside the keyboard filter, so releasing latched
+        // modifiers (more keyboard input) has to wait until it returns.
+        BindingAction::ToggleAccessibility => {
+            state.loop_handle.insert_idle(|state| {
+                let time = state.clock.now().as_millis();
+                for modifier in state.accessx.toggle() {
+                    key_input(state, modifier, KeyState::Released, time);
+                }
+            });
+        }
     }
 }
 
@@ -101,18 +219,31 @@ where
     B: smithay::backend::input::InputBackend,
     E: PointerMotionEvent<B>,
 {
-    let serial = SERIAL_COUNTER.next_serial();
     let delta: Point<f64, Logical> = (event.delta_x(), event.delta_y()).into();
-    state.pointer_location = state.pointer_location + delta;
-    clamp_pointer(state);
+    relative_motion(state, delta, (event.delta_x_unaccel(), event.delta_y_unaccel()).into(), event.time());
+    move_pointer(state, delta, event.time_msec());
+}
 
-    let pointer = match state.seat.get_pointer() { Some(p) => p, None => return };
-    let focus   = pointer_focus(state);
-    pointer.motion(state, focus, &MotionEvent {
-        location: state.pointer_location,
-        serial,
-        time: event.time_msec(),
-    });
+/// Send raw deltas for mouse look to the surface under the pointer. They go
+/// out whether or not the pointer may move.
+pub(crate) fn relative_motion(state: &mut GameframeState, delta: Point<f64, Logical>, delta_unaccel: Point<f64, Logical>, utime: u64) {
+    let Some(pointer) = state.seat.get_pointer() else { return };
+    let focus = pointer_focus(state);
+    pointer.relative_motion(state, focus, &RelativeMotionEvent { delta, delta_unaccel, utime });
+}
+
+/// Move the pointer by `delta` (relative devices and the gamepad cursor),
+/// unless the surface under it has locked it in place.
+pub(crate) fn move_pointer(state: &mut GameframeState, delta: Point<f64, Logical>, time: u32) {
+    let Some(to) = constraints::constrain(state, delta) else {
+        if let Some(pointer) = state.seat.get_pointer() {
+            pointer.frame(state);
+        }
+        return;
+    };
+    state.pointer_location = to;
+    clamp_pointer(state);
+    pointer_moved(state, time);
 }
 
 fn handle_pointer_abs<B, E>(state: &mut GameframeState, event: E)
@@ -120,16 +251,45 @@ where
     B: smithay::backend::input::InputBackend,
     E: PointerMotionAbsoluteEvent<B>,
 {
-    let serial = SERIAL_COUNTER.next_serial();
-    state.pointer_location = (event.x_transformed(1920), event.y_transformed(1080)).into();
+    // Absolute devices cover the output the pointer is on.
+    let output = state.output_at(state.pointer_location).or_else(|| state.placement_output());
+    let Some(geo) = output.and_then(|o| state.space.output_geometry(&o)) else { return };
+    let target = event.position_transformed(geo.size) + geo.loc.to_f64();
+    // A locked pointer ignores absolute devices; a confined one stops at
+    // the edge of its region.
+    let Some(to) = constraints::constrain(state, target - state.pointer_location) else { return };
+    state.pointer_location = to;
+    clamp_pointer(state);
+    pointer_moved(state, event.time_msec());
+}
 
+/// Send motion for the current `pointer_location` to the surface under it.
+pub(crate) fn pointer_moved(state: &mut GameframeState, time: u32) {
+    state.clicks.motion(state.pointer_location);
+    if let Some(drag) = state.window_drag.as_ref().filter(|_| state.clicks.dragging()) {
+        let loc = drag.origin + (state.pointer_location - drag.pointer).to_i32_round();
+        state.space.map_element(drag.window.clone(), loc, false);
+        state.queue_redraw_all();
+    }
+
+    let serial = SERIAL_COUNTER.next_serial();
     let pointer = match state.seat.get_pointer() { Some(p) => p, None => return };
     let focus   = pointer_focus(state);
+    let target  = focus.as_ref().map(|(surface, _)| surface.clone());
     pointer.motion(state, focus, &MotionEvent {
         location: state.pointer_location,
         serial,
-        time: event.time_msec(),
+        time,
     });
+    dnd::pointer_moved(state, target.as_ref());
+    constraints::activate_under(state);
+    state.split_focus_follows_pointer();
+    hover_focus::pointer_moved(state);
+    // The cursor moves (its plane or the frame it is drawn into), and the
+    // magnified view follows it.
+    if state.magnifier.active() || !matches!(state.cursor_status, CursorImageStatus::Hidden) {
+        state.queue_redraw_all();
+    }
 }
 
 fn handle_pointer_button<B, E>(state: &mut GameframeState, event: E)
@@ -137,13 +297,36 @@ where
     B: smithay::backend::input::InputBackend,
     E: PointerButtonEvent<B>,
 {
+    press_button(state, event.button_code(), event.state(), event.time_msec());
+}
+
+/// Press or release pointer `button` (evdev code) at the pointer location,
+/// activating the window under it on press. Also used by the gamepad.
+///
+/// Super + left button belongs to the compositor: dragging moves the window
+/// under the pointer, double-clicking centres it.
+pub(crate) fn press_button(state: &mut GameframeState, button: u32, button_state: ButtonState, time: u32) {
     let serial = SERIAL_COUNTER.next_serial();
 
-    if event.state() == ButtonState::Pressed {
-        let loc = state.pointer_location;
-        if let Some((window, _)) = state.space.element_under(loc) {
-            let window = window.clone();
+    if button_state == ButtonState::Pressed {
+        let loc   = state.pointer_location;
+        let count = state.clicks.press(button, loc, time);
+        if let Some((window, origin)) = state.window_under(loc) {
             state.activate_window(&window);
+            let super_held = state.seat.get_keyboard().is_some_and(|k| k.modifier_state().logo);
+            if super_held && button == BTN_LEFT {
+                if count == 2 {
+                    state.center_window(&window);
+                } else {
+                    state.window_drag = Some(WindowDrag { window, origin, pointer: loc });
+                }
+                return;
+            }
+        }
+    } else {
+        state.clicks.release();
+        if button == BTN_LEFT && state.window_drag.take().is_some() {
+            return;
         }
     }
 
@@ -154,9 +337,9 @@ where
     // Smithay 0.7: pointer::ButtonEvent.state is smithay::backend::input::ButtonState
     pointer.button(state, &ButtonEvent {
         serial,
-        time:   event.time_msec(),
-        button: event.button_code(),
-        state:  event.state(),   // backend::input::ButtonState – same type, no conversion needed
+        time,
+        button,
+        state:  button_state,   // backend::input::ButtonState – same type, no conversion needed
     });
 }
 
@@ -167,6 +350,26 @@ where
 {
     let pointer = match state.seat.get_pointer() { Some(p) => p, None => return };
 
+    // Super + wheel drives the magnifier instead of scrolling.
+    let super_held = state.seat.get_keyboard().is_some_and(|k| k.modifier_state().logo);
+    if super_held {
+        if let Some(v) = event.amount_v120(Axis::Vertical) {
+            state.magnifier.zoom_by(-(v / 120.0).round() as i32);
+            state.queue_redraw_all();
+            return;
+        }
+    }
+
+    pointer.axis(state, axis_frame(&event));
+    pointer.frame(state);
+}
+
+/// The wheel frame for `event`.
+pub(crate) fn axis_frame<B, E>(event: &E) -> AxisFrame
+where
+    B: smithay::backend::input::InputBackend,
+    E: PointerAxisEvent<B>,
+{
     // FIX: AxisFrame::v120/value take smithay::backend::input::Axis (same Axis from imports)
     // wl_pointer::Axis is a different type – do NOT use it here.
     let mut frame = AxisFrame::new(event.time_msec()).source(AxisSource::Wheel);
@@ -183,26 +386,53 @@ where
     if let Some(v) = event.amount(Axis::Horizontal) {
         frame = frame.value(Axis::Horizontal, v);
     }
+    frame
+}
 
-    pointer.axis(state, frame);
-    pointer.frame(state);
+// ── Touch ─────────────────────────────────────────────────────────────────────
+
+fn handle_touch_down<B, E>(state: &mut GameframeState, event: E)
+where
+    B: smithay::backend::input::InputBackend,
+    E: TouchDownEvent<B>,
+{
+    let Some(pos) = touch_position(state, &event) else { return };
+    touch::down(state, event.slot(), pos, event.time_msec());
+}
+
+fn handle_touch_motion<B, E>(state: &mut GameframeState, event: E)
+where
+    B: smithay::backend::input::InputBackend,
+    E: TouchMotionEvent<B>,
+{
+    let Some(pos) = touch_position(state, &event) else { return };
+    touch::motion(state, event.slot(), pos, event.time_msec());
+}
+
+/// Global position of a touch; the touchscreen covers the touch output.
+fn touch_position<B, E>(state: &GameframeState, event: &E) -> Option<Point<f64, Logical>>
+where
+    B: smithay::backend::input::InputBackend,
+    E: AbsolutePositionEvent<B>,
+{
+    let geo = touch::touch_output(state).and_then(|o| state.space.output_geometry(&o))?;
+    Some(event.position_transformed(geo.size) + geo.loc.to_f64())
 }
 
 // ── Helpers ───────────────────────────────────────────────────────────────────
 
-/// Returns the WlSurface under the pointer and its position.
-/// FIX: WaylandFocus::wl_surface() returns Option<Cow<'_, WlSurface>>.
-/// We extract an owned WlSurface via .into_owned() on the Cow.
+/// Returns the surface under the pointer and its global position. Hit-tests
+/// whole surface trees, so subsurfaces (video planes, client-side
+/// decorations) get pointer focus of their own.
 fn pointer_focus(state: &GameframeState) -> Option<(WlSurface, Point<f64, Logical>)> {
-    state.space
-        .element_under(state.pointer_location)
-        .and_then(|(window, loc)| {
-            // wl_surface() returns Option<Cow<'_, WlSurface>>
-            window.wl_surface().map(|cow| (cow.into_owned(), loc.to_f64()))
-        })
+    state.surface_under(state.pointer_location)
 }
 
+/// Keep the pointer on an output: motion into a gap of the layout (or past
+/// its outer edge) slides along the nearest output's edge instead, so the
+/// pointer crosses to a neighbouring monitor wherever the two touch.
 fn clamp_pointer(state: &mut GameframeState) {
-    state.pointer_location.x = state.pointer_location.x.max(0.0).min(1919.0);
-    state.pointer_location.y = state.pointer_location.y.max(0.0).min(1079.0);
+    if let Some(p) = state.clamp_to_layout(state.pointer_location) {
+        state.pointer_location = p;
+    }
 }
@@ -0,0 +1,255 @@
+use std::{
+    io::Write,
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+    utils::{Logical, Point},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    buffers,
+    clipboard,
+    compositor::{describe_vrr, pause_drm, resume_drm, set_output_mode, set_vrr},
+    headless::{add_virtual_output, list_virtual_outputs, remove_virtual_output},
+    hover_focus,
+    oneshot,
+    players,
+    presets::{self, Preset},
+    recorder::{self, start_recording, stop_recording},
+    restore,
+    rules,
+    state::GameframeState,
+    test_pattern::{self, Pattern},
+    tree,
+    upscale::set_upscale_mode,
+};
+
+/// `$XDG_RUNTIME_DIR/gameframe.sock`, next to the session lock file.
+pub fn socket_path() -> PathBuf {
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/tmp"))
+        .join("gameframe.sock")
+}
+
+// ── Server ────────────────────────────────────────────────────────────────────
+
+/// Listen on the control socket. Each connection sends one command line
+/// and gets the reply back, then is closed; replies start with `ok` or
+/// `error:`.
+pub fn init(loop_handle: &LoopHandle<'static, GameframeState>) -> Result<()> {
+    let path = socket_path();
+    // A socket left behind by a session that crashed.
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).with_context(|| format!("bind {}", path.display()))?;
+    listener.set_nonblocking(true)?;
+    info!(path = %path.display(), "Control socket ready");
+
+    loop_handle
+        .insert_source(Generic::new(listener, Interest::READ, Mode::Level), |_, listener, state| {
+            loop {
+                match listener.as_ref().accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = serve(&state.loop_handle, stream) {
+                            debug!("control client: {e:#}");
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                    Err(e) => {
+                        warn!("control socket: {e}");
+                        return Ok(PostAction::Continue);
+                    }
+                }
+            }
+        })
+        .map_err(|e| anyhow!("control socket source: {e}"))?;
+    Ok(())
+}
+
+/// Remove the socket file at shutdown.
+pub fn cleanup() { let _ = std::fs::remove_file(socket_path()); }
+
+/// Answer the one command line the client sends.
+fn serve(loop_handle: &LoopHandle<'static, GameframeState>, stream: UnixStream) -> Result<()> {
+    stream.set_nonblocking(true)?;
+    oneshot::serve(loop_handle, stream, |request| request.contains(&b'\n'), |state, request| {
+        let line = String::from_utf8_lossy(request);
+        let reply = match execute(state, line.lines().next().unwrap_or_default().trim()) {
+            Ok(out) if out.is_empty() => "ok\n".to_owned(),
+            Ok(out) => format!("ok\n{out}"),
+            Err(e)  => format!("error: {e:#}\n"),
+        };
+        reply.into_bytes()
+    })
+}
+
+fn execute(state: &mut GameframeState, command: &str) -> Result<String> {
+    debug!(command, "control command");
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args.as_slice() {
+        ["brightness"] | ["brightness", "get"] => {
+            Ok(state.brightness.get().iter().map(|(name, pct)| format!("{name} {pct}\n")).collect())
+        }
+        ["brightness", "set", value] => {
+            let percent: u32 = value.trim_end_matches('%').parse().context("brightness must be 0-100")?;
+            state.brightness.set(percent)?;
+            Ok(String::new())
+        }
+        ["brightness", "up"]   => state.brightness.step(1).map(|_| String::new()),
+        ["brightness", "down"] => state.brightness.step(-1).map(|_| String::new()),
+        ["restart"] => restore::restart(state).map(|path| format!("layout saved to {}, restarting\n", path.display())),
+        ["suspend"] => {
+            pause_drm(state, "suspend requested");
+            Ok(String::new())
+        }
+        ["resume"] => {
+            resume_drm(state, "resume requested");
+            Ok(String::new())
+        }
+        ["output", "add", mode] => add_virtual_output(state, mode).map(|name| format!("{name}\n")),
+        ["output", "remove", name] => remove_virtual_output(state, name).map(|_| String::new()),
+        ["output", "mode", name, spec] => set_output_mode(state, name, spec).map(|_| String::new()),
+        ["output", "upscale", name, mode] => set_upscale_mode(state, name, mode).map(|_| String::new()),
+        ["output", "move", name, x, y] => {
+            let output = state.space.outputs()
+                .find(|o| o.name() == *name)
+                .cloned()
+                .with_context(|| format!("no output {name}"))?;
+            let pos = Point::from((x.parse::<i32>().context("x")?, y.parse::<i32>().context("y")?));
+            state.set_output_position(&output, pos);
+            Ok(String::new())
+        }
+        ["output"] | ["output", "list"] => match state.backend.as_ref().and_then(|b| b.headless()) {
+            Some(headless) => Ok(list_virtual_outputs(headless)),
+            None           => bail!("virtual outputs need the headless backend"),
+        },
+        ["record"] => recorder::toggle_recording(state, None).map(|what| format!("{what}\n")),
+        ["record", "start"] => start_recording(state, None).map(|what| format!("{what}\n")),
+        ["record", "start", output] => start_recording(state, Some(output)).map(|what| format!("{what}\n")),
+        ["record", "stop"] => stop_recording(state).map(|path| format!("saved {}\n", path.display())),
+        ["capture", what @ ("hide" | "show")] => {
+            let window = state.focused_window().context("no focused window")?;
+            rules::set_capture_hidden(&window, *what == "hide");
+            state.queue_redraw_all();
+            Ok(String::new())
+        }
+        ["pointer"] => {
+            let p = state.pointer_location;
+            let output = state.output_at(p).map(|o| o.name()).unwrap_or_default();
+            Ok(format!("{:.0} {:.0} {output}\n", p.x, p.y))
+        }
+        ["pointer", "warp", x, y] => {
+            state.warp_pointer(parse_point(x, y)?);
+            Ok(String::new())
+        }
+        ["pointer", "warp", output, x, y] => {
+            let geo = state.space.outputs()
+                .find(|o| o.name() == *output)
+                .and_then(|o| state.space.output_geometry(o))
+                .with_context(|| format!("no output {output}"))?;
+            state.warp_pointer(parse_point(x, y)? + geo.loc.to_f64());
+            Ok(String::new())
+        }
+        ["bind"] => Ok(players::list_bindings(state)),
+        ["bind", args @ ..] => players::bind_command(state, args).map(|_| String::new()),
+        ["unbind", device @ ..] if !device.is_empty() => match state.players.unbind(&device.join(" ")) {
+            true  => Ok(String::new()),
+            false => bail!("{} is not bound", device.join(" ")),
+        },
+        ["buffers"] => Ok(state.buffers.summary()),
+        ["clients"] => Ok(buffers::clients(state)),
+        ["focus"] => Ok(format!("{}\n", hover_focus::model_name(state.config.input.focus.model))),
+        ["focus", model] => hover_focus::set_model(state, model).map(|_| String::new()),
+        ["tree"] => tree::dump(state),
+        ["preset"] => Ok(format!("{}\n", state.preset.map_or("none", Preset::name))),
+        ["preset", "next"] => {
+            presets::cycle(state);
+            Ok(String::new())
+        }
+        ["preset", name] => {
+            presets::apply(state, Preset::parse(name)?);
+            Ok(String::new())
+        }
+        ["vrr"] => describe_vrr(state),
+        ["vrr", output, "on"]  => set_vrr(state, output, true).map(|_| String::new()),
+        ["vrr", output, "off"] => set_vrr(state, output, false).map(|_| String::new()),
+        ["fps-cap"] => Ok(match state.config.display.fps_cap {
+            0   => "off\n".to_owned(),
+            fps => format!("{fps}\n"),
+        }),
+        ["fps-cap", "off"] => {
+            state.set_fps_cap(None);
+            Ok(String::new())
+        }
+        ["fps-cap", fps] => {
+            let fps: u32 = fps.parse().with_context(|| format!("bad frame rate {fps:?}"))?;
+            state.set_fps_cap((fps > 0).then_some(fps));
+            Ok(String::new())
+        }
+        ["test-pattern"] => Ok(test_pattern::describe(state)),
+        ["test-pattern", "off"] => {
+            test_pattern::hide(state);
+            Ok(String::new())
+        }
+        ["test-pattern", "next"] => {
+            test_pattern::cycle(state);
+            Ok(String::new())
+        }
+        ["test-pattern", name] => test_pattern::show(state, Pattern::parse(name)?, None).map(|_| String::new()),
+        ["test-pattern", name, output] => {
+            test_pattern::show(state, Pattern::parse(name)?, Some(output)).map(|_| String::new())
+        }
+        ["clipboard"] | ["clipboard", "list"] => Ok(clipboard::list_history(state)),
+        ["clipboard", "select", n] => {
+            clipboard::select_history(state, n.parse().context("entry number")?).map(|_| String::new())
+        }
+        ["clipboard", "clear"] => {
+            clipboard::clear_history(state);
+            Ok(String::new())
+        }
+        ["frames"] => {
+            let Some(drm) = state.backend.as_ref().and_then(|b| b.drm()) else {
+                bail!("frame statistics need the DRM backend");
+            };
+            Ok(drm.frame_stats().iter().map(|(name, s, refresh)| {
+                format!(
+                    "{name} presented {} late {} dropped {} refresh {:.3} Hz\n",
+                    s.presented, s.late, s.dropped, 1.0 / refresh.as_secs_f64(),
+                )
+            }).collect())
+        }
+        [] => bail!("empty command"),
+        _  => bail!("unknown command {command:?}"),
+    }
+}
+
+/// Logical coordinates given as two numbers.
+fn parse_point(x: &str, y: &str) -> Result<Point<f64, Logical>> {
+    Ok(Point::from((x.parse::<f64>().context("x")?, y.parse::<f64>().context("y")?)))
+}
+
+// ── Client ────────────────────────────────────────────────────────────────────
+
+/// Send `command` to the running session and return its reply, minus the
+/// `ok` line. An `error:` reply becomes an `Err`.
+pub fn request(command: &str) -> Result<String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("connect {} (is a session running?)", path.display()))?;
+    stream.write_all(format!("{command}\n").as_bytes())?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    let mut reply = String::new();
+    std::io::Read::read_to_string(&mut stream, &mut reply)?;
+    match reply.split_once('\n') {
+        Some(("ok", rest)) => Ok(rest.to_owned()),
+        _ => match reply.trim().strip_prefix("error: ") {
+            Some(msg) => bail!("{msg}"),
+            None      => bail!("unexpected reply {reply:?}"),
+        },
+    }
+}
@@ -1,16 +1,59 @@
+pub mod accessx;
+pub mod animation;
+pub mod backlog;
+pub mod blur;
+pub mod brightness;
+pub mod buffers;
+pub mod click;
+pub mod clipboard;
+pub mod color_filter;
 pub mod compositor;
 pub mod config;
+pub mod constraints;
 pub mod cursor;
+pub mod decorations;
 pub mod dmabuf;
+pub mod dnd;
+pub mod edid;
+pub mod fps_overlay;
 pub mod frame;
+pub mod gamepad;
+pub mod geometry_store;
+pub mod gpu_check;
+pub mod headless;
+pub mod hover_focus;
+pub mod idle;
 pub mod input_handler;
+pub mod ipc;
+pub mod magnifier;
+pub mod metrics;
+pub mod modes;
+pub mod nested;
+pub mod oneshot;
 pub mod output;
+pub mod players;
+pub mod presets;
+pub mod recorder;
+pub mod restore;
 pub mod render;
+pub mod rules;
+pub mod screencopy;
 pub mod session;
+pub mod shaders;
+pub mod spawn;
+pub mod split;
 pub mod state;
+pub mod supersample;
+pub mod tearing;
 pub mod telemetry;
+pub mod test_pattern;
+pub mod throttle;
+pub mod touch;
+pub mod tree;
+pub mod upscale;
 pub mod window;
+pub mod workspace;
 pub mod xwayland;
 
 pub use config::Config;
-pub use session::{print_status, run_session, stop_session, SessionOptions};
+pub use session::{print_status, run_session, stop_session, BackendKind, SessionOptions};
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use tracing::info;
+
+/// Zoom factor per scroll step / keypress.
+const STEP: f64 = 1.25;
+const MAX_LEVEL: f64 = 8.0;
+/// Level the toggle zooms to the first time.
+const DEFAULT_LEVEL: f64 = 2.0;
+/// Time constant of the eased approach to the target level.
+const SMOOTHING: Duration = Duration::from_millis(80);
+
+/// Screen magnifier. The output under the pointer is scaled about the
+/// pointer position, so the point under the cursor stays put on screen:
+/// what the cursor points at is exactly what a click hits, and no input
+/// remapping is needed. Moving the pointer pans the view.
+pub struct Magnifier {
+    level:     f64,
+    target:    f64,
+    /// Level restored by the toggle.
+    last:      f64,
+    last_tick: Option<Duration>,
+}
+
+impl Magnifier {
+    pub fn new() -> Self { Self { level: 1.0, target: 1.0, last: DEFAULT_LEVEL, last_tick: None } }
+
+    /// Current (possibly mid-transition) zoom factor.
+    pub fn level(&self) -> f64 { self.level }
+    pub fn active(&self) -> bool { self.level > 1.0 || self.target > 1.0 }
+
+    pub fn toggle(&mut self) {
+        if self.target > 1.0 {
+            self.last = self.target;
+            self.set_target(1.0);
+        } else {
+            self.set_target(self.last);
+        }
+    }
+
+    /// Zoom in (`steps` > 0) or out by whole steps.
+    pub fn zoom_by(&mut self, steps: i32) { self.set_target(self.target * STEP.powi(steps)); }
+
+    fn set_target(&mut self, target: f64) {
+        self.target = target.clamp(1.0, MAX_LEVEL);
+        info!(level = format!("{:.2}", self.target), "Magnifier");
+    }
+
+    /// Ease towards the target level. Returns whether it is still moving.
+    pub fn tick(&mut self, now: Duration) -> bool {
+        let dt = self.last_tick.map_or(Duration::ZERO, |t| now.saturating_sub(t));
+        self.last_tick = Some(now);
+        let diff = self.target - self.level;
+        if diff.abs() < 0.005 {
+            self.level = self.target;
+            self.last_tick = None;
+            return false;
+        }
+        self.level += diff * (1.0 - (-dt.as_secs_f64() / SMOOTHING.as_secs_f64()).exp());
+        true
+    }
+}
+
+impl Default for Magnifier {
+    fn default() -> Self { Self::new() }
+}
@@ -0,0 +1,152 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use smithay::reexports::calloop::{
+    generic::Generic,
+    timer::{TimeoutAction, Timer},
+    Interest, LoopHandle, Mode, PostAction,
+};
+use tracing::{debug, info, warn};
+
+use crate::{frame::FrameStats, oneshot, state::GameframeState, telemetry::read_telemetry};
+
+/// How often the FPS gauges are recomputed.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counters for the metrics endpoint that nothing else keeps.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Input events processed over the session.
+    pub input_events: u64,
+    /// Frames presented per output over the last sample interval, per second.
+    fps:              HashMap<String, f64>,
+    /// Presented counts at the last sample.
+    presented:        HashMap<String, u64>,
+}
+
+/// Serve Prometheus-style metrics over HTTP on `address` (`[metrics]`).
+/// Every request gets the same plain-text page, whatever its path.
+pub fn init(loop_handle: &LoopHandle<'static, GameframeState>, address: &str) -> Result<()> {
+    let listener = TcpListener::bind(address).with_context(|| format!("bind {address}"))?;
+    listener.set_nonblocking(true)?;
+    info!(%address, "Metrics endpoint ready");
+
+    loop_handle
+        .insert_source(Generic::new(listener, Interest::READ, Mode::Level), |_, listener, state| {
+            loop {
+                match listener.as_ref().accept() {
+                    Ok((stream, peer)) => {
+                        if let Err(e) = serve(&state.loop_handle, stream) {
+                            debug!(%peer, "metrics client: {e:#}");
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                    Err(e) => {
+                        warn!("metrics socket: {e}");
+                        return Ok(PostAction::Continue);
+                    }
+                }
+            }
+        })
+        .map_err(|e| anyhow!("metrics source: {e}"))?;
+
+    let _ = loop_handle.insert_source(Timer::from_duration(SAMPLE_INTERVAL), |_, _, state| {
+        sample(state);
+        TimeoutAction::ToDuration(SAMPLE_INTERVAL)
+    });
+    Ok(())
+}
+
+/// Answer one HTTP request. Only the request line matters; headers that
+/// follow it are left unread.
+fn serve(loop_handle: &LoopHandle<'static, GameframeState>, stream: TcpStream) -> Result<()> {
+    stream.set_nonblocking(true)?;
+    oneshot::serve(loop_handle, stream, |request| request.contains(&b'\n'), |state, request| {
+        let request = String::from_utf8_lossy(request);
+        let (status, body) = match request.split_whitespace().next() {
+            Some("GET") | Some("HEAD") => ("200 OK", render(state)),
+            _                          => ("405 Method Not Allowed", String::new()),
+        };
+        let mut response = format!(
+            "HTTP/1.0 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len(),
+        );
+        if !request.starts_with("HEAD") {
+            response.push_str(&body);
+        }
+        response.into_bytes()
+    })
+}
+
+/// Per-output frame counters of whichever backend has outputs of its own.
+fn output_stats(state: &GameframeState) -> Vec<(String, FrameStats)> {
+    let backend = state.backend.as_ref();
+    if let Some(drm) = backend.and_then(|b| b.drm()) {
+        return drm.frame_stats().into_iter().map(|(name, stats, _)| (name, stats)).collect();
+    }
+    if let Some(headless) = backend.and_then(|b| b.headless()) {
+        return headless.outputs.iter().map(|o| (o.output.name(), o.repaint.stats())).collect();
+    }
+    Vec::new()
+}
+
+fn sample(state: &mut GameframeState) {
+    let stats   = output_stats(state);
+    let metrics = &mut state.metrics;
+    metrics.fps.clear();
+    for (name, stats) in stats {
+        let previous = metrics.presented.insert(name.clone(), stats.presented).unwrap_or(stats.presented);
+        let frames = stats.presented.saturating_sub(previous);
+        metrics.fps.insert(name, frames as f64 / SAMPLE_INTERVAL.as_secs_f64());
+    }
+    metrics.presented.retain(|name, _| metrics.fps.contains_key(name));
+}
+
+/// The metrics page in the Prometheus text format.
+fn render(state: &GameframeState) -> String {
+    let mut out = String::new();
+    let stats = output_stats(state);
+    let counter = |out: &mut String, name: &str, help: &str, value: fn(&FrameStats) -> u64| {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} counter");
+        for (output, s) in &stats {
+            let _ = writeln!(out, "{name}{{output=\"{output}\"}} {}", value(s));
+        }
+    };
+    counter(&mut out, "gameframe_frames_presented_total", "Frames whose page flip completed.", |s| s.presented);
+    counter(&mut out, "gameframe_frames_late_total", "Presented frames that missed their vblank.", |s| s.late);
+    counter(&mut out, "gameframe_frames_dropped_total", "Frames rendered that never reached the screen.", |s| s.dropped);
+
+    let _ = writeln!(out, "# HELP gameframe_fps Frames presented per second over the last second.\n# TYPE gameframe_fps gauge");
+    let mut fps: Vec<_> = state.metrics.fps.iter().collect();
+    fps.sort_by(|a, b| a.0.cmp(b.0));
+    for (output, fps) in fps {
+        let _ = writeln!(out, "gameframe_fps{{output=\"{output}\"}} {fps}");
+    }
+
+    let clients = state.display_handle.backend_handle().all_clients().count();
+    let windows = state.space.elements().count();
+    let (_, buffer_bytes) = state.buffers.totals();
+    let card = state.backend.as_ref().and_then(|b| b.drm()).map(|d| d.primary().card.as_str());
+    let vram = card.and_then(|card| read_telemetry(card).vram_used);
+    let gauges = [
+        ("gameframe_clients", "Connected Wayland clients.", Some(clients as u64)),
+        ("gameframe_windows", "Windows mapped on the active workspace.", Some(windows as u64)),
+        ("gameframe_client_buffer_bytes", "Estimated GPU memory held by client buffers.", Some(buffer_bytes)),
+        ("gameframe_vram_used_bytes", "VRAM in use on the GPU (amdgpu only).", vram.map(|mib| mib * 1024 * 1024)),
+    ];
+    for (name, help, value) in gauges {
+        let Some(value) = value else { continue };
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}");
+    }
+    let _ = writeln!(
+        out,
+        "# HELP gameframe_input_events_total Input events processed.\n# TYPE gameframe_input_events_total counter\ngameframe_input_events_total {}",
+        state.metrics.input_events,
+    );
+    out
+}
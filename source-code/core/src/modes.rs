@@ -0,0 +1,296 @@
+use anyhow::{anyhow, bail, Context, Result};
+use drm::control::{connector, Mode, ModeFlags, ModeTypeFlags};
+use tracing::{info, warn};
+
+use crate::edid::RangeLimits;
+
+/// `"WxH"` or `"WxH@Hz"` (refresh may be fractional, e.g. `59.94`).
+pub fn parse_mode_spec(spec: &str) -> Result<(u16, u16, Option<f64>)> {
+    let (size, refresh) = match spec.split_once('@') {
+        Some((size, hz)) => (size, Some(hz.trim_end_matches("Hz").parse::<f64>().context("refresh rate")?)),
+        None             => (spec, None),
+    };
+    let (w, h) = size.split_once('x').context("expected WxH")?;
+    Ok((w.parse().context("width")?, h.parse().context("height")?, refresh))
+}
+
+/// Refresh rate of `mode` in Hz, from its timings.
+pub fn refresh_hz(mode: &Mode) -> f64 {
+    let (htotal, vtotal) = (mode.hsync().2 as f64, mode.vsync().2 as f64);
+    if htotal == 0.0 || vtotal == 0.0 { return mode.vrefresh() as f64; }
+    mode.clock() as f64 * 1000.0 / (htotal * vtotal)
+}
+
+/// The listed mode matching `spec`: same size and, if given, the closest
+/// refresh within 0.5 Hz; without a refresh the fastest one.
+pub fn find_mode(modes: &[Mode], spec: &str) -> Option<Mode> {
+    let (w, h, hz) = parse_mode_spec(spec).ok()?;
+    let sized = modes.iter().filter(|m| m.size() == (w, h));
+    match hz {
+        Some(hz) => sized
+            .filter(|m| (refresh_hz(m) - hz).abs() < 0.5)
+            .min_by(|a, b| (refresh_hz(a) - hz).abs().total_cmp(&(refresh_hz(b) - hz).abs()))
+            .copied(),
+        None => sized.max_by(|a, b| refresh_hz(a).total_cmp(&refresh_hz(b))).copied(),
+    }
+}
+
+// ── Custom modes ──────────────────────────────────────────────────────────────
+
+/// Highest pixel clock a modeline may give; DRM keeps the clock in kHz in
+/// 32 bits, and no link comes anywhere near this.
+const MAX_CLOCK_MHZ: f64 = 100_000.0;
+
+/// A mode from an X11-style modeline: `clock(MHz) hdisp hsyncstart hsyncend
+/// htotal vdisp vsyncstart vsyncend vtotal [flags]`, flags being any of
+/// `+hsync -hsync +vsync -vsync interlace`. A leading quoted name is
+/// skipped, so `cvt`/`gtf` output can be pasted as is.
+pub fn parse_modeline(line: &str) -> Result<Mode> {
+    let line = line.trim().trim_start_matches("Modeline").trim();
+    let line = match line.strip_prefix('"') {
+        Some(rest) => rest.split_once('"').context("unterminated mode name")?.1,
+        None       => line,
+    };
+    let mut fields = line.split_whitespace();
+    let clock_mhz: f64 = fields.next().context("missing pixel clock")?.parse().context("pixel clock")?;
+    if !(clock_mhz > 0.0 && clock_mhz <= MAX_CLOCK_MHZ) {
+        bail!("pixel clock {clock_mhz} MHz is out of range");
+    }
+    let mut t = [0u16; 8];
+    for (i, v) in t.iter_mut().enumerate() {
+        *v = fields.next().with_context(|| format!("missing timing {}", i + 1))?.parse().context("timing")?;
+    }
+    let mut flags = ModeFlags::empty();
+    for flag in fields {
+        flags |= match flag.to_ascii_lowercase().as_str() {
+            "+hsync"    => ModeFlags::PHSYNC,
+            "-hsync"    => ModeFlags::NHSYNC,
+            "+vsync"    => ModeFlags::PVSYNC,
+            "-vsync"    => ModeFlags::NVSYNC,
+            "interlace" => ModeFlags::INTERLACE,
+            f => bail!("unknown modeline flag {f:?}"),
+        };
+    }
+    let [hd, hss, hse, ht, vd, vss, vse, vt] = t;
+    if !(hd <= hss && hss <= hse && hse <= ht && vd <= vss && vss <= vse && vse <= vt) || hd == 0 || vd == 0 {
+        bail!("timings out of order");
+    }
+    Ok(build_mode((clock_mhz * 1000.0).round() as u32, [hd, hss, hse, ht], [vd, vss, vse, vt], flags))
+}
+
+/// CVT reduced-blanking (v1) timings for `w`×`h` at `hz`, which digital
+/// panels accept well and which keep the pixel clock low at high refresh.
+pub fn cvt_rb(w: u16, h: u16, hz: f64) -> Result<Mode> {
+    const H_BLANK: u32 = 160;
+    const H_SYNC: u32 = 32;
+    const V_FRONT_PORCH: u32 = 3;
+    const MIN_V_BACK_PORCH: u32 = 6;
+    const MIN_V_BLANK_US: f64 = 460.0;
+    const CLOCK_STEP_KHZ: f64 = 250.0;
+
+    if w < 320 || h < 200 || !(10.0..=500.0).contains(&hz) {
+        bail!("{w}x{h}@{hz} is out of range");
+    }
+    let h_pixels = (w as u32 / 8) * 8;
+    let v_lines  = h as u32;
+    // Sync width encodes the aspect ratio.
+    let v_sync = match h_pixels * 1000 / v_lines {
+        1333        => 4,  // 4:3
+        1777 | 1778 => 5,  // 16:9
+        1600        => 6,  // 16:10
+        1250 | 1666 => 7,  // 5:4, 15:9
+        _           => 10,
+    };
+    let h_period_us = (1_000_000.0 / hz - MIN_V_BLANK_US) / v_lines as f64;
+    let vbi_lines   = ((MIN_V_BLANK_US / h_period_us) as u32 + 1).max(V_FRONT_PORCH + v_sync + MIN_V_BACK_PORCH);
+    let v_total     = v_lines + vbi_lines;
+    let h_total     = h_pixels + H_BLANK;
+    let clock_khz   = ((hz * v_total as f64 * h_total as f64 / 1000.0) / CLOCK_STEP_KHZ).floor() * CLOCK_STEP_KHZ;
+
+    let h_sync_start = h_pixels + H_BLANK / 2 - H_SYNC;
+    let v_sync_start = v_lines + V_FRONT_PORCH;
+    // DRM timings are 16-bit; totals past that can't be described.
+    let timing = |v: u32| u16::try_from(v).map_err(|_| anyhow!("{w}x{h}@{hz} is out of range"));
+    Ok(build_mode(
+        clock_khz as u32,
+        [timing(h_pixels)?, timing(h_sync_start)?, timing(h_sync_start + H_SYNC)?, timing(h_total)?],
+        [timing(v_lines)?, timing(v_sync_start)?, timing(v_sync_start + v_sync)?, timing(v_total)?],
+        ModeFlags::PHSYNC | ModeFlags::NVSYNC,
+    ))
+}
+
+fn build_mode(clock_khz: u32, h: [u16; 4], v: [u16; 4], flags: ModeFlags) -> Mode {
+    let vrefresh = (clock_khz as f64 * 1000.0 / (h[3] as f64 * v[3] as f64)).round() as u32;
+    let mut name = [0 as std::ffi::c_char; 32];
+    for (dst, src) in name.iter_mut().zip(format!("{}x{}", h[0], v[0]).bytes()) {
+        *dst = src as std::ffi::c_char;
+    }
+    Mode::from(drm::ffi::drm_mode_modeinfo {
+        clock:       clock_khz,
+        hdisplay:    h[0],
+        hsync_start: h[1],
+        hsync_end:   h[2],
+        htotal:      h[3],
+        hskew:       0,
+        vdisplay:    v[0],
+        vsync_start: v[1],
+        vsync_end:   v[2],
+        vtotal:      v[3],
+        vscan:       0,
+        vrefresh,
+        flags:       flags.bits(),
+        type_:       ModeTypeFlags::USERDEF.bits(),
+        name,
+    })
+}
+
+/// Whether the monitor claims to handle `mode`, per its EDID range limits.
+/// Monitors without range limits get the benefit of the doubt.
+pub fn check_limits(mode: &Mode, limits: Option<&RangeLimits>) -> Result<()> {
+    let Some(limits) = limits else { return Ok(()) };
+    let hz    = refresh_hz(mode);
+    let h_khz = mode.clock() as f64 / mode.hsync().2.max(1) as f64;
+    if hz < limits.v_hz.0 as f64 - 0.5 || hz > limits.v_hz.1 as f64 + 0.5 {
+        bail!("{hz:.2} Hz is outside the monitor's {}-{} Hz", limits.v_hz.0, limits.v_hz.1);
+    }
+    if h_khz < limits.h_khz.0 as f64 - 0.5 || h_khz > limits.h_khz.1 as f64 + 0.5 {
+        bail!("{h_khz:.1} kHz line rate is outside the monitor's {}-{} kHz", limits.h_khz.0, limits.h_khz.1);
+    }
+    if let Some(max) = limits.max_clock_khz {
+        if mode.clock() > max {
+            bail!("{} MHz pixel clock exceeds the monitor's {} MHz", mode.clock() / 1000, max / 1000);
+        }
+    }
+    Ok(())
+}
+
+// ── Selection ─────────────────────────────────────────────────────────────────
+
+/// What the config asks for on one connector.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ModeRequest<'a> {
+    /// `[display.outputs.<name>] modeline`.
+    pub modeline: Option<&'a str>,
+    /// `[display.outputs.<name>] mode`; generated with CVT if not listed.
+    pub mode:     Option<&'a str>,
+    /// `display.preferred_mode` / `--mode`; only picked from the list.
+    pub preferred: Option<&'a str>,
+}
+
+/// The mode to light `connector` up with: a configured modeline or custom
+/// mode the monitor can take, else the configured listed mode, else the
+/// monitor's preferred one, else its largest at the highest refresh.
+pub fn select_mode(connector: &connector::Info, request: ModeRequest<'_>, limits: Option<&RangeLimits>) -> Result<Mode> {
+    let modes = connector.modes();
+    if let Some(mode) = custom_mode(modes, request, limits) {
+        return Ok(mode);
+    }
+    if modes.is_empty() {
+        bail!("No modes for connector {:?}", connector.handle());
+    }
+    if let Some(pref) = request.preferred {
+        match find_mode(modes, pref) {
+            Some(m) => return Ok(m),
+            None    => warn!("Mode '{pref}' not found – using EDID preferred"),
+        }
+    }
+    if let Some(m) = modes.iter().find(|m| m.mode_type().contains(ModeTypeFlags::PREFERRED)) {
+        return Ok(*m);
+    }
+    Ok(*modes.iter().max_by_key(|m| (m.size().0 as u32 * m.size().1 as u32, m.vrefresh())).unwrap())
+}
+
+fn custom_mode(modes: &[Mode], request: ModeRequest<'_>, limits: Option<&RangeLimits>) -> Option<Mode> {
+    let (mode, source) = if let Some(line) = request.modeline {
+        match parse_modeline(line) {
+            Ok(m)  => (m, "modeline"),
+            Err(e) => { warn!("Ignoring modeline {line:?}: {e:#}"); return None; }
+        }
+    } else {
+        let spec = request.mode?;
+        if let Some(m) = find_mode(modes, spec) {
+            return Some(m);
+        }
+        let generated = parse_mode_spec(spec).and_then(|(w, h, hz)| cvt_rb(w, h, hz.unwrap_or(60.0)));
+        match generated {
+            Ok(m)  => (m, "CVT"),
+            Err(e) => { warn!("Ignoring mode {spec:?}: {e:#}"); return None; }
+        }
+    };
+    if let Err(e) = check_limits(&mode, limits) {
+        warn!(mode = ?mode.name(), "Rejecting custom mode: {e:#}");
+        return None;
+    }
+    info!(mode = ?mode.name(), hz = format!("{:.2}", refresh_hz(&mode)), clock_khz = mode.clock(), source, "Custom mode");
+    Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock, horizontal and vertical timings and flags of `mode`.
+    fn timings(mode: &Mode) -> (u32, [u16; 4], [u16; 4], ModeFlags) {
+        let ((hd, vd), (hss, hse, ht), (vss, vse, vt)) = (mode.size(), mode.hsync(), mode.vsync());
+        (mode.clock(), [hd, hss, hse, ht], [vd, vss, vse, vt], mode.flags())
+    }
+
+    #[test]
+    fn cvt_rb_matches_cvt_r() {
+        // `cvt -r 1920 1080 60`
+        let mode = cvt_rb(1920, 1080, 60.0).unwrap();
+        let flags = ModeFlags::PHSYNC | ModeFlags::NVSYNC;
+        assert_eq!(timings(&mode), (138_500, [1920, 1968, 2000, 2080], [1080, 1083, 1088, 1111], flags));
+        // `cvt -r 2560 1440 60`
+        let mode = cvt_rb(2560, 1440, 60.0).unwrap();
+        assert_eq!(timings(&mode), (241_500, [2560, 2608, 2640, 2720], [1440, 1443, 1448, 1481], flags));
+        // `cvt -r 1280 800 60`
+        let mode = cvt_rb(1280, 800, 60.0).unwrap();
+        assert_eq!(timings(&mode), (71_000, [1280, 1328, 1360, 1440], [800, 803, 809, 823], flags));
+    }
+
+    #[test]
+    fn cvt_rb_rejects_what_drm_cannot_describe() {
+        assert!(cvt_rb(65535, 1080, 60.0).is_err());
+        assert!(cvt_rb(1920, 65535, 60.0).is_err());
+        assert!(cvt_rb(300, 1080, 60.0).is_err());
+        assert!(cvt_rb(1920, 1080, 5.0).is_err());
+        assert!(cvt_rb(1920, 1080, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn modeline_from_cvt_is_pasted_as_is() {
+        let line = r#"Modeline "1920x1080R"  138.50  1920 1968 2000 2080  1080 1083 1088 1111 +hsync -vsync"#;
+        let mode = parse_modeline(line).unwrap();
+        assert_eq!(timings(&mode), timings(&cvt_rb(1920, 1080, 60.0).unwrap()));
+        assert_eq!(mode.vrefresh(), 60);
+    }
+
+    #[test]
+    fn malformed_modelines_are_rejected() {
+        for line in [
+            "",
+            r#""1920x1080R 138.50 1920 1968 2000 2080 1080 1083 1088 1111"#,
+            "fast 1920 1968 2000 2080 1080 1083 1088 1111",
+            "-138.50 1920 1968 2000 2080 1080 1083 1088 1111",
+            "0 1920 1968 2000 2080 1080 1083 1088 1111",
+            "NaN 1920 1968 2000 2080 1080 1083 1088 1111",
+            "inf 1920 1968 2000 2080 1080 1083 1088 1111",
+            "138.50 1920 1968 2000 2080 1080 1083 1088",
+            "138.50 1920 1968 2000 2080 1080 1083 1088 70000",
+            "138.50 1920 2000 1968 2080 1080 1083 1088 1111",
+            "138.50 0 0 0 2080 1080 1083 1088 1111",
+            "138.50 1920 1968 2000 2080 1080 1083 1088 1111 +csync",
+        ] {
+            assert!(parse_modeline(line).is_err(), "{line:?} was accepted");
+        }
+    }
+
+    #[test]
+    fn mode_spec_takes_an_optional_refresh() {
+        assert_eq!(parse_mode_spec("2560x1440").unwrap(), (2560, 1440, None));
+        assert_eq!(parse_mode_spec("1920x1080@59.94Hz").unwrap(), (1920, 1080, Some(59.94)));
+        assert!(parse_mode_spec("1920").is_err());
+        assert!(parse_mode_spec("1920x1080@fast").is_err());
+    }
+}
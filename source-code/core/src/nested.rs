@@ -0,0 +1,1034 @@
+use std::{
+    fs::File,
+    os::unix::{
+        fs::FileExt,
+        io::{AsFd, FromRawFd},
+    },
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::{GbmAllocator, GbmBufferFlags},
+            Allocator, Fourcc, Modifier,
+        },
+        drm::DrmDeviceFd,
+        input::{
+            AbsolutePositionEvent, Axis, AxisRelativeDirection, AxisSource, ButtonState, Device,
+            DeviceCapability, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent,
+            PointerAxisEvent, PointerButtonEvent, PointerMotionEvent, UnusedEvent,
+        },
+        renderer::{damage::OutputDamageTracker, element::RenderElementStates, gles::GlesRenderer, Bind, ExportMem},
+    },
+    desktop::utils::OutputPresentationFeedback,
+    input::keyboard::Keycode,
+    output::{Mode, Output, PhysicalProperties, Scale, Subpixel},
+    reexports::{
+        calloop::{
+            generic::Generic,
+            timer::{TimeoutAction, Timer},
+            Interest, LoopHandle, Mode as TriggerMode, PostAction,
+        },
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+    },
+    utils::{Buffer as BufferCoord, Monotonic, Physical, Rectangle, Size, Time, Transform},
+    wayland::presentation::Refresh,
+};
+use tracing::{debug, info, info_span, trace, warn};
+use wayland_client::{
+    globals::{registry_queue_init, GlobalListContents},
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_callback::{self, WlCallback},
+        wl_compositor::WlCompositor,
+        wl_keyboard::{self, WlKeyboard},
+        wl_pointer::{self, WlPointer},
+        wl_registry::WlRegistry,
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum, WaylandError,
+};
+use wayland_protocols::{
+    wp::{
+        linux_dmabuf::zv1::client::{
+            zwp_linux_buffer_params_v1::{self, ZwpLinuxBufferParamsV1},
+            zwp_linux_dmabuf_v1::{self, ZwpLinuxDmabufV1},
+        },
+        pointer_constraints::zv1::client::{
+            zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
+            zwp_pointer_constraints_v1::{self, ZwpPointerConstraintsV1},
+        },
+        relative_pointer::zv1::client::{
+            zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1,
+            zwp_relative_pointer_v1::{self, ZwpRelativePointerV1},
+        },
+    },
+    xdg::{
+        decoration::zv1::client::{
+            zxdg_decoration_manager_v1::ZxdgDecorationManagerV1,
+            zxdg_toplevel_decoration_v1::{self, ZxdgToplevelDecorationV1},
+        },
+        shell::client::{
+            xdg_surface::{self, XdgSurface},
+            xdg_toplevel::{self, XdgToplevel},
+            xdg_wm_base::{self, XdgWmBase},
+        },
+    },
+};
+
+use crate::{
+    compositor::Backend,
+    constraints,
+    cursor::cursor_elements,
+    dmabuf::init_dmabuf_global,
+    frame::{RepaintPhase, RepaintState},
+    headless::{find_render_node, open_render_node},
+    input_handler::{self, process_input_event},
+    render::{
+        apply_color_filter, frame_callbacks_pending, magnify, mask_for_capture, output_elements,
+        send_frame_callbacks, take_presentation_feedback, update_primary_scanout_outputs, FrameElements, Scene,
+        CLEAR_COLOR,
+    },
+    state::GameframeState,
+    supersample::supersample,
+    upscale,
+};
+
+/// Nominal refresh of the host window, in mHz. The parent compositor paces
+/// us through frame callbacks, so this only feeds `wl_output` and the
+/// estimated vblanks of frames that weren't committed.
+const NESTED_REFRESH_MHZ: i32 = 60_000;
+
+/// Window size when the parent leaves it to us.
+const DEFAULT_SIZE: (i32, i32) = (1280, 720);
+
+/// Most buffers the parent may hold at once before we stop drawing.
+const MAX_BUFFERS: usize = 4;
+
+// ── Nested backend state ──────────────────────────────────────────────────────
+
+/// Gameframe running as a window inside another Wayland compositor (like
+/// gamescope's nested mode). A single output mirrors the host window size.
+///
+/// Frames are drawn on a render node exactly as for a virtual output and
+/// handed to the parent as dmabufs (`zwp_linux_dmabuf_v1`), or copied into
+/// `wl_shm` buffers when it can't import ours. Its frame callbacks stand in
+/// for vblank.
+pub struct NestedBackend {
+    pub renderer:   GlesRenderer,
+    allocator:      GbmAllocator<DrmDeviceFd>,
+    pub output:     Output,
+    damage_tracker: OutputDamageTracker,
+    pub repaint:    RepaintState,
+    pending_feedback: Option<OutputPresentationFeedback>,
+    conn:           Connection,
+    qh:             QueueHandle<Host>,
+    host:           Host,
+    window:         HostWindow,
+    present:        Present,
+    buffers:        Vec<HostBuffer>,
+    /// Buffers of an old size the parent still holds, destroyed once it
+    /// releases them.
+    retired:        Vec<HostBuffer>,
+    /// The parent's pointer constraints, if it has them.
+    pointer_constraints: Option<ZwpPointerConstraintsV1>,
+    /// Lock on the parent's pointer while a client constrains ours.
+    locked_pointer: Option<ZwpLockedPointerV1>,
+}
+
+/// Our toplevel on the parent.
+struct HostWindow {
+    surface:   WlSurface,
+    _toplevel: XdgToplevel,
+    _xdg:      XdgSurface,
+}
+
+/// How frames reach the parent.
+enum Present {
+    /// Our buffers themselves, imported by the parent's GPU.
+    Dmabuf(ZwpLinuxDmabufV1),
+    /// Read back and copied into shared memory.
+    Shm(WlShm),
+}
+
+/// One frame's worth of buffer, as drawn by us and as known to the parent.
+struct HostBuffer {
+    dmabuf:    Dmabuf,
+    wl_buffer: WlBuffer,
+    /// Attached and not yet released by the parent.
+    busy:      Arc<AtomicBool>,
+    /// Frames since this buffer was last drawn; 0 if it never was.
+    age:       usize,
+    /// Shared memory behind `wl_buffer` on the `wl_shm` path.
+    shm:       Option<File>,
+}
+
+impl Drop for HostBuffer {
+    fn drop(&mut self) {
+        self.wl_buffer.destroy();
+    }
+}
+
+impl NestedBackend {
+    /// Ask for a redraw. Returns true if the caller should schedule a render
+    /// now; otherwise one is already queued or follows the host's next frame.
+    pub fn queue_redraw(&mut self) -> bool { self.repaint.queue_redraw() }
+
+    fn size(&self) -> Size<i32, Physical> {
+        self.output.current_mode().map_or(DEFAULT_SIZE.into(), |m| m.size)
+    }
+
+    /// Draw `elements` into a free buffer and commit it to the host window.
+    /// Returns whether a buffer was committed (there was damage).
+    fn draw(&mut self, elements: &[FrameElements]) -> Result<(bool, RenderElementStates)> {
+        let index = match self.buffers.iter().position(|b| !b.busy.load(Ordering::Acquire)) {
+            Some(index) => index,
+            None if self.buffers.len() < MAX_BUFFERS => {
+                let buffer = self.allocate()?;
+                self.buffers.push(buffer);
+                self.buffers.len() - 1
+            }
+            None => bail!("the host holds all {MAX_BUFFERS} buffers"),
+        };
+        let size = self.size();
+        let buffer = &mut self.buffers[index];
+        let mut fb = self.renderer.bind(&mut buffer.dmabuf).context("bind host buffer")?;
+        let res = self.damage_tracker
+            .render_output(&mut self.renderer, &mut fb, buffer.age, elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("render_output: {e:?}"))?;
+        let Some(damage) = res.damage.cloned() else { return Ok((false, res.states)) };
+        let states = res.states;
+        // The parent reads the buffer as soon as it is committed.
+        if let Err(e) = res.sync.wait() {
+            debug!("nested sync: {e:?}");
+        }
+        if let Some(file) = &buffer.shm {
+            let region = Rectangle::<i32, BufferCoord>::from_size((size.w, size.h).into());
+            let mapping = self.renderer.copy_framebuffer(&fb, region, Fourcc::Abgr8888).context("copy_framebuffer")?;
+            let mut pixels = self.renderer.map_texture(&mapping)
+                .map_err(|e| anyhow!("map_texture: {e:?}"))?
+                .to_vec();
+            // RGBA bytes to the BGRX every wl_shm supports.
+            for px in pixels.chunks_exact_mut(4) {
+                px.swap(0, 2);
+            }
+            file.write_all_at(&pixels, 0).context("write shm buffer")?;
+        }
+        drop(fb);
+
+        let surface = &self.window.surface;
+        surface.attach(Some(&buffer.wl_buffer), 0, 0);
+        for rect in &damage {
+            surface.damage_buffer(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+        }
+        surface.frame(&self.qh, ());
+        surface.commit();
+        buffer.busy.store(true, Ordering::Release);
+        if let Err(e) = self.conn.flush() {
+            warn!("nested flush: {e}");
+        }
+        trace!(rects = damage.len(), "nested frame");
+
+        for b in self.buffers.iter_mut().filter(|b| b.age > 0) {
+            b.age += 1;
+        }
+        self.buffers[index].age = 1;
+        Ok((true, states))
+    }
+
+    fn allocate(&mut self) -> Result<HostBuffer> {
+        let size = self.size();
+        // Linear so the parent can import it whichever GPU it renders on.
+        let bo = self.allocator
+            .create_buffer(size.w as u32, size.h as u32, Fourcc::Xrgb8888, &[Modifier::Linear])
+            .with_context(|| format!("allocate {}x{} buffer", size.w, size.h))?;
+        let dmabuf = bo.export().map_err(|e| anyhow!("export dmabuf: {e:?}"))?;
+        let busy = Arc::new(AtomicBool::new(false));
+        let (wl_buffer, shm) = match &self.present {
+            Present::Dmabuf(global) => {
+                let params = global.create_params(&self.qh, ());
+                let modifier = u64::from(dmabuf.format().modifier);
+                for (plane, ((fd, offset), stride)) in
+                    dmabuf.handles().zip(dmabuf.offsets()).zip(dmabuf.strides()).enumerate()
+                {
+                    params.add(fd, plane as u32, offset, stride, (modifier >> 32) as u32, modifier as u32);
+                }
+                let wl_buffer = params.create_immed(
+                    size.w, size.h, Fourcc::Xrgb8888 as u32,
+                    zwp_linux_buffer_params_v1::Flags::empty(), &self.qh, busy.clone(),
+                );
+                params.destroy();
+                (wl_buffer, None)
+            }
+            Present::Shm(shm) => {
+                let stride = size.w * 4;
+                let len = stride * size.h;
+                // SAFETY: memfd_create returns a new fd nobody else owns, or -1.
+                let fd = unsafe { libc::memfd_create(c"gameframe-nested".as_ptr(), libc::MFD_CLOEXEC) };
+                if fd < 0 {
+                    return Err(std::io::Error::last_os_error()).context("memfd_create");
+                }
+                // SAFETY: see above.
+                let file = unsafe { File::from_raw_fd(fd) };
+                file.set_len(len as u64).context("size shm buffer")?;
+                let pool: WlShmPool = shm.create_pool(file.as_fd(), len, &self.qh, ());
+                let wl_buffer = pool.create_buffer(0, size.w, size.h, stride, wl_shm::Format::Xrgb8888, &self.qh, busy.clone());
+                pool.destroy();
+                (wl_buffer, Some(file))
+            }
+        };
+        Ok(HostBuffer { dmabuf, wl_buffer, busy, age: 0, shm })
+    }
+}
+
+// ── Init ──────────────────────────────────────────────────────────────────────
+
+/// Open a window on the parent Wayland compositor and use it as the only
+/// output. Input comes from the host's seat via the same window, with raw
+/// deltas from its relative pointer when it has one.
+pub fn init(
+    state:       &mut GameframeState,
+    loop_handle: &LoopHandle<'static, GameframeState>,
+) -> Result<()> {
+    let conn = Connection::connect_to_env().context("connect to the parent compositor")?;
+    let (globals, mut queue) = registry_queue_init::<Host>(&conn).context("parent globals")?;
+    let qh = queue.handle();
+    let compositor: WlCompositor = globals.bind(&qh, 4..=6, ()).context("parent has no wl_compositor")?;
+    let wm_base: XdgWmBase = globals.bind(&qh, 1..=6, ()).context("parent has no xdg_wm_base")?;
+    let shm: WlShm = globals.bind(&qh, 1..=1, ()).context("parent has no wl_shm")?;
+    // Version 3: the formats we may use arrive as `modifier` events.
+    let dmabuf: Option<ZwpLinuxDmabufV1> = globals.bind(&qh, 3..=3, ()).ok();
+    let _seat: Option<WlSeat> = globals.bind(&qh, 5..=8, ()).ok();
+    let decorations: Option<ZxdgDecorationManagerV1> = globals.bind(&qh, 1..=1, ()).ok();
+    let relative_pointers: Option<ZwpRelativePointerManagerV1> = globals.bind(&qh, 1..=1, ()).ok();
+    let pointer_constraints: Option<ZwpPointerConstraintsV1> = globals.bind(&qh, 1..=1, ()).ok();
+
+    let surface  = compositor.create_surface(&qh, ());
+    let xdg      = wm_base.get_xdg_surface(&surface, &qh, ());
+    let toplevel = xdg.get_toplevel(&qh, ());
+    toplevel.set_title("Gameframe".into());
+    toplevel.set_app_id("gameframe".into());
+    if let Some(decorations) = &decorations {
+        let decoration = decorations.get_toplevel_decoration(&toplevel, &qh, ());
+        decoration.set_mode(zxdg_toplevel_decoration_v1::Mode::ServerSide);
+    }
+    surface.commit();
+
+    let mut host = Host { relative_pointers, ..Host::default() };
+    while !host.configured {
+        queue.blocking_dispatch(&mut host).context("wait for the parent's configure")?;
+    }
+    host.events.clear();
+    let size: Size<i32, Physical> = host.size.into();
+
+    let linear = (Fourcc::Xrgb8888 as u32, u64::from(Modifier::Linear));
+    let present = match dmabuf {
+        Some(global) if host.dmabuf_formats.contains(&linear) => Present::Dmabuf(global),
+        _ => {
+            info!("Parent can't import linear XRGB8888 dmabufs; frames are copied through wl_shm");
+            Present::Shm(shm)
+        }
+    };
+
+    let node = find_render_node().context("no render node in /dev/dri")?;
+    let (gbm, renderer) = open_render_node(&node)?;
+    info!(node = %node.display(), dmabuf = matches!(present, Present::Dmabuf(_)), "Nested renderer ready");
+
+    let mode = Mode { size, refresh: NESTED_REFRESH_MHZ };
+    let output = Output::new(
+        "WAYLAND-NESTED".into(),
+        PhysicalProperties {
+            size:          (0, 0).into(),
+            subpixel:      Subpixel::Unknown,
+            make:          "Gameframe".into(),
+            model:         "Nested".into(),
+            serial_number: String::new(),
+        },
+    );
+    let _global = output.create_global::<GameframeState>(&state.display_handle);
+    output.change_current_state(
+        Some(mode),
+        Some(Transform::Normal),
+        Some(Scale::Fractional(state.config.display.scale.unwrap_or(1.0))),
+        Some((0, 0).into()),
+    );
+    output.set_preferred(mode);
+    state.map_output(&output);
+    info!(w = size.w, h = size.h, "Nested output ready");
+
+    match init_dmabuf_global(&renderer, &mut state.dmabuf_state, &state.display_handle) {
+        Ok(global) => state.dmabuf_global = Some(global),
+        Err(e)     => warn!("DMABUF global failed (non-fatal): {e}"),
+    }
+
+    state.backend = Some(Backend::Nested(NestedBackend {
+        renderer,
+        allocator: GbmAllocator::new(gbm, GbmBufferFlags::RENDERING),
+        damage_tracker: OutputDamageTracker::from_output(&output),
+        repaint: RepaintState::new(NESTED_REFRESH_MHZ),
+        pending_feedback: None,
+        output: output.clone(),
+        conn,
+        qh,
+        host,
+        window: HostWindow { surface, _toplevel: toplevel, _xdg: xdg },
+        present,
+        buffers: Vec::new(),
+        retired: Vec::new(),
+        pointer_constraints,
+        locked_pointer: None,
+    }));
+
+    loop_handle
+        .insert_source(Generic::new(queue, Interest::READ, TriggerMode::Level), |_, queue, state| {
+            // SAFETY: the queue is never dropped while the source is alive.
+            let queue = unsafe { queue.get_mut() };
+            if let Some(guard) = queue.prepare_read() {
+                match guard.read() {
+                    Ok(_) => {}
+                    Err(WaylandError::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        info!("Parent compositor gone ({e}) – shutting down");
+                        state.running = false;
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            }
+            dispatch_host(state, queue);
+            Ok(PostAction::Continue)
+        })
+        .map_err(|e| anyhow!("parent compositor source: {e}"))?;
+
+    state.queue_redraw(&output);
+    Ok(())
+}
+
+/// Dispatch what the parent sent and act on it.
+fn dispatch_host(state: &mut GameframeState, queue: &mut EventQueue<Host>) {
+    let Some(nested) = state.backend.as_mut().and_then(|b| b.nested_mut()) else { return };
+    if let Err(e) = queue.dispatch_pending(&mut nested.host) {
+        warn!("parent compositor: {e}");
+        state.running = false;
+        return;
+    }
+    // Pongs and configure acks go out right away.
+    let _ = nested.conn.flush();
+    nested.retired.retain(|b| b.busy.load(Ordering::Acquire));
+    for event in std::mem::take(&mut nested.host.events) {
+        match event {
+            HostEvent::Resize(size) => resize(state, size),
+            HostEvent::FrameDone    => nested_vblank(state, true),
+            HostEvent::Input(event) => process_input_event(state, event),
+            HostEvent::Relative(event) => {
+                input_handler::relative_motion(state, event.delta(), event.delta_unaccel(), event.utime);
+            }
+            HostEvent::Close => {
+                info!("Host window closed – shutting down");
+                state.running = false;
+            }
+        }
+    }
+    lock_host_pointer(state);
+}
+
+/// Lock the parent's pointer while a client has locked or confined ours, so
+/// it can't leave our window and motion keeps coming as relative deltas;
+/// our side then holds or confines the pointer as usual. On release the
+/// parent's pointer is put back where ours is.
+fn lock_host_pointer(state: &mut GameframeState) {
+    let wanted   = constraints::active(state);
+    let location = state.pointer_location;
+    let area     = state.space.outputs().next().and_then(|o| state.space.output_geometry(o));
+    let Some(nested) = state.backend.as_mut().and_then(|b| b.nested_mut()) else { return };
+    match (wanted, nested.locked_pointer.take()) {
+        (true, Some(locked)) => {
+            nested.locked_pointer = Some(locked);
+            return;
+        }
+        (true, None) => {
+            let (Some(constraints), Some(pointer)) = (&nested.pointer_constraints, &nested.host.pointer) else { return };
+            nested.locked_pointer = Some(constraints.lock_pointer(
+                &nested.window.surface, pointer, None,
+                zwp_pointer_constraints_v1::Lifetime::Persistent, &nested.qh, (),
+            ));
+            debug!("host pointer locked");
+        }
+        (false, Some(locked)) => {
+            if let Some(area) = area {
+                let (w, h) = nested.host.size;
+                let at = location - area.loc.to_f64();
+                locked.set_cursor_position_hint(
+                    at.x / area.size.w.max(1) as f64 * w as f64,
+                    at.y / area.size.h.max(1) as f64 * h as f64,
+                );
+                nested.window.surface.commit();
+            }
+            locked.destroy();
+            nested.host.pointer_locked = false;
+            debug!("host pointer unlocked");
+        }
+        (false, None) => return,
+    }
+    let _ = nested.conn.flush();
+}
+
+/// The parent resized our window: the output takes on its size, and the
+/// layout follows as for a mode switch.
+fn resize(state: &mut GameframeState, size: Size<i32, Physical>) {
+    let old = state.space.outputs().next().and_then(|o| state.space.output_geometry(o));
+    let Some(nested) = state.backend.as_mut().and_then(|b| b.nested_mut()) else { return };
+    if nested.size() == size { return; }
+    let mode = Mode { size, refresh: NESTED_REFRESH_MHZ };
+    nested.output.change_current_state(Some(mode), None, None, None);
+    nested.output.set_preferred(mode);
+    nested.damage_tracker = OutputDamageTracker::from_output(&nested.output);
+    // Buffers the parent still holds go when it releases them.
+    let busy = nested.buffers.drain(..).filter(|b| b.busy.load(Ordering::Acquire));
+    nested.retired.extend(busy);
+    debug!(w = size.w, h = size.h, "host window resized");
+    let output = nested.output.clone();
+    if let Some(old) = old {
+        state.output_resized(&output, old);
+    }
+}
+
+// ── Rendering ─────────────────────────────────────────────────────────────────
+
+/// Draw the space into the host window. The frame goes through the same
+/// elements as a DRM output's (cursor included; the host's is hidden over
+/// our window) and is paced by the parent's frame callbacks.
+pub fn render_nested(state: &mut GameframeState) {
+    let now     = Duration::from(state.clock.now());
+    let focused = state.focused_window();
+    let output  = match state.backend.as_mut().and_then(|b| b.nested_mut()) {
+        Some(nested) => nested.output.clone(),
+        None => return,
+    };
+    let upscaled = upscale::upscaled(state, &output);
+    let Some(nested) = state.backend.as_mut().and_then(|b| b.nested_mut()) else { return };
+    let _span = info_span!("render_nested", frame = nested.repaint.stats().presented).entered();
+
+    if let RepaintPhase::Waiting { .. } = nested.repaint.phase() {
+        nested.repaint.queue_redraw();
+        return;
+    }
+
+    let scene = Scene {
+        space:      &state.space,
+        animations: &state.animations,
+        config:     &state.config,
+        focused:    focused.as_ref(),
+        focused_output: state.focused_output.as_ref(),
+        dnd_icon:   state.dnd.as_ref().and_then(|d| d.icon_at(state.pointer_location)),
+        upscaled,
+    };
+    let renderer = &mut nested.renderer;
+    upscale::set_filter(renderer, scene.upscaled.as_ref());
+    let elements = output_elements(renderer, &output, &scene, now);
+    let elements = magnify(elements, &scene, &output, state.magnifier.level(), state.pointer_location);
+    let mut elements = supersample(renderer, &output, elements, &state.config);
+    apply_color_filter(renderer, &mut elements, &output, state.color_filter.transform());
+    if let Some(geo) = state.space.output_geometry(&output) {
+        let scale = output.current_scale().fractional_scale();
+        let pointers = std::iter::once((&state.cursor_status, state.pointer_location))
+            .chain(state.players.cursors());
+        let cursors: Vec<FrameElements> = pointers
+            .flat_map(|(status, location)| cursor_elements(
+                renderer, &mut state.cursor_theme, status, location, geo, scale,
+            ))
+            .map(FrameElements::Cursor)
+            .collect();
+        elements.splice(0..0, cursors);
+    }
+    let mut masked = 0;
+    if let Some(recorder) = state.recorder.as_mut().filter(|r| r.output() == &output) {
+        masked = mask_for_capture(&mut elements, &scene, &output, state.magnifier.level(), state.pointer_location);
+        recorder.capture(renderer, &elements, now);
+    }
+    let (flip, states) = match nested.draw(&elements[masked..]) {
+        Ok(r)  => r,
+        Err(e) => {
+            warn!("nested render failed: {e:#}");
+            nested.repaint.frame_dropped();
+            (false, RenderElementStates::default())
+        }
+    };
+
+    let frame_time = nested.repaint.next_vblank(now);
+    let refresh    = nested.repaint.refresh_interval();
+    update_primary_scanout_outputs(&state.space, &output, &states);
+    if let Some(mut stale) = nested.pending_feedback.take() {
+        stale.discarded();
+    }
+    let waiting = !flip && frame_callbacks_pending(&state.space, &output);
+    if flip {
+        nested.pending_feedback = Some(take_presentation_feedback(&state.space, &output, &states));
+    }
+
+    let animating = state.animations.tick(now) | state.magnifier.tick(now);
+    if !flip && !waiting && !animating {
+        nested.repaint.frame_skipped();
+        return;
+    }
+    nested.repaint.frame_queued(flip, now);
+    if waiting {
+        send_frame_callbacks(state, &output, frame_time);
+    }
+    if animating {
+        state.queue_redraw(&output);
+    }
+    if !flip {
+        // Nothing was committed, so no frame callback will come from the
+        // parent; wake up when it would have.
+        let delay = match frame_time.saturating_sub(now) {
+            d if d.is_zero() => refresh,
+            d => d,
+        };
+        let _ = state.loop_handle.insert_source(Timer::from_duration(delay), |_, _, state| {
+            nested_vblank(state, false);
+            TimeoutAction::Drop
+        });
+    }
+}
+
+/// The parent is ready for our next frame (`host` is true; the last one is
+/// on its way to the screen), or the estimated vblank of a frame that wasn't
+/// committed elapsed.
+fn nested_vblank(state: &mut GameframeState, host: bool) {
+    let now = Duration::from(state.clock.now());
+    let Some(nested) = state.backend.as_mut().and_then(|b| b.nested_mut()) else { return };
+    if host != matches!(nested.repaint.phase(), RepaintPhase::Waiting { flip: true, .. }) { return; }
+    let (flip, redraw) = nested.repaint.on_vblank(now, false);
+    if flip {
+        // The parent doesn't say when it shows the frame; report when it
+        // asked for the next one, without hardware flags.
+        if let Some(mut feedback) = nested.pending_feedback.take() {
+            let refresh = Refresh::Fixed(nested.repaint.refresh_interval());
+            feedback.presented(Time::<Monotonic>::from(now), refresh, 0, wp_presentation_feedback::Kind::empty());
+        }
+        let output = nested.output.clone();
+        send_frame_callbacks(state, &output, now);
+    }
+    if redraw {
+        render_nested(state);
+    }
+}
+
+// ── Parent compositor events ──────────────────────────────────────────────────
+
+/// What the parent compositor sent, gathered while its events are
+/// dispatched and acted on afterwards with the whole state at hand.
+#[derive(Default)]
+struct Host {
+    events:         Vec<HostEvent>,
+    /// Size from the last `xdg_toplevel.configure`; 0 leaves it to us.
+    pending_size:   (i32, i32),
+    /// Size of our window, which input positions are relative to.
+    size:           (i32, i32),
+    configured:     bool,
+    /// Format and modifier pairs the parent imports.
+    dmabuf_formats: Vec<(u32, u64)>,
+    pointer:        Option<WlPointer>,
+    /// The parent's relative pointer manager, and our relative pointer.
+    relative_pointers: Option<ZwpRelativePointerManagerV1>,
+    relative_pointer:  Option<ZwpRelativePointerV1>,
+    /// The parent has locked its pointer for us: motion only comes as
+    /// relative deltas.
+    pointer_locked: bool,
+    keyboard:       Option<WlKeyboard>,
+    /// Keys held down in our window, released if it loses the keyboard.
+    pressed:        Vec<u32>,
+    /// Scroll of the pointer frame being gathered.
+    axis:           Option<HostAxis>,
+}
+
+enum HostEvent {
+    Resize(Size<i32, Physical>),
+    FrameDone,
+    Close,
+    Input(InputEvent<NestedInput>),
+    /// Raw deltas alongside the parent's absolute motion.
+    Relative(HostRelative),
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for Host {
+    fn event(_: &mut Self, _: &WlRegistry, _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents, _: &Connection, _: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<XdgWmBase, ()> for Host {
+    fn event(_: &mut Self, wm_base: &XdgWmBase, event: xdg_wm_base::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for Host {
+    fn event(host: &mut Self, xdg: &XdgSurface, event: xdg_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        let xdg_surface::Event::Configure { serial } = event else { return };
+        xdg.ack_configure(serial);
+        host.size = match host.pending_size {
+            (w, h) if w > 0 && h > 0 => (w, h),
+            _ if host.configured => host.size,
+            _ => DEFAULT_SIZE,
+        };
+        host.configured = true;
+        host.events.push(HostEvent::Resize(host.size.into()));
+    }
+}
+
+impl Dispatch<XdgToplevel, ()> for Host {
+    fn event(host: &mut Self, _: &XdgToplevel, event: xdg_toplevel::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            xdg_toplevel::Event::Configure { width, height, .. } => host.pending_size = (width, height),
+            xdg_toplevel::Event::Close => host.events.push(HostEvent::Close),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, ()> for Host {
+    fn event(host: &mut Self, _: &WlCallback, event: wl_callback::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_callback::Event::Done { .. } = event {
+            host.events.push(HostEvent::FrameDone);
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, Arc<AtomicBool>> for Host {
+    fn event(_: &mut Self, _: &WlBuffer, event: wl_buffer::Event, busy: &Arc<AtomicBool>, _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_buffer::Event::Release = event {
+            busy.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl Dispatch<ZwpLinuxDmabufV1, ()> for Host {
+    fn event(host: &mut Self, _: &ZwpLinuxDmabufV1, event: zwp_linux_dmabuf_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let zwp_linux_dmabuf_v1::Event::Modifier { format, modifier_hi, modifier_lo } = event {
+            host.dmabuf_formats.push((format, (u64::from(modifier_hi) << 32) | u64::from(modifier_lo)));
+        }
+    }
+}
+
+impl Dispatch<WlSeat, ()> for Host {
+    fn event(host: &mut Self, seat: &WlSeat, event: wl_seat::Event, _: &(), _: &Connection, qh: &QueueHandle<Self>) {
+        let wl_seat::Event::Capabilities { capabilities: WEnum::Value(caps) } = event else { return };
+        match (caps.contains(wl_seat::Capability::Pointer), host.pointer.take()) {
+            (true, Some(pointer)) => host.pointer = Some(pointer),
+            (true, None) => {
+                let pointer = seat.get_pointer(qh, ());
+                host.relative_pointer = host.relative_pointers.as_ref().map(|m| m.get_relative_pointer(&pointer, qh, ()));
+                host.pointer = Some(pointer);
+            }
+            (false, Some(pointer)) => {
+                if let Some(relative) = host.relative_pointer.take() {
+                    relative.destroy();
+                }
+                pointer.release();
+            }
+            (false, None)         => {}
+        }
+        match (caps.contains(wl_seat::Capability::Keyboard), host.keyboard.take()) {
+            (true, Some(keyboard)) => host.keyboard = Some(keyboard),
+            (true, None)           => host.keyboard = Some(seat.get_keyboard(qh, ())),
+            (false, Some(keyboard)) => keyboard.release(),
+            (false, None)          => {}
+        }
+    }
+}
+
+impl Dispatch<WlPointer, ()> for Host {
+    fn event(host: &mut Self, pointer: &WlPointer, event: wl_pointer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            wl_pointer::Event::Enter { serial, surface_x, surface_y, .. } => {
+                // We draw the cursor ourselves.
+                pointer.set_cursor(serial, None, 0, 0);
+                host.motion(0, surface_x, surface_y);
+            }
+            wl_pointer::Event::Motion { time, surface_x, surface_y } => host.motion(time, surface_x, surface_y),
+            wl_pointer::Event::Button { time, button, state, .. } => {
+                let pressed = state == WEnum::Value(wl_pointer::ButtonState::Pressed);
+                host.input(InputEvent::PointerButton { event: HostButton { time, button, pressed } });
+            }
+            wl_pointer::Event::AxisSource { axis_source: WEnum::Value(source) } => {
+                host.axis.get_or_insert_with(HostAxis::default).source = match source {
+                    wl_pointer::AxisSource::Finger     => AxisSource::Finger,
+                    wl_pointer::AxisSource::Continuous => AxisSource::Continuous,
+                    wl_pointer::AxisSource::WheelTilt  => AxisSource::WheelTilt,
+                    _                                  => AxisSource::Wheel,
+                };
+            }
+            wl_pointer::Event::Axis { time, axis: WEnum::Value(axis), value } => {
+                let frame = host.axis.get_or_insert_with(HostAxis::default);
+                frame.time = time;
+                frame.amount[axis_index(axis)] = Some(value);
+            }
+            wl_pointer::Event::AxisDiscrete { axis: WEnum::Value(axis), discrete } => {
+                let frame = host.axis.get_or_insert_with(HostAxis::default);
+                frame.v120[axis_index(axis)] = Some(discrete as f64 * 120.0);
+            }
+            wl_pointer::Event::AxisValue120 { axis: WEnum::Value(axis), value120 } => {
+                let frame = host.axis.get_or_insert_with(HostAxis::default);
+                frame.v120[axis_index(axis)] = Some(value120 as f64);
+            }
+            wl_pointer::Event::Frame => {
+                if let Some(event) = host.axis.take() {
+                    host.input(InputEvent::PointerAxis { event });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwpRelativePointerV1, ()> for Host {
+    fn event(host: &mut Self, _: &ZwpRelativePointerV1, event: zwp_relative_pointer_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        let zwp_relative_pointer_v1::Event::RelativeMotion { utime_hi, utime_lo, dx, dy, dx_unaccel, dy_unaccel } = event else {
+            return;
+        };
+        let event = HostRelative {
+            utime:   (u64::from(utime_hi) << 32) | u64::from(utime_lo),
+            delta:   (dx, dy),
+            unaccel: (dx_unaccel, dy_unaccel),
+        };
+        // While the parent's pointer is locked this is all the motion there
+        // is; otherwise its absolute motion moves ours.
+        if host.pointer_locked {
+            host.input(InputEvent::PointerMotion { event });
+        } else {
+            host.events.push(HostEvent::Relative(event));
+        }
+    }
+}
+
+impl Dispatch<ZwpLockedPointerV1, ()> for Host {
+    fn event(host: &mut Self, _: &ZwpLockedPointerV1, event: zwp_locked_pointer_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            zwp_locked_pointer_v1::Event::Locked   => host.pointer_locked = true,
+            zwp_locked_pointer_v1::Event::Unlocked => host.pointer_locked = false,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlKeyboard, ()> for Host {
+    fn event(host: &mut Self, _: &WlKeyboard, event: wl_keyboard::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            // Keycodes are evdev's; our own keymap applies to them.
+            wl_keyboard::Event::Key { time, key, state, .. } => {
+                let pressed = state == WEnum::Value(wl_keyboard::KeyState::Pressed);
+                host.pressed.retain(|&k| k != key);
+                if pressed {
+                    host.pressed.push(key);
+                }
+                host.input(InputEvent::Keyboard { event: HostKey { time, key, pressed } });
+            }
+            // Whatever is still held (the parent's own shortcut, say) would
+            // otherwise stay down for our clients.
+            wl_keyboard::Event::Leave { .. } => {
+                for key in std::mem::take(&mut host.pressed) {
+                    host.input(InputEvent::Keyboard { event: HostKey { time: 0, key, pressed: false } });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Host {
+    fn input(&mut self, event: InputEvent<NestedInput>) {
+        self.events.push(HostEvent::Input(event));
+    }
+
+    fn motion(&mut self, time: u32, x: f64, y: f64) {
+        let (w, h) = self.size;
+        let event = HostMotion { time, x, y, size: (w as f64, h as f64) };
+        self.input(InputEvent::PointerMotionAbsolute { event });
+    }
+}
+
+fn axis_index(axis: wl_pointer::Axis) -> usize {
+    match axis {
+        wl_pointer::Axis::HorizontalScroll => 1,
+        _ => 0,
+    }
+}
+
+macro_rules! ignore_events {
+    ($($proxy:ty),* $(,)?) => {$(
+        impl Dispatch<$proxy, ()> for Host {
+            fn event(_: &mut Self, _: &$proxy, _: <$proxy as wayland_client::Proxy>::Event,
+                _: &(), _: &Connection, _: &QueueHandle<Self>) {}
+        }
+    )*};
+}
+
+ignore_events!(
+    WlCompositor, WlSurface, WlShm, WlShmPool, ZwpLinuxBufferParamsV1,
+    ZxdgDecorationManagerV1, ZxdgToplevelDecorationV1, ZwpRelativePointerManagerV1, ZwpPointerConstraintsV1,
+);
+
+// ── Host input ────────────────────────────────────────────────────────────────
+
+/// Input from the parent's seat, as a smithay input backend so it takes the
+/// same path as libinput's.
+#[derive(Debug)]
+pub struct NestedInput;
+
+/// The parent's seat: one pointer and one keyboard.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NestedDevice;
+
+impl Device for NestedDevice {
+    fn id(&self) -> String { "wayland-nested".into() }
+    fn name(&self) -> String { "Wayland host seat".into() }
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        matches!(capability, DeviceCapability::Keyboard | DeviceCapability::Pointer)
+    }
+    fn usb_id(&self) -> Option<(u32, u32)> { None }
+    fn syspath(&self) -> Option<PathBuf> { None }
+}
+
+/// A key, by evdev code.
+pub struct HostKey {
+    time:    u32,
+    key:     u32,
+    pressed: bool,
+}
+
+/// Pointer position in our window.
+pub struct HostMotion {
+    time: u32,
+    x:    f64,
+    y:    f64,
+    /// Window size the position is relative to.
+    size: (f64, f64),
+}
+
+/// Pointer motion from the parent's relative pointer.
+pub struct HostRelative {
+    /// Microseconds, unlike the other events' milliseconds.
+    utime:   u64,
+    delta:   (f64, f64),
+    unaccel: (f64, f64),
+}
+
+pub struct HostButton {
+    time:    u32,
+    button:  u32,
+    pressed: bool,
+}
+
+/// One pointer frame's scroll; vertical first, then horizontal.
+pub struct HostAxis {
+    time:   u32,
+    source: AxisSource,
+    amount: [Option<f64>; 2],
+    v120:   [Option<f64>; 2],
+}
+
+impl Default for HostAxis {
+    fn default() -> Self {
+        Self { time: 0, source: AxisSource::Wheel, amount: [None; 2], v120: [None; 2] }
+    }
+}
+
+impl InputBackend for NestedInput {
+    type Device = NestedDevice;
+    type KeyboardKeyEvent = HostKey;
+    type PointerAxisEvent = HostAxis;
+    type PointerButtonEvent = HostButton;
+    type PointerMotionEvent = HostRelative;
+    type PointerMotionAbsoluteEvent = HostMotion;
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type SwitchToggleEvent = UnusedEvent;
+    type SpecialEvent = UnusedEvent;
+}
+
+macro_rules! host_event {
+    ($($event:ty),*) => {$(
+        impl Event<NestedInput> for $event {
+            fn time(&self) -> u64 { self.time as u64 * 1000 }
+            fn device(&self) -> NestedDevice { NestedDevice }
+        }
+    )*};
+}
+
+host_event!(HostKey, HostMotion, HostButton, HostAxis);
+
+impl Event<NestedInput> for HostRelative {
+    fn time(&self) -> u64 { self.utime }
+    fn device(&self) -> NestedDevice { NestedDevice }
+}
+
+impl PointerMotionEvent<NestedInput> for HostRelative {
+    fn delta_x(&self) -> f64 { self.delta.0 }
+    fn delta_y(&self) -> f64 { self.delta.1 }
+    fn delta_x_unaccel(&self) -> f64 { self.unaccel.0 }
+    fn delta_y_unaccel(&self) -> f64 { self.unaccel.1 }
+}
+
+impl KeyboardKeyEvent<NestedInput> for HostKey {
+    fn key_code(&self) -> Keycode { (self.key + 8).into() }
+    fn state(&self) -> KeyState {
+        if self.pressed { KeyState::Pressed } else { KeyState::Released }
+    }
+    fn count(&self) -> u32 { self.pressed as u32 }
+}
+
+impl AbsolutePositionEvent<NestedInput> for HostMotion {
+    fn x(&self) -> f64 { self.x / self.size.0.max(1.0) }
+    fn y(&self) -> f64 { self.y / self.size.1.max(1.0) }
+    fn x_transformed(&self, width: i32) -> f64 { self.x() * width as f64 }
+    fn y_transformed(&self, height: i32) -> f64 { self.y() * height as f64 }
+}
+
+impl PointerButtonEvent<NestedInput> for HostButton {
+    fn button_code(&self) -> u32 { self.button }
+    fn state(&self) -> ButtonState {
+        if self.pressed { ButtonState::Pressed } else { ButtonState::Released }
+    }
+}
+
+impl PointerAxisEvent<NestedInput> for HostAxis {
+    fn amount(&self, axis: Axis) -> Option<f64> { self.amount[slot(axis)] }
+    fn amount_v120(&self, axis: Axis) -> Option<f64> { self.v120[slot(axis)] }
+    fn source(&self) -> AxisSource { self.source }
+    fn relative_direction(&self, _: Axis) -> AxisRelativeDirection { AxisRelativeDirection::Identical }
+}
+
+fn slot(axis: Axis) -> usize {
+    match axis {
+        Axis::Horizontal => 1,
+        _ => 0,
+    }
+}
@@ -0,0 +1,126 @@
+//! Connections that send one request and get one reply, served from the
+//! event loop (the control socket, the metrics endpoint). A connection is
+//! read and written only when its socket is ready, so a slow or idle peer
+//! never stalls rendering or input; one that hasn't been answered and
+//! flushed within [`TIMEOUT`] is dropped.
+
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::fd::OwnedFd,
+    rc::Rc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use smithay::reexports::calloop::{
+    generic::Generic,
+    timer::{TimeoutAction, Timer},
+    Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+};
+use tracing::debug;
+
+use crate::state::GameframeState;
+
+/// How long a peer gets to send its request and read the reply.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request read; a peer sending more is answered with what came so
+/// far.
+const MAX_REQUEST: usize = 4096;
+
+/// Serve the connection `stream`, which must be non-blocking. The request
+/// is read until `complete` holds for what came so far (or the peer stops
+/// sending), then `reply` answers it and the connection is closed once the
+/// answer is written.
+pub fn serve(
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    stream:      impl Into<OwnedFd>,
+    complete:    impl Fn(&[u8]) -> bool + 'static,
+    reply:       impl FnOnce(&mut GameframeState, &[u8]) -> Vec<u8> + 'static,
+) -> Result<()> {
+    let file = File::from(stream.into());
+    // Whichever source currently serves the connection, for the timeout.
+    let current: Rc<Cell<Option<RegistrationToken>>> = Rc::new(Cell::new(None));
+
+    let handle  = loop_handle.clone();
+    let serving = current.clone();
+    let mut request = Vec::new();
+    let mut reply   = Some(reply);
+    let token = loop_handle
+        .insert_source(Generic::new(file, Interest::READ, Mode::Level), move |_, file, state| {
+            let mut chunk = [0u8; 1024];
+            let eof = match file.as_ref().read(&mut chunk) {
+                Ok(0) => true,
+                Ok(n) => { request.extend_from_slice(&chunk[..n]); false }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                Err(e) => {
+                    debug!("read request: {e}");
+                    serving.set(None);
+                    return Ok(PostAction::Remove);
+                }
+            };
+            if eof && request.is_empty() {
+                serving.set(None);
+                return Ok(PostAction::Remove);
+            }
+            if !eof && !complete(&request) && request.len() < MAX_REQUEST {
+                return Ok(PostAction::Continue);
+            }
+            let Some(reply) = reply.take() else { return Ok(PostAction::Remove) };
+            let answer = reply(state, &request);
+            serving.set(None);
+            if let Err(e) = send(&handle, file.as_ref(), answer, &serving) {
+                debug!("send reply: {e:#}");
+            }
+            Ok(PostAction::Remove)
+        })
+        .map_err(|e| anyhow!("connection source: {e}"))?;
+    current.set(Some(token));
+
+    let handle = loop_handle.clone();
+    let _ = loop_handle.insert_source(Timer::from_duration(TIMEOUT), move |_, _, _| {
+        if let Some(token) = current.take() {
+            debug!("connection timed out");
+            handle.remove(token);
+        }
+        TimeoutAction::Drop
+    });
+    Ok(())
+}
+
+/// Write `answer` to `file`: right away as far as the socket takes it, the
+/// rest as it drains. `current` is set to the source doing that.
+fn send(
+    loop_handle: &LoopHandle<'static, GameframeState>,
+    mut file:    &File,
+    answer:      Vec<u8>,
+    current:     &Rc<Cell<Option<RegistrationToken>>>,
+) -> Result<()> {
+    let mut written = match file.write(&answer) {
+        Ok(n) => n,
+        Err(e) if e.kind() == ErrorKind::WouldBlock => 0,
+        Err(e) => return Err(e.into()),
+    };
+    if written == answer.len() { return Ok(()); }
+
+    let writing = current.clone();
+    let token = loop_handle
+        .insert_source(Generic::new(file.try_clone()?, Interest::WRITE, Mode::Level), move |_, file, _| {
+            match file.as_ref().write(&answer[written..]) {
+                Ok(0) => {}
+                Ok(n) => {
+                    written += n;
+                    if written < answer.len() { return Ok(PostAction::Continue); }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(PostAction::Continue),
+                Err(e) => debug!("send reply: {e}"),
+            }
+            writing.set(None);
+            Ok(PostAction::Remove)
+        })
+        .map_err(|e| anyhow!("connection source: {e}"))?;
+    current.set(Some(token));
+    Ok(())
+}
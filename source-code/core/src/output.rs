@@ -1,24 +1,314 @@
-use std::collections::HashMap;
-use anyhow::Result;
-use drm::control::{connector, crtc, Device as ControlDevice, Mode};
+use std::{collections::HashMap, time::Duration};
+use anyhow::{bail, Context, Result};
+use drm::control::{connector, crtc, Device as ControlDevice, Mode, ModeTypeFlags};
 use smithay::{
     backend::{
-        allocator::gbm::GbmAllocator,
-        drm::{DrmDevice, DrmDeviceFd},
+        allocator::{
+            gbm::{GbmAllocator, GbmDevice},
+            Fourcc, Modifier,
+        },
+        drm::{
+            compositor::DrmCompositor, exporter::gbm::GbmFramebufferExporter, DrmDevice,
+            DrmDeviceFd, GbmBufferedSurface, VrrSupport,
+        },
         renderer::{damage::OutputDamageTracker, gles::GlesRenderer},
     },
+    desktop::utils::OutputPresentationFeedback,
     output::{Mode as WlMode, Output, PhysicalProperties, Scale, Subpixel},
-    reexports::wayland_server::DisplayHandle,
+    reexports::wayland_server::{backend::GlobalId, DisplayHandle},
     utils::Transform,
 };
-use tracing::info;
+use tracing::{info, warn};
+
+use crate::{
+    edid::{plausible_size, read_edid, EdidInfo},
+    frame::{AdaptiveSync, FrameStats, Lfc, PresentMode, RepaintState},
+    tearing::TearingSurface,
+};
+
+/// Formats we ask GBM for when allocating scanout buffers, in preference order.
+const SCANOUT_FORMATS: &[Fourcc] = &[Fourcc::Argb8888, Fourcc::Xrgb8888];
+
+pub type GbmSurface = GbmBufferedSurface<GbmAllocator<DrmDeviceFd>, ()>;
+
+pub type GbmDrmCompositor = DrmCompositor<
+    GbmAllocator<DrmDeviceFd>,
+    GbmFramebufferExporter<DrmDeviceFd>,
+    (),
+    DrmDeviceFd,
+>;
+
+/// How frames reach the CRTC.
+pub enum OutputSurface {
+    /// Atomic KMS: elements are assigned to primary, cursor and overlay planes
+    /// per frame, and whatever can't be scanned out directly is composited
+    /// with GL onto the primary plane.
+    Atomic(Box<GbmDrmCompositor>),
+    /// Legacy KMS (radeon, nouveau, …): everything is composited with GL into
+    /// a single scanout buffer using the output's damage tracker.
+    Composited(GbmSurface),
+}
+
+impl OutputSurface {
+    pub fn is_atomic(&self) -> bool { matches!(self, Self::Atomic(_)) }
+
+    /// Switch between fixed-cadence and immediate presentation. Immediate
+    /// presentation relies on VRR, so it's a no-op on legacy KMS. Fails if
+    /// the kernel rejects `VRR_ENABLED`.
+    pub fn set_present_mode(&mut self, mode: PresentMode) -> Result<()> {
+        match self {
+            Self::Atomic(c) => {
+                c.use_vrr(mode == PresentMode::Immediate)
+                    .map_err(|e| anyhow::anyhow!("VRR toggle rejected: {e:?}"))?;
+            }
+            Self::Composited(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Format of the scanout buffers.
+    pub fn format(&self) -> Fourcc {
+        match self {
+            Self::Atomic(c)     => c.format(),
+            Self::Composited(s) => s.format(),
+        }
+    }
+
+    /// Forget what the scanout buffers hold, so the next render redraws and
+    /// flips the whole frame even if nothing changed: how a frame is shown
+    /// twice.
+    pub fn repeat_frame(&mut self) {
+        match self {
+            Self::Atomic(c)     => c.reset_buffers(),
+            Self::Composited(s) => s.reset_buffers(),
+        }
+    }
+
+    /// Drop everything cached about the hardware's state and the scanout
+    /// buffers' contents, so the next commit is a full modeset of a fully
+    /// repainted frame.
+    pub fn reset(&mut self) -> Result<()> {
+        match self {
+            Self::Atomic(c) => {
+                c.reset_state().context("DrmCompositor::reset_state")?;
+                c.reset_buffers();
+            }
+            Self::Composited(s) => s.reset_buffers(),
+        }
+        Ok(())
+    }
+
+    /// Scan out in `mode` from the next commit on. Fails, leaving the
+    /// current mode in place, if the driver rejects it.
+    pub fn use_mode(&mut self, mode: Mode) -> Result<()> {
+        match self {
+            Self::Atomic(c)     => c.use_mode(mode).map_err(|e| anyhow::anyhow!("mode rejected: {e:?}"))?,
+            Self::Composited(s) => s.use_mode(mode).map_err(|e| anyhow::anyhow!("mode rejected: {e:?}"))?,
+        }
+        Ok(())
+    }
+
+    /// Switch the CRTC and its planes off (DPMS off). The next commit after
+    /// a [`OutputSurface::reset`] lights the output up again.
+    pub fn blank(&mut self) -> Result<()> {
+        match self {
+            Self::Atomic(c)     => c.clear().context("DrmCompositor::clear")?,
+            Self::Composited(s) => s.surface().clear().context("DrmSurface::clear")?,
+        }
+        Ok(())
+    }
+
+    /// Mark the queued frame as presented after its vblank.
+    pub fn frame_submitted(&mut self) -> Result<()> {
+        match self {
+            Self::Atomic(c)     => { c.frame_submitted().context("DrmCompositor::frame_submitted")?; }
+            Self::Composited(s) => { s.frame_submitted().context("GbmBufferedSurface::frame_submitted")?; }
+        }
+        Ok(())
+    }
+}
+
+/// `wl_output` transform for a rotation in degrees. Unknown values fall back
+/// to no rotation.
+pub fn rotation_transform(degrees: u32) -> Transform {
+    match degrees {
+        0   => Transform::Normal,
+        90  => Transform::_90,
+        180 => Transform::_180,
+        270 => Transform::_270,
+        d   => { warn!(rotation = d, "Unsupported rotation – using 0"); Transform::Normal }
+    }
+}
+
+/// Scales automatic scaling picks from, smallest first.
+const AUTO_SCALES: [f64; 7] = [1.0, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+/// Smallest logical width automatic scaling leaves an output with.
+const AUTO_MIN_LOGICAL_WIDTH: f64 = 1024.0;
+
+/// Scale that makes an output of `size_mm` and `pixels` show roughly 96
+/// logical DPI (about 120 on panels under 20", which sit closer to the eye),
+/// snapped to the nearest common scale. Unknown sizes get 1.
+pub fn auto_scale(size_mm: (i32, i32), pixels: (i32, i32)) -> f64 {
+    let ((mm_w, mm_h), (px_w, px_h)) = (size_mm, pixels);
+    if mm_w <= 0 || mm_h <= 0 || px_w <= 0 { return 1.0; }
+    let diagonal_in = ((mm_w * mm_w + mm_h * mm_h) as f64).sqrt() / 25.4;
+    let dpi         = px_w as f64 / (mm_w as f64 / 25.4);
+    let reference   = if diagonal_in < 20.0 { 120.0 } else { 96.0 };
+    let ideal       = dpi / reference;
+    AUTO_SCALES
+        .into_iter()
+        .filter(|&s| s == 1.0 || px_w as f64 / s >= AUTO_MIN_LOGICAL_WIDTH)
+        .min_by(|a, b| (a - ideal).abs().total_cmp(&(b - ideal).abs()))
+        .unwrap_or(1.0)
+}
+
+/// The `wl_output` mode for a DRM mode.
+pub fn wl_mode(mode: &Mode) -> WlMode {
+    WlMode {
+        size:    (mode.size().0 as i32, mode.size().1 as i32).into(),
+        refresh: mode.vrefresh() as i32 * 1000,
+    }
+}
+
+/// Kernel-style connector name, e.g. "DP-1" or "HDMI-A-2". Used as the
+/// `wl_output` name and as the key for per-output config.
+pub fn connector_name(info: &connector::Info) -> String {
+    format!("{}-{}", info.interface().as_str(), info.interface_id())
+}
+
+/// A DRM output on any of the backend's devices; CRTC handles are only
+/// unique per device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputId {
+    /// `dev_t` of the DRM device.
+    pub device: libc::dev_t,
+    pub crtc:   crtc::Handle,
+}
 
 pub struct GameframeOutput {
     pub output:         Output,
+    /// The `wl_output` global, withdrawn when the monitor is unplugged.
+    pub global:         GlobalId,
     pub crtc:           crtc::Handle,
     pub connector:      connector::Handle,
     pub mode:           Mode,
+    pub surface:        OutputSurface,
     pub damage_tracker: OutputDamageTracker,
+    pub repaint:        RepaintState,
+    pub adaptive:       AdaptiveSync,
+    /// `wp_presentation` feedback of the frame in flight, sent when its flip
+    /// completes.
+    pub pending_feedback: Option<OutputPresentationFeedback>,
+    /// Frames are flipped without waiting for vblank, through
+    /// `tearing_surface` instead of `surface`.
+    pub tearing:          bool,
+    /// Async flips; `None` where the driver can't do them.
+    pub tearing_surface:  Option<TearingSurface>,
+    /// Low framerate compensation while VRR is on; `None` without a usable
+    /// VRR range.
+    pub lfc:              Option<Lfc>,
+    /// The CRTC has `VRR_ENABLED` and the monitor says it can vary its
+    /// refresh; only then is VRR ever switched on.
+    pub vrr_capable:      bool,
+    /// VRR held on by [`GameframeOutput::set_vrr`]: every frame is
+    /// presented as soon as it is ready, not only late ones under
+    /// `adaptive_sync`.
+    pub vrr:              bool,
+}
+
+impl GameframeOutput {
+    /// Start over after the device was paused: a flip in flight then will
+    /// never complete, and the screen contents are gone.
+    pub fn reset(&mut self) {
+        if let Err(e) = self.surface.reset() {
+            warn!(crtc = ?self.crtc, "reset: {e:#}");
+        }
+        // The surface brings the output back up; tearing resumes after.
+        self.tearing = false;
+        if let Some(tearing) = self.tearing_surface.as_mut() {
+            tearing.reset();
+        }
+        self.damage_tracker = OutputDamageTracker::from_output(&self.output);
+        self.repaint.reset();
+        if let Some(mut feedback) = self.pending_feedback.take() {
+            feedback.discarded();
+        }
+    }
+
+    /// Switch to `mode` (taking effect with the next frame, which is drawn
+    /// in full at the new size). A mode the driver rejects is an error and
+    /// changes nothing.
+    pub fn set_mode(&mut self, mode: Mode) -> Result<()> {
+        // The surface does the modeset; tearing resumes after.
+        self.set_tearing(false);
+        self.surface.use_mode(mode)?;
+        self.mode = mode;
+        if let Some(tearing) = self.tearing_surface.as_mut() {
+            let (w, h) = mode.size();
+            tearing.resize((w as u32, h as u32));
+        }
+        let wl = wl_mode(&mode);
+        self.output.add_mode(wl);
+        self.output.change_current_state(Some(wl), None, None, None);
+        self.damage_tracker = OutputDamageTracker::from_output(&self.output);
+        self.repaint.set_refresh(wl.refresh);
+        info!(output = %self.output.name(), mode = ?mode.name(), "Mode set");
+        Ok(())
+    }
+
+    /// How frames are presented now: immediately while VRR is held on,
+    /// otherwise as adaptive sync last decided.
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vrr { PresentMode::Immediate } else { self.adaptive.mode() }
+    }
+
+    /// Start or stop flipping without waiting for vblank. Returns whether
+    /// frames tear now: never where the driver can't flip asynchronously.
+    pub fn set_tearing(&mut self, allowed: bool) -> bool {
+        let on = allowed && self.tearing_surface.is_some();
+        if on == self.tearing { return on; }
+        self.tearing = on;
+        match self.tearing_surface.as_mut() {
+            Some(tearing) if on => tearing.take_over(),
+            // The surface's idea of what is on screen is stale: commit all
+            // of its state and repaint in full.
+            _ => {
+                if let Err(e) = self.surface.reset() {
+                    warn!(crtc = ?self.crtc, "reset after tearing: {e:#}");
+                }
+            }
+        }
+        on
+    }
+
+    /// A page flip completed: mark its frame presented. Returns whether it
+    /// was an async flip.
+    pub fn frame_submitted(&mut self) -> Result<bool> {
+        if let Some(torn) = self.tearing_surface.as_mut().and_then(|t| t.frame_submitted()) {
+            return Ok(torn);
+        }
+        self.surface.frame_submitted()?;
+        Ok(false)
+    }
+
+    /// Hold VRR on (or let adaptive sync drive it again). If the kernel
+    /// rejects the property the output stays on vsync.
+    pub fn set_vrr(&mut self, on: bool) -> Result<()> {
+        if on && !self.vrr_capable {
+            bail!("{} can't do VRR", self.output.name());
+        }
+        if on == self.vrr { return Ok(()); }
+        self.vrr = on;
+        if let Err(e) = self.surface.set_present_mode(self.present_mode()) {
+            warn!(output = %self.output.name(), "{e:#}; staying on vsync");
+            self.vrr = false;
+            self.adaptive.reset();
+            let _ = self.surface.set_present_mode(PresentMode::Vsync);
+            return Err(e);
+        }
+        info!(output = %self.output.name(), on, "VRR");
+        Ok(())
+    }
 }
 
 pub struct OutputManager {
@@ -28,55 +318,166 @@ pub struct OutputManager {
 impl OutputManager {
     pub fn new() -> Self { Self { outputs: HashMap::new() } }
 
+    /// Light up `connector` on `crtc` as the output `name`. With
+    /// `cross_gpu`, `renderer` belongs to another GPU than `drm`: frames
+    /// reach this one's display engine in linear buffers, composited every
+    /// time, without VRR or async flips.
     #[allow(clippy::too_many_arguments)]
     pub fn add_output(
         &mut self,
         drm: &mut DrmDevice,
-        _allocator: GbmAllocator<DrmDeviceFd>,
-        _renderer:  &mut GlesRenderer,
+        gbm: &GbmDevice<DrmDeviceFd>,
+        allocator: GbmAllocator<DrmDeviceFd>,
+        renderer:  &mut GlesRenderer,
         display_handle: &DisplayHandle,
+        name:      String,
+        cross_gpu: bool,
         connector: connector::Handle,
         crtc:      crtc::Handle,
         mode:      Mode,
-        scale:     f64,
+        scale:     Option<f64>,
+        transform: Transform,
         vrr:       bool,
-    ) -> Result<()> {
+        vrr_range: Option<(u32, u32)>,
+    ) -> Result<Output> {
         let connector_info   = drm.get_connector(connector, true)?;
-        let (phys_w, phys_h) = connector_info.size().unwrap_or((0, 0));
         let (pix_w, pix_h)   = (mode.size().0 as i32, mode.size().1 as i32);
+        // Without an EDID, describe the output by its connector.
+        let edid = read_edid(drm, connector).unwrap_or_else(|| {
+            let (w, h) = connector_info.size().unwrap_or((0, 0));
+            EdidInfo {
+                make:   "Unknown".into(),
+                model:  format!("{} output", connector_info.interface().as_str()),
+                serial: String::new(),
+                size:   plausible_size((w as i32, h as i32)),
+                limits: None,
+            }
+        });
 
-        let wl_mode = WlMode {
-            size:    (pix_w, pix_h).into(),
-            refresh: mode.vrefresh() as i32 * 1000,
-        };
+        let wl_mode = wl_mode(&mode);
 
+        info!(output = %name, make = %edid.make, model = %edid.model, serial = %edid.serial, size_mm = ?edid.size, "Monitor");
+        let scale = scale.unwrap_or_else(|| {
+            let scale = auto_scale(edid.size, (pix_w, pix_h));
+            info!(output = %name, scale, "Automatic scale");
+            scale
+        });
         let output = Output::new(
-            format!("GAMEFRAME-{}", self.outputs.len()),
+            name,
             PhysicalProperties {
-                size:     (phys_w as i32, phys_h as i32).into(),
-                subpixel: Subpixel::Unknown,
-                make:     format!("{:?}", connector_info.interface()),
-                model:    "Gameframe Output".into(),
+                size:          edid.size.into(),
+                subpixel:      Subpixel::Unknown,
+                make:          edid.make,
+                model:         edid.model,
+                serial_number: edid.serial,
             },
         );
-        output.create_global::<crate::state::GameframeState>(display_handle);
+        let global = output.create_global::<crate::state::GameframeState>(display_handle);
+        // Everything the monitor lists, for clients and mode switching; the
+        // one it flags preferred is advertised as such even if another was
+        // picked.
+        for listed in connector_info.modes() {
+            output.add_mode(self::wl_mode(listed));
+        }
         output.add_mode(wl_mode);
-        output.set_preferred(wl_mode);
-        output.change_current_state(Some(wl_mode), Some(Transform::Normal), None, Some((0, 0).into()));
-        output.change_current_state(None, None, Some(Scale::Fractional(scale)), None);
+        let preferred = connector_info.modes().iter().find(|m| m.mode_type().contains(ModeTypeFlags::PREFERRED));
+        output.set_preferred(preferred.map_or(wl_mode, self::wl_mode));
+        // Scale and transform must be set before the damage tracker and
+        // DrmCompositor are created, both derive their geometry from them.
+        output.change_current_state(
+            Some(wl_mode),
+            Some(transform),
+            Some(Scale::Fractional(scale)),
+            Some((0, 0).into()),
+        );
+
+        let drm_surface = drm
+            .create_surface(crtc, mode, &[connector])
+            .with_context(|| format!("create_surface for {crtc:?}"))?;
+        let mut render_formats = renderer.egl_context().dmabuf_render_formats().clone();
+        if cross_gpu {
+            // Tiling layouts are private to each GPU.
+            render_formats = render_formats.iter().filter(|f| f.modifier == Modifier::Linear).copied().collect();
+        }
+        let atomic = drm.is_atomic() && !cross_gpu;
+        // Immediate presentation goes through DrmCompositor only.
+        let vrr_capable = atomic && matches!(
+            drm_surface.vrr_supported(connector),
+            Ok(VrrSupport::Supported | VrrSupport::RequiresModeset),
+        );
+
+        let tearing_planes = atomic.then(|| {
+            let planes = drm_surface.planes();
+            let others = planes.cursor.iter().chain(&planes.overlay).map(|p| p.handle).collect();
+            (drm_surface.plane(), others)
+        });
+        let tearing_allocator = allocator.clone();
+        let surface = if atomic {
+            let planes = drm_surface.planes();
+            info!(
+                ?crtc,
+                cursor_planes  = planes.cursor.len(),
+                overlay_planes = planes.overlay.len(),
+                "Atomic KMS – direct plane scanout enabled"
+            );
+            let compositor = DrmCompositor::new(
+                &output,
+                drm_surface,
+                None,
+                allocator,
+                GbmFramebufferExporter::new(gbm.clone(), None.into()),
+                SCANOUT_FORMATS.iter().copied(),
+                render_formats,
+                drm.cursor_size(),
+                Some(gbm.clone()),
+            )
+            .with_context(|| format!("DrmCompositor for {crtc:?}"))?;
+            OutputSurface::Atomic(Box::new(compositor))
+        } else {
+            match cross_gpu {
+                true  => info!(?crtc, "Rendered on another GPU – compositing every frame into linear buffers"),
+                false => warn!(?crtc, "Legacy KMS – no plane assignment, compositing every frame with GL"),
+            }
+            let surface = GbmBufferedSurface::new(drm_surface, allocator, SCANOUT_FORMATS, render_formats)
+                .with_context(|| format!("GbmBufferedSurface for {crtc:?}"))?;
+            OutputSurface::Composited(surface)
+        };
+
+        let tearing_surface = match cross_gpu {
+            true  => None,
+            false => TearingSurface::new(
+                drm.device_fd().clone(),
+                crtc,
+                tearing_allocator,
+                (pix_w as u32, pix_h as u32),
+                surface.format(),
+                tearing_planes,
+            )
+            .unwrap_or_else(|e| {
+                warn!(?crtc, "Async page flips unavailable: {e:#}");
+                None
+            }),
+        };
 
         let damage_tracker = OutputDamageTracker::from_output(&output);
+        let repaint        = RepaintState::new(wl_mode.refresh);
+        let lfc            = vrr_range.filter(|_| vrr).and_then(Lfc::new);
 
         info!(
             ?connector, ?crtc,
             mode   = ?mode.name(),
             pixels = ?(pix_w, pix_h),
-            scale, vrr,
+            scale, ?transform, vrr, vrr_capable, ?vrr_range, lfc = lfc.is_some(),
+            async_flips = tearing_surface.is_some(),
             "Output configured"
         );
 
-        self.outputs.insert(crtc, GameframeOutput { output, crtc, connector, mode, damage_tracker });
-        Ok(())
+        self.outputs.insert(crtc, GameframeOutput {
+            output: output.clone(), global, crtc, connector, mode, surface, damage_tracker, repaint,
+            adaptive: AdaptiveSync::new(), pending_feedback: None, tearing: false, tearing_surface, lfc,
+            vrr_capable, vrr: false,
+        });
+        Ok(output)
     }
 
     pub fn output_count(&self) -> usize { self.outputs.len() }
@@ -89,8 +490,47 @@ impl OutputManager {
         self.outputs.values_mut()
     }
 
+    pub fn get(&self, crtc: crtc::Handle) -> Option<&GameframeOutput> {
+        self.outputs.get(&crtc)
+    }
+
+    pub fn get_mut(&mut self, crtc: crtc::Handle) -> Option<&mut GameframeOutput> {
+        self.outputs.get_mut(&crtc)
+    }
+
+    /// CRTC driving the given Wayland output, if it belongs to this device.
+    pub fn crtc_for(&self, output: &Output) -> Option<crtc::Handle> {
+        self.outputs.values().find(|o| &o.output == output).map(|o| o.crtc)
+    }
+
+    /// CRTC driving `connector`, if it has an output.
+    pub fn crtc_for_connector(&self, connector: connector::Handle) -> Option<crtc::Handle> {
+        self.outputs.values().find(|o| o.connector == connector).map(|o| o.crtc)
+    }
+
+    pub fn crtcs(&self) -> Vec<crtc::Handle> {
+        self.outputs.keys().copied().collect()
+    }
+
+    /// Forget the output on `crtc` (its connector was unplugged). Lookups by
+    /// that handle return `None` from then on.
+    pub fn remove(&mut self, crtc: crtc::Handle) -> Option<GameframeOutput> {
+        self.outputs.remove(&crtc)
+    }
+
+    /// The output on the lowest-numbered CRTC, so the choice does not depend
+    /// on map iteration order.
     pub fn primary_output(&self) -> Option<&Output> {
-        self.outputs.values().next().map(|o| &o.output)
+        self.outputs.values().min_by_key(|o| u32::from(o.crtc)).map(|o| &o.output)
+    }
+
+    /// Presentation counters and measured refresh of every output, by name.
+    pub fn frame_stats(&self) -> Vec<(String, FrameStats, Duration)> {
+        let mut stats: Vec<_> = self.outputs.values()
+            .map(|o| (o.output.name(), o.repaint.stats(), o.repaint.refresh_interval()))
+            .collect();
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        stats
     }
 }
 
@@ -0,0 +1,254 @@
+use anyhow::{bail, Context, Result};
+use smithay::{
+    backend::input::{
+        Device, Event, InputBackend, InputEvent, KeyboardKeyEvent, PointerAxisEvent,
+        PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    },
+    desktop::{Window, WindowSurfaceType},
+    input::{
+        keyboard::{FilterResult, XkbConfig},
+        pointer::{ButtonEvent, CursorImageStatus, MotionEvent},
+        Seat, SeatState,
+    },
+    utils::{Logical, Point, Rectangle, SERIAL_COUNTER},
+    wayland::seat::WaylandFocus,
+};
+use tracing::{info, warn};
+
+use crate::{input_handler::axis_frame, state::GameframeState};
+
+/// What a device is bound to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindTarget {
+    /// A split-screen pane (1 or 2), whatever window is in it.
+    Pane(usize),
+    Window(Window),
+}
+
+/// A seat of its own for the devices bound to one window, so each player
+/// keeps keyboard focus and a pointer on their own game while the main seat
+/// goes on following the pointer.
+struct PlayerSeat {
+    seat:    Seat<GameframeState>,
+    window:  Window,
+    pointer: Point<f64, Logical>,
+    /// Cursor the window last set for this seat's pointer.
+    cursor:  CursorImageStatus,
+}
+
+/// Input devices bound to windows (split-screen co-op). Events from a bound
+/// device go straight to its window through a separate seat, regardless of
+/// pointer and keyboard focus; compositor keybindings don't apply to them.
+/// Devices match by case-insensitive name substring. Bindings made over the
+/// control socket win over `[[input.bind]]`.
+#[derive(Default)]
+pub struct Players {
+    bindings: Vec<(String, BindTarget)>,
+    seats:    Vec<PlayerSeat>,
+}
+
+impl Players {
+    pub fn bind(&mut self, device: &str, target: BindTarget) {
+        let device = device.to_lowercase();
+        self.bindings.retain(|(d, _)| *d != device);
+        info!(device, ?target, "Input device bound");
+        self.bindings.push((device, target));
+    }
+
+    /// Returns whether `device` had a binding.
+    pub fn unbind(&mut self, device: &str) -> bool {
+        let device = device.to_lowercase();
+        let before = self.bindings.len();
+        self.bindings.retain(|(d, _)| *d != device);
+        before != self.bindings.len()
+    }
+
+    /// Drop the seat and bindings of a window that is gone.
+    pub fn window_closed(&mut self, seat_state: &mut SeatState<GameframeState>, window: &Window) {
+        self.seats.retain(|s| {
+            let keep = &s.window != window;
+            if !keep { seat_state.remove_seat(&s.seat); }
+            keep
+        });
+        self.bindings.retain(|(_, t)| t != &BindTarget::Window(window.clone()));
+    }
+
+    /// Each player seat with the window it drives and its pointer position.
+    pub fn seats(&self) -> impl Iterator<Item = (&Seat<GameframeState>, &Window, Point<f64, Logical>)> {
+        self.seats.iter().map(|s| (&s.seat, &s.window, s.pointer))
+    }
+
+    /// Each player's cursor and where it is.
+    pub fn cursors(&self) -> impl Iterator<Item = (&CursorImageStatus, Point<f64, Logical>)> {
+        self.seats.iter().map(|s| (&s.cursor, s.pointer))
+    }
+
+    /// A client set the cursor for `seat`'s pointer.
+    pub fn set_cursor(&mut self, seat: &Seat<GameframeState>, cursor: CursorImageStatus) {
+        if let Some(player) = self.seats.iter_mut().find(|s| &s.seat == seat) {
+            player.cursor = cursor;
+        }
+    }
+}
+
+/// The window `device` is bound to right now, if any: runtime bindings
+/// first, then `[[input.bind]]`. Pane bindings only hold in split-screen.
+fn bound_window(state: &GameframeState, device: &str) -> Option<Window> {
+    let name = device.to_lowercase();
+    let config = state.config.input.bind.iter().map(|b| (b.device.to_lowercase(), BindTarget::Pane(b.pane)));
+    let target = state.players.bindings
+        .iter()
+        .cloned()
+        .chain(config)
+        .find(|(d, _)| name.contains(d.as_str()))?
+        .1;
+    match target {
+        BindTarget::Pane(n) => state.split.as_ref()?.pane(n.checked_sub(1)?),
+        BindTarget::Window(w) => state.space.elements().any(|e| e == &w).then_some(w),
+    }
+}
+
+/// Deliver `event` through its device's player seat if the device is
+/// bound. Returns the event back if it isn't.
+pub fn route<B: InputBackend>(state: &mut GameframeState, event: InputEvent<B>) -> Option<InputEvent<B>> {
+    let device = match &event {
+        InputEvent::Keyboard { event }              => event.device().name(),
+        InputEvent::PointerMotion { event }         => event.device().name(),
+        InputEvent::PointerMotionAbsolute { event } => event.device().name(),
+        InputEvent::PointerButton { event }         => event.device().name(),
+        InputEvent::PointerAxis { event }           => event.device().name(),
+        _ => return Some(event),
+    };
+    if state.players.bindings.is_empty() && state.config.input.bind.is_empty() {
+        return Some(event);
+    }
+    let Some(window) = bound_window(state, &device) else { return Some(event) };
+    let Some(idx) = player_seat(state, &window) else { return Some(event) };
+    let Some(rect) = state.space.element_geometry(&window) else { return None };
+
+    match event {
+        InputEvent::Keyboard { event } => {
+            let Some(keyboard) = state.players.seats[idx].seat.get_keyboard() else { return None };
+            let serial = SERIAL_COUNTER.next_serial();
+            let surface = window.wl_surface().map(|s| s.into_owned());
+            if keyboard.current_focus() != surface {
+                keyboard.set_focus(state, surface, serial);
+            }
+            keyboard.input::<(), _>(state, event.key_code(), event.state(), serial, event.time_msec(), |_, _, _| {
+                FilterResult::Forward
+            });
+        }
+        InputEvent::PointerMotion { event } => {
+            let delta: Point<f64, Logical> = (event.delta_x(), event.delta_y()).into();
+            let to = state.players.seats[idx].pointer + delta;
+            move_player_pointer(state, idx, &window, rect, to, event.time_msec());
+        }
+        InputEvent::PointerMotionAbsolute { event } => {
+            // Absolute devices span the bound window.
+            let to = event.position_transformed(rect.size) + rect.loc.to_f64();
+            move_player_pointer(state, idx, &window, rect, to, event.time_msec());
+        }
+        InputEvent::PointerButton { event } => {
+            let Some(pointer) = state.players.seats[idx].seat.get_pointer() else { return None };
+            pointer.button(state, &ButtonEvent {
+                serial: SERIAL_COUNTER.next_serial(),
+                time:   event.time_msec(),
+                button: event.button_code(),
+                state:  event.state(),
+            });
+            pointer.frame(state);
+        }
+        InputEvent::PointerAxis { event } => {
+            let Some(pointer) = state.players.seats[idx].seat.get_pointer() else { return None };
+            pointer.axis(state, axis_frame(&event));
+            pointer.frame(state);
+        }
+        _ => {}
+    }
+    None
+}
+
+/// Move a player's pointer to `to`, kept inside their window.
+fn move_player_pointer(
+    state:  &mut GameframeState,
+    idx:    usize,
+    window: &Window,
+    rect:   Rectangle<i32, Logical>,
+    to:     Point<f64, Logical>,
+    time:   u32,
+) {
+    let (x, y) = (rect.loc.x as f64, rect.loc.y as f64);
+    let to = Point::from((
+        to.x.clamp(x, x + rect.size.w as f64 - 1.0),
+        to.y.clamp(y, y + rect.size.h as f64 - 1.0),
+    ));
+    let player = &mut state.players.seats[idx];
+    player.pointer = to;
+    let Some(pointer) = player.seat.get_pointer() else { return };
+    let Some(loc) = state.space.element_location(window) else { return };
+    let focus = window
+        .surface_under(to - loc.to_f64(), WindowSurfaceType::ALL)
+        .map(|(surface, offset)| (surface, (loc + offset).to_f64()));
+    pointer.motion(state, focus, &MotionEvent { location: to, serial: SERIAL_COUNTER.next_serial(), time });
+    pointer.frame(state);
+    state.queue_redraw_all();
+}
+
+/// Index of the seat for `window`, created on first use.
+fn player_seat(state: &mut GameframeState, window: &Window) -> Option<usize> {
+    if let Some(idx) = state.players.seats.iter().position(|s| &s.window == window) {
+        return Some(idx);
+    }
+    let name = format!("gameframe-player{}", state.players.seats.len() + 2);
+    let mut seat = state.seat_state.new_wl_seat(&state.display_handle, &name);
+    let input = &state.config.input;
+    let keymap = input.keymap_file.as_ref().and_then(|p| std::fs::read_to_string(p).ok());
+    let keyboard = match seat.add_keyboard(XkbConfig::default(), input.repeat_delay as i32, input.repeat_rate as i32) {
+        Ok(k)  => k,
+        Err(e) => { warn!(seat = name, "player keyboard: {e:?}"); return None; }
+    };
+    // Same layout as the main seat.
+    if let Some(keymap) = keymap {
+        if let Err(e) = keyboard.set_keymap_from_string(state, keymap) {
+            warn!(seat = name, "player keymap: {e:?}");
+        }
+    }
+    seat.add_pointer();
+    let pointer = state.space.element_geometry(window)
+        .map_or_else(Default::default, |r| (r.loc + Point::from((r.size.w / 2, r.size.h / 2))).to_f64());
+    info!(seat = name, "Player seat created");
+    let cursor = CursorImageStatus::default_named();
+    state.players.seats.push(PlayerSeat { seat, window: window.clone(), pointer, cursor });
+    Some(state.players.seats.len() - 1)
+}
+
+// ── Control ───────────────────────────────────────────────────────────────────
+
+/// `bind DEVICE… pane N` or `bind DEVICE… focused`; device names may
+/// contain spaces.
+pub fn bind_command(state: &mut GameframeState, args: &[&str]) -> Result<()> {
+    let (device, target) = match args {
+        [device @ .., "pane", n] => match n.parse::<usize>().context("pane")? {
+            n @ (1 | 2) => (device, BindTarget::Pane(n)),
+            _ => bail!("pane is 1 or 2"),
+        },
+        [device @ .., "focused"] => (device, BindTarget::Window(state.focused_window().context("no focused window")?)),
+        _ => bail!("expected DEVICE pane N or DEVICE focused"),
+    };
+    if device.is_empty() { bail!("no device name"); }
+    state.players.bind(&device.join(" "), target);
+    Ok(())
+}
+
+/// One line per binding, runtime ones first.
+pub fn list_bindings(state: &GameframeState) -> String {
+    let runtime = state.players.bindings.iter().map(|(device, target)| match target {
+        BindTarget::Pane(n)   => format!("{device} pane {n}\n"),
+        BindTarget::Window(w) => {
+            let (app_id, _) = crate::rules::window_identity(w);
+            format!("{device} window {}\n", app_id.unwrap_or_else(|| "?".into()))
+        }
+    });
+    let config = state.config.input.bind.iter().map(|b| format!("{} pane {} (config)\n", b.device.to_lowercase(), b.pane));
+    runtime.chain(config).collect()
+}
@@ -0,0 +1,97 @@
+use anyhow::{bail, Result};
+
+use crate::{
+    config::{AdaptiveSyncMode, Config, TearingMode},
+    state::GameframeState,
+};
+
+/// Named bundles of the latency / smoothness settings, switched at runtime
+/// with Super+Shift+P or `gameframe preset`. Each preset sets exactly these
+/// fields and nothing else:
+///
+/// | setting                  | latency  | balanced        | quality |
+/// |--------------------------|----------|-----------------|---------|
+/// | `display.tearing`        | always   | fullscreen-only | never   |
+/// | `display.adaptive_sync`  | always   | games           | off     |
+/// | `animations.enabled`     | false    | true            | true    |
+/// | `effects.blur`           | false    | false           | true    |
+/// | `effects.shadow`         | false    | false           | true    |
+/// | `effects.supersample`    | 1        | (kept)          | (kept)  |
+///
+/// Buffering depth and frame-callback pacing are fixed by the backend and
+/// not part of any preset. A config reload brings back the file's
+/// `[animations]` and `[effects]`; `[display]` keeps the preset's values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Latency,
+    Balanced,
+    Quality,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [Preset::Latency, Preset::Balanced, Preset::Quality];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Latency  => "latency",
+            Preset::Balanced => "balanced",
+            Preset::Quality  => "quality",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|p| p.name() == name) {
+            Some(preset) => Ok(preset),
+            None         => bail!("unknown preset {name:?} (latency, balanced, quality)"),
+        }
+    }
+
+    /// The one after `self`, wrapping around.
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|p| *p == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    fn apply_to(self, config: &mut Config) {
+        let (display, effects, animations) = (&mut config.display, &mut config.effects, &mut config.animations);
+        match self {
+            Preset::Latency => {
+                display.tearing       = TearingMode::Always;
+                display.adaptive_sync = AdaptiveSyncMode::Always;
+                animations.enabled    = false;
+                effects.blur          = false;
+                effects.shadow        = false;
+                effects.supersample   = 1.0;
+            }
+            Preset::Balanced => {
+                display.tearing       = TearingMode::FullscreenOnly;
+                display.adaptive_sync = AdaptiveSyncMode::Games;
+                animations.enabled    = true;
+                effects.blur          = false;
+                effects.shadow        = false;
+            }
+            Preset::Quality => {
+                display.tearing       = TearingMode::Never;
+                display.adaptive_sync = AdaptiveSyncMode::Off;
+                animations.enabled    = true;
+                effects.blur          = true;
+                effects.shadow        = true;
+            }
+        }
+    }
+}
+
+/// Switch to `preset` and repaint with it.
+pub fn apply(state: &mut GameframeState, preset: Preset) {
+    preset.apply_to(&mut state.config);
+    state.preset = Some(preset);
+    tracing::info!(preset = preset.name(), "Preset applied");
+    state.overlay.push_toast(format!("Preset: {}", preset.name()), 90);
+    state.queue_redraw_all();
+}
+
+/// Switch to the preset after the current one (latency if none is set).
+pub fn cycle(state: &mut GameframeState) {
+    let next = state.preset.map_or(Preset::Latency, Preset::next);
+    apply(state, next);
+}
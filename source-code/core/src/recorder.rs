@@ -0,0 +1,256 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+    thread::JoinHandle,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            gles::{GlesMapping, GlesRenderer, GlesTexture},
+            Bind, ExportMem, Offscreen,
+        },
+    },
+    output::Output,
+    utils::{Buffer as BufferCoord, Physical, Rectangle, Size, Transform},
+};
+use tracing::{debug, info, info_span, warn};
+
+use crate::{
+    config::RecorderConfig,
+    render::{FrameElements, CLEAR_COLOR},
+    state::GameframeState,
+};
+
+/// Frames waiting for the encoder before new ones are dropped, so a slow
+/// encoder costs recorded frames rather than compositor frames.
+const QUEUE_DEPTH: usize = 4;
+
+struct Frame {
+    /// Since the recording started.
+    time:   Duration,
+    pixels: Vec<u8>,
+}
+
+/// An output being recorded. Each composited frame is drawn once more into
+/// an offscreen texture and read back asynchronously; the pixels are mapped
+/// one frame later (so the GPU copy never stalls a repaint) and handed to
+/// an `ffmpeg` process fed from its own thread.
+pub struct Recorder {
+    output:         Output,
+    path:           PathBuf,
+    size:           Size<i32, Physical>,
+    texture:        GlesTexture,
+    damage_tracker: OutputDamageTracker,
+    fresh:          bool,
+    /// Readback started for the previous frame, and when it was rendered.
+    pending:        Option<(GlesMapping, Duration)>,
+    started:        Duration,
+    frames:         Option<SyncSender<Frame>>,
+    encoder:        Option<JoinHandle<Result<()>>>,
+    dropped:        u64,
+}
+
+impl Recorder {
+    /// Start recording `output` into a new file in the configured directory.
+    pub fn start(renderer: &mut GlesRenderer, config: &RecorderConfig, output: &Output, now: Duration) -> Result<Self> {
+        let mode  = output.current_mode().context("output has no mode")?;
+        let size  = output.current_transform().transform_size(mode.size);
+        let scale = output.current_scale().fractional_scale();
+        let fps   = if config.fps > 0 { config.fps } else { (mode.refresh as u32 + 500) / 1000 }.max(1);
+
+        let dir = config.directory.clone().unwrap_or_else(default_directory);
+        std::fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path  = dir.join(format!("gameframe-{}-{stamp}.{}", output.name(), config.container));
+
+        let texture: GlesTexture = renderer
+            .create_buffer(Fourcc::Abgr8888, size.to_logical(1).to_buffer(1, Transform::Normal))
+            .with_context(|| format!("{}x{} capture buffer", size.w, size.h))?;
+
+        let child = spawn_ffmpeg(config, size, fps, &path)?;
+        let (tx, rx) = sync_channel(QUEUE_DEPTH);
+        let frame_len = size.w as usize * size.h as usize * 4;
+        let encoder = std::thread::Builder::new()
+            .name("gameframe-recorder".into())
+            .spawn(move || encode(child, rx, fps, frame_len))
+            .context("spawn encoder thread")?;
+
+        info!(output = output.name(), path = %path.display(), codec = %config.codec, fps, "Recording started");
+        Ok(Self {
+            output: output.clone(),
+            path,
+            size,
+            texture,
+            damage_tracker: OutputDamageTracker::new(size, scale, Transform::Normal),
+            fresh: true,
+            pending: None,
+            started: now,
+            frames: Some(tx),
+            encoder: Some(encoder),
+            dropped: 0,
+        })
+    }
+
+    pub fn output(&self) -> &Output { &self.output }
+
+    /// Record the frame just composited for the output.
+    pub fn capture(&mut self, renderer: &mut GlesRenderer, elements: &[FrameElements], now: Duration) {
+        let _span = info_span!("recorder_capture", output = self.output.name()).entered();
+        self.flush(renderer);
+        if let Err(e) = self.render(renderer, elements, now) {
+            debug!(output = self.output.name(), "recorder capture: {e:#}");
+        }
+    }
+
+    /// Finish the file: hand over the last frame and wait for the encoder.
+    /// Returns the file written.
+    pub fn stop(mut self, renderer: &mut GlesRenderer) -> Result<PathBuf> {
+        self.flush(renderer);
+        self.frames = None;
+        let encoder = self.encoder.take().context("encoder already stopped")?;
+        encoder.join().map_err(|_| anyhow!("encoder thread panicked"))??;
+        info!(path = %self.path.display(), dropped = self.dropped, "Recording saved");
+        Ok(self.path.clone())
+    }
+
+    fn render(&mut self, renderer: &mut GlesRenderer, elements: &[FrameElements], now: Duration) -> Result<()> {
+        let age = if self.fresh { 0 } else { 1 };
+        let mut fb = renderer.bind(&mut self.texture).context("bind capture buffer")?;
+        self.damage_tracker
+            .render_output(renderer, &mut fb, age, elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("render_output: {e:?}"))?;
+        self.fresh = false;
+        let region = Rectangle::<i32, BufferCoord>::from_size((self.size.w, self.size.h).into());
+        let mapping = renderer.copy_framebuffer(&fb, region, Fourcc::Abgr8888).context("copy_framebuffer")?;
+        self.pending = Some((mapping, now.saturating_sub(self.started)));
+        Ok(())
+    }
+
+    /// Map the previous frame's readback, which has finished by now, and
+    /// queue it for the encoder.
+    fn flush(&mut self, renderer: &mut GlesRenderer) {
+        let Some((mapping, time)) = self.pending.take() else { return };
+        let Some(frames) = &self.frames else { return };
+        let pixels = match renderer.map_texture(&mapping) {
+            Ok(pixels) => pixels.to_vec(),
+            Err(e) => { debug!("recorder map: {e:?}"); return; }
+        };
+        match frames.try_send(Frame { time, pixels }) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                debug!(dropped = self.dropped, "encoder behind, frame dropped");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("encoder exited, recording stopped");
+                self.frames = None;
+            }
+        }
+    }
+}
+
+/// `$XDG_VIDEOS_DIR`, else `~/Videos`.
+fn default_directory() -> PathBuf {
+    if let Ok(dir) = std::env::var("XDG_VIDEOS_DIR") {
+        return PathBuf::from(dir);
+    }
+    std::env::var("HOME").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from("/tmp")).join("Videos")
+}
+
+// ── Encoding ──────────────────────────────────────────────────────────────────
+
+fn spawn_ffmpeg(config: &RecorderConfig, size: Size<i32, Physical>, fps: u32, path: &Path) -> Result<Child> {
+    let mut cmd = Command::new("ffmpeg");
+    cmd.args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(["-f", "rawvideo", "-pixel_format", "rgba"])
+        .args(["-video_size", &format!("{}x{}", size.w, size.h)])
+        .args(["-framerate", &fps.to_string(), "-i", "-"]);
+    // VAAPI encoders take surfaces on the GPU; NVENC and software encoders
+    // read system memory directly.
+    if config.codec.ends_with("_vaapi") {
+        cmd.args(["-vaapi_device", &config.vaapi_device, "-vf", "format=nv12,hwupload"]);
+    }
+    cmd.args(["-c:v", &config.codec, "-b:v", &config.bitrate]).arg(path);
+    cmd.stdin(Stdio::piped()).stdout(Stdio::null());
+    cmd.spawn().context("spawn ffmpeg (is it installed?)")
+}
+
+/// Feed frames to ffmpeg at a constant rate. The compositor only renders
+/// when something changed, so each frame is repeated until the next one
+/// arrives; frames landing in the same slot replace each other.
+fn encode(mut child: Child, frames: Receiver<Frame>, fps: u32, frame_len: usize) -> Result<()> {
+    let mut stdin   = child.stdin.take().context("ffmpeg stdin")?;
+    let interval    = Duration::from_secs_f64(1.0 / fps as f64);
+    let mut written = 0u64;
+    let mut last: Option<Vec<u8>> = None;
+    let result = (|| -> Result<()> {
+        for frame in frames {
+            if frame.pixels.len() != frame_len {
+                bail!("frame is {} bytes, expected {frame_len}", frame.pixels.len());
+            }
+            let slot = (frame.time.as_secs_f64() / interval.as_secs_f64()) as u64;
+            if let Some(prev) = &last {
+                while written < slot {
+                    stdin.write_all(prev).context("write to ffmpeg")?;
+                    written += 1;
+                }
+            }
+            last = Some(frame.pixels);
+        }
+        if let Some(prev) = &last {
+            stdin.write_all(prev).context("write to ffmpeg")?;
+        }
+        Ok(())
+    })();
+    drop(stdin);
+    let status = child.wait().context("wait for ffmpeg")?;
+    result?;
+    if !status.success() {
+        bail!("ffmpeg exited with {status}");
+    }
+    Ok(())
+}
+
+// ── Control ───────────────────────────────────────────────────────────────────
+
+/// Start recording `output` (by name; the focused output if `None`), or
+/// stop the running recording. Returns a line describing what happened.
+pub fn toggle_recording(state: &mut GameframeState, output: Option<&str>) -> Result<String> {
+    if state.recorder.is_some() {
+        return stop_recording(state).map(|path| format!("saved {}", path.display()));
+    }
+    start_recording(state, output)
+}
+
+pub fn start_recording(state: &mut GameframeState, output: Option<&str>) -> Result<String> {
+    if state.recorder.is_some() { bail!("already recording"); }
+    let output = match output {
+        Some(name) => state.space.outputs().find(|o| o.name() == name).cloned()
+            .with_context(|| format!("no output {name}"))?,
+        None => state.focused_output.clone().or_else(|| state.placement_output()).context("no output")?,
+    };
+    let now = Duration::from(state.clock.now());
+    let Some(backend) = state.backend.as_mut() else { bail!("no backend") };
+    let recorder = Recorder::start(backend.renderer(), &state.config.recorder, &output, now)?;
+    let reply = format!("recording {} to {}", output.name(), recorder.path.display());
+    state.recorder = Some(recorder);
+    state.overlay.push_toast(format!("Recording {}", output.name()), 90);
+    state.queue_redraw(&output);
+    Ok(reply)
+}
+
+/// Stop the running recording, if any, and return the file it wrote.
+pub fn stop_recording(state: &mut GameframeState) -> Result<PathBuf> {
+    let recorder = state.recorder.take().context("not recording")?;
+    let Some(backend) = state.backend.as_mut() else { bail!("no backend") };
+    let path = recorder.stop(backend.renderer())?;
+    state.overlay.push_toast("Recording saved", 90);
+    Ok(path)
+}
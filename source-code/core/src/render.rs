@@ -1,62 +1,890 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
 use smithay::{
-    backend::renderer::{
-        damage::OutputDamageTracker,
-        element::surface::WaylandSurfaceRenderElement,
-        gles::GlesRenderer,
+    backend::{
+        drm::{compositor::FrameFlags, DrmEventMetadata, DrmEventTime},
+        renderer::{
+            element::{
+                default_primary_scanout_output_compare,
+                solid::{SolidColorBuffer, SolidColorRenderElement},
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                texture::TextureRenderElement,
+                utils::RescaleRenderElement,
+                AsRenderElements, Element, Id, Kind, RenderElementStates,
+            },
+            gles::{element::PixelShaderElement, GlesRenderer, GlesTexture},
+            Bind,
+        },
+    },
+    desktop::{
+        utils::{
+            send_frames_surface_tree, surface_presentation_feedback_flags_from_states,
+            surface_primary_scanout_output, update_surface_primary_scanout_output, OutputPresentationFeedback,
+        },
+        layer_map_for_output, Space, Window,
     },
-    desktop::{Space, Window},
     output::Output,
+    reexports::{
+        calloop::timer::{TimeoutAction, Timer},
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    render_elements,
+    utils::{Logical, Monotonic, Physical, Point, Rectangle, Scale, Size, Time},
+    wayland::{
+        compositor::{SurfaceAttributes, SurfaceData},
+        presentation::Refresh,
+        shell::wlr_layer::Layer,
+    },
+};
+use tracing::{debug, field::Empty, info_span, trace, warn};
+
+use crate::{
+    animation::{window_effect, Animations},
+    blur::{blur_shader, BlurElement, BlurShader},
+    color_filter::{ColorFilterElement, ColorTransform},
+    config::{Config, DimInactive, UpscaleMode},
+    decorations::{decoration_shaders, shadow_element, DecorationShaders, RoundedElement},
+    cursor::{cursor_elements, cursor_surfaces, CursorRenderElement},
+    frame::{PresentMode, RepaintPhase},
+    output::{GameframeOutput, OutputId, OutputSurface},
+    rules,
+    state::GameframeState,
+    supersample::{supersample, SharpenElement},
+    throttle::Throttle,
+    touch,
+    upscale::{self, Upscaled},
 };
-use tracing::debug;
 
-use crate::cursor::render_software_cursor;
+pub(crate) const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
 
-pub struct FrameResult {
-    pub presented: bool,
-    pub frame_ms:  f32,
+render_elements! {
+    /// One surface of a window, optionally with rounded corners.
+    pub WindowRenderElement<=GlesRenderer>;
+    Surface = WaylandSurfaceRenderElement<GlesRenderer>,
+    Rounded = RoundedElement,
 }
 
-/// Render one frame.
+render_elements! {
+    /// Everything Gameframe draws on an output.
+    pub OutputRenderElements<=GlesRenderer>;
+    Window   = WindowRenderElement,
+    Animated = RescaleRenderElement<WindowRenderElement>,
+    Snapshot = RescaleRenderElement<TextureRenderElement<GlesTexture>>,
+    Blur     = BlurElement,
+    Shadow   = PixelShaderElement,
+    Dim      = SolidColorRenderElement,
+    /// A fullscreen window upscaled through FSR (see `upscale`).
+    Fsr      = SharpenElement,
+}
+
+render_elements! {
+    /// A frame for one output, scaled about the pointer while the magnifier
+    /// is on and run through the colour filter last.
+    pub FrameElements<=GlesRenderer>;
+    Plain  = OutputRenderElements,
+    Zoomed = RescaleRenderElement<OutputRenderElements>,
+    Filter = ColorFilterElement,
+    /// The whole frame, rendered larger or smaller offscreen (see `supersample`).
+    Supersampled = TextureRenderElement<GlesTexture>,
+    /// The same, upscaled through the sharpening shader.
+    Sharpened = SharpenElement,
+    /// Drawn by a pixel shader on top of the finished frame: a calibration
+    /// pattern in place of everything else (see `test_pattern`), or the FPS
+    /// counter.
+    Shader = PixelShaderElement,
+    /// A pointer cursor, in front of everything (see `cursor`).
+    Cursor = CursorRenderElement,
+}
+
+/// Render one frame for the output `id` and queue it for scanout.
+///
+/// Only that output is drawn; every output runs its own repaint loop off its
+/// own vblanks (see [`handle_vblank`]). If a flip is still in flight the
+/// redraw is deferred until it completes.
 ///
-/// Uses `render_elements_for_output` then `damage_tracker.render_output`.
-/// `render_output` in Smithay 0.7 requires a bound target (GBM surface).
-/// Without one, we collect elements and track damage but skip the GPU submit.
-/// The caller (DRM vblank handler) is responsible for target binding.
-pub fn render_frame(
-    renderer:         &mut GlesRenderer,
-    damage_tracker:   &mut OutputDamageTracker,
-    space:            &Space<Window>,
-    output:           &Output,
-    pointer_location: smithay::utils::Point<f64, smithay::utils::Logical>,
-    cursor_status:    &smithay::input::pointer::CursorImageStatus,
-) -> FrameResult {
-    let t_start = std::time::Instant::now();
-    let scale_f64 = output.current_scale().fractional_scale();
-    // FIX: render_elements_for_output expects f32 scale, not f64
-    let scale_f32 = scale_f64 as f32;
-
-    // FIX: render_elements_for_output returns Result<Vec<SpaceRenderElements<...>>, OutputError>
-    let elements_result = space.render_elements_for_output(renderer, output, scale_f32);
-
-    let presented = match elements_result {
+/// A frame without damage is not submitted. Unless a client is waiting for
+/// a frame callback or an animation is running, the output then goes idle:
+/// no flip, no vblank timer, nothing until the next damage queues a redraw.
+/// The first frame after the output is created or reset (mode set, VT
+/// switch, unblank) always repaints in full, since the damage tracker and
+/// scanout buffers start out empty.
+pub fn render_output(state: &mut GameframeState, id: OutputId) {
+    let now = Duration::from(state.clock.now());
+    let Some(output) = state.backend.as_mut()
+        .and_then(|b| b.drm_mut())
+        .and_then(|b| b.output_mut(id))
+        .map(|o| o.output.clone())
+    else {
+        return;
+    };
+    let span = info_span!("render_output", output = %output.name(), frame = Empty, elements = Empty).entered();
+    let adaptive  = state.adaptive_sync_applies(&output);
+    let late_tear = adaptive && state.late_frames_may_tear(&output);
+    let vrr_ok    = state.config.display.vrr;
+    let tearing   = state.tearing_allowed(&output);
+    let focused  = state.focused_window();
+    // The FPS counter follows the first output's frames only.
+    let hud_here = state.space.outputs().next() == Some(&output);
+    let upscaled = upscale::upscaled(state, &output);
+    let Some(backend) = state.backend.as_mut().and_then(|b| b.drm_mut()) else { return };
+    if backend.paused || backend.blanked { return; }
+    let Some((out, renderer)) = backend.output_and_renderer(id) else { return };
+
+    if let RepaintPhase::Waiting { .. } = out.repaint.phase() {
+        out.repaint.queue_redraw();
+        return;
+    }
+    // Too soon after the last frame for the frame cap: render when it allows.
+    if let Some(delay) = state.frame_pacer.delay(out.repaint.last_render(), now) {
+        out.repaint.frame_deferred();
+        let _ = state.loop_handle.insert_source(Timer::from_duration(delay), move |_, _, state| {
+            handle_vblank(state, id, None);
+            TimeoutAction::Drop
+        });
+        return;
+    }
+
+    update_present_mode(out, adaptive, vrr_ok, late_tear, now);
+    update_tearing(out, tearing || (late_tear && out.adaptive.mode() == PresentMode::Immediate));
+
+    let started  = Instant::now();
+    let scene    = Scene {
+        space:      &state.space,
+        animations: &state.animations,
+        config:     &state.config,
+        focused:    focused.as_ref(),
+        focused_output: state.focused_output.as_ref(),
+        dnd_icon:   state.dnd.as_ref().and_then(|d| d.icon_at(state.pointer_location)),
+        upscaled,
+    };
+    span.record("frame", out.repaint.stats().presented);
+    upscale::set_filter(renderer, scene.upscaled.as_ref());
+    let pattern = state.test_pattern.as_mut()
+        .filter(|p| p.output == output)
+        .zip(state.space.output_geometry(&output))
+        .and_then(|(p, geo)| p.element(renderer, geo.size));
+    let mut elements = match pattern {
+        // The panel's own response is what is being looked at: no clients,
+        // magnifier, supersampling or colour filter.
+        Some(pattern) => vec![FrameElements::Shader(pattern)],
+        None => {
+            let elements = info_span!("output_elements")
+                .in_scope(|| output_elements(renderer, &out.output, &scene, now));
+            span.record("elements", elements.len());
+            let elements = magnify(elements, &scene, &out.output, state.magnifier.level(), state.pointer_location);
+            let mut elements = supersample(renderer, &out.output, elements, &state.config);
+            apply_color_filter(renderer, &mut elements, &out.output, state.color_filter.transform());
+            let hud = state.space.output_geometry(&output).filter(|_| hud_here)
+                .and_then(|geo| state.fps_overlay.element(renderer, geo.size));
+            if let Some(hud) = hud {
+                elements.insert(0, FrameElements::Shader(hud));
+            }
+            if let Some(geo) = state.space.output_geometry(&output) {
+                let scale = output.current_scale().fractional_scale();
+                let pointers = std::iter::once((&state.cursor_status, state.pointer_location))
+                    .chain(state.players.cursors());
+                let cursors: Vec<FrameElements> = pointers
+                    .flat_map(|(status, location)| cursor_elements(
+                        renderer, &mut state.cursor_theme, status, location, geo, scale,
+                    ))
+                    .map(FrameElements::Cursor)
+                    .collect();
+                elements.splice(0..0, cursors);
+            }
+            elements
+        }
+    };
+    let mut masked = 0;
+    if let Some(recorder) = state.recorder.as_mut().filter(|r| r.output() == &output) {
+        masked = mask_for_capture(&mut elements, &scene, &output, state.magnifier.level(), state.pointer_location);
+        recorder.capture(renderer, &elements, now);
+    }
+    let (flip, states) = match draw_frame(renderer, out, &elements[masked..]) {
+        Ok(r)  => r,
         Err(e) => {
-            tracing::error!("render_elements_for_output error: {e:?}");
+            warn!(?id, "render failed: {e:#}");
+            out.repaint.frame_dropped();
+            (false, RenderElementStates::default())
+        }
+    };
+    out.adaptive.record_render(started.elapsed());
+    if flip && hud_here {
+        state.fps_overlay.frame(now);
+    }
+
+    let frame_time = out.repaint.next_vblank(now);
+    let refresh    = out.repaint.refresh_interval();
+
+    update_primary_scanout_outputs(&state.space, &output, &states);
+    if let Some(mut stale) = out.pending_feedback.take() {
+        stale.discarded();
+    }
+    let waiting = !flip && frame_callbacks_pending(&state.space, &output);
+    if flip {
+        // Frame callbacks and presentation feedback go out when the flip
+        // completes, stamped with the kernel's timestamp (`handle_vblank`).
+        out.pending_feedback = Some(take_presentation_feedback(&state.space, &output, &states));
+    }
+
+    // Animations advance one step per repaint; keep the loop going (paced
+    // by vblank like any other redraw) until they have all finished.
+    let animating = state.animations.tick(now) | state.magnifier.tick(now)
+        | state.test_pattern.as_ref().is_some_and(|p| p.output == output && p.animated());
+    if !flip && !waiting && !animating {
+        // Static screen: nothing to show and nobody to pace.
+        trace!(?id, "idle");
+        out.repaint.frame_skipped();
+        return;
+    }
+    out.repaint.frame_queued(flip, now);
+    if waiting {
+        send_frame_callbacks(state, &output, frame_time);
+    }
+    if animating {
+        state.queue_redraw(&output);
+    }
+
+    if !flip {
+        // Nothing went to the screen, so no vblank event will arrive; wake up
+        // when the next one would have instead of spinning on client commits
+        // (a client answering each frame callback with an undamaged commit).
+        let delay = match frame_time.saturating_sub(now) {
+            d if d.is_zero() => refresh,
+            d => d,
+        };
+        let _ = state.loop_handle.insert_source(Timer::from_duration(delay), move |_, _, state| {
+            handle_vblank(state, id, None);
+            TimeoutAction::Drop
+        });
+    }
+}
+
+/// Run the adaptive vsync heuristic for the frame about to be drawn and
+/// switch the output's presentation mode when it changes. Immediate
+/// presentation switches VRR on where `vrr` allows it and the output can,
+/// and flips asynchronously where `tear` allows it and the driver can (see
+/// `render_output`); an output that can do neither stays on vsync. Outputs
+/// with VRR held on present every frame immediately and skip the heuristic.
+fn update_present_mode(out: &mut GameframeOutput, adaptive: bool, vrr: bool, tear: bool, now: Duration) {
+    if out.vrr { return; }
+    let via_vrr  = vrr && out.vrr_capable;
+    let adaptive = adaptive && (via_vrr || (tear && out.tearing_surface.is_some()));
+    let prev = out.adaptive.mode();
+    let next = match (adaptive, out.repaint.last_vblank()) {
+        (true, Some(last)) => out.adaptive.on_frame(now.saturating_sub(last), out.repaint.refresh_interval()),
+        (true, None)       => prev,
+        (false, _)         => { out.adaptive.reset(); PresentMode::Vsync }
+    };
+    if next != prev {
+        debug!(crtc = ?out.crtc, ?next, via_vrr, "adaptive sync");
+        // Immediate through tearing alone leaves VRR off.
+        let vrr_mode = if via_vrr { next } else { PresentMode::Vsync };
+        if out.vrr_capable {
+            if let Err(e) = out.surface.set_present_mode(vrr_mode) {
+                debug!(?next, "adaptive sync: {e:#}");
+            }
+        }
+    }
+}
+
+/// Apply the tearing policy's decision for the frame about to be queued.
+fn update_tearing(out: &mut GameframeOutput, allowed: bool) {
+    let was = out.tearing;
+    let now = out.set_tearing(allowed);
+    if now != was {
+        debug!(crtc = ?out.crtc, tearing = now, "tearing");
+    }
+}
+
+/// A page flip completed (or an estimated vblank elapsed) on the output
+/// `id`. `meta` is the DRM event's timestamp and sequence, `None` for
+/// estimated vblanks.
+pub fn handle_vblank(state: &mut GameframeState, id: OutputId, meta: Option<&DrmEventMetadata>) {
+    // Page-flip events carry CLOCK_MONOTONIC, the same clock as `state.clock`.
+    let hw_time = meta.and_then(|m| match m.time {
+        DrmEventTime::Monotonic(t) => Some(t),
+        DrmEventTime::Realtime(_)  => None,
+    });
+    let time = hw_time.unwrap_or_else(|| state.clock.now().into());
+    let _span = info_span!("vblank", crtc = ?id.crtc, hw = hw_time.is_some()).entered();
+    let Some(out) = state.backend.as_mut()
+        .and_then(|b| b.drm_mut())
+        .and_then(|b| b.output_mut(id))
+    else {
+        return;
+    };
+
+    let (flip, redraw) = out.repaint.on_vblank(time, hw_time.is_some());
+    if flip {
+        let torn = out.frame_submitted().unwrap_or_else(|e| {
+            warn!(?id, "frame_submitted: {e:#}");
             false
+        });
+        if let Some(mut feedback) = out.pending_feedback.take() {
+            let kind = match torn {
+                true  => wp_presentation_feedback::Kind::empty(),
+                false => wp_presentation_feedback::Kind::Vsync,
+            };
+            let flags = if hw_time.is_some() {
+                kind | wp_presentation_feedback::Kind::HwClock | wp_presentation_feedback::Kind::HwCompletion
+            } else {
+                kind
+            };
+            let refresh = match out.present_mode() {
+                PresentMode::Vsync     => Refresh::Fixed(out.repaint.refresh_interval()),
+                PresentMode::Immediate => Refresh::Variable(out.repaint.refresh_interval()),
+            };
+            let seq = meta.map_or(0, |m| m.sequence as u64);
+            feedback.presented(Time::<Monotonic>::from(time), refresh, seq, flags);
+        }
+        if let Some(lfc) = out.lfc.filter(|_| out.present_mode() == PresentMode::Immediate) {
+            let presented = out.repaint.stats().presented;
+            let _ = state.loop_handle.insert_source(Timer::from_duration(lfc.repeat_after()), move |_, _, state| {
+                repeat_frame(state, id, presented);
+                TimeoutAction::Drop
+            });
         }
-        Ok(_elements) => {
-            // elements collected – in the full DRM pipeline we'd pass them to
-            // damage_tracker.render_output(renderer, &mut gbm_target, 0, &elements, clear)
-            // For now: signal that the frame was "presented" (no GPU work without target)
-            debug!("elements collected, DRM target needed for submit");
-            true
+        let output = out.output.clone();
+        send_frame_callbacks(state, &output, time);
+    }
+    if redraw {
+        render_output(state, id);
+    }
+}
+
+/// Low framerate compensation: nothing new has been flipped on the output
+/// `id` since frame number `presented`, and VRR would let the panel drop
+/// below its minimum refresh. Show the same frame again.
+fn repeat_frame(state: &mut GameframeState, id: OutputId, presented: u64) {
+    let Some(out) = state.backend.as_mut()
+        .and_then(|b| b.drm_mut())
+        .filter(|b| !b.paused && !b.blanked)
+        .and_then(|b| b.output_mut(id))
+    else {
+        return;
+    };
+    let flipping = matches!(out.repaint.phase(), RepaintPhase::Waiting { flip: true, .. });
+    if flipping || out.repaint.stats().presented != presented || out.present_mode() != PresentMode::Immediate {
+        return;
+    }
+    trace!(?id, "LFC: repeating the last frame");
+    match out.tearing_surface.as_mut().filter(|_| out.tearing) {
+        Some(tearing) => tearing.reset_buffers(),
+        None          => out.surface.repeat_frame(),
+    }
+    let output = out.output.clone();
+    state.queue_redraw(&output);
+}
+
+/// Compositor state needed to build an output's elements, borrowed next to
+/// the renderer (which lives in the backend).
+pub(crate) struct Scene<'a> {
+    pub space:      &'a Space<Window>,
+    pub animations: &'a Animations,
+    pub config:     &'a Config,
+    /// Window with keyboard focus, if any.
+    pub focused:    Option<&'a Window>,
+    pub focused_output: Option<&'a Output>,
+    /// Drag-and-drop icon and its top-left in the global layout.
+    pub dnd_icon:   Option<(&'a WlSurface, Point<i32, Logical>)>,
+    /// Fullscreen window scaled up on the output being drawn (see `upscale`).
+    pub upscaled:   Option<Upscaled>,
+}
+
+/// Stable element id for the blur behind a window.
+struct BlurId(Id);
+
+/// Per-output fill for `display.background`.
+struct Background(RefCell<SolidColorBuffer>);
+
+/// Everything drawn on `output`, front to back: snapshots of windows fading
+/// out, then mapped windows from top to bottom with open animations and
+/// window rules applied, then the outgoing workspace while a switch slides
+/// it away, then the background.
+pub(crate) fn output_elements(
+    renderer: &mut GlesRenderer,
+    output:   &Output,
+    scene:    &Scene<'_>,
+    now:      Duration,
+) -> Vec<OutputRenderElements> {
+    let Some(output_geo) = scene.space.output_geometry(output) else { return Vec::new() };
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    let animations = scene.animations;
+    let mut elements: Vec<OutputRenderElements> = Vec::new();
+
+    // Placed by its global position, so on a drag across outputs each one
+    // draws the part of the icon over it.
+    if let Some((icon, loc)) = scene.dnd_icon {
+        let loc = (loc - output_geo.loc).to_physical_precise_round(scale);
+        let surfaces: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+            render_elements_from_surface_tree(renderer, icon, loc, scale, 1.0, Kind::Unspecified);
+        elements.extend(surfaces.into_iter().map(|e| OutputRenderElements::Window(e.into())));
+    }
+    let (above, below) = touch::layer_order(Some(output));
+    push_layers(&mut elements, renderer, output, scale, above);
+    for closing in animations.closing() {
+        if !closing.geometry.overlaps(output_geo) { continue; }
+        let (alpha, zoom) = window_effect(1.0 - closing.animation.progress(now));
+        let loc  = (closing.geometry.loc - output_geo.loc).to_physical_precise_round(scale);
+        let size = closing.geometry.size.to_physical_precise_round(scale);
+        let elem = TextureRenderElement::from_texture_buffer(
+            loc.to_f64(), &closing.snapshot, Some(alpha), None, Some(closing.geometry.size), Kind::Unspecified,
+        );
+        let center = loc + Point::from((size.w / 2, size.h / 2));
+        elements.push(RescaleRenderElement::from_element(elem, center, zoom).into());
+    }
+
+    let pass = WindowPass {
+        scene,
+        output,
+        scale,
+        output_size: output.current_mode().map_or_else(Default::default, |m| {
+            output.current_transform().transform_size(m.size)
+        }),
+        blur: if scene.config.effects.blur { blur_shader(renderer) } else { None },
+        decorations: if scene.config.effects.decorated() { decoration_shaders(renderer) } else { None },
+    };
+
+    // During a workspace slide both workspaces are shifted by a fraction of
+    // the output width; the outgoing one is no longer in the space.
+    let (out_dx, in_dx) = match animations.workspace_slide() {
+        Some(slide) => {
+            let (o, i) = slide.offsets(now);
+            let w = output_geo.size.w as f64;
+            ((o * w).round() as i32, (i * w).round() as i32)
         }
+        None => (0, 0),
     };
+    let origin = output_geo.loc - Point::from((in_dx, 0));
 
-    // Software cursor stub
-    render_software_cursor(renderer, cursor_status, pointer_location, scale_f64);
+    // Windows entirely behind opaque content above them aren't drawn at all;
+    // the damage tracker culls what is partly hidden.
+    let mut covered = opaque_area(&elements, scale);
+    for window in scene.space.elements_for_output(output).rev() {
+        let Some(loc) = scene.space.element_location(window) else { continue };
+        if let Some(bbox) = scene.space.element_bbox(window) {
+            let bbox = Rectangle::new(bbox.loc - origin, bbox.size).to_physical_precise_round(scale);
+            if bbox.subtract_rects(covered.iter().copied()).is_empty() {
+                trace!(app_id = ?rules::window_identity(window).0, "occluded, not drawn");
+                continue;
+            }
+        }
+        let start = elements.len();
+        let progress = animations.open_progress(window, now);
+        match scene.upscaled.as_ref().filter(|u| &u.window == window) {
+            Some(u) => pass.push(&mut elements, renderer, window, u.loc - Point::from((in_dx, 0)), None, Some(u)),
+            None    => pass.push(&mut elements, renderer, window, loc - origin, progress, None),
+        }
+        covered.extend(opaque_area(&elements[start..], scale));
+    }
+    if let Some(slide) = animations.workspace_slide() {
+        let origin = output_geo.loc - Point::from((out_dx, 0));
+        for (window, loc) in slide.outgoing.iter().rev() {
+            pass.push(&mut elements, renderer, window, *loc - origin, None, None);
+        }
+    }
+    push_layers(&mut elements, renderer, output, scale, below);
+    // The clear colour is black; any other background is a solid element
+    // under everything, so it also fills offscreen (supersampled, recorded)
+    // copies of the frame.
+    let background = scene.config.display.background_for(&output.name());
+    if background != CLEAR_COLOR {
+        let data = output.user_data();
+        data.insert_if_missing(|| Background(RefCell::new(SolidColorBuffer::default())));
+        let mut buffer = data.get::<Background>().unwrap().0.borrow_mut();
+        buffer.update(output_geo.size, background);
+        elements.push(OutputRenderElements::Dim(SolidColorRenderElement::from_buffer(
+            &buffer, (0, 0), scale, 1.0, Kind::Unspecified,
+        )));
+    }
+    elements
+}
+
+/// Opaque parts of `elements`, in output-relative physical pixels.
+fn opaque_area(elements: &[OutputRenderElements], scale: Scale<f64>) -> Vec<Rectangle<i32, Physical>> {
+    elements
+        .iter()
+        .flat_map(|e| {
+            let loc = e.geometry(scale).loc;
+            e.opaque_regions(scale).iter().map(|r| Rectangle::new(r.loc + loc, r.size)).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Layer surfaces (panels, wallpapers, notifications) on `layers` of
+/// `output`, topmost first.
+fn push_layers(
+    elements: &mut Vec<OutputRenderElements>,
+    renderer: &mut GlesRenderer,
+    output:   &Output,
+    scale:    Scale<f64>,
+    layers:   &[Layer],
+) {
+    let map = layer_map_for_output(output);
+    for &layer in layers {
+        for surface in map.layers_on(layer).rev() {
+            let Some(geo) = map.layer_geometry(surface) else { continue };
+            let surfaces: Vec<WaylandSurfaceRenderElement<GlesRenderer>> =
+                surface.render_elements(renderer, geo.loc.to_physical_precise_round(scale), scale, 1.0);
+            elements.extend(surfaces.into_iter().map(|e| OutputRenderElements::Window(e.into())));
+        }
+    }
+}
+
+/// Apply the magnifier: scale the whole frame by `zoom` about the pointer if
+/// it is on `output`.
+pub(crate) fn magnify(
+    elements: Vec<OutputRenderElements>,
+    scene:    &Scene<'_>,
+    output:   &Output,
+    zoom:     f64,
+    pointer:  Point<f64, Logical>,
+) -> Vec<FrameElements> {
+    let output_geo = scene.space.output_geometry(output).filter(|g| g.to_f64().contains(pointer));
+    let Some(output_geo) = output_geo.filter(|_| zoom > 1.0) else {
+        return elements.into_iter().map(FrameElements::Plain).collect();
+    };
+    let scale  = output.current_scale().fractional_scale();
+    let origin = (pointer - output_geo.loc.to_f64()).to_physical(scale).to_i32_round();
+    elements
+        .into_iter()
+        .map(|e| FrameElements::Zoomed(RescaleRenderElement::from_element(e, origin, zoom)))
+        .collect()
+}
+
+/// Solid cover for a window hidden from captures.
+struct CaptureMask(RefCell<SolidColorBuffer>);
+
+/// Put an opaque black quad in front of the frame over every window on
+/// `output` that is hidden from captures (see [`rules::capture_hidden`]),
+/// magnified like the rest of the frame. Returns how many were added: the
+/// whole list is what gets recorded, `&elements[n..]` what goes on screen.
+pub(crate) fn mask_for_capture(
+    elements: &mut Vec<FrameElements>,
+    scene:    &Scene<'_>,
+    output:   &Output,
+    zoom:     f64,
+    pointer:  Point<f64, Logical>,
+) -> usize {
+    let Some(output_geo) = scene.space.output_geometry(output) else { return 0 };
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    let masks: Vec<OutputRenderElements> = scene.space
+        .elements()
+        .filter(|w| rules::capture_hidden(&scene.config.window_rules, w))
+        .filter_map(|window| {
+            // The bounding box, so popups and subsurfaces are covered too.
+            let bbox = scene.space.element_bbox(window)?.intersection(output_geo)?;
+            let data = window.user_data();
+            data.insert_if_missing(|| CaptureMask(RefCell::new(SolidColorBuffer::default())));
+            let mut buffer = data.get::<CaptureMask>().unwrap().0.borrow_mut();
+            buffer.update(bbox.size, CLEAR_COLOR);
+            let loc = (bbox.loc - output_geo.loc).to_physical_precise_round(scale);
+            Some(OutputRenderElements::Dim(SolidColorRenderElement::from_buffer(
+                &buffer, loc, scale, 1.0, Kind::Unspecified,
+            )))
+        })
+        .collect();
+    let n = masks.len();
+    elements.splice(0..0, magnify(masks, scene, output, zoom, pointer));
+    n
+}
+
+/// Put the colour filter pass in front of the frame, so it runs after
+/// everything else has been drawn.
+pub(crate) fn apply_color_filter(
+    renderer:  &mut GlesRenderer,
+    elements:  &mut Vec<FrameElements>,
+    output:    &Output,
+    transform: ColorTransform,
+) {
+    if let Some(filter) = ColorFilterElement::new(renderer, output, transform) {
+        elements.insert(0, FrameElements::Filter(filter));
+    }
+}
+
+/// Per-output parameters for drawing windows.
+struct WindowPass<'a> {
+    scene:       &'a Scene<'a>,
+    output:      &'a Output,
+    scale:       Scale<f64>,
+    output_size: Size<i32, Physical>,
+    blur:        Option<Rc<BlurShader>>,
+    decorations: Option<DecorationShaders>,
+}
+
+impl WindowPass<'_> {
+    /// Append the elements of `window`, whose geometry origin is at `loc`
+    /// relative to the output, applying its window rules, decorations and
+    /// open-animation `progress` if any, and scaled up as `upscale` says.
+    /// In front of the window: the dim quad; back-to-front behind it: blur,
+    /// then shadow.
+    fn push(
+        &self,
+        elements: &mut Vec<OutputRenderElements>,
+        renderer: &mut GlesRenderer,
+        window:   &Window,
+        loc:      Point<i32, Logical>,
+        progress: Option<f64>,
+        upscale:  Option<&Upscaled>,
+    ) {
+        let effects = &self.scene.config.effects;
+        let rule    = rules::resolve(&self.scene.config.window_rules, window);
+        let active  = self.scene.focused == Some(window);
+        let opacity = rules::window_opacity(&rule, window, active);
+        let (anim_alpha, zoom) = progress.map_or((1.0, 1.0), window_effect);
+        let dimmed  = match effects.dim_inactive {
+            DimInactive::Off     => false,
+            DimInactive::Windows => !active,
+            DimInactive::Outputs => self.scene.focused_output.is_some_and(|o| o != self.output),
+        };
+        let alpha = anim_alpha * opacity;
+
+        let geo        = window.geometry();
+        let render_loc = (loc - geo.loc).to_physical_precise_round(self.scale);
+        let geo_phys   = Rectangle::new(
+            render_loc + geo.loc.to_physical_precise_round(self.scale),
+            geo.size.to_physical_precise_round(self.scale),
+        );
+        // What the window covers on screen.
+        let factor     = upscale.map_or(1.0, |u| u.factor);
+        let drawn_size = geo.size.to_f64().upscale(factor).to_i32_round();
+        let drawn      = Rectangle::new(geo_phys.loc, drawn_size.to_physical_precise_round(self.scale));
+
+        // Only the main surface is rounded; subsurfaces and popups are drawn
+        // as-is.
+        let rounding = self.decorations.as_ref()
+            .filter(|_| effects.corner_radius > 0.0)
+            .zip(window.toplevel().map(|t| Id::from_wayland_resource(t.wl_surface())));
+        let surfaces = window
+            .render_elements::<WaylandSurfaceRenderElement<_>>(renderer, render_loc, self.scale, alpha)
+            .into_iter()
+            .map(|e| match &rounding {
+                Some((shaders, main)) if e.id() == main => {
+                    let elem_loc = e.geometry(self.scale).loc;
+                    let geo_in   = Rectangle::new(geo_phys.loc - elem_loc, geo_phys.size);
+                    let radius   = effects.corner_radius * self.scale.x as f32;
+                    WindowRenderElement::from(RoundedElement::new(e, shaders.rounded.clone(), geo_in, radius))
+                }
+                _ => WindowRenderElement::from(e),
+            });
+        // The dim quad sits in front of the window.
+        if dimmed {
+            let data = window.user_data();
+            data.insert_if_missing(|| RefCell::new(SolidColorBuffer::default()));
+            let mut buffer = data.get::<RefCell<SolidColorBuffer>>().unwrap().borrow_mut();
+            buffer.update(drawn_size, [0.0, 0.0, 0.0, 1.0]);
+            elements.push(OutputRenderElements::Dim(SolidColorRenderElement::from_buffer(
+                &buffer, drawn.loc, self.scale, effects.dim_strength.clamp(0.0, 1.0) * alpha, Kind::Unspecified,
+            )));
+        }
+        let fsr = match upscale.map(|u| u.mode) {
+            Some(UpscaleMode::Fsr { sharpness }) => upscale::fsr(renderer, self.output, window, alpha, drawn, sharpness),
+            _ => None,
+        };
+        match progress {
+            None if fsr.is_some() => elements.extend(fsr.map(OutputRenderElements::Fsr)),
+            None if factor != 1.0 => {
+                elements.extend(surfaces.map(|e| RescaleRenderElement::from_element(e, drawn.loc, factor).into()));
+            }
+            None    => elements.extend(surfaces.map(OutputRenderElements::Window)),
+            Some(_) => {
+                let center = geo_phys.loc + Point::from((geo_phys.size.w / 2, geo_phys.size.h / 2));
+                elements.extend(surfaces.map(|e| RescaleRenderElement::from_element(e, center, zoom).into()));
+            }
+        }
+
+        // Blur goes right behind the window so it samples what is below it.
+        if let (true, Some(shader)) = (rule.blur, &self.blur) {
+            let data = window.user_data();
+            data.insert_if_missing(|| BlurId(Id::new()));
+            let id = data.get::<BlurId>().unwrap().0.clone();
+            elements.push(OutputRenderElements::Blur(BlurElement::new(
+                id, drawn, self.output_size, shader.clone(), effects.blur_passes, effects.blur_offset,
+            )));
+        }
+
+        if let (true, Some(shaders)) = (effects.shadow, &self.decorations) {
+            let shadow = shadow_element(window, &shaders.shadow, Rectangle::new(loc, drawn_size), effects, alpha);
+            elements.push(OutputRenderElements::Shadow(shadow));
+        }
+    }
+}
+
+/// Draw `elements` for the output and queue the result for scanout. Returns
+/// whether anything was queued, plus the element states of the frame.
+///
+/// On atomic devices the `DrmCompositor` assigns elements to planes and only
+/// composites what it must; legacy devices always composite with GL. While
+/// the output tears everything is composited and flipped asynchronously.
+fn draw_frame(
+    renderer: &mut GlesRenderer,
+    out:      &mut GameframeOutput,
+    elements: &[FrameElements],
+) -> Result<(bool, RenderElementStates)> {
+    let _span = info_span!("draw_frame", atomic = out.surface.is_atomic(), tearing = out.tearing).entered();
+    if let Some(tearing) = out.tearing_surface.as_mut().filter(|_| out.tearing) {
+        let (mut dmabuf, age) = tearing.next_buffer()?;
+        let mut fb = renderer.bind(&mut dmabuf).context("bind tearing buffer")?;
+        let res = out
+            .damage_tracker
+            .render_output(renderer, &mut fb, age, elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("damage_tracker.render_output: {e:?}"))?;
+        let (damaged, sync, states) = (res.damage.is_some(), res.sync, res.states);
+        if !damaged {
+            trace!(crtc = ?out.crtc, "no damage, skipping flip");
+            return Ok((false, states));
+        }
+        // An async flip shows the buffer at once, so it must be complete.
+        if let Err(e) = sync.wait() {
+            debug!(crtc = ?out.crtc, "tearing buffer sync: {e:?}");
+        }
+        if let Err(e) = tearing.queue_buffer() {
+            // Don't try again: stay on vsync from here on.
+            warn!(crtc = ?out.crtc, "async flip rejected, tearing disabled: {e:#}");
+            out.set_tearing(false);
+            out.tearing_surface = None;
+            return Ok((false, states));
+        }
+        return Ok((true, states));
+    }
+    match &mut out.surface {
+        OutputSurface::Atomic(compositor) => {
+            let frame = compositor
+                .render_frame(renderer, elements, CLEAR_COLOR, FrameFlags::DEFAULT)
+                .map_err(|e| anyhow!("DrmCompositor::render_frame: {e:?}"))?;
+            let (is_empty, states) = (frame.is_empty, frame.states);
+            if is_empty {
+                trace!(crtc = ?out.crtc, "no damage, skipping flip");
+                return Ok((false, states));
+            }
+            compositor
+                .queue_frame(())
+                .map_err(|e| anyhow!("DrmCompositor::queue_frame: {e:?}"))?;
+            Ok((true, states))
+        }
+        OutputSurface::Composited(surface) => {
+            let (mut dmabuf, age) = surface.next_buffer().context("next_buffer")?;
+            let mut fb = renderer.bind(&mut dmabuf).context("bind scanout buffer")?;
+            let res = out
+                .damage_tracker
+                .render_output(renderer, &mut fb, age as usize, elements, CLEAR_COLOR)
+                .map_err(|e| anyhow!("damage_tracker.render_output: {e:?}"))?;
+
+            // The damage tracker only repaints (scissors) the regions damaged
+            // since this buffer was last used, and the same rectangles go to
+            // the flip so unchanged areas are never recomposited.
+            let Some(damage) = res.damage.cloned() else {
+                trace!(crtc = ?out.crtc, "no damage, skipping flip");
+                return Ok((false, res.states));
+            };
+            trace!(
+                crtc    = ?out.crtc,
+                age,
+                rects   = damage.len(),
+                percent = damage_percent(&damage, out.mode.size()),
+                "partial composition"
+            );
+            surface
+                .queue_buffer(Some(res.sync), Some(damage), ())
+                .context("queue_buffer")?;
+            Ok((true, res.states))
+        }
+    }
+}
+
+/// Share of the output covered by `damage`, for diagnosing partial redraws.
+fn damage_percent(damage: &[Rectangle<i32, Physical>], (w, h): (u16, u16)) -> f32 {
+    let total = w as f32 * h as f32;
+    if total == 0.0 { return 0.0; }
+    let damaged: f32 = damage.iter().map(|r| r.size.w as f32 * r.size.h as f32).sum();
+    (damaged / total * 100.0).min(100.0)
+}
+
+/// Record which surfaces were drawn on `output` in the frame just rendered,
+/// so callbacks and feedback go to each surface from one output only.
+pub(crate) fn update_primary_scanout_outputs(space: &Space<Window>, output: &Output, states: &RenderElementStates) {
+    for window in space.elements_for_output(output) {
+        window.with_surfaces(|surface, data| {
+            update_surface_primary_scanout_output(
+                surface, output, data, states, default_primary_scanout_output_compare,
+            );
+        });
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.with_surfaces(|surface, data| {
+            update_surface_primary_scanout_output(
+                surface, output, data, states, default_primary_scanout_output_compare,
+            );
+        });
+    }
+}
+
+/// Send frame callbacks to the windows and layer surfaces whose primary
+/// scanout output is `output`, stamped with `frame_time`: the flip that
+/// showed the frame, or the estimated next vblank when nothing was flipped.
+/// Background windows are held to `display.background_fps`.
+pub(crate) fn send_frame_callbacks(state: &GameframeState, output: &Output, frame_time: Duration) {
+    let mut throttle = Throttle::new(state);
+    for window in state.space.elements_for_output(output) {
+        if !throttle.allow(window, frame_time) { continue; }
+        window.send_frame(output, frame_time, Some(Duration::ZERO), surface_primary_scanout_output);
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.send_frame(output, frame_time, Some(Duration::ZERO), surface_primary_scanout_output);
+    }
+    let icon = state.dnd.as_ref().and_then(|d| d.icon.as_ref());
+    if let Some(icon) = icon.filter(|_| state.output_at(state.pointer_location).as_ref() == Some(output)) {
+        send_frames_surface_tree(icon, output, frame_time, Some(Duration::ZERO), |_, _| Some(output.clone()));
+    }
+    for (cursor, location) in cursor_surfaces(state) {
+        if state.output_at(location).as_ref() == Some(output) {
+            send_frames_surface_tree(cursor, output, frame_time, Some(Duration::ZERO), |_, _| Some(output.clone()));
+        }
+    }
+    throttle.schedule(state, output);
+}
+
+/// Whether any surface shown on `output` asked for a frame callback that
+/// hasn't been sent yet.
+pub(crate) fn frame_callbacks_pending(space: &Space<Window>, output: &Output) -> bool {
+    let mut pending = false;
+    let mut check = |_: &WlSurface, states: &SurfaceData| {
+        pending |= !states.cached_state.get::<SurfaceAttributes>().current().frame_callbacks.is_empty();
+    };
+    for window in space.elements_for_output(output) {
+        window.with_surfaces(&mut check);
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.with_surfaces(&mut check);
+    }
+    pending
+}
 
-    let frame_ms = t_start.elapsed().as_secs_f32() * 1000.0;
-    FrameResult { presented, frame_ms }
+/// Take the pending `wp_presentation` feedback of the windows and layer
+/// surfaces shown on `output`, to be reported once the frame reaches the screen.
+pub(crate) fn take_presentation_feedback(
+    space:  &Space<Window>,
+    output: &Output,
+    states: &RenderElementStates,
+) -> OutputPresentationFeedback {
+    let mut feedback = OutputPresentationFeedback::new(output);
+    for window in space.elements_for_output(output) {
+        window.take_presentation_feedback(&mut feedback, surface_primary_scanout_output, |surface, _| {
+            surface_presentation_feedback_flags_from_states(surface, states)
+        });
+    }
+    for layer in layer_map_for_output(output).layers() {
+        layer.take_presentation_feedback(&mut feedback, surface_primary_scanout_output, |surface, _| {
+            surface_presentation_feedback_flags_from_states(surface, states)
+        });
+    }
+    feedback
 }
 
 pub fn now_us() -> u64 {
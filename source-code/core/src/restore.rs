@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use smithay::{
+    desktop::Window,
+    utils::{Logical, Point, Rectangle},
+};
+use tracing::{info, warn};
+
+use crate::{rules, state::GameframeState};
+
+/// How long after startup saved windows wait for their apps to come back.
+const RESTORE_WINDOW: Duration = Duration::from_secs(120);
+
+/// Layout written by `gameframe restart` and read back by the next
+/// session. Clients lose their connection across the restart; the layout
+/// comes back as they reconnect and open their windows again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Layout {
+    workspace: usize,
+    /// Output name → global logical `[x, y]`.
+    outputs:   HashMap<String, [i32; 2]>,
+    windows:   Vec<SavedWindow>,
+}
+
+/// Geometry is global logical `[x, y, w, h]` of the window geometry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SavedWindow {
+    app_id:    String,
+    title:     Option<String>,
+    workspace: usize,
+    geometry:  [i32; 4],
+}
+
+/// Where a window from before the restart goes.
+pub struct Placement {
+    pub workspace: usize,
+    pub geometry:  Rectangle<i32, Logical>,
+}
+
+/// What is left to restore of the layout saved before a restart.
+#[derive(Debug, Default)]
+pub struct Restore {
+    outputs: HashMap<String, [i32; 2]>,
+    windows: Vec<SavedWindow>,
+    until:   Option<Instant>,
+}
+
+impl Restore {
+    /// Saved position of the output called `name`.
+    pub fn output_position(&self, name: &str) -> Option<Point<i32, Logical>> {
+        self.outputs.get(name).map(|&[x, y]| Point::from((x, y)))
+    }
+
+    /// Claim the saved entry for a newly opened window: same app and title
+    /// first, else the app's first unclaimed window. Each entry is used once.
+    pub fn take_window(&mut self, window: &Window) -> Option<Placement> {
+        if self.until.is_some_and(|t| Instant::now() > t) {
+            self.windows.clear();
+        }
+        let (Some(app_id), title) = rules::window_identity(window) else { return None };
+        let idx = self.windows.iter().position(|w| w.app_id == app_id && w.title == title)
+            .or_else(|| self.windows.iter().position(|w| w.app_id == app_id))?;
+        let saved = self.windows.remove(idx);
+        let [x, y, w, h] = saved.geometry;
+        Some(Placement { workspace: saved.workspace, geometry: Rectangle::new((x, y).into(), (w, h).into()) })
+    }
+}
+
+/// Write the current layout for the next session to pick up.
+pub fn save(state: &GameframeState) -> Result<PathBuf> {
+    let active = state.workspaces.active();
+    let mapped = state.space.elements().filter_map(|w| Some((active, w, state.space.element_location(w)?)));
+    let windows = mapped
+        .chain(state.workspaces.stashed())
+        .filter_map(|(workspace, window, loc)| {
+            let (app_id, title) = rules::window_identity(window);
+            let size = window.geometry().size;
+            Some(SavedWindow { app_id: app_id?, title, workspace, geometry: [loc.x, loc.y, size.w, size.h] })
+        })
+        .collect();
+    let outputs = state.space.outputs()
+        .filter_map(|o| Some((o.name(), state.space.output_geometry(o)?.loc)))
+        .map(|(name, loc)| (name, [loc.x, loc.y]))
+        .collect();
+    let layout = Layout { workspace: active, outputs, windows };
+
+    let path = layout_path();
+    std::fs::write(&path, toml::to_string(&layout)?).with_context(|| format!("write {}", path.display()))?;
+    info!(path = %path.display(), windows = layout.windows.len(), "Layout saved for restart");
+    Ok(path)
+}
+
+/// Pick up the layout a restart left behind, if any: switch to its
+/// workspace and keep the rest for outputs and windows as they appear.
+/// The file is removed, so a later plain start begins fresh.
+pub fn resume(state: &mut GameframeState) {
+    let path = layout_path();
+    let Ok(raw) = std::fs::read_to_string(&path) else { return };
+    let _ = std::fs::remove_file(&path);
+    let layout: Layout = match toml::from_str(&raw) {
+        Ok(layout) => layout,
+        Err(e) => {
+            warn!(path = %path.display(), "ignoring saved layout: {e}");
+            return;
+        }
+    };
+    info!(windows = layout.windows.len(), workspace = layout.workspace + 1, "Restoring layout from before restart");
+    state.workspaces.switch_to(layout.workspace, &mut state.space);
+    state.restore = Restore {
+        outputs: layout.outputs,
+        windows: layout.windows,
+        until:   Some(Instant::now() + RESTORE_WINDOW),
+    };
+}
+
+/// Save the layout and stop the event loop; the compositor then starts
+/// itself again (see `compositor::run`).
+pub fn restart(state: &mut GameframeState) -> Result<PathBuf> {
+    let path = save(state)?;
+    state.restart = true;
+    state.running = false;
+    Ok(path)
+}
+
+/// `$XDG_RUNTIME_DIR/gameframe-layout.toml`: gone after a reboot, which
+/// no layout should survive.
+fn layout_path() -> PathBuf {
+    std::env::var("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/tmp"))
+        .join("gameframe-layout.toml")
+}
@@ -0,0 +1,106 @@
+use std::cell::Cell;
+
+use smithay::{
+    desktop::Window,
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceData},
+};
+
+use crate::config::WindowRule;
+
+/// `app_id` and `title` of a window's toplevel, as last committed. For X11
+/// windows the `WM_CLASS` class stands in for the app id.
+pub fn window_identity(window: &Window) -> (Option<String>, Option<String>) {
+    if let Some(x11) = window.x11_surface() {
+        return (Some(x11.class()).filter(|c| !c.is_empty()), Some(x11.title()).filter(|t| !t.is_empty()));
+    }
+    let Some(toplevel) = window.toplevel() else { return (None, None) };
+    with_states(toplevel.wl_surface(), |states| {
+        states
+            .data_map
+            .get::<XdgToplevelSurfaceData>()
+            .map(|d| {
+                let d = d.lock().unwrap();
+                (d.app_id.clone(), d.title.clone())
+            })
+            .unwrap_or_default()
+    })
+}
+
+impl WindowRule {
+    /// A rule matches when every criterion it sets matches: `app_id` exactly,
+    /// `title` as a substring. A rule with no criteria matches every window.
+    pub fn matches(&self, app_id: Option<&str>, title: Option<&str>) -> bool {
+        self.app_id.as_deref().map_or(true, |want| app_id == Some(want))
+            && self.title.as_deref().map_or(true, |want| title.is_some_and(|t| t.contains(want)))
+    }
+}
+
+/// The effective rule for one window: every matching rule applied in config
+/// order, later ones overriding earlier ones field by field.
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedRule {
+    pub opacity:          Option<f32>,
+    pub inactive_opacity: Option<f32>,
+    pub blur:             bool,
+    pub position:         Option<[i32; 2]>,
+    pub no_capture:       bool,
+    pub tearing:          Option<bool>,
+}
+
+pub fn resolve(rules: &[WindowRule], window: &Window) -> ResolvedRule {
+    let (app_id, title) = window_identity(window);
+    let mut out = ResolvedRule::default();
+    for rule in rules.iter().filter(|r| r.matches(app_id.as_deref(), title.as_deref())) {
+        if rule.opacity.is_some()          { out.opacity          = rule.opacity; }
+        if rule.inactive_opacity.is_some() { out.inactive_opacity = rule.inactive_opacity; }
+        if let Some(b) = rule.blur         { out.blur             = b; }
+        if rule.position.is_some()         { out.position         = rule.position; }
+        if let Some(n) = rule.no_capture   { out.no_capture       = n; }
+        if rule.tearing.is_some()          { out.tearing          = rule.tearing; }
+    }
+    out
+}
+
+/// Opacity set at runtime with the opacity keybindings; overrides rules.
+#[derive(Debug, Default)]
+struct OpacityOverride(Cell<Option<f32>>);
+
+pub fn opacity_override(window: &Window) -> Option<f32> {
+    window.user_data().get::<OpacityOverride>().and_then(|o| o.0.get())
+}
+
+/// Nudge the runtime opacity of `window` by `delta`, starting from `current`.
+pub fn adjust_opacity(window: &Window, current: f32, delta: f32) -> f32 {
+    let data  = window.user_data();
+    data.insert_if_missing(OpacityOverride::default);
+    let value = (current + delta).clamp(0.1, 1.0);
+    data.get::<OpacityOverride>().unwrap().0.set(Some(value));
+    value
+}
+
+/// Rule/keybinding opacity of `window`, before the client's own
+/// `wp_alpha_modifier_v1` multiplier (Smithay applies that when building the
+/// surface render elements, so the two multiply).
+pub fn window_opacity(rule: &ResolvedRule, window: &Window, active: bool) -> f32 {
+    opacity_override(window)
+        .or(if active { rule.opacity } else { rule.inactive_opacity.or(rule.opacity) })
+        .unwrap_or(1.0)
+        .clamp(0.0, 1.0)
+}
+
+/// Capture exclusion set at runtime (`capture hide` on the control socket);
+/// overrides rules.
+#[derive(Debug, Default)]
+struct CaptureOverride(Cell<Option<bool>>);
+
+pub fn set_capture_hidden(window: &Window, hidden: bool) {
+    let data = window.user_data();
+    data.insert_if_missing(CaptureOverride::default);
+    data.get::<CaptureOverride>().unwrap().0.set(Some(hidden));
+}
+
+/// Whether `window` must be left out of recordings and other captures.
+pub fn capture_hidden(rules: &[WindowRule], window: &Window) -> bool {
+    window.user_data().get::<CaptureOverride>().and_then(|o| o.0.get())
+        .unwrap_or_else(|| resolve(rules, window).no_capture)
+}
@@ -0,0 +1,268 @@
+//! wlr-screencopy for the headless backend: a capture client (a streaming
+//! encoder, `wf-recorder`, `grim`) gets frames of a virtual output copied
+//! into its own buffer, a dmabuf on the GPU or wl_shm through a readback.
+//! Frames are copied as drawn, cursor included; outputs of the other
+//! backends answer every capture with `failed`.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, Buffer, Fourcc},
+        renderer::{buffer_dimensions, gles::GlesRenderer, Bind, Blit, ExportMem, TextureFilter},
+    },
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Buffer as BufferCoord, Logical, Physical, Rectangle, Size},
+    wayland::{
+        dmabuf::get_dmabuf,
+        shm::{with_buffer_contents, with_buffer_contents_mut},
+    },
+};
+use tracing::debug;
+
+use crate::{headless::HeadlessBackend, state::GameframeState};
+
+const VERSION: u32 = 3;
+
+/// Advertise `zwlr_screencopy_manager_v1`.
+pub fn init(state: &mut GameframeState) {
+    state.display_handle.create_global::<GameframeState, ZwlrScreencopyManagerV1, _>(VERSION, ());
+    state.globals.push(ZwlrScreencopyManagerV1::interface().name);
+}
+
+/// What a `zwlr_screencopy_frame_v1` copies.
+pub struct FrameData {
+    /// The output and the part of it copied, in its buffer coordinates;
+    /// `None` when it can't be captured.
+    target: Option<(Output, Rectangle<i32, Physical>)>,
+    copied: AtomicBool,
+}
+
+/// A copy requested by a client, held by its virtual output until a frame
+/// it can be served from is drawn.
+pub struct Capture {
+    frame:       ZwlrScreencopyFrameV1,
+    buffer:      WlBuffer,
+    region:      Rectangle<i32, Physical>,
+    with_damage: bool,
+}
+
+impl Capture {
+    /// Whether a frame drawn with `damage` can serve the capture: any frame
+    /// for `copy`, one changed inside the region for `copy_with_damage`.
+    pub fn ready(&self, damage: Option<&[Rectangle<i32, Physical>]>) -> bool {
+        !self.with_damage || damage.is_some_and(|d| d.iter().any(|r| r.overlaps(self.region)))
+    }
+
+    /// Copy `src`, the output's newest frame drawn at `now`, into the
+    /// client's buffer and tell the client.
+    pub fn serve(self, renderer: &mut GlesRenderer, src: &mut Dmabuf, damage: Option<&[Rectangle<i32, Physical>]>, now: Duration) {
+        if !self.frame.is_alive() { return; }
+        if !self.buffer.is_alive() { return self.fail(); }
+        if let Err(e) = copy(renderer, src, &self.buffer, self.region) {
+            debug!("screencopy: {e:#}");
+            return self.fail();
+        }
+        self.frame.flags(zwlr_screencopy_frame_v1::Flags::empty());
+        if self.with_damage {
+            for rect in damage.unwrap_or_default().iter().filter_map(|r| r.intersection(self.region)) {
+                let loc = rect.loc - self.region.loc;
+                self.frame.damage(loc.x as u32, loc.y as u32, rect.size.w as u32, rect.size.h as u32);
+            }
+        }
+        let secs = now.as_secs();
+        self.frame.ready((secs >> 32) as u32, secs as u32, now.subsec_nanos());
+    }
+
+    pub fn fail(self) {
+        if self.frame.is_alive() { self.frame.failed(); }
+    }
+}
+
+/// Copy `region` of `src` into `dst`, blitting into a dmabuf or reading
+/// back into wl_shm memory.
+fn copy(renderer: &mut GlesRenderer, src: &mut Dmabuf, dst: &WlBuffer, region: Rectangle<i32, Physical>) -> Result<()> {
+    let src_fb = renderer.bind(src).context("bind output buffer")?;
+    if let Ok(dmabuf) = get_dmabuf(dst) {
+        let mut dmabuf = dmabuf.clone();
+        let mut dst_fb = renderer.bind(&mut dmabuf).context("bind capture buffer")?;
+        let sync = renderer
+            .blit(&src_fb, &mut dst_fb, region, Rectangle::from_size(region.size), TextureFilter::Nearest)
+            .map_err(|e| anyhow!("blit: {e:?}"))?;
+        // The client reads the buffer as soon as it hears it is ready.
+        if let Err(e) = sync.wait() {
+            debug!("screencopy sync: {e:?}");
+        }
+        return Ok(());
+    }
+
+    let read = Rectangle::<i32, BufferCoord>::new((region.loc.x, region.loc.y).into(), (region.size.w, region.size.h).into());
+    let mapping = renderer.copy_framebuffer(&src_fb, read, Fourcc::Abgr8888).context("copy_framebuffer")?;
+    let pixels = renderer.map_texture(&mapping).map_err(|e| anyhow!("map_texture: {e:?}"))?;
+    let row = region.size.w as usize * 4;
+    with_buffer_contents_mut(dst, |ptr, len, data| {
+        let (offset, stride) = (data.offset as usize, data.stride as usize);
+        if offset + stride * (region.size.h as usize - 1) + row > len {
+            bail!("shm buffer too small");
+        }
+        // Mapped for `len` bytes while the closure runs.
+        let memory = unsafe { std::slice::from_raw_parts_mut(ptr, len) };
+        for (y, line) in pixels.chunks_exact(row).enumerate() {
+            let out = &mut memory[offset + y * stride..][..row];
+            // RGBA bytes to the BGRX of wl_shm's xrgb8888.
+            for (px, rgba) in out.chunks_exact_mut(4).zip(line.chunks_exact(4)) {
+                px.copy_from_slice(&[rgba[2], rgba[1], rgba[0], rgba[3]]);
+            }
+        }
+        Ok(())
+    })
+    .map_err(|e| anyhow!("shm buffer: {e:?}"))?
+}
+
+/// Why `buffer` can't take a frame of `size`, if it can't.
+fn check(buffer: &WlBuffer, size: Size<i32, Physical>) -> Result<(), String> {
+    let dims = buffer_dimensions(buffer).ok_or("buffer has no size")?;
+    if (dims.w, dims.h) != (size.w, size.h) {
+        return Err(format!("buffer is {}x{}, the frame {}x{}", dims.w, dims.h, size.w, size.h));
+    }
+    if let Ok(dmabuf) = get_dmabuf(buffer) {
+        return match dmabuf.format().code {
+            Fourcc::Xrgb8888 | Fourcc::Argb8888 => Ok(()),
+            code => Err(format!("dmabuf format {code:?} isn't xrgb8888")),
+        };
+    }
+    match with_buffer_contents(buffer, |_, _, data| (data.format, data.stride)) {
+        Ok((wl_shm::Format::Xrgb8888 | wl_shm::Format::Argb8888, stride)) if stride >= size.w * 4 => Ok(()),
+        Ok((format, stride)) => Err(format!("shm buffer is {format:?} with stride {stride}, not xrgb8888")),
+        Err(_) => Err("neither a dmabuf nor a wl_shm buffer".into()),
+    }
+}
+
+/// Start a capture of `output` (`region` of it, in logical coordinates
+/// relative to it, if given) and list the buffers it takes.
+fn capture_output(
+    state:     &GameframeState,
+    data_init: &mut DataInit<'_, GameframeState>,
+    frame:     New<ZwlrScreencopyFrameV1>,
+    output:    &WlOutput,
+    region:    Option<Rectangle<i32, Logical>>,
+) {
+    let output = Output::from_resource(output)
+        .filter(|o| state.backend.as_ref().and_then(|b| b.headless()).is_some_and(|h| h.outputs.iter().any(|v| &v.output == o)));
+    let full = output.as_ref().and_then(|o| o.current_mode()).map(|m| Rectangle::from_size(m.size));
+    let region = match (full, region) {
+        (Some(full), Some(region)) => {
+            let scale = output.as_ref().map_or(1.0, |o| o.current_scale().fractional_scale());
+            region.to_physical_precise_round(scale).intersection(full)
+        }
+        (full, _) => full,
+    };
+    let target = output.zip(region);
+    let frame = data_init.init(frame, FrameData { target: target.clone(), copied: AtomicBool::new(false) });
+    let Some((_, region)) = target else {
+        frame.failed();
+        return;
+    };
+    let (w, h) = (region.size.w as u32, region.size.h as u32);
+    frame.buffer(wl_shm::Format::Xrgb8888, w, h, w * 4);
+    if frame.version() >= 3 {
+        frame.linux_dmabuf(Fourcc::Xrgb8888 as u32, w, h);
+        frame.buffer_done();
+    }
+}
+
+/// Hand the copy to the frame's virtual output: served from the newest
+/// frame right away, or held for the next one.
+fn copy_frame(state: &mut GameframeState, frame: &ZwlrScreencopyFrameV1, data: &FrameData, buffer: WlBuffer, with_damage: bool) {
+    if data.copied.swap(true, Ordering::Relaxed) {
+        frame.post_error(zwlr_screencopy_frame_v1::Error::AlreadyUsed, "frame was already copied");
+        return;
+    }
+    let Some((output, region)) = &data.target else {
+        frame.failed();
+        return;
+    };
+    if let Err(msg) = check(&buffer, region.size) {
+        frame.post_error(zwlr_screencopy_frame_v1::Error::InvalidBuffer, msg);
+        return;
+    }
+    let now = Duration::from(state.clock.now());
+    let capture = Capture { frame: frame.clone(), buffer, region: *region, with_damage };
+    let Some(HeadlessBackend { renderer, outputs, .. }) = state.backend.as_mut().and_then(|b| b.headless_mut()) else {
+        return capture.fail();
+    };
+    let Some(out) = outputs.iter_mut().find(|o| &o.output == output) else {
+        return capture.fail();
+    };
+    if out.capture(renderer, capture, now) && !with_damage {
+        state.queue_redraw(output);
+    }
+}
+
+impl GlobalDispatch<ZwlrScreencopyManagerV1, ()> for GameframeState {
+    fn bind(
+        _state:    &mut Self,
+        _dh:       &DisplayHandle,
+        _client:   &Client,
+        resource:  New<ZwlrScreencopyManagerV1>,
+        _data:     &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<ZwlrScreencopyManagerV1, ()> for GameframeState {
+    fn request(
+        state:     &mut Self,
+        _client:   &Client,
+        _resource: &ZwlrScreencopyManagerV1,
+        request:   zwlr_screencopy_manager_v1::Request,
+        _data:     &(),
+        _dh:       &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput { frame, output, .. } => {
+                capture_output(state, data_init, frame, &output, None);
+            }
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion { frame, output, x, y, width, height, .. } => {
+                let region = Rectangle::new((x, y).into(), (width, height).into());
+                capture_output(state, data_init, frame, &output, Some(region));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, FrameData> for GameframeState {
+    fn request(
+        state:     &mut Self,
+        _client:   &Client,
+        resource:  &ZwlrScreencopyFrameV1,
+        request:   zwlr_screencopy_frame_v1::Request,
+        data:      &FrameData,
+        _dh:       &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => copy_frame(state, resource, data, buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => copy_frame(state, resource, data, buffer, true),
+            _ => {}
+        }
+    }
+}
@@ -2,17 +2,37 @@ use std::path::PathBuf;
 use anyhow::Result;
 use tracing::{error, info, warn};
 use gameframe_gpu::GpuVendor;
-use crate::{compositor, Config};
+use crate::{compositor, gpu_check, Config};
+
+/// Which backend the compositor drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackendKind {
+    /// Pick one: DRM on a VT, nested inside a Wayland session, otherwise
+    /// headless.
+    #[default]
+    Auto,
+    /// Own the GPU via DRM/KMS and libinput (run from a TTY).
+    Drm,
+    /// Run as a window inside an existing Wayland session.
+    WaylandNested,
+    /// No display: virtual outputs rendered offscreen for streaming.
+    Headless,
+}
 
 pub struct SessionOptions {
+    pub backend:      BackendKind,
     pub gpu_vendor:   Option<GpuVendor>,
-    pub drm_device:   Option<PathBuf>,
+    /// DRM node path or PCI address of the GPU to use; auto-detected if unset.
+    pub drm_device:   Option<String>,
     pub initial_exec: Option<String>,
     pub config:       Config,
+    /// File `config` was read from; re-read by the reload binding.
+    pub config_path:  Option<PathBuf>,
 }
 
 pub async fn run_session(opts: SessionOptions) -> Result<()> {
     info!(
+        backend  = ?opts.backend,
         gpu      = %opts.gpu_vendor.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "auto".into()),
         fps_cap  = opts.config.display.fps_cap,
         hdr      = opts.config.display.hdr,
@@ -73,6 +93,12 @@ pub async fn print_status() -> Result<()> {
     } else {
         println!("No active Gameframe session.");
     }
+    for failure in gpu_check::recorded() {
+        println!("  {} failed at {}: {}", failure.device, failure.stage, failure.error);
+        for hint in &failure.hints {
+            println!("    hint: {hint}");
+        }
+    }
     Ok(())
 }
 
@@ -0,0 +1,215 @@
+use smithay::backend::renderer::gles::{GlesRenderer, GlesTexProgram, UniformName, UniformType};
+use tracing::warn;
+
+/// FSR 1.0 edge-adaptive spatial upsampling (EASU), one pass from the
+/// small texture (`texel` apart) to the output size: a 12-tap Lanczos-2
+/// whose kernel is stretched along the local edge direction, clamped to the
+/// nearest four texels to avoid ringing.
+const EASU_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision highp float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+uniform vec2 texel;
+uniform float strength;
+
+vec3 fetch(vec2 base, float x, float y) {
+    return texture2D(tex, base + vec2(x, y) * texel).rgb;
+}
+
+float luma(vec3 c) {
+    return c.g + 0.5 * (c.r + c.b);
+}
+
+// Edge direction and length from one bilinear quadrant (a above, b left,
+// c centre, d right, e below), weighted by `w`.
+void edge(inout vec2 dir, inout float len, float w, float a, float b, float c, float d, float e) {
+    float lenX = abs(d - b) / max(max(abs(d - c), abs(c - b)), 1e-5);
+    lenX = clamp(lenX, 0.0, 1.0);
+    dir.x += (d - b) * w;
+    len   += lenX * lenX * w;
+    float lenY = abs(e - a) / max(max(abs(e - c), abs(c - a)), 1e-5);
+    lenY = clamp(lenY, 0.0, 1.0);
+    dir.y += (e - a) * w;
+    len   += lenY * lenY * w;
+}
+
+void tap(inout vec3 acc, inout float wsum, vec2 off, vec2 dir, vec2 len2, float lob, float clp, vec3 c) {
+    vec2 v = vec2(dot(off, dir), dot(off, vec2(-dir.y, dir.x))) * len2;
+    float d2 = min(dot(v, v), clp);
+    float wb = 0.4 * d2 - 1.0;
+    float wa = lob * d2 - 1.0;
+    wb *= wb;
+    wa *= wa;
+    float w = (1.5625 * wb - 0.5625) * wa;
+    acc  += c * w;
+    wsum += w;
+}
+
+void main() {
+    vec2 pp = v_coords / texel - 0.5;
+    vec2 fp = floor(pp);
+    pp -= fp;
+    vec2 base = (fp + 0.5) * texel;
+
+    //    b c
+    //  e f g h
+    //  i j k l
+    //    n o
+    vec3 b = fetch(base,  0.0, -1.0), c = fetch(base,  1.0, -1.0);
+    vec3 e = fetch(base, -1.0,  0.0), f = fetch(base,  0.0,  0.0);
+    vec3 g = fetch(base,  1.0,  0.0), h = fetch(base,  2.0,  0.0);
+    vec3 i = fetch(base, -1.0,  1.0), j = fetch(base,  0.0,  1.0);
+    vec3 k = fetch(base,  1.0,  1.0), l = fetch(base,  2.0,  1.0);
+    vec3 n = fetch(base,  0.0,  2.0), o = fetch(base,  1.0,  2.0);
+    float bL = luma(b), cL = luma(c), eL = luma(e), fL = luma(f), gL = luma(g), hL = luma(h);
+    float iL = luma(i), jL = luma(j), kL = luma(k), lL = luma(l), nL = luma(n), oL = luma(o);
+
+    vec2 dir = vec2(0.0);
+    float len = 0.0;
+    edge(dir, len, (1.0 - pp.x) * (1.0 - pp.y), bL, eL, fL, gL, jL);
+    edge(dir, len, pp.x * (1.0 - pp.y),         cL, fL, gL, hL, kL);
+    edge(dir, len, (1.0 - pp.x) * pp.y,         fL, iL, jL, kL, nL);
+    edge(dir, len, pp.x * pp.y,                 gL, jL, kL, lL, oL);
+
+    float dirR = dot(dir, dir);
+    bool flatArea = dirR < 1.0 / 32768.0;
+    dir = flatArea ? vec2(1.0, 0.0) : dir * inversesqrt(dirR);
+    len = 0.5 * len;
+    len *= len;
+    float stretch = 1.0 / max(abs(dir.x), abs(dir.y));
+    vec2 len2 = vec2(1.0 + (stretch - 1.0) * len, 1.0 - 0.5 * len);
+    float lob = 0.5 - 0.29 * len;
+    float clp = 1.0 / lob;
+
+    vec3 acc = vec3(0.0);
+    float wsum = 0.0;
+    tap(acc, wsum, vec2( 0.0, -1.0) - pp, dir, len2, lob, clp, b);
+    tap(acc, wsum, vec2( 1.0, -1.0) - pp, dir, len2, lob, clp, c);
+    tap(acc, wsum, vec2(-1.0,  1.0) - pp, dir, len2, lob, clp, i);
+    tap(acc, wsum, vec2( 0.0,  1.0) - pp, dir, len2, lob, clp, j);
+    tap(acc, wsum, vec2( 0.0,  0.0) - pp, dir, len2, lob, clp, f);
+    tap(acc, wsum, vec2(-1.0,  0.0) - pp, dir, len2, lob, clp, e);
+    tap(acc, wsum, vec2( 1.0,  1.0) - pp, dir, len2, lob, clp, k);
+    tap(acc, wsum, vec2( 2.0,  1.0) - pp, dir, len2, lob, clp, l);
+    tap(acc, wsum, vec2( 2.0,  0.0) - pp, dir, len2, lob, clp, h);
+    tap(acc, wsum, vec2( 1.0,  0.0) - pp, dir, len2, lob, clp, g);
+    tap(acc, wsum, vec2( 1.0,  2.0) - pp, dir, len2, lob, clp, o);
+    tap(acc, wsum, vec2( 0.0,  2.0) - pp, dir, len2, lob, clp, n);
+
+    vec3 lo = min(min(f, g), min(j, k));
+    vec3 hi = max(max(f, g), max(j, k));
+    vec4 color = vec4(clamp(acc / wsum, lo, hi), 1.0);
+#if !defined(NO_ALPHA)
+    color.a = texture2D(tex, v_coords).a;
+#endif
+    color *= alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.3, 0.0, 0.2) + color * 0.8;
+#endif
+    gl_FragColor = color;
+}
+"#;
+
+/// FSR 1.0 robust contrast-adaptive sharpening (RCAS) of the upscaled
+/// frame (`texel` apart), at `strength` 0 (none) to 1 (FSR's maximum). The
+/// sharpening lobe is limited so it never pushes a pixel past its
+/// neighbours' range.
+const RCAS_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+uniform vec2 texel;
+uniform float strength;
+
+void main() {
+    vec4 centre = texture2D(tex, v_coords);
+    vec3 e = centre.rgb;
+    vec3 b = texture2D(tex, v_coords + vec2(0.0, -texel.y)).rgb;
+    vec3 d = texture2D(tex, v_coords + vec2(-texel.x, 0.0)).rgb;
+    vec3 f = texture2D(tex, v_coords + vec2( texel.x, 0.0)).rgb;
+    vec3 h = texture2D(tex, v_coords + vec2(0.0,  texel.y)).rgb;
+
+    vec3 mn = min(min(b, d), min(f, h));
+    vec3 mx = max(max(b, d), max(f, h));
+    vec3 hitMin = min(mn, e) / (4.0 * mx + 1e-5);
+    vec3 hitMax = (1.0 - max(mx, e)) / (4.0 * min(mn, e) - 4.0 - 1e-5);
+    vec3 lobeRGB = max(-hitMin, hitMax);
+    float lobe = max(-0.1875, min(max(lobeRGB.r, max(lobeRGB.g, lobeRGB.b)), 0.0)) * strength;
+
+    vec4 color = vec4((lobe * (b + d + f + h) + e) / (4.0 * lobe + 1.0), centre.a);
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0);
+#endif
+    color *= alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.3, 0.0, 0.2) + color * 0.8;
+#endif
+    gl_FragColor = color;
+}
+"#;
+
+/// The two FSR passes. Both take the `texel` size of the texture they read
+/// and a `strength` (unused by EASU).
+#[derive(Clone)]
+pub struct FsrShaders {
+    pub easu: GlesTexProgram,
+    pub rcas: GlesTexProgram,
+}
+
+/// The FSR programs for `renderer`'s context, compiled on first use.
+/// `None` if compilation failed (logged once; upscaling is then bilinear).
+pub fn fsr_shaders(renderer: &mut GlesRenderer) -> Option<FsrShaders> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<Option<FsrShaders>>() {
+        return cached.clone();
+    }
+    let uniforms = [
+        UniformName::new("texel", UniformType::_2f),
+        UniformName::new("strength", UniformType::_1f),
+    ];
+    let easu = renderer.compile_custom_texture_shader(EASU_SRC, &uniforms);
+    let rcas = renderer.compile_custom_texture_shader(RCAS_SRC, &uniforms);
+    let shaders = match (easu, rcas) {
+        (Ok(easu), Ok(rcas)) => Some(FsrShaders { easu, rcas }),
+        (Err(e), _) | (_, Err(e)) => { warn!("FSR shaders: {e:?}"); None }
+    };
+    renderer.egl_context().user_data().insert_if_missing(|| shaders.clone());
+    shaders
+}
@@ -0,0 +1,48 @@
+use std::{collections::HashMap, process::Command};
+
+use tracing::{info, warn};
+
+use crate::state::GameframeState;
+
+/// What every child starts with, so games and toolkits that would pick X11
+/// (or ask XWayland) by default talk Wayland natively.
+const WAYLAND_ENV: [(&str, &str); 6] = [
+    ("XDG_SESSION_TYPE",   "wayland"),
+    ("GDK_BACKEND",        "wayland"),
+    ("QT_QPA_PLATFORM",    "wayland"),
+    ("SDL_VIDEODRIVER",    "wayland"),
+    ("CLUTTER_BACKEND",    "wayland"),
+    ("MOZ_ENABLE_WAYLAND", "1"),
+];
+
+/// `sh -c command` connected to `wayland_display`, with the defaults above
+/// and then `session.env` on top. An empty value in `session.env` removes
+/// the variable instead, e.g. `SDL_VIDEODRIVER = ""` for a game whose
+/// bundled SDL has no Wayland support.
+pub fn shell(command: &str, wayland_display: &str, env: &HashMap<String, String>) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]).env("WAYLAND_DISPLAY", wayland_display);
+    for (key, value) in WAYLAND_ENV {
+        if !env.contains_key(key) {
+            cmd.env(key, value);
+        }
+    }
+    for (key, value) in env {
+        if value.is_empty() {
+            cmd.env_remove(key);
+        } else {
+            cmd.env(key, value);
+        }
+    }
+    cmd
+}
+
+/// Start `command` in the session (launch bindings, idle commands). A
+/// failure to start is logged, not returned.
+pub fn launch(state: &GameframeState, command: &str) {
+    if command.is_empty() { return; }
+    info!(command, "Launching");
+    if let Err(e) = shell(command, &state.socket_name, &state.config.session.env).spawn() {
+        warn!(command, "spawn: {e}");
+    }
+}
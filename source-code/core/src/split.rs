@@ -0,0 +1,107 @@
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
+    utils::{Logical, Point, Rectangle, Size},
+};
+use tracing::info;
+
+use crate::config::SplitOrientation;
+
+/// A window taking part in split-screen, with where it was before.
+struct SplitPane {
+    window:    Window,
+    prev_loc:  Point<i32, Logical>,
+    prev_size: Size<i32, Logical>,
+}
+
+/// Two windows shown fullscreen in the halves of one output (couch co-op).
+/// Each client is told it is fullscreen at half the output size; input
+/// coordinates need no remapping since each pane is an ordinary window
+/// placed at its half in the layout.
+pub struct SplitScreen {
+    pub output: Output,
+    panes:      [SplitPane; 2],
+}
+
+impl SplitScreen {
+    /// Lay `windows` (currently at `locs`) out over the halves of `area`,
+    /// the output's logical rectangle.
+    pub fn new(
+        output:      Output,
+        area:        Rectangle<i32, Logical>,
+        orientation: SplitOrientation,
+        windows:     [(Window, Point<i32, Logical>); 2],
+    ) -> Self {
+        let halves = halves(area, orientation);
+        let panes = windows.map(|(window, prev_loc)| {
+            let prev_size = window.geometry().size;
+            SplitPane { window, prev_loc, prev_size }
+        });
+        for (pane, half) in panes.iter().zip(halves) {
+            configure(&pane.window, Some(half.size), true);
+        }
+        info!(output = output.name(), ?orientation, "Split-screen on");
+        Self { output, panes }
+    }
+
+    pub fn contains(&self, window: &Window) -> bool { self.panes.iter().any(|p| &p.window == window) }
+
+    /// Window of pane `idx` (0: left/top, 1: right/bottom).
+    pub fn pane(&self, idx: usize) -> Option<Window> { self.panes.get(idx).map(|p| p.window.clone()) }
+
+    /// Windows and their pane rectangles.
+    pub fn layout(&self, area: Rectangle<i32, Logical>, orientation: SplitOrientation) -> [(Window, Point<i32, Logical>); 2] {
+        let halves = halves(area, orientation);
+        [
+            (self.panes[0].window.clone(), halves[0].loc),
+            (self.panes[1].window.clone(), halves[1].loc),
+        ]
+    }
+
+    /// Take the windows out of fullscreen again. Returns the surviving
+    /// windows with the location to put them back at.
+    pub fn end(self, gone: Option<&Window>) -> Vec<(Window, Point<i32, Logical>)> {
+        info!("Split-screen off");
+        self.panes
+            .into_iter()
+            .filter(|p| Some(&p.window) != gone)
+            .map(|p| {
+                configure(&p.window, Some(p.prev_size).filter(|s| !s.is_empty()), false);
+                (p.window, p.prev_loc)
+            })
+            .collect()
+    }
+}
+
+fn halves(area: Rectangle<i32, Logical>, orientation: SplitOrientation) -> [Rectangle<i32, Logical>; 2] {
+    match orientation {
+        SplitOrientation::Horizontal => {
+            let w = area.size.w / 2;
+            [
+                Rectangle::new(area.loc, (w, area.size.h).into()),
+                Rectangle::new(area.loc + Point::from((w, 0)), (area.size.w - w, area.size.h).into()),
+            ]
+        }
+        SplitOrientation::Vertical => {
+            let h = area.size.h / 2;
+            [
+                Rectangle::new(area.loc, (area.size.w, h).into()),
+                Rectangle::new(area.loc + Point::from((0, h)), (area.size.w, area.size.h - h).into()),
+            ]
+        }
+    }
+}
+
+fn configure(window: &Window, size: Option<Size<i32, Logical>>, fullscreen: bool) {
+    let Some(toplevel) = window.toplevel() else { return };
+    toplevel.with_pending_state(|s| {
+        s.size = size;
+        if fullscreen {
+            s.states.set(xdg_toplevel::State::Fullscreen);
+        } else {
+            s.states.unset(xdg_toplevel::State::Fullscreen);
+        }
+    });
+    toplevel.send_pending_configure();
+}
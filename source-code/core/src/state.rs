@@ -1,43 +1,161 @@
 use smithay::{
-    delegate_compositor, delegate_data_device, delegate_dmabuf,
-    delegate_layer_shell, delegate_output, delegate_primary_selection,
-    delegate_seat, delegate_shm, delegate_xdg_shell,
-    desktop::{Space, Window},
-    input::{pointer::CursorImageStatus, Seat, SeatState},
+    delegate_alpha_modifier, delegate_compositor, delegate_content_type, delegate_data_device, delegate_dmabuf,
+    delegate_fractional_scale, delegate_idle_inhibit, delegate_layer_shell, delegate_output, delegate_pointer_constraints,
+    delegate_presentation, delegate_primary_selection, delegate_relative_pointer, delegate_seat, delegate_shm,
+    delegate_xdg_shell, delegate_xwayland_shell,
+    desktop::{
+        find_popup_root_surface, get_popup_toplevel_coords, layer_map_for_output, LayerSurface as DesktopLayer,
+        PopupKeyboardGrab, PopupKind, PopupManager, PopupPointerGrab, PopupUngrabStrategy, Space, Window,
+        WindowSurfaceType,
+    },
+    input::{keyboard::{LedState, XkbConfig}, pointer::{CursorImageStatus, Focus, PointerHandle}, Seat, SeatState},
+    output::Output,
     reexports::{
         calloop::LoopHandle,
+        wayland_protocols::{
+            wp::{
+                alpha_modifier::v1::server::wp_alpha_modifier_v1::WpAlphaModifierV1,
+                content_type::v1::server::wp_content_type_manager_v1::WpContentTypeManagerV1,
+                fractional_scale::v1::server::wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1,
+                idle_inhibit::zv1::server::zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1,
+                pointer_constraints::zv1::server::zwp_pointer_constraints_v1::ZwpPointerConstraintsV1,
+                presentation_time::server::wp_presentation::WpPresentation,
+                primary_selection::zv1::server::zwp_primary_selection_device_manager_v1::ZwpPrimarySelectionDeviceManagerV1,
+                relative_pointer::zv1::server::zwp_relative_pointer_manager_v1::ZwpRelativePointerManagerV1,
+            },
+            xdg::{
+                shell::server::{xdg_toplevel, xdg_wm_base::XdgWmBase},
+                xdg_output::zv1::server::zxdg_output_manager_v1::ZxdgOutputManagerV1,
+            },
+            xwayland::shell::v1::server::xwayland_shell_v1::XwaylandShellV1,
+        },
+        wayland_protocols_wlr::layer_shell::v1::server::zwlr_layer_shell_v1::ZwlrLayerShellV1,
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
-            protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface},
+            protocol::{
+                wl_buffer::WlBuffer, wl_compositor::WlCompositor, wl_data_device_manager::{DndAction, WlDataDeviceManager},
+                wl_data_source::WlDataSource, wl_output::WlOutput, wl_seat::WlSeat, wl_shm::WlShm,
+                wl_subcompositor::WlSubcompositor, wl_surface::WlSurface,
+            },
             Display, DisplayHandle, Resource,   // FIX: Resource for .id()
         },
     },
-    utils::{Clock, Logical, Monotonic, Point, Serial, SERIAL_COUNTER},
+    utils::{Clock, Logical, Monotonic, Point, Rectangle, Serial, Size, SERIAL_COUNTER},
     wayland::{
+        alpha_modifier::AlphaModifierState,
         buffer::BufferHandler,
-        compositor::{CompositorClientState, CompositorHandler, CompositorState},
+        compositor::{
+            get_parent, is_sync_subsurface, send_surface_state, with_states,
+            CompositorClientState, CompositorHandler, CompositorState,
+        },
+        content_type::{ContentType, ContentTypeState, ContentTypeSurfaceCachedState},
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler, FractionalScaleManagerState},
+        idle_inhibit::{IdleInhibitHandler, IdleInhibitManagerState},
         dmabuf::{DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
         output::{OutputHandler, OutputManagerState},
+        pointer_constraints::{PointerConstraintsHandler, PointerConstraintsState},
+        presentation::PresentationState,
+        relative_pointer::RelativePointerManagerState,
         selection::{
             data_device::{
                 ClientDndGrabHandler, DataDeviceHandler, DataDeviceState,
                 ServerDndGrabHandler,
             },
             primary_selection::{PrimarySelectionHandler, PrimarySelectionState},
-            SelectionHandler,
+            SelectionHandler, SelectionSource, SelectionTarget,
         },
         shell::{
-            wlr_layer::{Layer, LayerSurface, WlrLayerShellHandler, WlrLayerShellState},
-            xdg::{PopupSurface, PositionerState, ToplevelSurface, XdgShellHandler, XdgShellState},
+            wlr_layer::{Layer, LayerSurface, LayerSurfaceData, WlrLayerShellHandler, WlrLayerShellState},
+            xdg::{
+                PopupSurface, PositionerState, SurfaceCachedState, ToplevelSurface, XdgShellHandler,
+                XdgShellState,
+            },
         },
+        seat::WaylandFocus,
         shm::{ShmHandler, ShmState},
+        xwayland_shell::XWaylandShellState,
     },
+    xwayland::XWaylandClientData,
     backend::allocator::Buffer,   // FIX: for dmabuf.format()
 };
 
+use std::{cell::RefCell, os::fd::OwnedFd, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use drm::control::Mode;
 use gameframe_input::InputManager;
 use gameframe_overlay::Overlay;
-use crate::{config::Config, window::WindowStack};
+use crate::{
+    accessx::AccessX,
+    brightness::Brightness,
+    backlog::Backlog,
+    buffers::{Buffers, ClientUsage},
+    click::{ClickTracker, WindowDrag},
+    hover_focus::HoverFocus,
+    clipboard::{self, Clipboard},
+    animation::{bbox_origin, snapshot_window, Animations},
+    compositor::{update_keyboard_leds, Backend},
+    cursor::{self, CursorTheme},
+    constraints,
+    dnd::{self, Drag},
+    frame::FramePacer,
+    config::{AdaptiveSyncMode, ColorFilter, Config, DimInactive, TearingMode},
+    geometry_store::GeometryStore,
+    headless::render_virtual,
+    idle::Idle,
+    input_handler::pointer_moved,
+    magnifier::Magnifier,
+    metrics::Metrics,
+    nested::render_nested,
+    players::Players,
+    presets::Preset,
+    recorder::Recorder,
+    restore::Restore,
+    render::render_output,
+    rules,
+    split::SplitScreen,
+    test_pattern::TestPattern,
+    fps_overlay::FpsOverlay,
+    touch::{self, Gestures},
+    upscale,
+    window::WindowStack,
+    workspace::Workspaces,
+    xwayland::{self, XWaylandState},
+};
+
+/// Offset between cascaded new windows (logical px).
+const CASCADE_STEP: i32 = 32;
+
+/// `point` moved into `rect` (the last pixel row and column included).
+fn clamp_into(rect: Rectangle<i32, Logical>, point: Point<f64, Logical>) -> Point<f64, Logical> {
+    let (x, y) = (rect.loc.x as f64, rect.loc.y as f64);
+    Point::from((
+        point.x.clamp(x, x + rect.size.w as f64 - 1.0),
+        point.y.clamp(y, y + rect.size.h as f64 - 1.0),
+    ))
+}
+
+fn distance_sq(a: Point<f64, Logical>, b: Point<f64, Logical>) -> f64 {
+    let d = a - b;
+    d.x * d.x + d.y * d.y
+}
+
+/// Kept on a fullscreen window: the output it covers, and its geometry from
+/// before (none if it went fullscreen before it was mapped).
+struct Fullscreen(RefCell<Option<(Output, Option<Rectangle<i32, Logical>>)>>);
+
+/// The same for a maximized window.
+struct Maximized(RefCell<Option<(Output, Option<Rectangle<i32, Logical>>)>>);
+
+/// The output `window` is fullscreen on, if it is.
+fn fullscreen_output(window: &Window) -> Option<Output> {
+    window.user_data().get::<Fullscreen>()?.0.borrow().as_ref().map(|(output, _)| output.clone())
+}
+
+/// The output `window` is maximized on, if it is.
+fn maximized_output(window: &Window) -> Option<Output> {
+    window.user_data().get::<Maximized>()?.0.borrow().as_ref().map(|(output, _)| output.clone())
+}
 
 // ── Central state ─────────────────────────────────────────────────────────────
 
@@ -53,21 +171,88 @@ pub struct GameframeState {
     pub primary_selection: PrimarySelectionState,
     pub dmabuf_state:      DmabufState,
     pub dmabuf_global:     Option<DmabufGlobal>,
+    pub content_type:      ContentTypeState,
+    pub fractional_scale:  FractionalScaleManagerState,
+    pub alpha_modifier:    AlphaModifierState,
+    pub presentation:      PresentationState,
+    pub idle_inhibit:      IdleInhibitManagerState,
+    pub pointer_constraints: PointerConstraintsState,
+    pub relative_pointer:  RelativePointerManagerState,
+    pub xwayland_shell:    XWaylandShellState,
+    /// Interfaces of the globals created with the state, for `gameframe
+    /// tree`; outputs and the dmabuf global come and go with the backend.
+    pub globals:           Vec<&'static str>,
 
     pub space:            Space<Window>,
+    /// Toplevels that haven't committed their first buffer yet.
+    pub unmapped:         Vec<Window>,
+    pub popups:           PopupManager,
+    /// Client buffers attached to surfaces, checked on commit.
+    pub buffers:          Buffers,
+    /// Clients with events their full socket couldn't take yet.
+    pub backlog:          Backlog,
+    /// Counters for the metrics endpoint.
+    pub metrics:          Metrics,
     pub window_stack:     WindowStack,
     pub seat:             Seat<Self>,
     pub cursor_status:    CursorImageStatus,
+    pub cursor_theme:     CursorTheme,
     pub pointer_location: Point<f64, Logical>,
+    pub clicks:           ClickTracker,
+    pub hover_focus:      HoverFocus,
+    /// Window being moved with Super + drag.
+    pub window_drag:      Option<WindowDrag>,
+    /// Client drag-and-drop in progress.
+    pub dnd:              Option<Drag>,
+    /// Touches on the touchscreen and gestures in progress.
+    pub gestures:         Gestures,
+    /// `display.fps_cap`, applied by every DRM output's repaint loop.
+    pub frame_pacer:      FramePacer,
 
     pub config:        Config,
     pub overlay:       Overlay,
     pub input_manager: InputManager,
+    pub accessx:       AccessX,
+    pub magnifier:     Magnifier,
+    pub color_filter:  ColorFilter,
+    pub brightness:    Brightness,
+    /// Screen recording in progress, if any.
+    pub recorder:      Option<Recorder>,
+    /// Calibration pattern replacing an output's content, if any.
+    pub test_pattern:  Option<TestPattern>,
+    pub fps_overlay:   FpsOverlay,
     pub running:       bool,
     pub clock:         Clock<Monotonic>,
     pub loop_handle:   LoopHandle<'static, Self>,
     pub socket_name:   String,
+    /// Config file, for reloading; `None` if the session has none.
+    pub config_path:   Option<PathBuf>,
     pub last_frame_us: u64,
+    pub animations:    Animations,
+    pub workspaces:    Workspaces,
+    /// Left-stick deflection of a gamepad driving the pointer (-1..1 each).
+    pub gamepad_stick:  [f64; 2],
+    /// Active split-screen layout, if any.
+    pub split:          Option<SplitScreen>,
+    /// Input devices bound to windows, with their seats.
+    pub players:        Players,
+    pub idle:           Idle,
+    pub clipboard:      Clipboard,
+    /// Latency / quality preset last switched to, if any.
+    pub preset:         Option<Preset>,
+    /// Per-app window geometry; `None` unless `session.remember_geometry`.
+    pub geometry_store: Option<GeometryStore>,
+    /// Layout left by `gameframe restart`, applied as windows reappear.
+    pub restore:        Restore,
+    /// Start again once the event loop exits (`gameframe restart`).
+    pub restart:        bool,
+    /// Output of the window with keyboard focus.
+    pub focused_output: Option<Output>,
+    pub xwayland:       XWaylandState,
+
+    /// Renderer and per-output scanout state of the DRM or nested backend;
+    /// `None` until the backend is initialised.
+    pub backend: Option<Backend>,
 }
 
 impl GameframeState {
@@ -90,7 +275,42 @@ impl GameframeState {
         let primary_selection = PrimarySelectionState::new::<Self>(&dh);
         let seat              = seat_state.new_wl_seat(&dh, "gameframe-seat0");
         let dmabuf_state      = DmabufState::new();
+        let content_type      = ContentTypeState::new::<Self>(&dh);
+        let fractional_scale  = FractionalScaleManagerState::new::<Self>(&dh);
+        let alpha_modifier    = AlphaModifierState::new::<Self>(&dh);
+        let presentation      = PresentationState::new::<Self>(&dh, clock.id() as u32);
+        let idle_inhibit      = IdleInhibitManagerState::new::<Self>(&dh);
+        let pointer_constraints = PointerConstraintsState::new::<Self>(&dh);
+        let relative_pointer  = RelativePointerManagerState::new::<Self>(&dh);
+        let xwayland_shell    = XWaylandShellState::new::<Self>(&dh);
+        let globals = [
+            WlCompositor::interface(),
+            WlSubcompositor::interface(),
+            XdgWmBase::interface(),
+            ZwlrLayerShellV1::interface(),
+            WlShm::interface(),
+            ZxdgOutputManagerV1::interface(),
+            WlDataDeviceManager::interface(),
+            ZwpPrimarySelectionDeviceManagerV1::interface(),
+            WlSeat::interface(),
+            WpContentTypeManagerV1::interface(),
+            WpFractionalScaleManagerV1::interface(),
+            WpAlphaModifierV1::interface(),
+            WpPresentation::interface(),
+            ZwpIdleInhibitManagerV1::interface(),
+            ZwpPointerConstraintsV1::interface(),
+            ZwpRelativePointerManagerV1::interface(),
+            XwaylandShellV1::interface(),
+        ]
+        .iter()
+        .map(|interface| interface.name)
+        .collect();
 
+        let animations    = Animations::new(&config.animations);
+        let geometry_store = config.session.remember_geometry.then(GeometryStore::load);
+        let accessx        = AccessX::new(&config.accessibility);
+        let color_filter   = config.accessibility.color_filter;
+        let brightness     = Brightness::detect(&config.display);
         let overlay       = Overlay::new(config.overlay.width, config.overlay.height);
         let input_manager = InputManager::new(gameframe_input::default_keybindings())
             .expect("InputManager::new");
@@ -107,38 +327,1110 @@ impl GameframeState {
             primary_selection,
             dmabuf_state,
             dmabuf_global: None,
+            content_type,
+            fractional_scale,
+            alpha_modifier,
+            presentation,
+            idle_inhibit,
+            pointer_constraints,
+            relative_pointer,
+            xwayland_shell,
+            globals,
             space: Space::default(),
+            unmapped: Vec::new(),
+            popups: PopupManager::default(),
+            buffers: Buffers::default(),
+            backlog: Backlog::default(),
+            metrics: Metrics::default(),
             window_stack: WindowStack::new(),
             seat,
             cursor_status:    CursorImageStatus::default_named(),
+            cursor_theme:     CursorTheme::load(),
             pointer_location: Point::from((0.0, 0.0)),
+            clicks:           ClickTracker::new(&config.input),
+            hover_focus:      HoverFocus::default(),
+            window_drag:      None,
+            dnd:              None,
+            gestures:         Gestures::default(),
+            frame_pacer:      FramePacer::new(config.display.fps_cap),
             config,
             overlay,
             input_manager,
+            accessx,
+            magnifier:     Magnifier::new(),
+            color_filter,
+            brightness,
+            recorder:      None,
+            test_pattern:  None,
+            fps_overlay:   FpsOverlay::default(),
             running:      true,
             clock,
             loop_handle,
             socket_name,
+            config_path:   None,
             last_frame_us: 0,
+            animations,
+            workspaces:    Workspaces::new(),
+            gamepad_stick: [0.0; 2],
+            split:         None,
+            players:       Players::default(),
+            idle:          Idle::default(),
+            clipboard:     Clipboard::default(),
+            preset:        None,
+            geometry_store,
+            restore:       Restore::default(),
+            restart:       false,
+            focused_output: None,
+            xwayland:       XWaylandState::default(),
+            backend:       None,
         }
     }
 
-    /// Set keyboard focus to the topmost window.
+    /// Set keyboard focus to the topmost window on the active workspace.
+    /// The previously focused window's popups are dismissed; focus held by a
+    /// popup of the window that keeps focus is left alone.
     pub fn refresh_focus(&mut self) {
         // FIX: Serial::from(u32) not from Time<Monotonic>
         let serial = SERIAL_COUNTER.next_serial();
-        // FIX: WaylandFocus in scope → top_surface() works
-        if let Some(surface) = self.window_stack.top_surface() {
-            if let Some(kb) = self.seat.get_keyboard() {
-                kb.set_focus(self, Some(surface), serial);
+        let surface = self.window_stack
+            .iter()
+            .find(|w| !self.workspaces.is_stashed(w))
+            .and_then(|w| w.wl_surface())
+            .map(|cow| cow.into_owned());
+        let Some(kb) = self.seat.get_keyboard() else { return };
+        let current = kb.current_focus().map(|s| self.popup_root(&s));
+        if current == surface {
+            return;
+        }
+        if let Some(old) = current {
+            dismiss_popups(&old);
+        }
+        kb.set_focus(self, surface, serial);
+    }
+
+    /// Initial configure and first map of a toplevel that isn't in the space
+    /// yet. Returns `false` if `surface` isn't such a toplevel.
+    fn commit_unmapped(&mut self, surface: &WlSurface) -> bool {
+        let Some(idx) = self.unmapped.iter().position(|w| w.toplevel().is_some_and(|t| t.wl_surface() == surface)) else {
+            return false;
+        };
+        let window   = self.unmapped[idx].clone();
+        let toplevel = window.toplevel().unwrap().clone();
+        if !toplevel.is_initial_configure_sent() {
+            // No size: the client picks its natural size; the bounds tell it
+            // how much room the output has. A fullscreen or maximize request
+            // made before this already set the size.
+            let bounds = self.placement_output().and_then(|o| self.usable_area(&o)).map(|g| g.size);
+            let sized = fullscreen_output(&window).is_some() || maximized_output(&window).is_some();
+            toplevel.with_pending_state(|s| {
+                if !sized { s.size = None; }
+                s.bounds = bounds;
+            });
+            toplevel.send_configure();
+            return true;
+        }
+        window.on_commit();
+        if !window.bbox().is_empty() {
+            self.unmapped.remove(idx);
+            self.map_window(window);
+        }
+        true
+    }
+
+    /// Initial configure and relayout of a layer surface, or a repaint for
+    /// a popup or subsurface of one. Returns `false` if `surface` doesn't
+    /// belong to a layer surface.
+    fn commit_layer(&mut self, surface: &WlSurface) -> bool {
+        let mut root = self.popup_root(surface);
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        let Some(output) = self.layer_output(&root) else { return false };
+        if &root == surface {
+            let mut map = layer_map_for_output(&output);
+            let zone = map.non_exclusive_zone();
+            // Arranged before the initial configure, so that carries the size
+            // the anchors, margins and other layers' exclusive zones leave.
+            map.arrange();
+            if map.non_exclusive_zone() != zone {
+                tracing::debug!(output = %output.name(), zone = ?map.non_exclusive_zone(), "exclusive zone changed");
+            }
+            let initial = with_states(surface, |states| {
+                !states.data_map.get::<LayerSurfaceData>().is_some_and(|d| d.lock().unwrap().initial_configure_sent)
+            });
+            if initial {
+                if let Some(layer) = map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL) {
+                    layer.layer_surface().send_configure();
+                }
+            }
+        }
+        self.queue_redraw(&output);
+        true
+    }
+
+    /// Output whose layer map holds the layer surface `surface`.
+    fn layer_output(&self, surface: &WlSurface) -> Option<Output> {
+        self.space
+            .outputs()
+            .find(|o| layer_map_for_output(o).layer_for_surface(surface, WindowSurfaceType::TOPLEVEL).is_some())
+            .cloned()
+    }
+
+    /// Part of `output` not reserved by layer surfaces' exclusive zones
+    /// (panels, docks), in global coordinates; where new windows go.
+    pub fn usable_area(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let geo = self.space.output_geometry(output)?;
+        let mut zone = layer_map_for_output(output).non_exclusive_zone();
+        zone.loc += geo.loc;
+        Some(zone)
+    }
+
+    /// First free slot on the diagonal starting at `start`, so windows opened
+    /// one after another don't stack exactly on top of each other. Wraps back
+    /// to `start` when the window would leave `output`.
+    fn cascade(&self, start: Point<i32, Logical>, size: Size<i32, Logical>, output: Rectangle<i32, Logical>) -> Point<i32, Logical> {
+        let mut loc = start;
+        while self.space.elements().any(|w| self.space.element_location(w) == Some(loc)) {
+            loc += Point::from((CASCADE_STEP, CASCADE_STEP));
+            if !output.contains_rect(Rectangle::new(loc, size)) {
+                return start;
+            }
+        }
+        loc
+    }
+
+    /// Output new windows are placed on: the focused one, else the first.
+    pub fn placement_output(&self) -> Option<Output> {
+        self.focused_output.clone()
+            .filter(|o| self.space.outputs().any(|m| m == o))
+            .or_else(|| self.space.outputs().next().cloned())
+    }
+
+    /// Map a toplevel that just committed its first buffer. Where it was
+    /// before a `gameframe restart` wins, then a window rule position, then
+    /// the geometry remembered for the app; otherwise
+    /// the window opens at its natural size on the placement output, centred
+    /// and cascaded off windows already there. Windows larger than the output
+    /// are asked to shrink to it, within their min/max size hints. Windows
+    /// that asked for fullscreen or to be maximized before mapping go to
+    /// their output's corner (below panels, if maximized).
+    pub(crate) fn map_window(&mut self, window: Window) {
+        let rule     = rules::resolve(&self.config.window_rules, &window);
+        let mut loc  = Point::from((0, 0));
+        let restored = self.restore.take_window(&window);
+        let remembered = match &restored {
+            Some(placement) => Some(placement.geometry),
+            None            => self.remembered_geometry(&window).filter(|_| rule.position.is_none()),
+        };
+        if let Some(area) = fullscreen_output(&window).and_then(|o| self.space.output_geometry(&o)) {
+            loc = area.loc;
+        } else if let Some(area) = maximized_output(&window).and_then(|o| self.usable_area(&o)) {
+            loc = area.loc;
+        } else if let Some(geo) = remembered {
+            let size = constrain_window_size(&window, geo.size);
+            if size != window.geometry().size {
+                request_size(&window, size);
+            }
+            loc = geo.loc;
+        } else if let Some(out) = self.placement_output().and_then(|o| self.usable_area(&o)) {
+            let mut size = window.geometry().size;
+            if size.w > out.size.w || size.h > out.size.h {
+                size = constrain_window_size(&window, (size.w.min(out.size.w), size.h.min(out.size.h)).into());
+                request_size(&window, size);
+            }
+            loc = match rule.position {
+                Some([x, y]) => out.loc + Point::from((x, y)),
+                None => {
+                    let centre = out.loc + Point::from((((out.size.w - size.w) / 2).max(0), ((out.size.h - size.h) / 2).max(0)));
+                    self.cascade(centre, size, out)
+                }
+            };
+        }
+
+        // X11 clients place themselves: tell them where they ended up.
+        if let Some(x11) = window.x11_surface() {
+            let size = x11.geometry().size;
+            let _ = x11.configure(Rectangle::new(loc, size));
+        }
+
+        self.window_stack.push(window.clone());
+        if let Some(ws) = restored.map(|p| p.workspace).filter(|&ws| ws != self.workspaces.active()) {
+            self.workspaces.stash(ws, window, loc);
+            return;
+        }
+        self.space.map_element(window.clone(), loc, true);
+        self.send_scale_transform(&window);
+        self.animations.window_committed(&window, self.clock.now().into());
+        self.refresh_focus();
+        self.overlay.push_toast("Application launched", 180);
+        // FIX: use Resource trait for .id()
+        tracing::info!(
+            surface = ?window.wl_surface().map(|s| s.id()),
+            x11 = window.is_x11(),
+            "new toplevel – stack depth: {}", self.window_stack.len()
+        );
+        for output in self.space.outputs_for_element(&window) {
+            self.queue_redraw(&output);
+        }
+    }
+
+    /// Geometry remembered for `window`'s app, if it still fits on one of the
+    /// current outputs.
+    fn remembered_geometry(&self, window: &Window) -> Option<Rectangle<i32, Logical>> {
+        let store = self.geometry_store.as_ref()?;
+        let (app_id, title) = rules::window_identity(window);
+        let geo = store.lookup(app_id.as_deref()?, title.as_deref())?;
+        self.space
+            .outputs()
+            .filter_map(|o| self.space.output_geometry(o))
+            .any(|out| out.contains_rect(geo))
+            .then_some(geo)
+    }
+
+    /// Remember where `window` is for the next time its app opens one.
+    /// Remember where `window` was: in the space, or at `stashed` on an
+    /// inactive workspace.
+    fn record_geometry(&mut self, window: &Window, stashed: Option<Point<i32, Logical>>) {
+        let Some(store) = self.geometry_store.as_mut() else { return };
+        let (Some(app_id), title) = rules::window_identity(window) else { return };
+        let geo = match stashed {
+            Some(loc) => Rectangle::new(loc, window.geometry().size),
+            None => {
+                let Some(geo) = self.space.element_geometry(window) else { return };
+                geo
+            }
+        };
+        store.record(&app_id, title.as_deref(), geo);
+        if let Err(e) = store.save() {
+            tracing::warn!("saving window geometry: {e:#}");
+        }
+    }
+
+    /// `window` was closed or unmapped by its client, on the active
+    /// workspace or stashed on another: take it out of the stacking order,
+    /// drop its seat and split pane and remember where it was. A mapped
+    /// window also plays the close animation and hands focus on.
+    pub(crate) fn window_gone(&mut self, window: &Window) {
+        self.window_stack.remove(window);
+        let stashed = self.workspaces.remove_window(window);
+        if stashed.is_none() && self.space.element_location(window).is_none() { return; }
+        let outputs: Vec<Output> = self.space.outputs_for_element(window);
+        self.players.window_closed(&mut self.seat_state, window);
+        if self.split.as_ref().is_some_and(|s| s.contains(window)) {
+            self.end_split(Some(window));
+        } else {
+            self.record_geometry(window, stashed);
+        }
+        if stashed.is_some() { return; }
+
+        // Snapshot now – the client's buffers go away with its surface.
+        if self.animations.enabled() {
+            let location = self.space.element_location(window).unwrap_or_default();
+            let scale    = outputs.first().map_or(1.0, |o| o.current_scale().fractional_scale());
+            if let Some(renderer) = self.backend.as_mut().map(|b| b.renderer()) {
+                match snapshot_window(renderer, window, scale) {
+                    Ok(snapshot) => {
+                        let geometry = Rectangle::new(bbox_origin(window, location), window.bbox().size);
+                        self.animations.window_closed(window, snapshot, geometry, self.clock.now().into());
+                    }
+                    Err(e) => tracing::debug!("close animation skipped: {e:#}"),
+                }
+            }
+        }
+
+        self.space.unmap_elem(window);
+        self.refresh_focus();
+        for output in &outputs {
+            self.queue_redraw(output);
+        }
+    }
+
+    /// Apply the popup's positioner constraint adjustments (flip, slide,
+    /// resize) so it stays within the outputs its toplevel is on. Sets the
+    /// pending geometry; the caller sends the configure.
+    pub fn unconstrain_popup(&self, popup: &PopupSurface) {
+        let kind = PopupKind::Xdg(popup.clone());
+        let Some(window) = find_popup_root_surface(&kind).ok().and_then(|r| self.window_for_surface(&r)) else { return };
+        let Some(bounds) = self.space
+            .outputs_for_element(&window)
+            .iter()
+            .filter_map(|o| self.space.output_geometry(o))
+            .reduce(|a, b| a.merge(b))
+        else { return };
+        let Some(window_geo) = self.space.element_geometry(&window) else { return };
+
+        // The positioner works relative to the parent's window geometry.
+        let mut target = bounds;
+        target.loc -= get_popup_toplevel_coords(&kind) + window_geo.loc;
+        popup.with_pending_state(|s| s.geometry = s.positioner.get_unconstrained_geometry(target));
+    }
+
+    /// Toplevel surface a popup (possibly nested) belongs to; other surfaces
+    /// are returned as-is.
+    pub fn popup_root(&self, surface: &WlSurface) -> WlSurface {
+        self.popups
+            .find_popup(surface)
+            .and_then(|popup| find_popup_root_surface(&popup).ok())
+            .unwrap_or_else(|| surface.clone())
+    }
+
+    /// Change the opacity of the focused window by `delta` (runtime override
+    /// on top of window rules).
+    pub fn adjust_focused_opacity(&mut self, delta: f32) {
+        let Some(window) = self.focused_window() else { return };
+        let rule    = rules::resolve(&self.config.window_rules, &window);
+        let current = rules::window_opacity(&rule, &window, true);
+        let value   = rules::adjust_opacity(&window, current, delta);
+        tracing::info!(opacity = value, "Window opacity");
+        for output in self.space.outputs_for_element(&window) {
+            self.queue_redraw(&output);
+        }
+    }
+
+    /// Window with keyboard focus, if it is a mapped toplevel.
+    pub fn focused_window(&self) -> Option<Window> {
+        let focus = self.seat.get_keyboard()?.current_focus()?;
+        self.window_for_surface(&focus)
+    }
+
+    /// Switch to workspace `idx` (0-based), sliding if animations allow.
+    pub fn switch_workspace(&mut self, idx: usize) {
+        self.end_split(None);
+        let from = self.workspaces.active();
+        let Some(outgoing) = self.workspaces.switch_to(idx, &mut self.space) else { return };
+        let direction = if idx > from { 1 } else { -1 };
+        self.animations.workspace_switched(outgoing, direction, self.clock.now().into());
+        self.refresh_focus();
+        self.queue_redraw_all();
+        self.warp_to_focus();
+    }
+
+    /// Re-read the config file. Everything except `[display]` (which holds
+    /// the command-line overrides and is applied when outputs are set up) is
+    /// replaced; settings read as they are used, such as effects, window
+    /// rules and the keymap, take effect immediately.
+    pub fn reload_config(&mut self) {
+        let Some(path) = self.config_path.clone() else { return };
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|raw| Ok(toml::from_str::<Config>(&raw)?));
+        let mut config = match parsed {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!(path = %path.display(), "config reload failed: {e:#}");
+                self.overlay.push_toast("Config reload failed", 180);
+                return;
+            }
+        };
+        config.display = self.config.display.clone();
+        if config.accessibility.color_filter != self.config.accessibility.color_filter {
+            self.color_filter = config.accessibility.color_filter;
+        }
+        self.config = config;
+        self.accessx.configure(&self.config.accessibility);
+        self.clicks.configure(&self.config.input);
+        if let Err(e) = self.load_keymap() {
+            tracing::warn!("keeping previous keymap: {e:#}");
+        }
+        tracing::info!(path = %path.display(), "Config reloaded");
+        self.overlay.push_toast("Config reloaded", 120);
+        self.queue_redraw_all();
+    }
+
+    /// Compile `input.keymap_file` (or the default layout if unset) and make
+    /// it the keyboard's keymap, sending it to the focused client. A keymap
+    /// that doesn't compile leaves the current one in place.
+    pub fn load_keymap(&mut self) -> anyhow::Result<()> {
+        let keyboard = self.seat.get_keyboard().context("seat has no keyboard")?;
+        match self.config.input.keymap_file.clone() {
+            Some(path) => {
+                let keymap = std::fs::read_to_string(&path).with_context(|| format!("read {}", path.display()))?;
+                keyboard
+                    .set_keymap_from_string(self, keymap)
+                    .map_err(|e| anyhow::anyhow!("compile {}: {e:?}", path.display()))?;
+                tracing::info!(path = %path.display(), "XKB keymap loaded");
+            }
+            None => keyboard
+                .set_xkb_config(self, XkbConfig::default())
+                .map_err(|e| anyhow::anyhow!("default keymap: {e:?}"))?,
+        }
+        Ok(())
+    }
+
+    /// Toggle split-screen for the two topmost windows of the active
+    /// workspace, on the placement output.
+    pub fn toggle_split(&mut self) {
+        if self.split.is_some() {
+            return self.end_split(None);
+        }
+        let windows: Vec<(Window, Point<i32, Logical>)> = self.window_stack
+            .iter()
+            .filter(|w| !self.workspaces.is_stashed(w))
+            .filter_map(|w| self.space.element_location(w).map(|loc| (w.clone(), loc)))
+            .take(2)
+            .collect();
+        let Ok(windows) = <[_; 2]>::try_from(windows) else {
+            tracing::info!("Split-screen needs two windows");
+            return;
+        };
+        let Some(output) = self.placement_output() else { return };
+        let Some(area) = self.space.output_geometry(&output) else { return };
+        let orientation = self.config.display.split;
+        let split = SplitScreen::new(output, area, orientation, windows);
+        for (window, loc) in split.layout(area, orientation) {
+            self.space.map_element(window, loc, false);
+        }
+        self.split = Some(split);
+        self.queue_redraw_all();
+    }
+
+    /// Leave split-screen, putting the windows back where they were. `gone`
+    /// is a pane whose window was just destroyed.
+    pub fn end_split(&mut self, gone: Option<&Window>) {
+        let Some(split) = self.split.take() else { return };
+        for (window, loc) in split.end(gone) {
+            if self.space.element_location(&window).is_some() {
+                self.space.map_element(window, loc, false);
             }
         }
+        self.queue_redraw_all();
+    }
+
+    /// In split-screen, keyboard focus follows the pane under the pointer.
+    pub fn split_focus_follows_pointer(&mut self) {
+        let Some(split) = &self.split else { return };
+        let Some((window, _)) = self.window_under(self.pointer_location) else { return };
+        if split.contains(&window) && self.focused_window().as_ref() != Some(&window) {
+            self.activate_window(&window);
+        }
+    }
+
+    /// Focus the next (`step` = 1) or previous (-1) window of the active
+    /// workspace, in stacking order.
+    pub fn cycle_focus(&mut self, step: i32) {
+        let windows: Vec<Window> = self.window_stack
+            .iter()
+            .filter(|w| !self.workspaces.is_stashed(w))
+            .cloned()
+            .collect();
+        if windows.len() < 2 { return; }
+        // Forward sends the focused window to the bottom; back raises the
+        // bottom one, so repeated steps walk the whole stack.
+        if step > 0 {
+            self.window_stack.send_to_bottom(&windows[0]);
+            self.refresh_focus();
+        } else {
+            self.activate_window(&windows[windows.len() - 1]);
+        }
+        self.warp_to_focus();
+    }
+
+    /// With `[input.focus] warp_pointer`, after focus moved by keybinding:
+    /// bring the pointer to the centre of the focused window, or of the
+    /// focused output if no window has focus. A pointer already on the
+    /// window stays where it is.
+    fn warp_to_focus(&mut self) {
+        if !self.config.input.focus.warp_pointer { return; }
+        let target = match self.focused_window().and_then(|w| self.space.element_geometry(&w)) {
+            Some(geo) if geo.to_f64().contains(self.pointer_location) => return,
+            Some(geo) => geo,
+            None => match self.placement_output().and_then(|o| self.space.output_geometry(&o)) {
+                Some(geo) => geo,
+                None => return,
+            },
+        };
+        let center = target.loc.to_f64() + target.size.to_f64().downscale(2.0).to_point();
+        self.warp_pointer(center);
+    }
+
+    /// Ask the focused window to close.
+    pub fn close_focused(&mut self) {
+        let Some(window) = self.focused_window() else { return };
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.send_close();
+        } else if let Some(x11) = window.x11_surface() {
+            let _ = x11.close();
+        }
     }
 
     pub fn activate_window(&mut self, window: &Window) {
         self.window_stack.bring_to_top(window);
+        // Drawing and hit-testing follow the space's order; keep it the same
+        // as the focus order so the active window is also the one on top.
+        if self.space.element_location(window).is_some() {
+            self.space.raise_element(window, false);
+            self.queue_redraw_all();
+        }
         self.refresh_focus();
     }
+
+    /// Give `window` keyboard focus and leave the stacking order alone
+    /// (focus follows mouse without raise). The next `refresh_focus` goes
+    /// back to the top window.
+    pub fn focus_window(&mut self, window: &Window) {
+        let Some(surface) = window.wl_surface().map(|cow| cow.into_owned()) else { return };
+        let Some(kb) = self.seat.get_keyboard() else { return };
+        let current = kb.current_focus().map(|s| self.popup_root(&s));
+        if current.as_ref() == Some(&surface) { return; }
+        if let Some(old) = current {
+            dismiss_popups(&old);
+        }
+        kb.set_focus(self, Some(surface), SERIAL_COUNTER.next_serial());
+    }
+
+    /// Centre `window` on the output it is on.
+    pub fn center_window(&mut self, window: &Window) {
+        let Some(out) = self.space.outputs_for_element(window).first().and_then(|o| self.space.output_geometry(o))
+        else {
+            return;
+        };
+        let size = window.geometry().size;
+        let loc  = out.loc + Point::from((((out.size.w - size.w) / 2).max(0), ((out.size.h - size.h) / 2).max(0)));
+        self.space.map_element(window.clone(), loc, false);
+        self.queue_redraw_all();
+    }
+
+    /// Make `window` cover `output` (the one it is on, or the placement
+    /// output, if `None`): it is told it is fullscreen at the output's size
+    /// and moved to its top-left. A window not mapped yet is placed there
+    /// when it is. Split-screen panes are fullscreen already and stay as
+    /// they are.
+    pub(crate) fn set_fullscreen(&mut self, window: &Window, output: Option<Output>) {
+        if self.split.as_ref().is_some_and(|s| s.contains(window)) { return; }
+        let Some(output) = output
+            .or_else(|| self.space.outputs_for_element(window).first().cloned())
+            .or_else(|| self.placement_output())
+        else {
+            return;
+        };
+        let Some(area) = self.space.output_geometry(&output) else { return };
+
+        let data = window.user_data();
+        data.insert_if_missing(|| Fullscreen(RefCell::new(None)));
+        let mut fullscreen = data.get::<Fullscreen>().unwrap().0.borrow_mut();
+        // Moving between outputs keeps the geometry from before the first request.
+        let restore = match fullscreen.take() {
+            Some((_, restore)) => restore,
+            None => self.space.element_geometry(window),
+        };
+        *fullscreen = Some((output.clone(), restore));
+        drop(fullscreen);
+
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|s| {
+                s.size = Some(area.size);
+                s.states.set(xdg_toplevel::State::Fullscreen);
+            });
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        } else if let Some(x11) = window.x11_surface() {
+            let _ = x11.set_fullscreen(true);
+            let _ = x11.configure(area);
+        }
+        if self.space.element_location(window).is_some() {
+            self.space.map_element(window.clone(), area.loc, true);
+            self.queue_redraw_all();
+        }
+        tracing::info!(output = %output.name(), "Window fullscreen");
+    }
+
+    /// Take `window` out of fullscreen, back to where it was and the size it
+    /// had. One that was fullscreen from the start stays where it is and
+    /// picks its own size.
+    pub(crate) fn unset_fullscreen(&mut self, window: &Window) {
+        let Some((_, restore)) = window.user_data().get::<Fullscreen>().and_then(|f| f.0.borrow_mut().take()) else {
+            return;
+        };
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|s| {
+                s.size = restore.map(|geo| geo.size);
+                s.states.unset(xdg_toplevel::State::Fullscreen);
+            });
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        } else if let Some(x11) = window.x11_surface() {
+            let _ = x11.set_fullscreen(false);
+            if let Some(geo) = restore {
+                let _ = x11.configure(geo);
+            }
+        }
+        if let Some(geo) = restore.filter(|_| self.space.element_location(window).is_some()) {
+            self.space.map_element(window.clone(), geo.loc, false);
+            self.queue_redraw_all();
+        }
+        tracing::info!("Window left fullscreen");
+    }
+
+    /// Maximize `window` on the output it is on (the placement output if it
+    /// isn't mapped yet): it fills the part of the output panels leave free
+    /// and is told it is maximized.
+    pub(crate) fn set_maximized(&mut self, window: &Window) {
+        if self.split.as_ref().is_some_and(|s| s.contains(window)) { return; }
+        let Some(output) = self.space.outputs_for_element(window).first().cloned().or_else(|| self.placement_output())
+        else {
+            return;
+        };
+        let Some(area) = self.usable_area(&output) else { return };
+
+        let data = window.user_data();
+        data.insert_if_missing(|| Maximized(RefCell::new(None)));
+        let mut maximized = data.get::<Maximized>().unwrap().0.borrow_mut();
+        let restore = match maximized.take() {
+            Some((_, restore)) => restore,
+            None => self.space.element_geometry(window),
+        };
+        *maximized = Some((output.clone(), restore));
+        drop(maximized);
+
+        // Fullscreen wins over maximized for size and place.
+        let fullscreen = fullscreen_output(window).is_some();
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|s| {
+                if !fullscreen { s.size = Some(area.size); }
+                s.states.set(xdg_toplevel::State::Maximized);
+            });
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        } else if let Some(x11) = window.x11_surface() {
+            let _ = x11.set_maximized(true);
+            if !fullscreen {
+                let _ = x11.configure(area);
+            }
+        }
+        if self.space.element_location(window).is_some() && !fullscreen {
+            self.space.map_element(window.clone(), area.loc, true);
+            self.queue_redraw_all();
+        }
+        tracing::info!(output = %output.name(), "Window maximized");
+    }
+
+    /// Put a maximized `window` back where it was, at the size it had. One
+    /// maximized from the start stays where it is and picks its own size.
+    /// A fullscreen one stays fullscreen, and leaving fullscreen later takes
+    /// it back to its geometry from before it was maximized.
+    pub(crate) fn unset_maximized(&mut self, window: &Window) {
+        let Some((_, restore)) = window.user_data().get::<Maximized>().and_then(|m| m.0.borrow_mut().take()) else {
+            return;
+        };
+        let fullscreen = fullscreen_output(window).is_some();
+        if let Some(f) = window.user_data().get::<Fullscreen>() {
+            // Fullscreen saved the maximized geometry; hand it ours instead.
+            if let Some((_, fullscreen_restore)) = f.0.borrow_mut().as_mut() {
+                *fullscreen_restore = restore;
+            }
+        }
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|s| {
+                if !fullscreen { s.size = restore.map(|geo| geo.size); }
+                s.states.unset(xdg_toplevel::State::Maximized);
+            });
+            if toplevel.is_initial_configure_sent() {
+                toplevel.send_pending_configure();
+            }
+        } else if let Some(x11) = window.x11_surface() {
+            let _ = x11.set_maximized(false);
+            if let Some(geo) = restore.filter(|_| !fullscreen) {
+                let _ = x11.configure(geo);
+            }
+        }
+        let mapped = self.space.element_location(window).is_some() && !fullscreen;
+        if let Some(geo) = restore.filter(|_| mapped) {
+            self.space.map_element(window.clone(), geo.loc, false);
+            self.queue_redraw_all();
+        }
+        tracing::info!("Window unmaximized");
+    }
+
+    /// Schedule a repaint of `output`. Renders on the next idle if the output
+    /// is idle, otherwise right after its pending vblank.
+    pub fn queue_redraw(&mut self, output: &Output) {
+        let backend = match self.backend.as_mut() {
+            Some(Backend::Drm(b))    => b,
+            Some(Backend::Nested(n)) => {
+                if n.queue_redraw() {
+                    self.loop_handle.insert_idle(render_nested);
+                }
+                return;
+            }
+            Some(Backend::Headless(h)) => {
+                let Some(out) = h.output_mut(output) else { return };
+                if out.repaint.queue_redraw() {
+                    let name = output.name();
+                    self.loop_handle.insert_idle(move |state| render_virtual(state, &name));
+                }
+                return;
+            }
+            None                     => return,
+        };
+        if backend.paused || backend.blanked { return; }
+        let Some(id) = backend.output_id(output) else { return };
+        let Some(out) = backend.output_mut(id) else { return };
+        if out.repaint.queue_redraw() {
+            self.loop_handle.insert_idle(move |state| render_output(state, id));
+        }
+    }
+
+    /// Cap every output's frame rate at `fps` (`None`: as fast as vblank
+    /// allows). Takes effect from the next frame.
+    pub fn set_fps_cap(&mut self, fps: Option<u32>) {
+        let fps = fps.unwrap_or(0);
+        self.config.display.fps_cap = fps;
+        self.frame_pacer.set_cap(fps);
+        tracing::info!(fps_cap = fps, "Frame rate cap");
+        self.queue_redraw_all();
+    }
+
+    pub fn queue_redraw_all(&mut self) {
+        let outputs: Vec<Output> = self.space.outputs().cloned().collect();
+        for output in &outputs {
+            self.queue_redraw(output);
+        }
+    }
+
+    /// Whether the adaptive vsync heuristic should drive `output` right now.
+    /// How late frames are then presented is up to the output: with VRR
+    /// unless `display.vrr` is off, by tearing if
+    /// [`late_frames_may_tear`](Self::late_frames_may_tear).
+    pub fn adaptive_sync_applies(&self, output: &Output) -> bool {
+        match self.config.display.adaptive_sync {
+            AdaptiveSyncMode::Off    => false,
+            AdaptiveSyncMode::Always => true,
+            AdaptiveSyncMode::Games  => self.window_stack.top().is_some_and(|w| {
+                self.space.outputs_for_element(w).contains(output)
+                    && w.toplevel().is_some_and(|t| is_game_surface(t.wl_surface()))
+            }),
+        }
+    }
+
+    /// Whether adaptive sync may flip late frames on `output` without
+    /// waiting for vblank: only for a game (`wp_content_type_v1` "game") on
+    /// top there, unless a window rule's `tearing` says otherwise.
+    pub fn late_frames_may_tear(&self, output: &Output) -> bool {
+        self.window_stack.top()
+            .filter(|w| self.space.outputs_for_element(w).contains(output))
+            .is_some_and(|w| {
+                rules::resolve(&self.config.window_rules, w).tearing
+                    .unwrap_or_else(|| w.wl_surface().is_some_and(|s| is_game_surface(&s)))
+            })
+    }
+
+    /// Whether frames on `output` may tear, per `display.tearing`.
+    pub fn tearing_allowed(&self, output: &Output) -> bool {
+        match self.config.display.tearing {
+            TearingMode::Never          => false,
+            TearingMode::Always         => true,
+            TearingMode::FullscreenOnly => self.fullscreen_window(output).is_some(),
+        }
+    }
+
+    /// The one window that owns `output`: it covers all of it, nothing else
+    /// is shown there (no other window, popup, panel or split), so a torn
+    /// frame can only ever cut through that window's own content.
+    pub fn fullscreen_window(&self, output: &Output) -> Option<&Window> {
+        if self.split.as_ref().is_some_and(|s| &s.output == output) { return None; }
+        let output_geo  = self.space.output_geometry(output)?;
+        let mut windows = self.space.elements_for_output(output);
+        let window = windows.next()?;
+        if windows.next().is_some() { return None; }
+        if !self.space.element_geometry(window)?.contains_rect(output_geo) { return None; }
+        // X11 menus are windows of their own, counted above.
+        let surface = window.wl_surface()?;
+        if PopupManager::popups_for_surface(&surface).next().is_some() { return None; }
+        let layers = layer_map_for_output(output);
+        if layers.layers_on(Layer::Top).chain(layers.layers_on(Layer::Overlay)).next().is_some() { return None; }
+        Some(window)
+    }
+
+    /// Place `output` in the global layout: at `[display.outputs.<name>]`
+    /// `position` if configured, else where it was before a restart,
+    /// otherwise right of every output mapped so far.
+    pub fn map_output(&mut self, output: &Output) {
+        let name = output.name();
+        let pos: Point<i32, Logical> = match self.config.display.outputs.get(&name).and_then(|c| c.position) {
+            Some([x, y]) => (x, y).into(),
+            None => self.restore.output_position(&name)
+                .unwrap_or_else(|| (self.layout_bounds().map_or(0, |b| b.loc.x + b.size.w), 0).into()),
+        };
+        output.change_current_state(None, None, None, Some(pos));
+        self.space.map_output(output, pos);
+        tracing::info!(%name, x = pos.x, y = pos.y, "Output mapped");
+    }
+
+    /// Move `output` to `pos` in the global layout at runtime. Windows
+    /// placed on it move along, and a pointer on it stays on the same spot
+    /// of the output.
+    pub fn set_output_position(&mut self, output: &Output, pos: Point<i32, Logical>) {
+        let Some(old) = self.space.output_geometry(output) else { return };
+        let delta = pos - old.loc;
+        let riding: Vec<(Window, Point<i32, Logical>)> = self.space
+            .elements()
+            .filter_map(|w| Some((w.clone(), self.space.element_location(w)?)))
+            .filter(|(_, loc)| old.contains(*loc))
+            .collect();
+        output.change_current_state(None, None, None, Some(pos));
+        self.space.map_output(output, pos);
+        for (window, loc) in riding {
+            self.space.map_element(window, loc + delta, false);
+        }
+        // The pointer rides along too, and whatever is under it afterwards
+        // hears about it.
+        let mut pointer = self.pointer_location;
+        if old.to_f64().contains(pointer) {
+            pointer += delta.to_f64();
+        }
+        self.warp_pointer(pointer);
+        tracing::info!(name = %output.name(), x = pos.x, y = pos.y, "Output moved");
+        self.queue_redraw_all();
+    }
+
+    /// Switch the DRM output `output` to `mode`, then lay it out again (see
+    /// [`output_resized`](Self::output_resized)). A mode the driver rejects
+    /// is an error and changes nothing.
+    pub fn set_output_mode(&mut self, output: &Output, mode: Mode) -> anyhow::Result<()> {
+        let old = self.space.output_geometry(output).context("output not in the layout")?;
+        let backend = self.backend.as_mut().and_then(|b| b.drm_mut()).context("mode switching needs the DRM backend")?;
+        let out = backend.output_id(output)
+            .and_then(|id| backend.output_mut(id))
+            .context("not a DRM output")?;
+        out.set_mode(mode)?;
+        self.output_resized(output, old);
+        Ok(())
+    }
+
+    /// `output`, which covered `old`, changed size. Panels are arranged for
+    /// the new size, windows that filled the old one are resized to fill the
+    /// new one, and split-screen on it ends.
+    pub fn output_resized(&mut self, output: &Output, old: Rectangle<i32, Logical>) {
+        if self.split.as_ref().is_some_and(|s| &s.output == output) {
+            self.end_split(None);
+        }
+        layer_map_for_output(output).arrange();
+        let Some(new) = self.space.output_geometry(output) else { return };
+        let filled: Vec<Window> = self.space
+            .elements()
+            .filter(|w| self.space.element_geometry(w) == Some(old))
+            .cloned()
+            .collect();
+        for window in filled {
+            if let Some(x11) = window.x11_surface() {
+                let _ = x11.configure(new);
+            } else {
+                request_size(&window, new.size);
+            }
+            self.space.map_element(window, new.loc, false);
+        }
+        if let Some(p) = self.clamp_to_layout(self.pointer_location) {
+            self.pointer_location = p;
+        }
+        self.queue_redraw_all();
+    }
+
+    /// Take `output` out of the layout and drop every reference to it:
+    /// split-screen on it ends, and windows left on no output move to the
+    /// placement output at the same offset.
+    pub fn unmap_output(&mut self, output: &Output) {
+        let Some(old) = self.space.output_geometry(output) else { return };
+        if self.split.as_ref().is_some_and(|s| &s.output == output) {
+            self.end_split(None);
+        }
+        if self.focused_output.as_ref() == Some(output) {
+            self.focused_output = None;
+        }
+        self.space.unmap_output(output);
+        tracing::info!(name = %output.name(), "Output unmapped");
+        // Panels and the like on it are told to go; the client may make new
+        // ones on another output.
+        for layer in layer_map_for_output(output).layers() {
+            layer.layer_surface().send_close();
+        }
+
+        let Some(target) = self.placement_output().and_then(|o| self.space.output_geometry(&o)) else { return };
+        let outputs: Vec<Rectangle<i32, Logical>> =
+            self.space.outputs().filter_map(|o| self.space.output_geometry(o)).collect();
+        let stranded: Vec<(Window, Point<i32, Logical>)> = self.space
+            .elements()
+            .filter_map(|w| Some((w.clone(), self.space.element_geometry(w)?)))
+            .filter(|(_, geo)| !outputs.iter().any(|o| o.overlaps(*geo)))
+            .filter_map(|(w, _)| {
+                let loc = self.space.element_location(&w)?;
+                Some((w, loc))
+            })
+            .collect();
+        for (window, loc) in stranded {
+            self.space.map_element(window, loc - old.loc + target.loc, false);
+        }
+        if let Some(p) = self.clamp_to_layout(self.pointer_location) {
+            self.pointer_location = p;
+        }
+        self.queue_redraw_all();
+    }
+
+    /// Output whose layout rectangle contains `point`.
+    pub fn output_at(&self, point: Point<f64, Logical>) -> Option<Output> {
+        self.space.output_under(point).next().cloned()
+    }
+
+    /// Bounding box of all mapped outputs in the global layout.
+    pub fn layout_bounds(&self) -> Option<Rectangle<i32, Logical>> {
+        self.space
+            .outputs()
+            .filter_map(|o| self.space.output_geometry(o))
+            .reduce(|a, b| a.merge(b))
+    }
+
+    /// `point` if it is on an output, else the nearest point that is. The
+    /// layout need not be a rectangle: outputs of different sizes or with
+    /// gaps between them leave areas no output covers. `None` without
+    /// outputs.
+    pub fn clamp_to_layout(&self, point: Point<f64, Logical>) -> Option<Point<f64, Logical>> {
+        self.space
+            .outputs()
+            .filter_map(|o| self.space.output_geometry(o))
+            .map(|geo| clamp_into(geo, point))
+            .min_by(|a, b| distance_sq(*a, point).total_cmp(&distance_sq(*b, point)))
+    }
+
+    /// Move the pointer to `to` without a device (control socket, centring on
+    /// locked surfaces). Points off every output land on the nearest edge of
+    /// the nearest one; the surface under the new location gets a motion
+    /// event and both outputs repaint the cursor.
+    pub fn warp_pointer(&mut self, to: Point<f64, Logical>) {
+        let Some(to) = self.clamp_to_layout(to) else { return };
+        let from = self.output_at(self.pointer_location);
+        self.pointer_location = to;
+        let time = self.clock.now().as_millis();
+        pointer_moved(self, time);
+        for output in from.into_iter().chain(self.output_at(to)) {
+            self.queue_redraw(&output);
+        }
+    }
+
+    /// Tell every surface of `window` the scale and transform of the output it
+    /// is (mostly) on, via `wl_surface.preferred_buffer_*` and
+    /// `wp_fractional_scale_v1`. Smithay only sends values that changed.
+    pub fn send_scale_transform(&self, window: &Window) {
+        let Some(output) = self.space.outputs_for_element(window).into_iter().next() else { return };
+        let scale     = output.current_scale();
+        let transform = output.current_transform();
+        window.with_surfaces(|surface, data| {
+            send_surface_state(surface, data, scale.integer_scale(), transform);
+            with_fractional_scale(data, |fs| fs.set_preferred_scale(scale.fractional_scale()));
+        });
+    }
+
+    /// Surface the pointer at `point` hits and its global position, topmost
+    /// first: overlay and top layer surfaces, windows in stacking order, then
+    /// bottom and background layers (bottom ones above the windows while
+    /// revealed by a touch gesture). Input regions are honoured, so clicks
+    /// on input-transparent parts fall through to what is below. For an
+    /// upscaled window the position is where `point` minus it gives the
+    /// surface-local point that was drawn under `point`.
+    pub fn surface_under(&self, point: Point<f64, Logical>) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let (above, below) = touch::layer_order(self.space.output_under(point).next());
+        self.layer_surface_under(point, above)
+            .or_else(|| {
+                let unscaled = upscale::unscale(self, point);
+                let (window, loc) = self.space.element_under(unscaled)?;
+                window
+                    .surface_under(unscaled - loc.to_f64(), WindowSurfaceType::ALL)
+                    .map(|(surface, offset)| (surface, point - (unscaled - (loc + offset).to_f64())))
+            })
+            .or_else(|| self.layer_surface_under(point, below))
+    }
+
+    /// Window the pointer at `point` hits and its location, unless a layer
+    /// surface above the windows (a panel, an on-screen keyboard) takes it.
+    pub fn window_under(&self, point: Point<f64, Logical>) -> Option<(Window, Point<i32, Logical>)> {
+        let (above, _) = touch::layer_order(self.space.output_under(point).next());
+        if self.layer_surface_under(point, above).is_some() { return None; }
+        self.space.element_under(upscale::unscale(self, point)).map(|(window, loc)| (window.clone(), loc))
+    }
+
+    pub(crate) fn layer_surface_under(&self, point: Point<f64, Logical>, layers: &[Layer]) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let output = self.space.output_under(point).next()?;
+        let origin = self.space.output_geometry(output)?.loc;
+        let map    = layer_map_for_output(output);
+        let local  = point - origin.to_f64();
+        layers.iter().find_map(|&layer| {
+            let surface = map.layer_under(layer, local)?;
+            let loc     = map.layer_geometry(surface)?.loc;
+            surface
+                .surface_under(local - loc.to_f64(), WindowSurfaceType::ALL)
+                .map(|(s, offset)| (s, (origin + loc + offset).to_f64()))
+        })
+    }
+
+    /// The window of `toplevel`, mapped or still waiting for its first buffer.
+    fn toplevel_window(&self, toplevel: &ToplevelSurface) -> Option<Window> {
+        self.space.elements().chain(&self.unmapped).find(|w| w.toplevel() == Some(toplevel)).cloned()
+    }
+
+    /// Mapped window whose toplevel owns `surface` (or its root surface, or
+    /// the popup tree it is part of).
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        let mut root = self.popup_root(surface);
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        self.space
+            .elements()
+            .find(|w| w.wl_surface().as_deref() == Some(&root))
+            .cloned()
+    }
+}
+
+/// [`constrain_size`] for either kind of window; X11 windows by their
+/// `WM_NORMAL_HINTS`.
+fn constrain_window_size(window: &Window, size: Size<i32, Logical>) -> Size<i32, Logical> {
+    if let Some(toplevel) = window.toplevel() {
+        return constrain_size(toplevel.wl_surface(), size);
+    }
+    let Some(x11) = window.x11_surface() else { return size };
+    let (min, max) = (x11.min_size().unwrap_or_default(), x11.max_size().unwrap_or_default());
+    let clamp = |v: i32, lo: i32, hi: i32| if hi > 0 { v.min(hi) } else { v }.max(lo);
+    (clamp(size.w, min.w, max.w), clamp(size.h, min.h, max.h)).into()
+}
+
+/// Ask `window` to resize to `size`. X11 windows keep their position.
+fn request_size(window: &Window, size: Size<i32, Logical>) {
+    if let Some(toplevel) = window.toplevel() {
+        toplevel.with_pending_state(|s| s.size = Some(size));
+        toplevel.send_pending_configure();
+    } else if let Some(x11) = window.x11_surface() {
+        let _ = x11.configure(Rectangle::new(x11.geometry().loc, size));
+    }
+}
+
+/// Clamp `size` to the toplevel's min/max size hints (0 = unset).
+pub fn constrain_size(surface: &WlSurface, size: Size<i32, Logical>) -> Size<i32, Logical> {
+    let (min, max) = with_states(surface, |states| {
+        let mut cached = states.cached_state.get::<SurfaceCachedState>();
+        let current    = cached.current();
+        (current.min_size, current.max_size)
+    });
+    let clamp = |v: i32, lo: i32, hi: i32| if hi > 0 { v.min(hi) } else { v }.max(lo);
+    (clamp(size.w, min.w, max.w), clamp(size.h, min.h, max.h)).into()
+}
+
+/// Send `popup_done` to every popup of the toplevel `root`.
+fn dismiss_popups(root: &WlSurface) {
+    let popups: Vec<PopupKind> = PopupManager::popups_for_surface(root).map(|(p, _)| p).collect();
+    for popup in &popups {
+        let _ = PopupManager::dismiss_popup(root, popup);
+    }
+}
+
+/// True if the client tagged `surface` as game content (wp_content_type_v1).
+pub fn is_game_surface(surface: &WlSurface) -> bool {
+    with_states(surface, |states| {
+        matches!(
+            states.cached_state.get::<ContentTypeSurfaceCachedState>().current().content_type(),
+            ContentType::Game
+        )
+    })
 }
 
 // ── Per-client data ───────────────────────────────────────────────────────────
@@ -146,11 +1438,21 @@ impl GameframeState {
 #[derive(Default)]
 pub struct GameframeClientData {
     pub compositor: CompositorClientState,
+    /// Name of the socket the client connected through; empty for clients
+    /// the compositor created itself (XWayland).
+    pub socket:     String,
+    /// Surfaces it has alive, for `gameframe clients`.
+    pub usage:      Arc<ClientUsage>,
 }
 
 impl ClientData for GameframeClientData {
-    fn initialized(&self, _: ClientId) {}
-    fn disconnected(&self, _: ClientId, _: DisconnectReason) {}
+    fn initialized(&self, client: ClientId) {
+        tracing::info!(?client, socket = %self.socket, "Client connected");
+    }
+
+    fn disconnected(&self, client: ClientId, reason: DisconnectReason) {
+        tracing::info!(?client, ?reason, "Client disconnected");
+    }
 }
 
 // ── Delegate macros ───────────────────────────────────────────────────────────
@@ -164,11 +1466,21 @@ delegate_seat!(GameframeState);
 delegate_data_device!(GameframeState);
 delegate_primary_selection!(GameframeState);
 delegate_dmabuf!(GameframeState);
+delegate_content_type!(GameframeState);
+delegate_fractional_scale!(GameframeState);
+delegate_alpha_modifier!(GameframeState);
+delegate_presentation!(GameframeState);
+delegate_idle_inhibit!(GameframeState);
+delegate_pointer_constraints!(GameframeState);
+delegate_relative_pointer!(GameframeState);
+delegate_xwayland_shell!(GameframeState);
 
 // ── BufferHandler ─────────────────────────────────────────────────────────────
 
 impl BufferHandler for GameframeState {
-    fn buffer_destroyed(&mut self, _buffer: &WlBuffer) {}
+    fn buffer_destroyed(&mut self, buffer: &WlBuffer) {
+        self.buffers.destroyed(buffer, self.backend.as_mut().map(Backend::renderer));
+    }
 }
 
 // ── CompositorHandler ─────────────────────────────────────────────────────────
@@ -181,10 +1493,51 @@ impl CompositorHandler for GameframeState {
         &self,
         client: &'a smithay::reexports::wayland_server::Client,
     ) -> &'a CompositorClientState {
+        if let Some(xwayland) = client.get_data::<XWaylandClientData>() {
+            return &xwayland.compositor_state;
+        }
         &client.get_data::<GameframeClientData>().unwrap().compositor
     }
+    fn new_surface(&mut self, surface: &WlSurface) {
+        if let Some(data) = surface.client().as_ref().and_then(|c| c.get_data::<GameframeClientData>()) {
+            data.usage.surface_created(surface);
+        }
+    }
+
     fn commit(&mut self, surface: &WlSurface) {
+        if !self.buffers.commit(surface) {
+            return;
+        }
         smithay::backend::renderer::utils::on_commit_buffer_handler::<Self>(surface);
+        self.popups.commit(surface);
+        if let Some(PopupKind::Xdg(popup)) = self.popups.find_popup(surface) {
+            if !popup.is_initial_configure_sent() {
+                // Geometry was set (and unconstrained) in `new_popup`.
+                let _ = popup.send_configure();
+            }
+        }
+        if is_sync_subsurface(surface)
+            || self.commit_unmapped(surface)
+            || self.commit_layer(surface)
+            || dnd::icon_committed(self, surface)
+            || cursor::surface_committed(self, surface)
+        {
+            return;
+        }
+        if let Some(window) = self.window_for_surface(surface) {
+            // Repaint where the window was as well as where its new buffer
+            // puts it, so a shrinking window leaves nothing behind.
+            let mut outputs: Vec<Output> = self.space.outputs_for_element(&window);
+            window.on_commit();
+            self.send_scale_transform(&window);
+            self.animations.window_committed(&window, self.clock.now().into());
+            for output in self.space.outputs_for_element(&window) {
+                if !outputs.contains(&output) { outputs.push(output); }
+            }
+            for output in &outputs {
+                self.queue_redraw(output);
+            }
+        }
     }
 }
 
@@ -195,53 +1548,135 @@ impl XdgShellHandler for GameframeState {
         &mut self.xdg_shell_state
     }
 
+    /// The window is mapped once the client has acked the initial configure
+    /// and attached a buffer (see `commit_unmapped`).
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
-        let window = Window::new_wayland_window(surface.clone());
-        self.window_stack.push(window.clone());
-        self.space.map_element(window, (0, 0), true);
-        surface.with_pending_state(|p| { p.size = None; });
-        surface.send_configure();
-        self.refresh_focus();
-        self.overlay.push_toast("Application launched", 180);
-        // FIX: use Resource trait for .id()
-        tracing::info!(
-            surface = ?surface.wl_surface().id(),
-            "new toplevel – stack depth: {}", self.window_stack.len()
-        );
+        let window = Window::new_wayland_window(surface);
+        self.animations.window_mapped(&window);
+        self.unmapped.push(window);
+    }
+
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, output: Option<WlOutput>) {
+        let output = output.as_ref().and_then(Output::from_resource);
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.set_fullscreen(&window, output);
+        }
     }
 
-    fn new_popup(&mut self, _surface: PopupSurface, _positioner: PositionerState) {}
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.unset_fullscreen(&window);
+        }
+    }
 
-    fn grab(
-        &mut self,
-        _surface: PopupSurface,
-        _seat: smithay::reexports::wayland_server::protocol::wl_seat::WlSeat,
-        _serial: Serial,
-    ) {}
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.set_maximized(&window);
+        }
+    }
 
-    fn reposition_request(
-        &mut self,
-        _surface: PopupSurface,
-        _positioner: PositionerState,
-        _token: u32,
-    ) {}
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if let Some(window) = self.toplevel_window(&surface) {
+            self.unset_maximized(&window);
+        }
+    }
+
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        if let Some(idx) = self.unmapped.iter().position(|w| w.toplevel() == Some(&surface)) {
+            let window = self.unmapped.remove(idx);
+            self.animations.window_dropped(&window);
+            return;
+        }
+        let window = self.space.elements()
+            .chain(self.workspaces.stashed().map(|(_, w, _)| w))
+            .find(|w| w.toplevel() == Some(&surface))
+            .cloned();
+        match window {
+            Some(window) => self.window_gone(&window),
+            None         => self.window_stack.remove_by_wl_surface(surface.wl_surface()),
+        }
+    }
+
+    fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
+        surface.with_pending_state(|s| s.geometry = positioner.get_geometry());
+        self.unconstrain_popup(&surface);
+        if let Err(e) = self.popups.track_popup(PopupKind::Xdg(surface)) {
+            tracing::warn!("popup: {e:?}");
+        }
+    }
+
+    /// Explicit grab (menus): keyboard and pointer go to the popup chain until
+    /// a click outside the client or a key dismisses it.
+    fn grab(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
+        let Some(seat) = Seat::<Self>::from_resource(&seat) else { return };
+        let popup = PopupKind::Xdg(surface);
+        let Some(root) = find_popup_root_surface(&popup).ok()
+            .filter(|root| self.window_for_surface(root).is_some())
+        else { return };
+        let Ok(mut grab) = self.popups.grab_popup(root, popup, &seat, serial) else { return };
+
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial) || keyboard.has_grab(grab.previous_serial().unwrap_or(serial)))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(self, PopupKeyboardGrab::new(&grab), serial);
+        }
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial) || pointer.has_grab(grab.previous_serial().unwrap_or_else(|| grab.serial())))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
+    }
+
+    fn reposition_request(&mut self, surface: PopupSurface, positioner: PositionerState, token: u32) {
+        surface.with_pending_state(|s| {
+            s.geometry   = positioner.get_geometry();
+            s.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        surface.send_repositioned(token);
+        let _ = surface.send_configure();
+    }
 }
 
 // ── Layer Shell ───────────────────────────────────────────────────────────────
 
 impl WlrLayerShellHandler for GameframeState {
     fn shell_state(&mut self) -> &mut WlrLayerShellState { &mut self.layer_shell_state }
-    fn new_layer_surface(
-        &mut self,
-        surface: LayerSurface,
-        _output: Option<smithay::reexports::wayland_server::protocol::wl_output::WlOutput>,
-        _layer: Layer,
-        namespace: String,
-    ) {
-        tracing::debug!(%namespace, "new layer surface");
-        let _ = surface;
+
+    /// The layer surface goes on the output the client asked for, else the
+    /// placement output. It is configured on its first commit (see
+    /// `commit_layer`).
+    fn new_layer_surface(&mut self, surface: LayerSurface, output: Option<WlOutput>, _layer: Layer, namespace: String) {
+        let Some(output) = output.as_ref().and_then(Output::from_resource).or_else(|| self.placement_output()) else {
+            tracing::debug!(%namespace, "layer surface with no output to go on");
+            surface.send_close();
+            return;
+        };
+        tracing::debug!(%namespace, output = %output.name(), "new layer surface");
+        if let Err(e) = layer_map_for_output(&output).map_layer(&DesktopLayer::new(surface, namespace)) {
+            tracing::warn!("map layer surface: {e}");
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: LayerSurface) {
+        let Some(output) = self.layer_output(surface.wl_surface()) else { return };
+        {
+            let mut map = layer_map_for_output(&output);
+            if let Some(layer) = map.layer_for_surface(surface.wl_surface(), WindowSurfaceType::TOPLEVEL).cloned() {
+                map.unmap_layer(&layer);
+            }
+        }
+        self.queue_redraw(&output);
     }
-    fn layer_destroyed(&mut self, _surface: LayerSurface) {}
 }
 
 // ── Output ────────────────────────────────────────────────────────────────────
@@ -267,7 +1702,48 @@ impl DmabufHandler for GameframeState {
     ) {
         // FIX: Buffer trait in scope → .format() available
         tracing::debug!("dmabuf import: {:?}", dmabuf.format());
-        drop(notifier); // dropping without .failed() = success
+        // Import now so a format, modifier or plane layout the GPU can't
+        // sample is refused here, not at the first frame.
+        // Without a renderer yet it is tried when the buffer is drawn.
+        use smithay::backend::renderer::ImportDma;
+        let imported = self.backend.as_mut().map(|b| b.renderer().import_dmabuf(&dmabuf, None));
+        match imported {
+            Some(Err(e)) => {
+                tracing::warn!("dmabuf import failed: {e:?}");
+                notifier.failed();
+            }
+            Some(Ok(_)) | None => { let _ = notifier.successful::<Self>(); }
+        }
+    }
+}
+
+// ── Fractional scale ──────────────────────────────────────────────────────────
+
+impl FractionalScaleHandler for GameframeState {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        if let Some(window) = self.window_for_surface(&surface) {
+            self.send_scale_transform(&window);
+        }
+    }
+}
+
+// ── Idle inhibit ──────────────────────────────────────────────────────────────
+
+impl IdleInhibitHandler for GameframeState {
+    fn inhibit(&mut self, surface: WlSurface) { self.idle.inhibit(surface); }
+
+    fn uninhibit(&mut self, surface: WlSurface) { self.idle.uninhibit(&surface); }
+}
+
+// ── Pointer constraints ───────────────────────────────────────────────────────
+
+impl PointerConstraintsHandler for GameframeState {
+    fn new_constraint(&mut self, surface: &WlSurface, pointer: &PointerHandle<Self>) {
+        constraints::new_constraint(self, surface, pointer);
+    }
+
+    fn cursor_position_hint(&mut self, surface: &WlSurface, pointer: &PointerHandle<Self>, location: Point<f64, Logical>) {
+        constraints::position_hint(self, surface, pointer, location);
     }
 }
 
@@ -280,13 +1756,40 @@ impl smithay::input::SeatHandler for GameframeState {
 
     fn seat_state(&mut self) -> &mut SeatState<Self> { &mut self.seat_state }
 
-    fn focus_changed(&mut self, _seat: &Seat<Self>, focused: Option<&WlSurface>) {
+    fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
+        // Player seats keep their focus on their own window.
+        if seat != &self.seat { return; }
+
         // FIX: Resource in scope → .id() available
         tracing::debug!(surface = ?focused.map(|s| s.id()), "focus changed");
+
+        // The focused output follows the focused window; with no window
+        // focused the last output keeps focus.
+        if let Some(output) = focused
+            .and_then(|s| self.window_for_surface(s))
+            .and_then(|w| self.space.outputs_for_element(&w).into_iter().next())
+        {
+            self.focused_output = Some(output);
+        }
+        constraints::focus_changed(self, focused);
+        let window = focused.and_then(|s| self.window_for_surface(s));
+        xwayland::focus_changed(self, window.as_ref());
+        if self.config.effects.dim_inactive != DimInactive::Off {
+            self.queue_redraw_all();
+        }
     }
 
-    fn cursor_image(&mut self, _seat: &Seat<Self>, image: CursorImageStatus) {
-        self.cursor_status = image;
+    fn cursor_image(&mut self, seat: &Seat<Self>, image: CursorImageStatus) {
+        if seat == &self.seat {
+            self.cursor_status = image;
+        } else {
+            self.players.set_cursor(seat, image);
+        }
+        self.queue_redraw_all();
+    }
+
+    fn led_state_changed(&mut self, _seat: &Seat<Self>, leds: LedState) {
+        update_keyboard_leds(self, leds);
     }
 }
 
@@ -294,12 +1797,36 @@ impl smithay::input::SeatHandler for GameframeState {
 
 impl SelectionHandler for GameframeState {
     type SelectionUserData = ();
+
+    fn new_selection(&mut self, ty: SelectionTarget, source: Option<SelectionSource>, seat: Seat<Self>) {
+        if ty == SelectionTarget::Clipboard {
+            clipboard::selection_changed(self, &seat, source);
+        }
+    }
+
+    fn send_selection(&mut self, ty: SelectionTarget, mime_type: String, fd: OwnedFd, _seat: Seat<Self>, _: &()) {
+        if ty == SelectionTarget::Clipboard {
+            clipboard::send(self, &mime_type, fd);
+        }
+    }
+}
+impl ClientDndGrabHandler for GameframeState {
+    fn started(&mut self, _source: Option<WlDataSource>, icon: Option<WlSurface>, _seat: Seat<Self>) {
+        dnd::started(self, icon);
+    }
+
+    fn dropped(&mut self, _target: Option<WlSurface>, _validated: bool, _seat: Seat<Self>) {
+        dnd::finished(self);
+    }
 }
-impl ClientDndGrabHandler for GameframeState {}
 impl ServerDndGrabHandler for GameframeState {}
 
 impl DataDeviceHandler for GameframeState {
     fn data_device_state(&self) -> &DataDeviceState { &self.data_device_state }
+
+    fn action_choice(&mut self, available: DndAction, preferred: DndAction) -> DndAction {
+        dnd::choose_action(self, available, preferred)
+    }
 }
 
 impl PrimarySelectionHandler for GameframeState {
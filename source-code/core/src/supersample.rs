@@ -0,0 +1,281 @@
+use std::cell::RefCell;
+
+use anyhow::{anyhow, Context, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::{
+                texture::TextureRenderElement, utils::RescaleRenderElement, Element, Id, Kind, RenderElement,
+                UnderlyingStorage,
+            },
+            gles::{GlesError, GlesFrame, GlesRenderer, GlesTexProgram, GlesTexture, Uniform, UniformName, UniformType},
+            utils::{CommitCounter, DamageSet, OpaqueRegions},
+            Bind, Offscreen, Renderer,
+        },
+    },
+    output::Output,
+    utils::{Buffer, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+use tracing::{debug, info_span, warn};
+
+use crate::{
+    config::Config,
+    render::{FrameElements, CLEAR_COLOR},
+};
+
+/// Largest supported factor; beyond this bilinear downsampling skips texels.
+pub const MAX_FACTOR: f64 = 4.0;
+
+/// Smallest `render_scale`; below it text is unreadable whatever the
+/// sharpening.
+pub const MIN_RENDER_SCALE: f64 = 0.25;
+
+/// Texture shader for the upscaled frame: an unsharp mask against the four
+/// neighbouring texels of the small frame (`texel` apart).
+const SHARPEN_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#if defined(EXTERNAL)
+#extension GL_OES_EGL_image_external : require
+#endif
+
+precision mediump float;
+#if defined(EXTERNAL)
+uniform samplerExternalOES tex;
+#else
+uniform sampler2D tex;
+#endif
+
+uniform float alpha;
+varying vec2 v_coords;
+
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+
+uniform vec2 texel;
+uniform float strength;
+
+void main() {
+    vec4 c = texture2D(tex, v_coords);
+    vec4 n = texture2D(tex, v_coords + vec2(0.0, -texel.y))
+           + texture2D(tex, v_coords + vec2(0.0,  texel.y))
+           + texture2D(tex, v_coords + vec2(-texel.x, 0.0))
+           + texture2D(tex, v_coords + vec2( texel.x, 0.0));
+    vec4 color = vec4(clamp(c.rgb + (c.rgb - n.rgb * 0.25) * strength, 0.0, 1.0), c.a);
+#if defined(NO_ALPHA)
+    color = vec4(color.rgb, 1.0);
+#endif
+    color *= alpha;
+
+#if defined(DEBUG_FLAGS)
+    if (tint == 1.0)
+        color = vec4(0.0, 0.3, 0.0, 0.2) + color * 0.8;
+#endif
+    gl_FragColor = color;
+}
+"#;
+
+/// The offscreen frame of one output, `factor` times its size.
+struct Target {
+    factor:         f64,
+    size:           Size<i32, Physical>,
+    scale:          f64,
+    texture:        GlesTexture,
+    damage_tracker: OutputDamageTracker,
+    /// Renewed whenever the texture changes, so the output's own damage
+    /// tracker sees the new frame.
+    id:             Id,
+    fresh:          bool,
+}
+
+struct SupersampleTarget(RefCell<Option<Target>>);
+
+/// Render `elements` at a different resolution into an offscreen texture
+/// and return the element that draws it back at output size, linearly
+/// filtered. The factor is `effects.supersample` times the output's
+/// `render_scale`.
+///
+/// Above 1 (supersampling) every pixel on screen averages `factor²`
+/// rendered ones, which smooths edges of scaled and rotated windows,
+/// rounded corners and shadows, at `factor²` times the fill rate. Below 1
+/// the whole desktop is composited with fewer pixels and upscaled, for
+/// panels the GPU can't fill at native resolution; the output's `sharpen`
+/// then offsets some of the blur. Either way it costs one full-screen pass,
+/// and direct scanout is never possible while it is on.
+///
+/// If the offscreen buffer can't be set up the elements are returned as is.
+pub(crate) fn supersample(
+    renderer: &mut GlesRenderer,
+    output:   &Output,
+    elements: Vec<FrameElements>,
+    config:   &Config,
+) -> Vec<FrameElements> {
+    let name   = output.name();
+    let factor = config.effects.supersample.clamp(1.0, MAX_FACTOR)
+        * config.display.render_scale_for(&name).clamp(MIN_RENDER_SCALE, 1.0);
+    if factor == 1.0 { return elements; }
+    let Some(mode) = output.current_mode() else { return elements };
+    let scale       = output.current_scale().fractional_scale();
+    let output_size = output.current_transform().transform_size(mode.size);
+
+    let data = output.user_data();
+    data.insert_if_missing(|| SupersampleTarget(RefCell::new(None)));
+    let mut slot = data.get::<SupersampleTarget>().unwrap().0.borrow_mut();
+    let size = output_size.to_f64().upscale(factor).to_i32_round();
+    if slot.as_ref().map_or(true, |t| t.factor != factor || t.size != size || t.scale != scale) {
+        match Target::new(renderer, factor, size, scale) {
+            Ok(t)  => *slot = Some(t),
+            Err(e) => {
+                warn!(output = name, factor, "offscreen rendering unavailable: {e:#}");
+                return elements;
+            }
+        }
+    }
+    let target = slot.as_mut().unwrap();
+    let _span = info_span!("supersample", factor).entered();
+
+    let scaled: Vec<_> = elements
+        .into_iter()
+        .map(|e| RescaleRenderElement::from_element(e, Point::<i32, Physical>::from((0, 0)), factor))
+        .collect();
+    if let Err(e) = target.render(renderer, &scaled) {
+        debug!(output = name, "offscreen render: {e:#}");
+    }
+
+    // Drawn at the output's scale, so give its size in logical units.
+    let logical = output_size.to_f64().to_logical(scale).to_i32_round();
+    let texture = TextureRenderElement::from_static_texture(
+        target.id.clone(),
+        renderer.context_id(),
+        (0.0, 0.0),
+        target.texture.clone(),
+        1,
+        Transform::Normal,
+        None,
+        None,
+        Some(logical),
+        Some(vec![Rectangle::from_size(logical)]),
+        Kind::Unspecified,
+    );
+    let strength = if factor < 1.0 { config.display.sharpen_for(&name) } else { 0.0 };
+    match sharpen_shader(renderer).filter(|_| strength > 0.0) {
+        Some(program) => {
+            let texel = [1.0 / target.size.w as f32, 1.0 / target.size.h as f32];
+            vec![FrameElements::Sharpened(SharpenElement::new(texture, program, texel, strength))]
+        }
+        None => vec![FrameElements::Supersampled(texture)],
+    }
+}
+
+impl Target {
+    fn new(renderer: &mut GlesRenderer, factor: f64, size: Size<i32, Physical>, scale: f64) -> Result<Self> {
+        let texture: GlesTexture = renderer
+            .create_buffer(Fourcc::Abgr8888, size.to_logical(1).to_buffer(1, Transform::Normal))
+            .with_context(|| format!("{}x{} offscreen buffer", size.w, size.h))?;
+        debug!(factor, w = size.w, h = size.h, "offscreen buffer allocated");
+        Ok(Self {
+            factor,
+            size,
+            scale,
+            texture,
+            // Elements keep their geometry at the output scale; the rescale
+            // wrappers do the enlarging.
+            damage_tracker: OutputDamageTracker::new(size, scale, Transform::Normal),
+            id:             Id::new(),
+            fresh:          true,
+        })
+    }
+
+    fn render(&mut self, renderer: &mut GlesRenderer, elements: &[RescaleRenderElement<FrameElements>]) -> Result<()> {
+        // The texture keeps the previous frame, so only new damage is drawn.
+        let age = if self.fresh { 0 } else { 1 };
+        let mut fb = renderer.bind(&mut self.texture).context("bind offscreen buffer")?;
+        let res = self.damage_tracker
+            .render_output(renderer, &mut fb, age, elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("render_output: {e:?}"))?;
+        if res.damage.is_some() {
+            self.id = Id::new();
+        }
+        self.fresh = false;
+        Ok(())
+    }
+}
+
+// ── Sharpening ────────────────────────────────────────────────────────────────
+
+struct SharpenShader(Option<GlesTexProgram>);
+
+/// The sharpening program for `renderer`'s context, compiled on first use.
+/// `None` if compilation failed (logged once; frames are then upscaled
+/// without it).
+fn sharpen_shader(renderer: &mut GlesRenderer) -> Option<GlesTexProgram> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<SharpenShader>() {
+        return cached.0.clone();
+    }
+    let program = renderer.compile_custom_texture_shader(
+        SHARPEN_SRC,
+        &[
+            UniformName::new("texel", UniformType::_2f),
+            UniformName::new("strength", UniformType::_1f),
+        ],
+    );
+    let program = program.inspect_err(|e| warn!("sharpen shader: {e:?}")).ok();
+    renderer.egl_context().user_data().insert_if_missing(|| SharpenShader(program.clone()));
+    program
+}
+
+/// A texture drawn through a shader taking its `texel` size and a
+/// `strength`: the upscaled frame through the sharpening shader, or an FSR
+/// pass (see `upscale`).
+pub struct SharpenElement {
+    inner:    TextureRenderElement<GlesTexture>,
+    program:  GlesTexProgram,
+    /// One texel of the texture, in texture coordinates.
+    texel:    [f32; 2],
+    strength: f32,
+}
+
+impl SharpenElement {
+    pub(crate) fn new(inner: TextureRenderElement<GlesTexture>, program: GlesTexProgram, texel: [f32; 2], strength: f32) -> Self {
+        Self { inner, program, texel, strength }
+    }
+}
+
+impl Element for SharpenElement {
+    fn id(&self) -> &Id { self.inner.id() }
+    fn current_commit(&self) -> CommitCounter { self.inner.current_commit() }
+    fn location(&self, scale: Scale<f64>) -> Point<i32, Physical> { self.inner.location(scale) }
+    fn src(&self) -> Rectangle<f64, Buffer> { self.inner.src() }
+    fn transform(&self) -> Transform { self.inner.transform() }
+    fn geometry(&self, scale: Scale<f64>) -> Rectangle<i32, Physical> { self.inner.geometry(scale) }
+    fn damage_since(&self, scale: Scale<f64>, commit: Option<CommitCounter>) -> DamageSet<i32, Physical> {
+        self.inner.damage_since(scale, commit)
+    }
+    fn opaque_regions(&self, scale: Scale<f64>) -> OpaqueRegions<i32, Physical> { self.inner.opaque_regions(scale) }
+    fn alpha(&self) -> f32 { self.inner.alpha() }
+    fn kind(&self) -> Kind { self.inner.kind() }
+}
+
+impl RenderElement<GlesRenderer> for SharpenElement {
+    fn draw(
+        &self,
+        frame:  &mut GlesFrame<'_, '_>,
+        src:    Rectangle<f64, Buffer>,
+        dst:    Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        opaque: &[Rectangle<i32, Physical>],
+    ) -> Result<(), GlesError> {
+        frame.override_default_tex_program(
+            self.program.clone(),
+            vec![Uniform::new("texel", self.texel), Uniform::new("strength", self.strength)],
+        );
+        let res = RenderElement::<GlesRenderer>::draw(&self.inner, frame, src, dst, damage, opaque);
+        frame.clear_tex_program_override();
+        res
+    }
+
+    fn underlying_storage(&self, _renderer: &mut GlesRenderer) -> Option<UnderlyingStorage<'_>> { None }
+}
@@ -0,0 +1,226 @@
+//! Async page flips: frames go on screen as soon as they are finished,
+//! mid-scanout, instead of at the next vblank.
+//!
+//! Neither `DrmCompositor` nor `GbmBufferedSurface` can flip without
+//! waiting for vblank, so while an output tears it renders into a swapchain
+//! of its own and flips that directly: a `DRM_MODE_PAGE_FLIP_ASYNC` page
+//! flip on legacy KMS, an atomic commit with `PAGE_FLIP_ASYNC` that changes
+//! nothing but the primary plane's framebuffer on atomic KMS. Going back to
+//! vsync hands the CRTC back to the output's surface, which then commits its
+//! whole state again.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context, Result};
+use drm::{
+    control::{
+        atomic::AtomicModeReq, crtc, framebuffer, plane, property, AtomicCommitFlags, Device as ControlDevice,
+        FbCmd2Flags, PageFlipFlags,
+    },
+    Device as BasicDevice, DriverCapability,
+};
+use smithay::backend::{
+    allocator::{
+        dmabuf::{AsDmabuf, Dmabuf},
+        gbm::{GbmAllocator, GbmBuffer},
+        Fourcc, Modifier, Slot, Swapchain,
+    },
+    drm::DrmDeviceFd,
+};
+use tracing::{debug, info};
+
+/// One output's swapchain for async flips.
+pub struct TearingSurface {
+    drm:       DrmDeviceFd,
+    crtc:      crtc::Handle,
+    size:      (u32, u32),
+    swapchain: Swapchain<GbmAllocator<DrmDeviceFd>>,
+    /// Property handles for flipping with atomic commits; `None` on legacy
+    /// KMS.
+    atomic:    Option<AtomicPlanes>,
+    /// Rendered by [`next_buffer`](Self::next_buffer), not flipped yet.
+    next:      Option<Slot<GbmBuffer>>,
+    /// Flip in flight (and whether it is async), and the buffer on screen.
+    pending:   Option<(Slot<GbmBuffer>, bool)>,
+    current:   Option<Slot<GbmBuffer>>,
+    /// Our buffers are on screen. Until then flips wait for vblank: the
+    /// first one swaps the surface's buffer for ours, which an async flip
+    /// can't do on every driver.
+    primed:    bool,
+}
+
+/// The CRTC's primary plane and the properties an atomic flip sets on it.
+struct AtomicPlanes {
+    primary: plane::Handle,
+    props:   HashMap<String, property::Handle>,
+    /// Cursor and overlay planes the output's `DrmCompositor` may have
+    /// lit; switched off while tearing, since everything is composited.
+    others:  Vec<plane::Handle>,
+}
+
+/// Framebuffer of one swapchain buffer, removed with it.
+struct Framebuffer {
+    drm:    DrmDeviceFd,
+    handle: framebuffer::Handle,
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let _ = self.drm.destroy_framebuffer(self.handle);
+    }
+}
+
+impl TearingSurface {
+    /// Async flips for `crtc` with buffers of `format`, or `None` if the
+    /// driver can't flip it without waiting for vblank. `primary` and
+    /// `others` are the CRTC's planes on atomic devices, `None` on legacy
+    /// ones.
+    pub fn new(
+        drm:       DrmDeviceFd,
+        crtc:      crtc::Handle,
+        allocator: GbmAllocator<DrmDeviceFd>,
+        size:      (u32, u32),
+        format:    Fourcc,
+        planes:    Option<(plane::Handle, Vec<plane::Handle>)>,
+    ) -> Result<Option<Self>> {
+        let cap = match planes {
+            Some(_) => DriverCapability::AtomicASyncPageFlip,
+            None    => DriverCapability::ASyncPageFlip,
+        };
+        if !matches!(drm.get_driver_capability(cap), Ok(v) if v != 0) {
+            info!(?crtc, "Driver has no async page flips; frames never tear");
+            return Ok(None);
+        }
+        let atomic = match planes {
+            Some((primary, others)) => {
+                let props = drm.get_properties(primary)
+                    .and_then(|set| set.as_hashmap(&drm))
+                    .context("primary plane properties")?
+                    .into_iter()
+                    .map(|(name, info)| (name, info.handle()))
+                    .collect();
+                Some(AtomicPlanes { primary, props, others })
+            }
+            None => None,
+        };
+        let swapchain = Swapchain::new(allocator, size.0, size.1, format, vec![Modifier::Invalid, Modifier::Linear]);
+        Ok(Some(Self {
+            drm, crtc, size, swapchain, atomic, next: None, pending: None, current: None, primed: false,
+        }))
+    }
+
+    /// Flip vsynced until our buffers are on screen again, e.g. after the
+    /// surface had the CRTC. Every buffer is redrawn in full.
+    pub fn take_over(&mut self) {
+        self.reset_buffers();
+        self.primed = false;
+    }
+
+    /// Redraw every buffer in full from the next frame on, so an unchanged
+    /// frame is flipped again.
+    pub fn reset_buffers(&mut self) {
+        self.swapchain.reset_buffer_ages();
+    }
+
+    /// Use buffers of `size` from the next frame on.
+    pub fn resize(&mut self, size: (u32, u32)) {
+        self.size = size;
+        self.swapchain.resize(size.0, size.1);
+        self.primed = false;
+    }
+
+    /// Forget flips that will never complete (the device was paused).
+    pub fn reset(&mut self) {
+        self.next    = None;
+        self.pending = None;
+        self.current = None;
+        self.take_over();
+    }
+
+    /// A buffer to render the next frame into, and its age for the damage
+    /// tracker.
+    pub fn next_buffer(&mut self) -> Result<(Dmabuf, usize)> {
+        let slot = self.swapchain.acquire().context("acquire tearing buffer")?.context("no free tearing buffer")?;
+        let dmabuf = slot.export().map_err(|e| anyhow!("export dmabuf: {e:?}"))?;
+        let age = slot.age() as usize;
+        self.next = Some(slot);
+        Ok((dmabuf, age))
+    }
+
+    /// Flip the buffer from [`next_buffer`](Self::next_buffer), which must be
+    /// fully rendered: it is shown right away.
+    pub fn queue_buffer(&mut self) -> Result<()> {
+        let slot = self.next.take().context("no buffer rendered")?;
+        let fb = self.framebuffer(&slot)?;
+        match &self.atomic {
+            Some(atomic) => self.commit(atomic, fb)?,
+            None => {
+                let flags = match self.primed {
+                    true  => PageFlipFlags::EVENT | PageFlipFlags::ASYNC,
+                    false => PageFlipFlags::EVENT,
+                };
+                self.drm.page_flip(self.crtc, fb, flags, None).context("page flip")?;
+            }
+        }
+        self.swapchain.submitted(&slot);
+        self.pending = Some((slot, self.primed));
+        Ok(())
+    }
+
+    /// A page flip completed on the CRTC. Returns whether it was async, or
+    /// `None` if it wasn't ours.
+    pub fn frame_submitted(&mut self) -> Option<bool> {
+        let (slot, async_flip) = self.pending.take()?;
+        self.current = Some(slot);
+        self.primed  = true;
+        Some(async_flip)
+    }
+
+    fn commit(&self, atomic: &AtomicPlanes, fb: framebuffer::Handle) -> Result<()> {
+        let prop = |name: &str| atomic.props.get(name).copied().with_context(|| format!("primary plane has no {name}"));
+        let mut req = AtomicModeReq::new();
+        req.add_property(atomic.primary, prop("FB_ID")?, property::Value::Framebuffer(Some(fb)));
+        let mut flags = AtomicCommitFlags::PAGE_FLIP_EVENT | AtomicCommitFlags::NONBLOCK;
+        if self.primed {
+            flags |= AtomicCommitFlags::PAGE_FLIP_ASYNC;
+        } else {
+            // The surface may have left a client buffer of another size
+            // scanned out on the primary plane, and the cursor and overlays
+            // lit: show all of ours and nothing else.
+            let (w, h) = self.size;
+            req.add_property(atomic.primary, prop("CRTC_ID")?, property::Value::CRTC(Some(self.crtc)));
+            for (name, value) in [
+                ("SRC_X", 0), ("SRC_Y", 0), ("SRC_W", (w as u64) << 16), ("SRC_H", (h as u64) << 16),
+                ("CRTC_X", 0), ("CRTC_Y", 0), ("CRTC_W", w as u64), ("CRTC_H", h as u64),
+            ] {
+                req.add_property(atomic.primary, prop(name)?, property::Value::UnsignedRange(value));
+            }
+            for &plane in &atomic.others {
+                if self.drm.get_plane(plane).ok().and_then(|p| p.crtc()) != Some(self.crtc) { continue; }
+                let props = self.drm.get_properties(plane)
+                    .and_then(|set| set.as_hashmap(&self.drm))
+                    .context("plane properties")?;
+                if let (Some(fb_id), Some(crtc_id)) = (props.get("FB_ID"), props.get("CRTC_ID")) {
+                    req.add_property(plane, fb_id.handle(), property::Value::Framebuffer(None));
+                    req.add_property(plane, crtc_id.handle(), property::Value::CRTC(None));
+                }
+            }
+            debug!(crtc = ?self.crtc, "taking the CRTC over for async flips");
+        }
+        self.drm.atomic_commit(flags, req).context("atomic commit")
+    }
+
+    fn framebuffer(&self, slot: &Slot<GbmBuffer>) -> Result<framebuffer::Handle> {
+        if let Some(fb) = slot.userdata().get::<Framebuffer>() {
+            return Ok(fb.handle);
+        }
+        let bo: &gbm::BufferObject<()> = slot;
+        let flags = match bo.modifier() {
+            Modifier::Invalid => FbCmd2Flags::empty(),
+            _                 => FbCmd2Flags::MODIFIERS,
+        };
+        let handle = self.drm.add_planar_framebuffer(bo, flags).context("add framebuffer")?;
+        slot.userdata().insert_if_missing(|| Framebuffer { drm: self.drm.clone(), handle });
+        Ok(handle)
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{bail, Context, Result};
+use smithay::{
+    backend::renderer::{
+        element::{Element, Kind},
+        gles::{element::PixelShaderElement, GlesPixelProgram, GlesRenderer, Uniform, UniformName, UniformType},
+    },
+    output::Output,
+    utils::{Logical, Rectangle, Size},
+};
+use tracing::{info, warn};
+
+use crate::state::GameframeState;
+
+// ── Shader ────────────────────────────────────────────────────────────────────
+
+/// Every pattern in one pixel shader, picked by `pattern` (see
+/// [`Pattern::uniforms`]). `size` is the output in physical pixels, so the
+/// fine checkerboard lines up with the panel's pixels.
+const PATTERN_SRC: &str = r#"#version 100
+//_DEFINES_
+
+#ifdef GL_FRAGMENT_PRECISION_HIGH
+precision highp float;
+#else
+precision mediump float;
+#endif
+uniform float alpha;
+#if defined(DEBUG_FLAGS)
+uniform float tint;
+#endif
+uniform vec2 size;
+varying vec2 v_coords;
+
+uniform float pattern;
+uniform vec3 color;
+uniform float frame;
+
+void main() {
+    vec2 px = v_coords * size;
+    vec3 c = color;
+    if (pattern == 1.0) {
+        // 16 equal steps from black to white.
+        c = vec3(min(floor(v_coords.x * 16.0), 15.0) / 15.0);
+    } else if (pattern == 2.0) {
+        // Smooth ramps in white, red, green and blue, one band each.
+        float band = floor(v_coords.y * 4.0);
+        vec3 hue = band < 1.0 ? vec3(1.0)
+                 : band < 2.0 ? vec3(1.0, 0.0, 0.0)
+                 : band < 3.0 ? vec3(0.0, 1.0, 0.0)
+                 : vec3(0.0, 0.0, 1.0);
+        c = hue * v_coords.x;
+    } else if (pattern == 3.0) {
+        // Single-pixel checks above, 64 px ones below; scaling blurs the former.
+        float cell = v_coords.y < 0.5 ? 1.0 : 64.0;
+        c = vec3(mod(floor(px.x / cell) + floor(px.y / cell), 2.0));
+    } else if (pattern == 4.0) {
+        // The top band flips every refresh; below, a block steps one of 16
+        // cells per refresh, so a skipped or repeated frame shows as a jump.
+        if (v_coords.y < 0.125) {
+            c = vec3(mod(frame, 2.0));
+        } else {
+            c = vec3(floor(v_coords.x * 16.0) == mod(frame, 16.0) ? 1.0 : 0.0);
+        }
+    }
+    gl_FragColor = vec4(c, 1.0) * alpha;
+}
+"#;
+
+/// The pattern program for `renderer`'s context, compiled on first use.
+/// `None` if compilation failed (logged once; patterns are then not shown).
+fn pattern_shader(renderer: &mut GlesRenderer) -> Option<GlesPixelProgram> {
+    if let Some(cached) = renderer.egl_context().user_data().get::<Option<GlesPixelProgram>>() {
+        return cached.clone();
+    }
+    let program = renderer.compile_custom_pixel_shader(
+        PATTERN_SRC,
+        &[
+            UniformName::new("pattern", UniformType::_1f),
+            UniformName::new("color", UniformType::_3f),
+            UniformName::new("frame", UniformType::_1f),
+        ],
+    );
+    let program = program.inspect_err(|e| warn!("test pattern shader: {e:?}")).ok();
+    renderer.egl_context().user_data().insert_if_missing(|| program.clone());
+    program
+}
+
+// ── Patterns ──────────────────────────────────────────────────────────────────
+
+/// Calibration patterns, in the order Super+T steps through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    White,
+    Black,
+    Red,
+    Green,
+    Blue,
+    /// 16-step grayscale ramp (gamma, black and white crush).
+    Ramp,
+    /// Smooth white / red / green / blue gradients (banding, HDR tone mapping).
+    Gradient,
+    /// Pixel and coarse checkerboards (scaling, overscan, subpixel layout).
+    Checkerboard,
+    /// Changes every refresh (dropped frames, VRR flicker, response time).
+    Strobe,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 9] = [
+        Pattern::White, Pattern::Black, Pattern::Red, Pattern::Green, Pattern::Blue,
+        Pattern::Ramp, Pattern::Gradient, Pattern::Checkerboard, Pattern::Strobe,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::White        => "white",
+            Pattern::Black        => "black",
+            Pattern::Red          => "red",
+            Pattern::Green        => "green",
+            Pattern::Blue         => "blue",
+            Pattern::Ramp         => "ramp",
+            Pattern::Gradient     => "gradient",
+            Pattern::Checkerboard => "checkerboard",
+            Pattern::Strobe       => "strobe",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|p| p.name() == name) {
+            Some(pattern) => Ok(pattern),
+            None => bail!(
+                "unknown pattern {name:?} ({})",
+                Self::ALL.map(Pattern::name).join(", "),
+            ),
+        }
+    }
+
+    /// The one after `self`, wrapping around.
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|p| *p == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    fn uniforms(self, frame: u32) -> Vec<Uniform<'static>> {
+        let (pattern, color) = match self {
+            Pattern::White        => (0.0, [1.0, 1.0, 1.0]),
+            Pattern::Black        => (0.0, [0.0, 0.0, 0.0]),
+            Pattern::Red          => (0.0, [1.0, 0.0, 0.0]),
+            Pattern::Green        => (0.0, [0.0, 1.0, 0.0]),
+            Pattern::Blue         => (0.0, [0.0, 0.0, 1.0]),
+            Pattern::Ramp         => (1.0, [0.0; 3]),
+            Pattern::Gradient     => (2.0, [0.0; 3]),
+            Pattern::Checkerboard => (3.0, [0.0; 3]),
+            Pattern::Strobe       => (4.0, [0.0; 3]),
+        };
+        vec![
+            Uniform::new("pattern", pattern),
+            Uniform::new("color", color),
+            // Only its value mod 16 matters; keep it exact in a float.
+            Uniform::new("frame", (frame % 16) as f32),
+        ]
+    }
+}
+
+/// A test pattern shown full-screen on one output in place of everything
+/// else (`gameframe test-pattern`, Super+Shift+T).
+pub struct TestPattern {
+    pub output:  Output,
+    pub pattern: Pattern,
+    /// Frames drawn so far; drives the strobe.
+    frame:       u32,
+    element:     Option<PixelShaderElement>,
+}
+
+impl TestPattern {
+    /// Whether the pattern changes every frame, so the output has to keep
+    /// repainting at its refresh rate.
+    pub fn animated(&self) -> bool { self.pattern == Pattern::Strobe }
+
+    /// What to draw on an output of logical `size` this frame. `None` if the
+    /// shader is unavailable, in which case the output shows its usual
+    /// content.
+    pub fn element(&mut self, renderer: &mut GlesRenderer, size: Size<i32, Logical>) -> Option<PixelShaderElement> {
+        let program  = pattern_shader(renderer)?;
+        let area     = Rectangle::from_size(size);
+        let animated = self.animated();
+        match &mut self.element {
+            Some(element) if element.geometry(1.0.into()).size == area.size.to_physical(1) => {
+                if animated {
+                    self.frame = self.frame.wrapping_add(1);
+                    element.update_uniforms(self.pattern.uniforms(self.frame));
+                }
+            }
+            slot => {
+                let uniforms = self.pattern.uniforms(self.frame);
+                *slot = Some(PixelShaderElement::new(program, area, Some(vec![area]), 1.0, uniforms, Kind::Unspecified));
+            }
+        }
+        self.element.clone()
+    }
+}
+
+// ── Control ───────────────────────────────────────────────────────────────────
+
+/// Show `pattern` on the output named `output` (the focused one if `None`).
+/// Only DRM outputs draw patterns.
+pub fn show(state: &mut GameframeState, pattern: Pattern, output: Option<&str>) -> Result<()> {
+    if state.backend.as_ref().and_then(|b| b.drm()).is_none() {
+        bail!("test patterns need the DRM backend");
+    }
+    let output = match output {
+        Some(name) => state.space.outputs().find(|o| o.name() == name).cloned()
+            .with_context(|| format!("no output {name}"))?,
+        None => state.placement_output().context("no output")?,
+    };
+    if let Some(old) = state.test_pattern.take() {
+        state.queue_redraw(&old.output);
+    }
+    info!(pattern = pattern.name(), output = %output.name(), "Test pattern");
+    state.overlay.push_toast(format!("Test pattern: {}", pattern.name()), 90);
+    state.queue_redraw(&output);
+    state.test_pattern = Some(TestPattern { output, pattern, frame: 0, element: None });
+    Ok(())
+}
+
+/// Go back to the output's normal content.
+pub fn hide(state: &mut GameframeState) {
+    if let Some(old) = state.test_pattern.take() {
+        info!(output = %old.output.name(), "Test pattern off");
+        state.queue_redraw(&old.output);
+    }
+}
+
+/// Super+Shift+T: the first pattern on the focused output, or off.
+pub fn toggle(state: &mut GameframeState) {
+    if state.test_pattern.is_some() {
+        hide(state);
+    } else if let Err(e) = show(state, Pattern::ALL[0], None) {
+        warn!("Test pattern: {e:#}");
+    }
+}
+
+/// Super+T: the next pattern on the same output (the first one if none is
+/// shown).
+pub fn cycle(state: &mut GameframeState) {
+    let (pattern, output) = match &state.test_pattern {
+        Some(current) => (current.pattern.next(), Some(current.output.name())),
+        None          => (Pattern::ALL[0], None),
+    };
+    if let Err(e) = show(state, pattern, output.as_deref()) {
+        warn!("Test pattern: {e:#}");
+    }
+}
+
+/// `pattern output` for `gameframe test-pattern`, or `off`.
+pub fn describe(state: &GameframeState) -> String {
+    match &state.test_pattern {
+        Some(p) => format!("{} {}\n", p.pattern.name(), p.output.name()),
+        None    => "off\n".to_owned(),
+    }
+}
@@ -0,0 +1,102 @@
+use std::{cell::Cell, time::Duration};
+
+use smithay::{
+    desktop::Window,
+    output::Output,
+    reexports::{
+        calloop::timer::{TimeoutAction, Timer},
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+    },
+};
+use tracing::trace;
+
+use crate::{render::send_frame_callbacks, state::GameframeState};
+
+/// Frame-callback pacing for background windows (`display.background_fps`).
+///
+/// A client that draws on frame callbacks renders at whatever rate it gets
+/// them, so holding them back from windows nobody is playing in leaves the
+/// GPU and the power budget to the game in front. The windows with keyboard
+/// focus (the main seat's and each split-screen player's) and fullscreen
+/// windows always run at the output's full rate.
+pub struct Throttle {
+    interval:   Option<Duration>,
+    foreground: Vec<Window>,
+    /// Earliest time a callback held back by this pass becomes due.
+    due:        Option<Duration>,
+}
+
+/// When `window` last got a frame callback while in the background.
+#[derive(Debug, Default)]
+struct LastFrame(Cell<Option<Duration>>);
+
+/// Set on an output while a timer for its held-back callbacks is armed.
+#[derive(Debug, Default)]
+struct TimerArmed(Cell<bool>);
+
+impl Throttle {
+    pub fn new(state: &GameframeState) -> Self {
+        let fps = state.config.display.background_fps;
+        let foreground = state.focused_window().into_iter()
+            .chain(state.players.seats().map(|(_, window, _)| window.clone()))
+            .collect();
+        Self {
+            interval: (fps > 0).then(|| Duration::from_secs_f64(1.0 / fps as f64)),
+            foreground,
+            due: None,
+        }
+    }
+
+    /// Whether `window` gets its frame callbacks at `time`.
+    pub fn allow(&mut self, window: &Window, time: Duration) -> bool {
+        let Some(interval) = self.interval else { return true };
+        if self.foreground.contains(window) || is_fullscreen(window) { return true; }
+
+        let data = window.user_data();
+        data.insert_if_missing(LastFrame::default);
+        let last = &data.get::<LastFrame>().unwrap().0;
+        match last.get() {
+            Some(sent) if time < sent + interval => {
+                let due = sent + interval;
+                self.due = Some(self.due.map_or(due, |d| d.min(due)));
+                false
+            }
+            _ => {
+                last.set(Some(time));
+                true
+            }
+        }
+    }
+
+    /// Make sure the callbacks held back on `output` go out once they are
+    /// due, even if nothing repaints the output before then.
+    pub fn schedule(self, state: &GameframeState, output: &Output) {
+        let Some(due) = self.due else { return };
+        let data = output.user_data();
+        data.insert_if_missing(TimerArmed::default);
+        let armed = &data.get::<TimerArmed>().unwrap().0;
+        if armed.replace(true) { return; }
+
+        let now   = Duration::from(state.clock.now());
+        let delay = due.saturating_sub(now);
+        trace!(output = %output.name(), ?delay, "background frame callbacks held back");
+        let output = output.clone();
+        let _ = state.loop_handle.insert_source(Timer::from_duration(delay), move |_, _, state| {
+            if let Some(armed) = output.user_data().get::<TimerArmed>() {
+                armed.0.set(false);
+            }
+            if state.space.outputs().any(|o| *o == output) {
+                let now = Duration::from(state.clock.now());
+                send_frame_callbacks(state, &output, now);
+            }
+            TimeoutAction::Drop
+        });
+    }
+}
+
+/// Whether the client was told its window is fullscreen (xdg) or set it so
+/// itself (X11).
+pub(crate) fn is_fullscreen(window: &Window) -> bool {
+    window.toplevel().is_some_and(|t| t.current_state().states.contains(xdg_toplevel::State::Fullscreen))
+        || window.x11_surface().is_some_and(|x| x.is_fullscreen())
+}
@@ -0,0 +1,337 @@
+use std::{cell::Cell, collections::HashMap, time::Duration};
+
+use smithay::{
+    backend::input::TouchSlot,
+    input::touch::{DownEvent, MotionEvent, UpEvent},
+    output::Output,
+    reexports::calloop::{
+        timer::{TimeoutAction, Timer},
+        RegistrationToken,
+    },
+    utils::{Logical, Point, SERIAL_COUNTER},
+    wayland::shell::wlr_layer::Layer,
+};
+use tracing::debug;
+
+use crate::{config::GestureAction, spawn, state::GameframeState, workspace::WORKSPACE_COUNT};
+
+/// Touches on the touchscreen and the gesture they may be part of.
+///
+/// Most touches go to the surface under them as they arrive. Two kinds are
+/// kept back: ones that land in the edge zone, until they either swipe
+/// inward far enough, are held still (long press), or turn out to be
+/// ordinary touches and are replayed to the app; and every finger once a
+/// third one is down, which the app sees cancelled.
+#[derive(Default)]
+pub struct Gestures {
+    touches: HashMap<TouchSlot, Touch>,
+    /// A three-finger gesture is in progress; `true` once it has fired, so
+    /// one swipe runs one action.
+    three_finger: Option<bool>,
+}
+
+struct Touch {
+    start: Point<f64, Logical>,
+    pos:   Point<f64, Logical>,
+    mode:  Mode,
+}
+
+enum Mode {
+    /// Forwarded to clients.
+    Client,
+    /// Started in the edge zone; not sent anywhere yet.
+    Edge { edge: Edge, long_press: Option<RegistrationToken> },
+    /// Used by a gesture; dropped until it lifts.
+    Consumed,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Edge { Left, Right, Top, Bottom }
+
+impl Edge {
+    fn gesture(self) -> &'static str {
+        match self {
+            Edge::Left   => "edge_left",
+            Edge::Right  => "edge_right",
+            Edge::Top    => "edge_top",
+            Edge::Bottom => "edge_bottom",
+        }
+    }
+
+    /// How far `delta` goes away from the edge, into the screen.
+    fn inward(self, delta: Point<f64, Logical>) -> f64 {
+        match self {
+            Edge::Left   => delta.x,
+            Edge::Right  => -delta.x,
+            Edge::Top    => delta.y,
+            Edge::Bottom => -delta.y,
+        }
+    }
+}
+
+/// Output touchscreens map to: the first one, which on a handheld or kiosk
+/// is the built-in panel.
+pub fn touch_output(state: &GameframeState) -> Option<Output> {
+    state.space.outputs().next().cloned()
+}
+
+/// A finger went down at `pos` (global).
+pub fn down(state: &mut GameframeState, slot: TouchSlot, pos: Point<f64, Logical>, time: u32) {
+    if let Some(output) = state.output_at(pos) {
+        if state.layer_surface_under(pos, &[Layer::Bottom]).is_none() {
+            set_panels_revealed(state, &output, false);
+        }
+    }
+    let config = &state.config.input.touch;
+    let edge = edge_at(state, pos).filter(|e| config.gestures && config.bindings.contains_key(e.gesture()));
+    let mode = if state.gestures.three_finger.is_some() {
+        Mode::Consumed
+    } else if let Some(edge) = edge {
+        Mode::Edge { edge, long_press: long_press_timer(state, slot) }
+    } else {
+        Mode::Client
+    };
+    let forward = matches!(mode, Mode::Client);
+    state.gestures.touches.insert(slot, Touch { start: pos, pos, mode });
+
+    if state.gestures.touches.len() == 3 && three_finger_bound(state) {
+        take_over(state);
+    } else if forward {
+        forward_down(state, slot, pos, time);
+    }
+}
+
+/// A finger moved to `pos`.
+pub fn motion(state: &mut GameframeState, slot: TouchSlot, pos: Point<f64, Logical>, time: u32) {
+    let distance = state.config.input.touch.swipe_distance;
+    let Some(touch) = state.gestures.touches.get_mut(&slot) else { return };
+    touch.pos = pos;
+    let delta = pos - touch.start;
+    match touch.mode {
+        Mode::Client => forward_motion(state, slot, pos, time),
+        Mode::Consumed => three_finger_moved(state),
+        Mode::Edge { edge, .. } => {
+            if edge.inward(delta) >= distance {
+                consume(state, slot);
+                run(state, edge.gesture());
+            } else if delta.x.hypot(delta.y) >= distance {
+                // Moving along the edge, or back out: the app's.
+                release(state, slot, time);
+            }
+        }
+    }
+}
+
+/// A finger lifted.
+pub fn up(state: &mut GameframeState, slot: TouchSlot, time: u32) {
+    let Some(touch) = state.gestures.touches.remove(&slot) else { return };
+    let forwarded = match touch.mode {
+        Mode::Client => true,
+        // A tap in the edge zone: the app gets it now.
+        Mode::Edge { long_press, .. } => {
+            if let Some(token) = long_press {
+                state.loop_handle.remove(token);
+            }
+            forward_down(state, slot, touch.start, time);
+            if touch.pos != touch.start {
+                forward_motion(state, slot, touch.pos, time);
+            }
+            true
+        }
+        Mode::Consumed => false,
+    };
+    if forwarded {
+        if let Some(handle) = state.seat.get_touch() {
+            handle.up(state, &UpEvent { slot, serial: SERIAL_COUNTER.next_serial(), time });
+        }
+    }
+    if state.gestures.touches.is_empty() {
+        state.gestures.three_finger = None;
+    }
+}
+
+/// End of a set of touch events from the device.
+pub fn frame(state: &mut GameframeState) {
+    if let Some(handle) = state.seat.get_touch() {
+        handle.frame(state);
+    }
+}
+
+/// The device dropped every touch.
+pub fn cancel(state: &mut GameframeState) {
+    for touch in std::mem::take(&mut state.gestures.touches).into_values() {
+        if let Mode::Edge { long_press: Some(token), .. } = touch.mode {
+            state.loop_handle.remove(token);
+        }
+    }
+    state.gestures.three_finger = None;
+    if let Some(handle) = state.seat.get_touch() {
+        handle.cancel(state);
+    }
+}
+
+// ── Gestures ──────────────────────────────────────────────────────────────────
+
+/// Edge zone of the output under `pos` that `pos` is in, the nearest edge
+/// in a corner.
+fn edge_at(state: &GameframeState, pos: Point<f64, Logical>) -> Option<Edge> {
+    let zone   = state.config.input.touch.edge_zone;
+    let output = state.output_at(pos)?;
+    let geo    = state.space.output_geometry(&output)?;
+    let local  = pos - geo.loc.to_f64();
+    let (w, h) = (geo.size.w as f64, geo.size.h as f64);
+    [(Edge::Left, local.x), (Edge::Right, w - local.x), (Edge::Top, local.y), (Edge::Bottom, h - local.y)]
+        .into_iter()
+        .filter(|(_, d)| *d < zone)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(edge, _)| edge)
+}
+
+fn long_press_timer(state: &GameframeState, slot: TouchSlot) -> Option<RegistrationToken> {
+    let config = &state.config.input.touch;
+    if config.long_press_ms == 0 || !config.bindings.contains_key("long_press") { return None; }
+    let timer = Timer::from_duration(Duration::from_millis(config.long_press_ms as u64));
+    state.loop_handle.insert_source(timer, move |_, _, state| {
+        long_pressed(state, slot);
+        TimeoutAction::Drop
+    }).ok()
+}
+
+fn long_pressed(state: &mut GameframeState, slot: TouchSlot) {
+    let slop = state.config.input.drag_threshold;
+    let Some(touch) = state.gestures.touches.get_mut(&slot) else { return };
+    let Mode::Edge { long_press, .. } = &mut touch.mode else { return };
+    // The timer removes itself.
+    *long_press = None;
+    let delta = touch.pos - touch.start;
+    if delta.x.hypot(delta.y) > slop { return; }
+    touch.mode = Mode::Consumed;
+    run(state, "long_press");
+}
+
+fn three_finger_bound(state: &GameframeState) -> bool {
+    let config = &state.config.input.touch;
+    config.gestures && config.bindings.keys().any(|g| g.starts_with("three_finger_"))
+}
+
+/// A third finger is down: every finger is the compositor's from here on,
+/// and clients see the ones they had cancelled.
+fn take_over(state: &mut GameframeState) {
+    let mut forwarded = false;
+    for touch in state.gestures.touches.values_mut() {
+        match std::mem::replace(&mut touch.mode, Mode::Consumed) {
+            Mode::Client => forwarded = true,
+            Mode::Edge { long_press: Some(token), .. } => state.loop_handle.remove(token),
+            _ => {}
+        }
+    }
+    state.gestures.three_finger = Some(false);
+    if forwarded {
+        if let Some(handle) = state.seat.get_touch() {
+            handle.cancel(state);
+        }
+    }
+}
+
+/// Fire the three-finger swipe once the fingers have moved far enough on
+/// average.
+fn three_finger_moved(state: &mut GameframeState) {
+    if state.gestures.three_finger != Some(false) { return; }
+    let touches = &state.gestures.touches;
+    let n = touches.len() as f64;
+    let (dx, dy) = touches.values().fold((0.0, 0.0), |(x, y), t| (x + t.pos.x - t.start.x, y + t.pos.y - t.start.y));
+    let (dx, dy) = (dx / n, dy / n);
+    if dx.hypot(dy) < state.config.input.touch.swipe_distance { return; }
+    state.gestures.three_finger = Some(true);
+    let gesture = if dx.abs() > dy.abs() {
+        if dx < 0.0 { "three_finger_left" } else { "three_finger_right" }
+    } else if dy < 0.0 {
+        "three_finger_up"
+    } else {
+        "three_finger_down"
+    };
+    run(state, gesture);
+}
+
+fn consume(state: &mut GameframeState, slot: TouchSlot) {
+    let Some(touch) = state.gestures.touches.get_mut(&slot) else { return };
+    if let Mode::Edge { long_press: Some(token), .. } = std::mem::replace(&mut touch.mode, Mode::Consumed) {
+        state.loop_handle.remove(token);
+    }
+}
+
+/// Hand a held-back touch to the app: down where it started, then where it
+/// is now.
+fn release(state: &mut GameframeState, slot: TouchSlot, time: u32) {
+    let Some(touch) = state.gestures.touches.get_mut(&slot) else { return };
+    if let Mode::Edge { long_press: Some(token), .. } = std::mem::replace(&mut touch.mode, Mode::Client) {
+        state.loop_handle.remove(token);
+    }
+    let (start, pos) = (touch.start, touch.pos);
+    forward_down(state, slot, start, time);
+    forward_motion(state, slot, pos, time);
+}
+
+fn run(state: &mut GameframeState, gesture: &str) {
+    let Some(action) = state.config.input.touch.bindings.get(gesture).cloned() else { return };
+    debug!(gesture, ?action, "touch gesture");
+    match action {
+        GestureAction::NextWorkspace => state.switch_workspace((state.workspaces.active() + 1) % WORKSPACE_COUNT),
+        GestureAction::PrevWorkspace => {
+            state.switch_workspace((state.workspaces.active() + WORKSPACE_COUNT - 1) % WORKSPACE_COUNT)
+        }
+        GestureAction::FocusNext     => state.cycle_focus(1),
+        GestureAction::FocusPrev     => state.cycle_focus(-1),
+        GestureAction::CloseWindow   => state.close_focused(),
+        GestureAction::ToggleOverlay => state.overlay.toggle(),
+        GestureAction::RevealPanels  => {
+            if let Some(output) = touch_output(state) {
+                set_panels_revealed(state, &output, true);
+            }
+        }
+        GestureAction::Launch(cmd)   => spawn::launch(state, &cmd),
+    }
+}
+
+// ── Clients ───────────────────────────────────────────────────────────────────
+
+/// Touch the surface under `pos`, activating its window as a click would.
+fn forward_down(state: &mut GameframeState, slot: TouchSlot, pos: Point<f64, Logical>, time: u32) {
+    let Some(handle) = state.seat.get_touch() else { return };
+    if let Some((window, _)) = state.window_under(pos) {
+        state.activate_window(&window);
+    }
+    let focus = state.surface_under(pos);
+    handle.down(state, focus, &DownEvent { slot, location: pos, serial: SERIAL_COUNTER.next_serial(), time });
+}
+
+fn forward_motion(state: &mut GameframeState, slot: TouchSlot, pos: Point<f64, Logical>, time: u32) {
+    let Some(handle) = state.seat.get_touch() else { return };
+    let focus = state.surface_under(pos);
+    handle.motion(state, focus, &MotionEvent { slot, location: pos, time });
+}
+
+// ── Revealed panels ───────────────────────────────────────────────────────────
+
+/// Set on an output whose bottom-layer panels are shown above its windows.
+struct PanelsRevealed(Cell<bool>);
+
+fn set_panels_revealed(state: &mut GameframeState, output: &Output, revealed: bool) {
+    output.user_data().insert_if_missing(|| PanelsRevealed(Cell::new(false)));
+    let flag = output.user_data().get::<PanelsRevealed>().unwrap();
+    if flag.0.replace(revealed) != revealed {
+        debug!(output = %output.name(), revealed, "panels");
+        state.queue_redraw(output);
+    }
+}
+
+/// Layers on `output` above its windows and below them, topmost first.
+/// Revealed panels move the bottom layer up.
+pub fn layer_order(output: Option<&Output>) -> (&'static [Layer], &'static [Layer]) {
+    let revealed = output.and_then(|o| o.user_data().get::<PanelsRevealed>()).is_some_and(|r| r.0.get());
+    if revealed {
+        (&[Layer::Overlay, Layer::Top, Layer::Bottom], &[Layer::Background])
+    } else {
+        (&[Layer::Overlay, Layer::Top], &[Layer::Bottom, Layer::Background])
+    }
+}
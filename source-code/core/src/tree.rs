@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use smithay::{
+    backend::renderer::utils::with_renderer_surface_state,
+    desktop::{PopupManager, Window},
+    input::Seat,
+    output::Output,
+    reexports::{
+        wayland_protocols::{
+            wp::linux_dmabuf::zv1::server::zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+            xdg::shell::server::xdg_toplevel,
+        },
+        wayland_server::{protocol::{wl_output::WlOutput, wl_surface::WlSurface}, Resource},
+    },
+    utils::{Logical, Point, Rectangle, Transform},
+    wayland::{
+        compositor::{get_children, get_role, with_states, SubsurfaceCachedState},
+        seat::WaylandFocus,
+    },
+};
+
+use crate::{rules, state::GameframeState};
+
+/// Bumped whenever a field changes meaning or goes away; new fields may be
+/// added without a bump.
+const TREE_VERSION: u32 = 1;
+
+/// Snapshot of the session for `gameframe tree`, in the spirit of
+/// `swaymsg -t get_tree`. Coordinates are logical and global unless noted.
+/// Windows are those mapped on the active workspace, topmost last; focus
+/// and seats refer to them by index into `windows`.
+#[derive(Serialize)]
+struct Tree {
+    version:   u32,
+    workspace: usize,
+    outputs:   Vec<OutputNode>,
+    windows:   Vec<WindowNode>,
+    focus:     FocusNode,
+    seats:     Vec<SeatNode>,
+    globals:   Vec<&'static str>,
+}
+
+#[derive(Serialize)]
+struct Rect {
+    x:      i32,
+    y:      i32,
+    width:  i32,
+    height: i32,
+}
+
+impl From<Rectangle<i32, Logical>> for Rect {
+    fn from(r: Rectangle<i32, Logical>) -> Self {
+        Self { x: r.loc.x, y: r.loc.y, width: r.size.w, height: r.size.h }
+    }
+}
+
+#[derive(Serialize)]
+struct ModeNode {
+    width:   i32,
+    height:  i32,
+    /// mHz.
+    refresh: i32,
+}
+
+#[derive(Serialize)]
+struct OutputNode {
+    name:      String,
+    make:      String,
+    model:     String,
+    geometry:  Option<Rect>,
+    mode:      Option<ModeNode>,
+    scale:     f64,
+    transform: &'static str,
+    focused:   bool,
+}
+
+#[derive(Serialize)]
+struct WindowNode {
+    app_id:     Option<String>,
+    title:      Option<String>,
+    /// Client process, when the socket credentials are known.
+    pid:        Option<i32>,
+    geometry:   Option<Rect>,
+    /// Geometry plus decorations, shadows and subsurfaces sticking out.
+    bbox:       Option<Rect>,
+    outputs:    Vec<String>,
+    activated:  bool,
+    fullscreen: bool,
+    surface:    SurfaceNode,
+    popups:     Vec<PopupNode>,
+}
+
+/// A surface and its subsurfaces, stacked bottom to top.
+#[derive(Serialize)]
+struct SurfaceNode {
+    /// Protocol object id, unique within its client.
+    id:          u32,
+    role:        Option<&'static str>,
+    /// Relative to the parent surface; 0,0 for a root.
+    x:           i32,
+    y:           i32,
+    /// Size of the attached buffer in logical pixels, if one is attached.
+    size:        Option<[i32; 2]>,
+    subsurfaces: Vec<SurfaceNode>,
+}
+
+#[derive(Serialize)]
+struct PopupNode {
+    /// Relative to the window's root surface.
+    x:       i32,
+    y:       i32,
+    surface: SurfaceNode,
+}
+
+#[derive(Serialize)]
+struct FocusNode {
+    output:         Option<String>,
+    /// Window holding keyboard focus on the main seat.
+    window:         Option<usize>,
+    /// Window under the pointer.
+    pointer_window: Option<usize>,
+    pointer:        [f64; 2],
+}
+
+#[derive(Serialize)]
+struct SeatNode {
+    name:     String,
+    /// Window the seat's keyboard is focused on.
+    keyboard: Option<usize>,
+    pointer:  [f64; 2],
+}
+
+/// The session as pretty-printed JSON, for the control socket.
+pub fn dump(state: &GameframeState) -> Result<String> {
+    let windows: Vec<Window> = state.space.elements().cloned().collect();
+    let index = |window: Option<Window>| window.and_then(|w| windows.iter().position(|e| *e == w));
+    let focus_of = |seat: &Seat<GameframeState>| {
+        index(seat.get_keyboard().and_then(|k| k.current_focus()).and_then(|s| state.window_for_surface(&s)))
+    };
+
+    let outputs = state.space.outputs().map(|o| output_node(state, o)).collect();
+    let window_nodes = windows.iter().map(|w| window_node(state, w)).collect();
+
+    let pointer_window = state.seat.get_pointer()
+        .and_then(|p| p.current_focus())
+        .and_then(|s| state.window_for_surface(&s));
+    let focus = FocusNode {
+        output:         state.focused_output.as_ref().map(Output::name),
+        window:         focus_of(&state.seat),
+        pointer_window: index(pointer_window),
+        pointer:        point(state.pointer_location),
+    };
+
+    let main = SeatNode { name: state.seat.name().to_owned(), keyboard: focus.window, pointer: focus.pointer };
+    let players = state.players.seats().map(|(seat, _, pointer)| SeatNode {
+        name:     seat.name().to_owned(),
+        keyboard: focus_of(seat),
+        pointer:  point(pointer),
+    });
+
+    let mut globals = state.globals.clone();
+    if state.space.outputs().next().is_some() { globals.push(WlOutput::interface().name); }
+    if state.dmabuf_global.is_some() { globals.push(ZwpLinuxDmabufV1::interface().name); }
+
+    let tree = Tree {
+        version:   TREE_VERSION,
+        workspace: state.workspaces.active(),
+        outputs,
+        windows:   window_nodes,
+        focus,
+        seats:     std::iter::once(main).chain(players).collect(),
+        globals,
+    };
+    let mut json = serde_json::to_string_pretty(&tree).context("serialize tree")?;
+    json.push('\n');
+    Ok(json)
+}
+
+fn output_node(state: &GameframeState, output: &Output) -> OutputNode {
+    let props = output.physical_properties();
+    OutputNode {
+        name:      output.name(),
+        make:      props.make,
+        model:     props.model,
+        geometry:  state.space.output_geometry(output).map(Rect::from),
+        mode:      output.current_mode().map(|m| ModeNode { width: m.size.w, height: m.size.h, refresh: m.refresh }),
+        scale:     output.current_scale().fractional_scale(),
+        transform: transform_name(output.current_transform()),
+        focused:   state.focused_output.as_ref() == Some(output),
+    }
+}
+
+fn window_node(state: &GameframeState, window: &Window) -> WindowNode {
+    let (app_id, title) = rules::window_identity(window);
+    let toplevel = window.toplevel();
+    let has_state = |s: xdg_toplevel::State| toplevel.is_some_and(|t| t.current_state().states.contains(s));
+    let x11 = window.x11_surface();
+    let root = window.wl_surface().map(|s| s.into_owned());
+    let pid = root.as_ref()
+        .and_then(|s| s.client())
+        .and_then(|c| c.get_credentials(&state.display_handle).ok())
+        .map(|c| c.pid);
+    let popups = root.as_ref()
+        .map(|root| {
+            PopupManager::popups_for_surface(root)
+                .map(|(popup, offset)| PopupNode { x: offset.x, y: offset.y, surface: surface_node(popup.wl_surface(), None) })
+                .collect()
+        })
+        .unwrap_or_default();
+    WindowNode {
+        app_id,
+        title,
+        pid,
+        geometry:   state.space.element_geometry(window).map(Rect::from),
+        bbox:       state.space.element_bbox(window).map(Rect::from),
+        outputs:    state.space.outputs_for_element(window).iter().map(Output::name).collect(),
+        activated:  has_state(xdg_toplevel::State::Activated) || x11.is_some_and(|x| x.is_activated()),
+        fullscreen: has_state(xdg_toplevel::State::Fullscreen) || x11.is_some_and(|x| x.is_fullscreen()),
+        surface:    root.as_ref().map(|s| surface_node(s, None)).unwrap_or_else(empty_surface),
+        popups,
+    }
+}
+
+/// `surface` and its subsurfaces; `location` is set for a subsurface.
+fn surface_node(surface: &WlSurface, location: Option<Point<i32, Logical>>) -> SurfaceNode {
+    let loc  = location.unwrap_or_default();
+    let size = with_renderer_surface_state(surface, |s| s.surface_size()).flatten();
+    let subsurfaces = get_children(surface)
+        .iter()
+        .map(|child| {
+            let loc = with_states(child, |states| {
+                states.cached_state.get::<SubsurfaceCachedState>().current().location
+            });
+            surface_node(child, Some(loc))
+        })
+        .collect();
+    SurfaceNode {
+        id:   surface.id().protocol_id(),
+        role: get_role(surface),
+        x:    loc.x,
+        y:    loc.y,
+        size: size.map(|s| [s.w, s.h]),
+        subsurfaces,
+    }
+}
+
+/// Stand-in for an X11 window whose Wayland surface is not associated yet.
+fn empty_surface() -> SurfaceNode {
+    SurfaceNode { id: 0, role: None, x: 0, y: 0, size: None, subsurfaces: Vec::new() }
+}
+
+fn point(p: Point<f64, Logical>) -> [f64; 2] { [p.x, p.y] }
+
+fn transform_name(transform: Transform) -> &'static str {
+    match transform {
+        Transform::Normal     => "normal",
+        Transform::_90        => "90",
+        Transform::_180       => "180",
+        Transform::_270       => "270",
+        Transform::Flipped    => "flipped",
+        Transform::Flipped90  => "flipped-90",
+        Transform::Flipped180 => "flipped-180",
+        Transform::Flipped270 => "flipped-270",
+    }
+}
@@ -0,0 +1,259 @@
+use std::cell::{Cell, RefCell};
+
+use anyhow::{anyhow, bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            damage::OutputDamageTracker,
+            element::{
+                surface::WaylandSurfaceRenderElement, texture::TextureRenderElement, AsRenderElements, Id, Kind,
+                RenderElement,
+            },
+            gles::{GlesRenderer, GlesTexProgram, GlesTexture},
+            Bind, Offscreen, Renderer, TextureFilter,
+        },
+    },
+    desktop::Window,
+    output::Output,
+    utils::{Logical, Physical, Point, Rectangle, Scale, Size, Transform},
+};
+use tracing::{debug, info, info_span, warn};
+
+use crate::{
+    config::{default_fsr_sharpness, Config, UpscaleMode},
+    render::CLEAR_COLOR,
+    shaders::fsr_shaders,
+    state::GameframeState,
+    supersample::SharpenElement,
+    throttle::is_fullscreen,
+};
+
+/// Upscale mode set at runtime (`gameframe output upscale`), kept on the
+/// output over its configured one.
+struct UpscaleOverride(Cell<Option<UpscaleMode>>);
+
+/// A fullscreen window drawn larger than it is, centred on its output.
+#[derive(Debug, Clone)]
+pub struct Upscaled {
+    pub window: Window,
+    /// Where its geometry origin is drawn, relative to the output.
+    pub loc:    Point<i32, Logical>,
+    pub factor: f64,
+    pub mode:   UpscaleMode,
+}
+
+/// How `output` scales fullscreen windows smaller than itself.
+pub fn upscale_mode(output: &Output, config: &Config) -> UpscaleMode {
+    output.user_data().get::<UpscaleOverride>()
+        .and_then(|o| o.0.get())
+        .unwrap_or_else(|| config.display.upscale_for(&output.name()))
+}
+
+/// Use `mode` on `output` over its configured one until the compositor
+/// exits.
+pub fn set_upscale_filter(output: &Output, mode: UpscaleMode) {
+    let data = output.user_data();
+    data.insert_if_missing(|| UpscaleOverride(Cell::new(None)));
+    data.get::<UpscaleOverride>().unwrap().0.set(Some(mode));
+    info!(output = output.name(), ?mode, "Upscale mode");
+}
+
+/// `gameframe output upscale NAME MODE`: `linear`, `nearest`,
+/// `integer-only`, or `fsr[:SHARPNESS]`.
+pub fn set_upscale_mode(state: &mut GameframeState, name: &str, mode: &str) -> Result<()> {
+    let mode = match mode.split_once(':') {
+        None if mode == "linear"  => UpscaleMode::Linear,
+        None if mode == "nearest" => UpscaleMode::Nearest,
+        None if mode == "integer" || mode == "integer-only" => UpscaleMode::IntegerOnly,
+        None if mode == "fsr" => UpscaleMode::Fsr { sharpness: default_fsr_sharpness() },
+        Some(("fsr", s)) => {
+            let sharpness: f32 = s.parse().with_context(|| format!("bad FSR sharpness {s:?}"))?;
+            UpscaleMode::Fsr { sharpness: sharpness.clamp(0.0, 1.0) }
+        }
+        _ => bail!("unknown upscale mode {mode:?} (linear, nearest, integer-only, fsr[:SHARPNESS])"),
+    };
+    let output = state.space.outputs()
+        .find(|o| o.name() == name)
+        .cloned()
+        .with_context(|| format!("no output {name}"))?;
+    set_upscale_filter(&output, mode);
+    state.queue_redraw(&output);
+    Ok(())
+}
+
+/// The window to upscale on `output`: the only one there, fullscreen, and
+/// smaller than the output (a game that kept its own resolution). Not
+/// during split-screen, where panes are half the output on purpose.
+pub fn upscaled(state: &GameframeState, output: &Output) -> Option<Upscaled> {
+    if state.split.as_ref().is_some_and(|s| &s.output == output) { return None; }
+    let output_geo  = state.space.output_geometry(output)?;
+    let mut windows = state.space.elements_for_output(output);
+    let window = windows.next()?;
+    if windows.next().is_some() || !is_fullscreen(window) { return None; }
+    let mode = upscale_mode(output, &state.config);
+    let (loc, factor) = fit(mode, window.geometry().size, output_geo.size)?;
+    Some(Upscaled { window: window.clone(), loc, factor, mode })
+}
+
+/// Offset and factor that centre `window` on `output` as large as `mode`
+/// allows with its aspect ratio kept; `None` unless it is smaller than the
+/// output.
+fn fit(mode: UpscaleMode, window: Size<i32, Logical>, output: Size<i32, Logical>) -> Option<(Point<i32, Logical>, f64)> {
+    if window.is_empty() || window.w > output.w || window.h > output.h || window == output { return None; }
+    let factor = (output.w as f64 / window.w as f64).min(output.h as f64 / window.h as f64);
+    let factor = match mode {
+        UpscaleMode::IntegerOnly => factor.floor(),
+        UpscaleMode::Linear | UpscaleMode::Nearest | UpscaleMode::Fsr { .. } => factor,
+    };
+    let size = window.to_f64().upscale(factor);
+    let loc  = Point::from(((output.w as f64 - size.w) / 2.0, (output.h as f64 - size.h) / 2.0));
+    Some((loc.to_i32_round(), factor))
+}
+
+/// Texture filtering for the next frame: nearest-neighbour while
+/// `upscaled` asks for it, bilinear otherwise.
+pub(crate) fn set_filter(renderer: &mut GlesRenderer, upscaled: Option<&Upscaled>) {
+    let filter = match upscaled.map(|u| u.mode) {
+        Some(UpscaleMode::Nearest | UpscaleMode::IntegerOnly) => TextureFilter::Nearest,
+        _ => TextureFilter::Linear,
+    };
+    if let Err(e) = renderer.upscale_filter(filter) {
+        debug!("upscale filter: {e:?}");
+    }
+}
+
+/// Where `point` (global) falls in the unscaled layout: on an output showing
+/// an upscaled window, the point of that window drawn under it; elsewhere
+/// `point` itself. Input is routed by this, so clicks land where they were
+/// aimed.
+pub(crate) fn unscale(state: &GameframeState, point: Point<f64, Logical>) -> Point<f64, Logical> {
+    let Some(output) = state.space.output_under(point).next() else { return point };
+    let Some(upscaled) = upscaled(state, output) else { return point };
+    let (Some(output_geo), Some(loc)) = (state.space.output_geometry(output), state.space.element_location(&upscaled.window))
+    else {
+        return point;
+    };
+    let drawn = (output_geo.loc + upscaled.loc).to_f64();
+    loc.to_f64() + (point - drawn).downscale(upscaled.factor)
+}
+
+// ── FSR ───────────────────────────────────────────────────────────────────────
+
+/// An offscreen texture one FSR pass draws into.
+struct Pass {
+    size:           Size<i32, Physical>,
+    texture:        GlesTexture,
+    damage_tracker: OutputDamageTracker,
+    /// Renewed whenever the texture changes, so the next pass sees it.
+    id:             Id,
+    fresh:          bool,
+}
+
+impl Pass {
+    fn new(renderer: &mut GlesRenderer, size: Size<i32, Physical>, scale: f64) -> Result<Self> {
+        let texture: GlesTexture = renderer
+            .create_buffer(Fourcc::Abgr8888, size.to_logical(1).to_buffer(1, Transform::Normal))
+            .with_context(|| format!("{}x{} FSR buffer", size.w, size.h))?;
+        Ok(Self {
+            size,
+            texture,
+            damage_tracker: OutputDamageTracker::new(size, scale, Transform::Normal),
+            id:             Id::new(),
+            fresh:          true,
+        })
+    }
+
+    fn render<E: RenderElement<GlesRenderer>>(&mut self, renderer: &mut GlesRenderer, elements: &[E]) -> Result<()> {
+        let age = if self.fresh { 0 } else { 1 };
+        let mut fb = renderer.bind(&mut self.texture).context("bind FSR buffer")?;
+        let res = self.damage_tracker
+            .render_output(renderer, &mut fb, age, elements, CLEAR_COLOR)
+            .map_err(|e| anyhow!("render_output: {e:?}"))?;
+        if res.damage.is_some() {
+            self.id = Id::new();
+        }
+        self.fresh = false;
+        Ok(())
+    }
+
+    /// The texture as an element `size` (logical) large at `loc`, read
+    /// through `program`.
+    fn element(
+        &self,
+        renderer: &GlesRenderer,
+        loc:      Point<i32, Physical>,
+        size:     Size<i32, Logical>,
+        program:  GlesTexProgram,
+        strength: f32,
+    ) -> SharpenElement {
+        let texture = TextureRenderElement::from_static_texture(
+            self.id.clone(),
+            renderer.context_id(),
+            loc.to_f64(),
+            self.texture.clone(),
+            1,
+            Transform::Normal,
+            None,
+            None,
+            Some(size),
+            None,
+            Kind::Unspecified,
+        );
+        let texel = [1.0 / self.size.w as f32, 1.0 / self.size.h as f32];
+        SharpenElement::new(texture, program, texel, strength)
+    }
+}
+
+/// Per-output FSR buffers: the window at its own size, then upscaled.
+struct FsrPasses(RefCell<Option<(Pass, Pass)>>);
+
+/// `window` run through FSR onto `drawn` (output-relative): rendered
+/// offscreen at its own size, upscaled by EASU into a second buffer, and
+/// returned as that buffer drawn through RCAS at `sharpness`. `None` if
+/// the shaders or buffers aren't available; the caller then scales the
+/// window bilinearly.
+pub(crate) fn fsr(
+    renderer:  &mut GlesRenderer,
+    output:    &Output,
+    window:    &Window,
+    alpha:     f32,
+    drawn:     Rectangle<i32, Physical>,
+    sharpness: f32,
+) -> Option<SharpenElement> {
+    let shaders = fsr_shaders(renderer)?;
+    let scale   = output.current_scale().fractional_scale();
+    let geo     = window.geometry();
+    let native  = geo.size.to_physical_precise_round(scale);
+    if native.is_empty() || drawn.size.is_empty() { return None; }
+
+    let data = output.user_data();
+    data.insert_if_missing(|| FsrPasses(RefCell::new(None)));
+    let mut slot = data.get::<FsrPasses>().unwrap().0.borrow_mut();
+    if slot.as_ref().map_or(true, |(a, b)| a.size != native || b.size != drawn.size) {
+        let passes = Pass::new(renderer, native, scale).and_then(|a| Ok((a, Pass::new(renderer, drawn.size, scale)?)));
+        match passes {
+            Ok(p)  => *slot = Some(p),
+            Err(e) => {
+                warn!(output = output.name(), "FSR unavailable: {e:#}");
+                return None;
+            }
+        }
+    }
+    let (source, upscaled) = slot.as_mut().unwrap();
+    let _span = info_span!("fsr", w = native.w, h = native.h).entered();
+
+    // Geometry origin at the buffer's top-left; decorations drawn outside
+    // the geometry (client-side shadows) are cut off.
+    let loc = (Point::default() - geo.loc).to_physical_precise_round(scale);
+    let surfaces = window.render_elements::<WaylandSurfaceRenderElement<_>>(renderer, loc, Scale::from(scale), alpha);
+    if let Err(e) = source.render(renderer, &surfaces) {
+        debug!("FSR source pass: {e:#}");
+    }
+    let logical = |size: Size<i32, Physical>| size.to_f64().to_logical(scale).to_i32_round();
+    let easu = source.element(renderer, Point::default(), logical(drawn.size), shaders.easu, 0.0);
+    if let Err(e) = upscaled.render(renderer, &[easu]) {
+        debug!("FSR upscale pass: {e:#}");
+    }
+    Some(upscaled.element(renderer, drawn.loc, logical(drawn.size), shaders.rcas, sharpness.clamp(0.0, 1.0)))
+}
@@ -24,6 +24,13 @@ impl WindowStack {
         }
     }
 
+    pub fn send_to_bottom(&mut self, window: &Window) {
+        if let Some(pos) = self.windows.iter().position(|w| w == window) {
+            let w = self.windows.remove(pos);
+            self.windows.push(w);
+        }
+    }
+
     /// Remove windows whose WlSurface matches.
     /// FIX: wl_surface() returns Option<Cow<'_, WlSurface>> – compare via as_ref()
     pub fn remove_by_wl_surface(&mut self, surface: &WlSurface) {
@@ -35,6 +42,10 @@ impl WindowStack {
         });
     }
 
+    pub fn remove(&mut self, window: &Window) {
+        self.windows.retain(|w| w != window);
+    }
+
     pub fn top(&self) -> Option<&Window> { self.windows.first() }
 
     /// Owned WlSurface of the topmost window.
@@ -0,0 +1,81 @@
+use smithay::{
+    desktop::{Space, Window},
+    utils::{Logical, Point},
+};
+use tracing::info;
+
+/// Number of workspaces (Super+1 … Super+9).
+pub const WORKSPACE_COUNT: usize = 9;
+
+/// A window parked on an inactive workspace with the location it had there.
+pub type StashedWindow = (Window, Point<i32, Logical>);
+
+/// Simple numbered workspaces on top of a single `Space`. Only the active
+/// workspace is mapped; the others are stashed and re-mapped on switch.
+pub struct Workspaces {
+    active:  usize,
+    stashed: Vec<Vec<StashedWindow>>,
+}
+
+impl Workspaces {
+    pub fn new() -> Self {
+        Self { active: 0, stashed: vec![Vec::new(); WORKSPACE_COUNT] }
+    }
+
+    pub fn active(&self) -> usize { self.active }
+
+    /// Make workspace `idx` active. Returns the windows that were just
+    /// unmapped (for the outgoing half of a slide), or `None` if nothing
+    /// changed.
+    pub fn switch_to(&mut self, idx: usize, space: &mut Space<Window>) -> Option<Vec<StashedWindow>> {
+        if idx >= WORKSPACE_COUNT || idx == self.active { return None; }
+
+        let outgoing: Vec<StashedWindow> = space
+            .elements()
+            .filter_map(|w| space.element_location(w).map(|loc| (w.clone(), loc)))
+            .collect();
+        for (window, _) in &outgoing {
+            space.unmap_elem(window);
+        }
+        for (window, loc) in std::mem::take(&mut self.stashed[idx]) {
+            space.map_element(window, loc, false);
+        }
+        self.stashed[self.active] = outgoing.clone();
+
+        info!(from = self.active + 1, to = idx + 1, "Workspace switch");
+        self.active = idx;
+        Some(outgoing)
+    }
+
+    /// Whether `window` is parked on an inactive workspace.
+    pub fn is_stashed(&self, window: &Window) -> bool {
+        self.stashed.iter().flatten().any(|(w, _)| w == window)
+    }
+
+    /// Every stashed window with its workspace and location there.
+    pub fn stashed(&self) -> impl Iterator<Item = (usize, &Window, Point<i32, Logical>)> {
+        self.stashed.iter().enumerate().flat_map(|(idx, ws)| ws.iter().map(move |(w, loc)| (idx, w, *loc)))
+    }
+
+    /// Park `window` on the inactive workspace `idx` without showing it.
+    pub fn stash(&mut self, idx: usize, window: Window, loc: Point<i32, Logical>) {
+        if let Some(ws) = self.stashed.get_mut(idx) {
+            ws.push((window, loc));
+        }
+    }
+
+    /// Forget `window` if it is stashed. Returns where it was on its
+    /// workspace.
+    pub fn remove_window(&mut self, window: &Window) -> Option<Point<i32, Logical>> {
+        for ws in &mut self.stashed {
+            if let Some(idx) = ws.iter().position(|(w, _)| w == window) {
+                return Some(ws.remove(idx).1);
+            }
+        }
+        None
+    }
+}
+
+impl Default for Workspaces {
+    fn default() -> Self { Self::new() }
+}
@@ -1,61 +1,72 @@
-use anyhow::Result;
-use smithay::reexports::{calloop::LoopHandle, wayland_server::Display};
-use tracing::info;
+use std::process::Stdio;
 
-pub struct XWaylandHandle {
-    pub display_number: u32,
+use anyhow::{anyhow, Context, Result};
+use smithay::{
+    desktop::Window,
+    utils::{Logical, Rectangle},
+    wayland::xwayland_shell::{XWaylandShellHandler, XWaylandShellState},
+    xwayland::{
+        xwm::{Reorder, ResizeEdge, XwmId},
+        X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler,
+    },
+};
+use tracing::{debug, info, warn};
+
+use crate::state::GameframeState;
+
+/// The X server for X11-only games and launchers, and the window manager
+/// connection to it. X11 toplevels become ordinary `Window`s in the space,
+/// placed and focused like Wayland ones; override-redirect windows
+/// (menus, tooltips, splash screens) go where the client put them and
+/// never take focus.
+#[derive(Default)]
+pub struct XWaylandState {
+    /// Set once Xwayland has started and accepted us as its window manager.
+    pub wm:             Option<X11Wm>,
+    pub display_number: Option<u32>,
 }
 
-/// Start XWayland and wire it into the calloop event loop.
-pub fn start<D: 'static>(
-    _loop_handle: &LoopHandle<'static, D>,
-    _display: &Display<D>,
-) -> Result<XWaylandHandle> {
+/// Spawn Xwayland and wire it into the event loop. `DISPLAY` is set right
+/// away, so clients started before the server is up wait for it instead of
+/// failing.
+pub fn start(state: &mut GameframeState) -> Result<()> {
     let path = find_xwayland()?;
     info!(binary = %path, "Starting XWayland");
 
-    // ── Full Smithay 0.7 XWayland wiring ─────────────────────────────────────
-    //
-    // use smithay::xwayland::{XWayland, XWaylandClientData, XWaylandEvent};
-    //
-    // let (xwayland, client_token) = XWayland::new(_loop_handle, _display)
-    //     .context("XWayland::new")?;
-    //
-    // _loop_handle
-    //     .insert_source(xwayland, |event, _, state: &mut D| match event {
-    //         XWaylandEvent::Ready {
-    //             connection, client, display_number, wm_fd,
-    //         } => {
-    //             info!("XWayland ready on :{display_number}");
-    //             std::env::set_var("DISPLAY", format!(":{display_number}"));
-    //
-    //             // Start the X11 window manager
-    //             if let Ok(xwm) = X11Wm::start_wm(
-    //                 state.loop_handle.clone(),
-    //                 wm_fd,
-    //                 connection,
-    //                 client,
-    //             ) {
-    //                 state.xwm = Some(xwm);
-    //             }
-    //         }
-    //         XWaylandEvent::Exited => {
-    //             warn!("XWayland exited unexpectedly");
-    //             state.xwm = None;
-    //         }
-    //     })
-    //     .context("XWayland event source")?;
-    //
-    // ── Stub: set DISPLAY so spawned apps find XWayland ──────────────────────
-    // The real wiring above requires GameframeState to implement XwmHandler
-    // (handle_request, map_window, unmap_window, etc.) which is a non-trivial
-    // addition; tracked as issue #12.
-
-    let display_number = 1u32;
+    let (xwayland, client) = XWayland::spawn(
+        &state.display_handle,
+        None,
+        std::iter::empty::<(String, String)>(),
+        true,
+        Stdio::null(),
+        Stdio::null(),
+        |_| (),
+    )
+    .context("spawning Xwayland")?;
+    let display_number = xwayland.display_number();
     std::env::set_var("DISPLAY", format!(":{display_number}"));
     info!("DISPLAY=:{display_number} set for X11 clients");
 
-    Ok(XWaylandHandle { display_number })
+    state.loop_handle
+        .insert_source(xwayland, move |event, _, state| match event {
+            XWaylandEvent::Ready { x11_socket, display_number } => {
+                match X11Wm::start_wm(state.loop_handle.clone(), x11_socket, client.clone()) {
+                    Ok(wm) => {
+                        info!("XWayland ready on :{display_number}");
+                        state.xwayland.wm = Some(wm);
+                        state.xwayland.display_number = Some(display_number);
+                    }
+                    Err(e) => warn!("X11 window manager: {e}"),
+                }
+            }
+            XWaylandEvent::Error => {
+                warn!("XWayland exited unexpectedly");
+                forget_x11_windows(state);
+                state.xwayland = XWaylandState::default();
+            }
+        })
+        .map_err(|e| anyhow!("XWayland event source: {e}"))?;
+    Ok(())
 }
 
 fn find_xwayland() -> Result<String> {
@@ -72,3 +83,187 @@ fn find_xwayland() -> Result<String> {
     }
     anyhow::bail!("Xwayland binary not found – install xwayland package")
 }
+
+/// Xwayland died: drop every X11 window, mapped or stashed on another
+/// workspace, the way [`XwmHandler::unmapped_window`] would have.
+fn forget_x11_windows(state: &mut GameframeState) {
+    let mut windows: Vec<Window> = Vec::new();
+    for window in state.space.elements()
+        .chain(state.window_stack.iter())
+        .chain(state.workspaces.stashed().map(|(_, w, _)| w))
+    {
+        if window.x11_surface().is_some() && !windows.contains(window) {
+            windows.push(window.clone());
+        }
+    }
+    for window in windows {
+        if window.x11_surface().is_some_and(|x| x.is_override_redirect()) {
+            state.window_stack.remove(&window);
+            state.workspaces.remove_window(&window);
+            let outputs = state.space.outputs_for_element(&window);
+            state.space.unmap_elem(&window);
+            for output in &outputs {
+                state.queue_redraw(output);
+            }
+        } else {
+            state.window_gone(&window);
+        }
+    }
+}
+
+/// The space's window for `surface`, if it is mapped.
+fn window_for(state: &GameframeState, surface: &X11Surface) -> Option<Window> {
+    state.space.elements().find(|w| w.x11_surface() == Some(surface)).cloned()
+}
+
+/// Keyboard focus moved: X11 windows learn whether they are the active
+/// one, and the focused one is raised in the X stacking order so the
+/// server routes its input there.
+pub fn focus_changed(state: &mut GameframeState, focused: Option<&Window>) {
+    let Some(wm) = state.xwayland.wm.as_mut() else { return };
+    for window in state.space.elements() {
+        let Some(x11) = window.x11_surface().filter(|x| !x.is_override_redirect()) else { continue };
+        let active = Some(window) == focused;
+        let _ = x11.set_activated(active);
+        if active {
+            let _ = wm.raise_window(x11);
+        }
+    }
+}
+
+// ── Window manager ────────────────────────────────────────────────────────────
+
+impl XwmHandler for GameframeState {
+    fn xwm_state(&mut self, _xwm: XwmId) -> &mut X11Wm {
+        self.xwayland.wm.as_mut().expect("XWM event without a window manager")
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn map_window_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Err(e) = surface.set_mapped(true) {
+            warn!("X11 map: {e}");
+            return;
+        }
+        debug!(class = %surface.class(), title = %surface.title(), "X11 window mapped");
+        let window = Window::new_x11_window(surface.clone());
+        if surface.is_fullscreen() {
+            self.set_fullscreen(&window, None);
+        } else if surface.is_maximized() {
+            self.set_maximized(&window);
+        }
+        self.map_window(window);
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        let loc = surface.geometry().loc;
+        let window = Window::new_x11_window(surface);
+        self.space.map_element(window.clone(), loc, true);
+        for output in self.space.outputs_for_element(&window) {
+            self.queue_redraw(&output);
+        }
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        let window = window_for(self, &surface);
+        if surface.is_override_redirect() {
+            if let Some(window) = window {
+                let outputs = self.space.outputs_for_element(&window);
+                self.space.unmap_elem(&window);
+                for output in &outputs {
+                    self.queue_redraw(output);
+                }
+            }
+            return;
+        }
+        // Stashed on another workspace if it isn't in the space.
+        let window = window.or_else(|| {
+            self.window_stack.iter().find(|w| w.x11_surface() == Some(&surface)).cloned()
+        });
+        if let Some(window) = window {
+            self.window_gone(&window);
+        }
+        let _ = surface.set_mapped(false);
+    }
+
+    fn destroyed_window(&mut self, xwm: XwmId, surface: X11Surface) {
+        // Normally unmapped first; a client that dies may skip that.
+        let known = window_for(self, &surface).is_some()
+            || self.window_stack.iter().any(|w| w.x11_surface() == Some(&surface));
+        if known {
+            self.unmapped_window(xwm, surface);
+        }
+    }
+
+    /// Sizes are the client's to pick, as with Wayland windows; where the
+    /// window goes is not (override-redirect windows never ask).
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        _x: Option<i32>,
+        _y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        let mut geo = surface.geometry();
+        if let Some(loc) = window_for(self, &surface).and_then(|w| self.space.element_location(&w)) {
+            geo.loc = loc;
+        }
+        if let Some(w) = w { geo.size.w = w as i32; }
+        if let Some(h) = h { geo.size.h = h as i32; }
+        let _ = surface.configure(geo);
+    }
+
+    /// Override-redirect windows move themselves; follow them.
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        geometry: Rectangle<i32, Logical>,
+        _above: Option<u32>,
+    ) {
+        if !surface.is_override_redirect() { return; }
+        let Some(window) = window_for(self, &surface) else { return };
+        self.space.map_element(window, geometry.loc, false);
+        self.queue_redraw_all();
+    }
+
+    fn fullscreen_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Some(window) = window_for(self, &surface) {
+            self.set_fullscreen(&window, None);
+        }
+    }
+
+    fn unfullscreen_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Some(window) = window_for(self, &surface) {
+            self.unset_fullscreen(&window);
+        }
+    }
+
+    fn maximize_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Some(window) = window_for(self, &surface) {
+            self.set_maximized(&window);
+        }
+    }
+
+    fn unmaximize_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Some(window) = window_for(self, &surface) {
+            self.unset_maximized(&window);
+        }
+    }
+
+    // Windows move with Super + drag, for X11 clients as for Wayland ones.
+    fn resize_request(&mut self, _xwm: XwmId, _window: X11Surface, _button: u32, _edge: ResizeEdge) {}
+
+    fn move_request(&mut self, _xwm: XwmId, _window: X11Surface, _button: u32) {}
+}
+
+impl XWaylandShellHandler for GameframeState {
+    fn xwayland_shell_state(&mut self) -> &mut XWaylandShellState {
+        &mut self.xwayland_shell
+    }
+}
@@ -0,0 +1,424 @@
+//! In-process compositor and test client for the integration tests.
+//!
+//! [`Server`] is Gameframe on the headless backend, driven by hand instead
+//! of by `compositor::run`; [`Client`] is a bare `wayland-client` connected
+//! to it over a socket pair. Both live on the test thread and are pumped in
+//! turn, so nothing blocks and every test is deterministic up to the
+//! virtual output's vblank timer. Each protocol gets its own test file and
+//! binds only the globals it exercises.
+//!
+//! The headless backend needs a DRM render node, so every test using the
+//! harness is `#[ignore]`d and run with `cargo test -- --ignored`.
+//! [`Server::start`] fails the test when there is no render node.
+
+#![allow(dead_code)]
+
+use std::{
+    ffi::c_void,
+    fs::File,
+    io::ErrorKind,
+    os::{
+        fd::{AsFd, AsRawFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use gameframe_core::{
+    backlog, headless,
+    session::{BackendKind, SessionOptions},
+    state::{GameframeClientData, GameframeState},
+    Config,
+};
+use smithay::{
+    input::keyboard::XkbConfig,
+    reexports::{calloop::EventLoop, wayland_server::Display},
+};
+use wayland_client::{
+    backend::{protocol::ProtocolError, WaylandError},
+    delegate_noop,
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_callback::{self, WlCallback},
+        wl_compositor::WlCompositor,
+        wl_output::WlOutput,
+        wl_registry::{self, WlRegistry},
+        wl_shm::{self, WlShm},
+        wl_shm_pool::WlShmPool,
+        wl_surface::WlSurface,
+    },
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::{self, XdgSurface},
+    xdg_toplevel::{self, XdgToplevel},
+    xdg_wm_base::{self, XdgWmBase},
+};
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        zwlr_layer_surface_v1::{self, ZwlrLayerSurfaceV1},
+    },
+    screencopy::v1::client::{
+        zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+        zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1,
+    },
+};
+
+/// How long [`Client::wait_until`] waits before failing the test. Several
+/// vblanks of the 60 Hz virtual output.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long one pump lets the event loop sleep for timers.
+const POLL: Duration = Duration::from_millis(1);
+
+// ── Compositor ────────────────────────────────────────────────────────────────
+
+pub struct Server {
+    pub state:  GameframeState,
+    display:    Display<GameframeState>,
+    event_loop: EventLoop<'static, GameframeState>,
+}
+
+impl Server {
+    /// Gameframe with one 1920x1080@60 virtual output. Panics if the
+    /// headless backend can't come up here.
+    pub fn start() -> Self {
+        let event_loop: EventLoop<'static, GameframeState> = EventLoop::try_new().expect("event loop");
+        let mut display = Display::new().expect("Wayland display");
+
+        let mut config = Config::default();
+        // Nothing from the user's session leaks in, and windows map in place.
+        config.animations.enabled = false;
+        config.session.remember_geometry = false;
+        config.display.virtual_outputs = vec!["1920x1080@60".into()];
+
+        let mut state = GameframeState::new(&mut display, event_loop.handle(), config.clone(), "gameframe-test".into());
+        let opts = SessionOptions {
+            backend:      BackendKind::Headless,
+            gpu_vendor:   None,
+            drm_device:   None,
+            initial_exec: None,
+            config,
+            config_path:  None,
+        };
+        if let Err(e) = headless::init(&mut state, &opts) {
+            panic!("headless backend unavailable: {e:#}");
+        }
+        assert!(state.space.outputs().next().is_some(), "no virtual output could be created");
+        state.seat.add_keyboard(XkbConfig::default(), 200, 25).expect("keyboard");
+        state.seat.add_pointer();
+
+        Self { state, display, event_loop }
+    }
+
+    /// A new client connection, as if accepted on the compositor's socket.
+    fn connect(&mut self) -> UnixStream {
+        let (server, client) = UnixStream::pair().expect("socket pair");
+        self.state.display_handle
+            .insert_client(server, Arc::new(GameframeClientData::default()))
+            .expect("insert client");
+        client
+    }
+
+    /// Handle client requests, run due timers and idle callbacks (which is
+    /// where rendering happens), and flush events to clients.
+    pub fn dispatch(&mut self) {
+        self.display.dispatch_clients(&mut self.state).expect("dispatch clients");
+        self.event_loop.dispatch(Some(POLL), &mut self.state).expect("dispatch event loop");
+        self.state.popups.cleanup();
+        backlog::flush_clients(&mut self.state);
+    }
+}
+
+// ── Client ────────────────────────────────────────────────────────────────────
+
+/// What the client has been told so far.
+#[derive(Debug, Default)]
+pub struct ClientState {
+    /// `(name, interface, version)` of every advertised global.
+    pub globals:     Vec<(u32, String, u32)>,
+    pub shm_formats: Vec<wl_shm::Format>,
+    /// Configures received and acked, oldest first.
+    pub configures:  Vec<Configure>,
+    /// A toplevel or layer surface was told to close.
+    pub closed:      bool,
+    /// Frame callbacks that fired.
+    pub frames:      usize,
+    /// Buffers the compositor released.
+    pub released:    usize,
+    /// Events of the last screencopy frame.
+    pub screencopy:  Screencopy,
+    /// Toplevel size and states of the configure sequence in progress.
+    pending_size:    Option<(i32, i32)>,
+    pending_states:  Vec<xdg_toplevel::State>,
+    syncs_done:      u32,
+}
+
+/// A configure, from `xdg_toplevel` + `xdg_surface` or from a layer surface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Configure {
+    pub serial: u32,
+    /// 0 in either direction leaves that size to the client.
+    pub size:   (i32, i32),
+    /// Toplevel states (maximized, fullscreen, …); empty for layer surfaces.
+    pub states: Vec<xdg_toplevel::State>,
+}
+
+impl Configure {
+    pub fn has(&self, state: xdg_toplevel::State) -> bool {
+        self.states.contains(&state)
+    }
+}
+
+/// What a `zwlr_screencopy_frame_v1` was told.
+#[derive(Debug, Default)]
+pub struct Screencopy {
+    /// wl_shm buffer size offered: width, height, stride.
+    pub shm:    Option<(u32, u32, u32)>,
+    /// dmabuf size offered.
+    pub dmabuf: Option<(u32, u32)>,
+    /// Every buffer type was offered.
+    pub done:   bool,
+    /// Damage reported before `ready`.
+    pub damage: Vec<(u32, u32, u32, u32)>,
+    pub ready:  bool,
+    pub failed: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Callback {
+    Sync(u32),
+    Frame,
+}
+
+pub struct Client {
+    pub conn:  Connection,
+    pub qh:    QueueHandle<ClientState>,
+    pub state: ClientState,
+    queue:     EventQueue<ClientState>,
+    registry:  WlRegistry,
+    syncs:     u32,
+}
+
+impl Client {
+    /// Connect to `server` and receive its globals.
+    pub fn connect(server: &mut Server) -> Self {
+        let stream = server.connect();
+        stream.set_nonblocking(true).expect("non-blocking socket");
+        let conn     = Connection::from_socket(stream).expect("connect");
+        let queue    = conn.new_event_queue();
+        let qh       = queue.handle();
+        let registry = conn.display().get_registry(&qh, ());
+        let mut client = Self { conn, qh, state: ClientState::default(), queue, registry, syncs: 0 };
+        client.roundtrip(server);
+        client
+    }
+
+    /// Bind the global implementing `I`, at `version` or what the
+    /// compositor offers if that is lower.
+    pub fn bind<I>(&self, version: u32) -> I
+    where
+        I: Proxy + 'static,
+        ClientState: Dispatch<I, ()>,
+    {
+        let interface = I::interface().name;
+        let (name, _, offered) = self.state.globals.iter()
+            .find(|(_, i, _)| i == interface)
+            .unwrap_or_else(|| panic!("{interface} is not advertised"));
+        self.registry.bind(*name, version.min(*offered), &self.qh, ())
+    }
+
+    /// Send requests, read whatever events have arrived and handle them.
+    pub fn pump(&mut self) {
+        self.queue.flush().expect("flush");
+        if let Some(guard) = self.queue.prepare_read() {
+            match guard.read() {
+                Ok(_) => {}
+                Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => panic!("read events: {e}"),
+            }
+        }
+        self.queue.dispatch_pending(&mut self.state).expect("dispatch events");
+    }
+
+    /// Pump both ends until the compositor has handled every request sent
+    /// so far and the client every event that answered them.
+    pub fn roundtrip(&mut self, server: &mut Server) {
+        self.syncs += 1;
+        let id = self.syncs;
+        self.conn.display().sync(&self.qh, Callback::Sync(id));
+        self.wait_until(server, "roundtrip", |state| state.syncs_done >= id);
+    }
+
+    /// Pump both ends until `done` holds; fails the test after [`TIMEOUT`].
+    pub fn wait_until(&mut self, server: &mut Server, what: &str, done: impl Fn(&ClientState) -> bool) {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            self.pump();
+            if done(&self.state) { return; }
+            assert!(Instant::now() < deadline, "timed out waiting for {what}");
+            if let Some(e) = self.conn.protocol_error() {
+                panic!("protocol error while waiting for {what}: {e:?}");
+            }
+            server.dispatch();
+        }
+    }
+
+    /// Pump both ends until the compositor posts a protocol error, and
+    /// return it; fails the test after [`TIMEOUT`].
+    pub fn protocol_error(&mut self, server: &mut Server) -> ProtocolError {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            // Once the error is in, reading and dispatching fail with it.
+            let _ = self.queue.flush();
+            if let Some(guard) = self.queue.prepare_read() {
+                let _ = guard.read();
+            }
+            let _ = self.queue.dispatch_pending(&mut self.state);
+            if let Some(e) = self.conn.protocol_error() { return e; }
+            assert!(Instant::now() < deadline, "timed out waiting for a protocol error");
+            server.dispatch();
+        }
+    }
+
+    /// Ask for a frame callback on `surface`; `ClientState::frames` counts
+    /// the ones that fire.
+    pub fn frame(&self, surface: &WlSurface) {
+        surface.frame(&self.qh, Callback::Frame);
+    }
+
+    /// A `width`x`height` ARGB8888 buffer filled with `pixel`, in a pool of
+    /// its own.
+    pub fn shm_buffer(&self, shm: &WlShm, width: i32, height: i32, pixel: u32) -> WlBuffer {
+        let stride = width * 4;
+        let size   = (stride * height) as usize;
+        let fd = unsafe {
+            let fd = libc::memfd_create(c"gameframe-test".as_ptr(), libc::MFD_CLOEXEC);
+            assert!(fd >= 0, "memfd_create: {}", std::io::Error::last_os_error());
+            OwnedFd::from_raw_fd(fd)
+        };
+        let file = File::from(fd);
+        file.set_len(size as u64).expect("size shm file");
+        unsafe {
+            let map = libc::mmap(std::ptr::null_mut(), size, libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0);
+            assert_ne!(map, libc::MAP_FAILED, "mmap: {}", std::io::Error::last_os_error());
+            std::slice::from_raw_parts_mut(map as *mut u32, size / 4).fill(pixel);
+            libc::munmap(map as *mut c_void, size);
+        }
+        let pool   = shm.create_pool(file.as_fd(), size as i32, &self.qh, ());
+        let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, &self.qh, ());
+        pool.destroy();
+        buffer
+    }
+}
+
+// ── Event handling ────────────────────────────────────────────────────────────
+
+impl Dispatch<WlRegistry, ()> for ClientState {
+    fn event(state: &mut Self, _: &WlRegistry, event: wl_registry::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            wl_registry::Event::Global { name, interface, version } => state.globals.push((name, interface, version)),
+            wl_registry::Event::GlobalRemove { name } => state.globals.retain(|(n, _, _)| *n != name),
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<WlCallback, Callback> for ClientState {
+    fn event(state: &mut Self, _: &WlCallback, event: wl_callback::Event, data: &Callback, _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_callback::Event::Done { .. } = event {
+            match *data {
+                Callback::Sync(id) => state.syncs_done = state.syncs_done.max(id),
+                Callback::Frame    => state.frames += 1,
+            }
+        }
+    }
+}
+
+impl Dispatch<WlShm, ()> for ClientState {
+    fn event(state: &mut Self, _: &WlShm, event: wl_shm::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_shm::Event::Format { format: WEnum::Value(format) } = event {
+            state.shm_formats.push(format);
+        }
+    }
+}
+
+impl Dispatch<WlBuffer, ()> for ClientState {
+    fn event(state: &mut Self, _: &WlBuffer, event: wl_buffer::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let wl_buffer::Event::Release = event {
+            state.released += 1;
+        }
+    }
+}
+
+impl Dispatch<XdgWmBase, ()> for ClientState {
+    fn event(_: &mut Self, wm_base: &XdgWmBase, event: xdg_wm_base::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<XdgSurface, ()> for ClientState {
+    fn event(state: &mut Self, surface: &XdgSurface, event: xdg_surface::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            surface.ack_configure(serial);
+            let size   = state.pending_size.take().unwrap_or((0, 0));
+            let states = std::mem::take(&mut state.pending_states);
+            state.configures.push(Configure { serial, size, states });
+        }
+    }
+}
+
+impl Dispatch<XdgToplevel, ()> for ClientState {
+    fn event(state: &mut Self, _: &XdgToplevel, event: xdg_toplevel::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            xdg_toplevel::Event::Configure { width, height, states } => {
+                state.pending_size   = Some((width, height));
+                state.pending_states = states
+                    .chunks_exact(4)
+                    .filter_map(|b| xdg_toplevel::State::try_from(u32::from_ne_bytes([b[0], b[1], b[2], b[3]])).ok())
+                    .collect();
+            }
+            xdg_toplevel::Event::Close => state.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrLayerSurfaceV1, ()> for ClientState {
+    fn event(state: &mut Self, surface: &ZwlrLayerSurfaceV1, event: zwlr_layer_surface_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        match event {
+            zwlr_layer_surface_v1::Event::Configure { serial, width, height } => {
+                surface.ack_configure(serial);
+                state.configures.push(Configure { serial, size: (width as i32, height as i32), states: Vec::new() });
+            }
+            zwlr_layer_surface_v1::Event::Closed => state.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrScreencopyFrameV1, ()> for ClientState {
+    fn event(state: &mut Self, _: &ZwlrScreencopyFrameV1, event: zwlr_screencopy_frame_v1::Event, _: &(), _: &Connection, _: &QueueHandle<Self>) {
+        let copy = &mut state.screencopy;
+        match event {
+            zwlr_screencopy_frame_v1::Event::Buffer { width, height, stride, .. } => copy.shm = Some((width, height, stride)),
+            zwlr_screencopy_frame_v1::Event::LinuxDmabuf { width, height, .. } => copy.dmabuf = Some((width, height)),
+            zwlr_screencopy_frame_v1::Event::BufferDone => copy.done = true,
+            zwlr_screencopy_frame_v1::Event::Damage { x, y, width, height } => copy.damage.push((x, y, width, height)),
+            zwlr_screencopy_frame_v1::Event::Ready { .. } => copy.ready = true,
+            zwlr_screencopy_frame_v1::Event::Failed => copy.failed = true,
+            _ => {}
+        }
+    }
+}
+
+delegate_noop!(ClientState: WlCompositor);
+delegate_noop!(ClientState: WlShmPool);
+delegate_noop!(ClientState: ZwlrLayerShellV1);
+delegate_noop!(ClientState: ZwlrScreencopyManagerV1);
+delegate_noop!(ClientState: ignore WlOutput);
+delegate_noop!(ClientState: ignore WlSurface);
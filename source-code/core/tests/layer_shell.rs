@@ -0,0 +1,80 @@
+//! wlr-layer-shell: configure from anchors, exclusive zones, frame callbacks.
+
+mod common;
+
+use common::{Client, Server};
+use smithay::desktop::layer_map_for_output;
+use wayland_client::protocol::{wl_compositor::WlCompositor, wl_shm::WlShm};
+use wayland_protocols_wlr::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::Anchor,
+};
+
+/// Height of the test panel.
+const BAR: i32 = 32;
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn anchored_bar_is_sized_reserves_space_and_gets_frame_callbacks() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor      = client.bind(6);
+    let layer_shell: ZwlrLayerShellV1 = client.bind(4);
+    let shm: WlShm                    = client.bind(1);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let layer   = layer_shell.get_layer_surface(&surface, None, Layer::Top, "test-bar".into(), &client.qh, ());
+    layer.set_anchor(Anchor::Top | Anchor::Left | Anchor::Right);
+    layer.set_size(0, BAR as u32);
+    layer.set_exclusive_zone(BAR);
+    surface.commit();
+    client.wait_until(&mut server, "layer configure", |state| !state.configures.is_empty());
+
+    // Stretched across the 1920x1080 virtual output.
+    assert_eq!(client.state.configures[0].size, (1920, BAR));
+
+    let buffer = client.shm_buffer(&shm, 1920, BAR, 0xcc00_0000);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.damage_buffer(0, 0, 1920, BAR);
+    client.frame(&surface);
+    surface.commit();
+    client.wait_until(&mut server, "frame callback", |state| state.frames >= 1);
+
+    let output = server.state.space.outputs().next().unwrap().clone();
+    let map = layer_map_for_output(&output);
+    assert_eq!(map.layers().count(), 1);
+    assert_eq!(map.non_exclusive_zone().loc.y, BAR, "exclusive zone not reserved");
+    assert!(!client.state.closed);
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn destroyed_layer_surface_gives_its_space_back() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor      = client.bind(6);
+    let layer_shell: ZwlrLayerShellV1 = client.bind(4);
+    let shm: WlShm                    = client.bind(1);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let layer   = layer_shell.get_layer_surface(&surface, None, Layer::Bottom, "test-dock".into(), &client.qh, ());
+    layer.set_anchor(Anchor::Bottom | Anchor::Left | Anchor::Right);
+    layer.set_size(0, BAR as u32);
+    layer.set_exclusive_zone(BAR);
+    surface.commit();
+    client.wait_until(&mut server, "layer configure", |state| !state.configures.is_empty());
+    let buffer = client.shm_buffer(&shm, 1920, BAR, 0xff00_0000);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.commit();
+    client.roundtrip(&mut server);
+
+    let output = server.state.space.outputs().next().unwrap().clone();
+    assert_eq!(layer_map_for_output(&output).non_exclusive_zone().size.h, 1080 - BAR);
+
+    layer.destroy();
+    surface.destroy();
+    client.roundtrip(&mut server);
+    let map = layer_map_for_output(&output);
+    assert_eq!(map.layers().count(), 0);
+    assert_eq!(map.non_exclusive_zone().size.h, 1080);
+}
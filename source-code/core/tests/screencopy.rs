@@ -0,0 +1,110 @@
+//! wlr-screencopy: buffers offered for a virtual output, copies, bad
+//! buffers, and the damage of partial redraws.
+
+mod common;
+
+use common::{Client, Server};
+use wayland_client::protocol::{wl_compositor::WlCompositor, wl_output::WlOutput, wl_shm::WlShm};
+use wayland_protocols_wlr::{
+    layer_shell::v1::client::{
+        zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+        zwlr_layer_surface_v1::Anchor,
+    },
+    screencopy::v1::client::{zwlr_screencopy_frame_v1, zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1},
+};
+
+/// Height of the test panel.
+const BAR: i32 = 32;
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn virtual_output_is_copied_into_shm() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let manager: ZwlrScreencopyManagerV1 = client.bind(3);
+    let output: WlOutput                 = client.bind(4);
+    let shm: WlShm                       = client.bind(1);
+
+    let frame = manager.capture_output(0, &output, &client.qh, ());
+    client.wait_until(&mut server, "buffer types", |state| state.screencopy.done);
+    assert_eq!(client.state.screencopy.shm, Some((1920, 1080, 1920 * 4)));
+    assert_eq!(client.state.screencopy.dmabuf, Some((1920, 1080)));
+
+    let buffer = client.shm_buffer(&shm, 1920, 1080, 0);
+    frame.copy(&buffer);
+    client.wait_until(&mut server, "copy", |state| state.screencopy.ready || state.screencopy.failed);
+    assert!(client.state.screencopy.ready, "copy failed");
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn region_is_offered_and_a_buffer_of_another_size_is_an_error() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let manager: ZwlrScreencopyManagerV1 = client.bind(3);
+    let output: WlOutput                 = client.bind(4);
+    let shm: WlShm                       = client.bind(1);
+
+    let frame = manager.capture_output_region(0, &output, 10, 20, 64, 32, &client.qh, ());
+    client.wait_until(&mut server, "buffer types", |state| state.screencopy.done);
+    assert_eq!(client.state.screencopy.shm, Some((64, 32, 64 * 4)));
+
+    let buffer = client.shm_buffer(&shm, 32, 32, 0);
+    frame.copy(&buffer);
+    let error = client.protocol_error(&mut server);
+    assert_eq!(error.object_interface, "zwlr_screencopy_frame_v1");
+    assert_eq!(error.code, zwlr_screencopy_frame_v1::Error::InvalidBuffer as u32);
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn small_update_redraws_only_its_damage() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor         = client.bind(6);
+    let layer_shell: ZwlrLayerShellV1    = client.bind(4);
+    let shm: WlShm                       = client.bind(1);
+    let manager: ZwlrScreencopyManagerV1 = client.bind(3);
+    let output: WlOutput                 = client.bind(4);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let layer   = layer_shell.get_layer_surface(&surface, None, Layer::Top, "test-bar".into(), &client.qh, ());
+    layer.set_anchor(Anchor::Top | Anchor::Left | Anchor::Right);
+    layer.set_size(0, BAR as u32);
+    surface.commit();
+    client.wait_until(&mut server, "layer configure", |state| !state.configures.is_empty());
+    let buffer = client.shm_buffer(&shm, 1920, BAR, 0xff00_0000);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.damage_buffer(0, 0, 1920, BAR);
+    client.frame(&surface);
+    surface.commit();
+    client.wait_until(&mut server, "frame callback", |state| state.frames >= 1);
+
+    // Damage of the frame that shows an 8x8 update at `x` of the bar.
+    let mut update = |x: i32, pixel: u32| {
+        client.state.screencopy = Default::default();
+        let frame = manager.capture_output(0, &output, &client.qh, ());
+        client.wait_until(&mut server, "buffer types", |state| state.screencopy.done);
+        let copy = client.shm_buffer(&shm, 1920, 1080, 0);
+        frame.copy_with_damage(&copy);
+        let buffer = client.shm_buffer(&shm, 1920, BAR, pixel);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(x, 4, 8, 8);
+        surface.commit();
+        client.wait_until(&mut server, "damaged frame", |state| state.screencopy.ready || state.screencopy.failed);
+        assert!(client.state.screencopy.ready, "copy failed");
+        frame.destroy();
+        std::mem::take(&mut client.state.screencopy.damage)
+    };
+
+    // Each virtual output buffer is repainted with what changed since it
+    // was last drawn, two frames ago, so the first update still carries
+    // the full bar.
+    update(10, 0xff20_0000);
+    update(40, 0xff40_0000);
+    let damage = update(70, 0xff60_0000);
+    assert!(!damage.is_empty(), "no damage reported");
+    for (x, y, w, h) in damage {
+        assert!(x >= 40 && x + w <= 78 && y >= 4 && y + h <= 12, "damage {x},{y} {w}x{h} beyond the last two updates");
+    }
+}
@@ -0,0 +1,75 @@
+//! wl_shm: formats and buffers, without any shell.
+
+mod common;
+
+use common::{Client, Server};
+use wayland_client::protocol::{wl_compositor::WlCompositor, wl_shm::{self, WlShm}, wl_surface};
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn advertises_the_mandatory_formats() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let _shm: WlShm = client.bind(1);
+    client.roundtrip(&mut server);
+
+    for format in [wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888] {
+        assert!(client.state.shm_formats.contains(&format), "{format:?} missing from {:?}", client.state.shm_formats);
+    }
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn buffer_on_a_surface_without_role_is_accepted() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor = client.bind(6);
+    let shm: WlShm = client.bind(1);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let buffer  = client.shm_buffer(&shm, 64, 64, 0xff00_80ff);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.damage_buffer(0, 0, 64, 64);
+    surface.commit();
+    client.roundtrip(&mut server);
+
+    assert!(client.conn.protocol_error().is_none());
+    assert_eq!(server.state.space.elements().count(), 0, "a surface without a role was mapped");
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn buffer_not_a_multiple_of_the_scale_is_an_error() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor = client.bind(6);
+    let shm: WlShm = client.bind(1);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let buffer  = client.shm_buffer(&shm, 63, 64, 0xff00_80ff);
+    surface.set_buffer_scale(2);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.commit();
+
+    let error = client.protocol_error(&mut server);
+    assert_eq!(error.object_interface, "wl_surface");
+    assert_eq!(error.code, wl_surface::Error::InvalidSize as u32);
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn buffer_not_a_multiple_of_the_scale_is_tolerated_before_v6() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let compositor: WlCompositor = client.bind(5);
+    let shm: WlShm = client.bind(1);
+
+    let surface = compositor.create_surface(&client.qh, ());
+    let buffer  = client.shm_buffer(&shm, 63, 64, 0xff00_80ff);
+    surface.set_buffer_scale(2);
+    surface.attach(Some(&buffer), 0, 0);
+    surface.commit();
+    client.roundtrip(&mut server);
+
+    assert!(client.conn.protocol_error().is_none());
+}
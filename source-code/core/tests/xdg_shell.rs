@@ -0,0 +1,198 @@
+//! xdg-shell toplevels: configure, map, frame callbacks, unmap, maximize,
+//! fullscreen.
+
+mod common;
+
+use common::{Client, Configure, Server};
+use wayland_client::protocol::{wl_compositor::WlCompositor, wl_shm::WlShm, wl_surface::WlSurface};
+use wayland_protocols::xdg::shell::client::{
+    xdg_surface::XdgSurface,
+    xdg_toplevel::{State, XdgToplevel},
+    xdg_wm_base::XdgWmBase,
+};
+
+struct Toplevel {
+    surface:     WlSurface,
+    xdg_surface: XdgSurface,
+    toplevel:    XdgToplevel,
+}
+
+/// A toplevel that has made its initial (bufferless) commit.
+fn create_toplevel(client: &Client, title: &str) -> Toplevel {
+    let compositor: WlCompositor = client.bind(6);
+    let wm_base: XdgWmBase       = client.bind(6);
+    let surface     = compositor.create_surface(&client.qh, ());
+    let xdg_surface = wm_base.get_xdg_surface(&surface, &client.qh, ());
+    let toplevel    = xdg_surface.get_toplevel(&client.qh, ());
+    toplevel.set_title(title.into());
+    toplevel.set_app_id("gameframe.test".into());
+    surface.commit();
+    Toplevel { surface, xdg_surface, toplevel }
+}
+
+/// Attach a buffer of the configured size (or 640x480 if the client gets
+/// to choose) and ask for a frame callback.
+fn draw(client: &Client, toplevel: &Toplevel) {
+    let shm: WlShm = client.bind(1);
+    let (w, h) = match client.state.configures.last().map(|c| c.size) {
+        Some((w, h)) if w > 0 && h > 0 => (w, h),
+        _ => (640, 480),
+    };
+    let buffer = client.shm_buffer(&shm, w, h, 0xff20_2020);
+    toplevel.surface.attach(Some(&buffer), 0, 0);
+    toplevel.surface.damage_buffer(0, 0, w, h);
+    client.frame(&toplevel.surface);
+    toplevel.surface.commit();
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn initial_commit_gets_a_configure_and_does_not_map() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let _toplevel = create_toplevel(&client, "configure");
+    client.wait_until(&mut server, "initial configure", |state| !state.configures.is_empty());
+
+    assert_eq!(client.state.configures.len(), 1);
+    assert_eq!(server.state.space.elements().count(), 0, "mapped without a buffer");
+    assert_eq!(server.state.unmapped.len(), 1);
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn buffer_maps_the_window_and_frame_callbacks_arrive() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let toplevel = create_toplevel(&client, "map");
+    client.wait_until(&mut server, "initial configure", |state| !state.configures.is_empty());
+
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.elements().count(), 1, "window not mapped");
+    assert!(server.state.unmapped.is_empty());
+
+    client.wait_until(&mut server, "frame callback", |state| state.frames >= 1);
+
+    // Mapped windows keep getting them as they redraw.
+    draw(&client, &toplevel);
+    client.wait_until(&mut server, "second frame callback", |state| state.frames >= 2);
+    assert!(client.conn.protocol_error().is_none());
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn destroying_the_toplevel_unmaps_it() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let toplevel = create_toplevel(&client, "unmap");
+    client.wait_until(&mut server, "initial configure", |state| !state.configures.is_empty());
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.elements().count(), 1);
+
+    toplevel.toplevel.destroy();
+    toplevel.xdg_surface.destroy();
+    toplevel.surface.destroy();
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.elements().count(), 0, "window still mapped");
+}
+
+/// A 640x480 toplevel, drawn and mapped.
+fn mapped_toplevel(server: &mut Server, client: &mut Client, title: &str) -> Toplevel {
+    let toplevel = create_toplevel(client, title);
+    client.wait_until(server, "initial configure", |state| !state.configures.is_empty());
+    draw(client, &toplevel);
+    client.roundtrip(server);
+    toplevel
+}
+
+/// Wait for the configure answering the request just made.
+fn next_configure(server: &mut Server, client: &mut Client, what: &str) -> Configure {
+    let seen = client.state.configures.len();
+    client.wait_until(server, what, |state| state.configures.len() > seen);
+    client.state.configures.last().cloned().unwrap()
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn maximize_fills_the_output_and_unmaximize_restores_the_window() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let toplevel = mapped_toplevel(&mut server, &mut client, "maximize");
+    let window = server.state.space.elements().next().cloned().expect("window not mapped");
+    let before = server.state.space.element_geometry(&window).unwrap();
+
+    toplevel.toplevel.set_maximized();
+    let configure = next_configure(&mut server, &mut client, "maximized configure");
+    assert!(configure.has(State::Maximized));
+    assert_eq!(configure.size, (1920, 1080));
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    let geo = server.state.space.element_geometry(&window).unwrap();
+    assert_eq!((geo.loc.x, geo.loc.y, geo.size.w, geo.size.h), (0, 0, 1920, 1080));
+
+    toplevel.toplevel.unset_maximized();
+    let configure = next_configure(&mut server, &mut client, "unmaximized configure");
+    assert!(!configure.has(State::Maximized));
+    assert_eq!(configure.size, (640, 480));
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.element_geometry(&window), Some(before));
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn unmaximizing_while_fullscreen_keeps_the_geometry_from_before_maximize() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let toplevel = mapped_toplevel(&mut server, &mut client, "maximize fullscreen");
+    let window = server.state.space.elements().next().cloned().expect("window not mapped");
+    let before = server.state.space.element_geometry(&window).unwrap();
+
+    toplevel.toplevel.set_maximized();
+    next_configure(&mut server, &mut client, "maximized configure");
+    draw(&client, &toplevel);
+    toplevel.toplevel.set_fullscreen(None);
+    next_configure(&mut server, &mut client, "fullscreen configure");
+    draw(&client, &toplevel);
+
+    toplevel.toplevel.unset_maximized();
+    let configure = next_configure(&mut server, &mut client, "unmaximized configure");
+    assert!(configure.has(State::Fullscreen) && !configure.has(State::Maximized));
+    assert_eq!(configure.size, (1920, 1080), "left fullscreen early");
+
+    toplevel.toplevel.unset_fullscreen();
+    let configure = next_configure(&mut server, &mut client, "windowed configure");
+    assert!(!configure.has(State::Fullscreen) && !configure.has(State::Maximized), "still {:?}", configure.states);
+    assert_eq!(configure.size, (640, 480));
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.element_geometry(&window), Some(before));
+}
+
+#[test]
+#[ignore = "needs a DRM render node"]
+fn fullscreen_covers_the_output_and_unset_fullscreen_restores_the_window() {
+    let mut server = Server::start();
+    let mut client = Client::connect(&mut server);
+    let toplevel = mapped_toplevel(&mut server, &mut client, "fullscreen");
+    let window = server.state.space.elements().next().cloned().expect("window not mapped");
+    let before = server.state.space.element_geometry(&window).unwrap();
+
+    toplevel.toplevel.set_fullscreen(None);
+    let configure = next_configure(&mut server, &mut client, "fullscreen configure");
+    assert!(configure.has(State::Fullscreen));
+    assert_eq!(configure.size, (1920, 1080));
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    let geo = server.state.space.element_geometry(&window).unwrap();
+    assert_eq!((geo.loc.x, geo.loc.y, geo.size.w, geo.size.h), (0, 0, 1920, 1080));
+
+    toplevel.toplevel.unset_fullscreen();
+    let configure = next_configure(&mut server, &mut client, "windowed configure");
+    assert!(!configure.has(State::Fullscreen));
+    assert_eq!(configure.size, (640, 480));
+    draw(&client, &toplevel);
+    client.roundtrip(&mut server);
+    assert_eq!(server.state.space.element_geometry(&window), Some(before));
+}
@@ -16,14 +16,34 @@ pub struct GpuInfo {
     pub driver:      String,
     pub drm_node:    PathBuf,   // /dev/dri/cardN
     pub render_node: Option<String>, // /dev/dri/renderDN
+    pub pci_address: String,    // 0000:03:00.0
     pub caps:        GpuCapabilities,
 }
 
+impl GpuInfo {
+    /// Whether `spec` (lowercase) names this GPU: its PCI address, with or
+    /// without the domain, its `vendor:device` id, or one of its nodes.
+    fn matches(&self, spec: &str) -> bool {
+        self.pci_address == spec
+            || self.pci_address.split_once(':').is_some_and(|(_, short)| short == spec)
+            || format!("{:04x}:{:04x}", self.vendor_id, self.device_id) == spec
+            || self.drm_node.as_os_str() == spec
+            || self.render_node.as_deref() == Some(spec)
+    }
+}
+
 /// Detect primary (best) GPU.
 pub fn detect_primary() -> Result<Option<GpuInfo>> {
     Ok(detect_all()?.into_iter().next())
 }
 
+/// Find the GPU `spec` names: a PCI address (`0000:03:00.0` or `03:00.0`),
+/// a `vendor:device` id (`1002:67df`) or a `/dev/dri` node.
+pub fn find_gpu(spec: &str) -> Result<Option<GpuInfo>> {
+    let spec = spec.trim().to_lowercase();
+    Ok(detect_all()?.into_iter().find(|g| g.matches(&spec)))
+}
+
 /// Detect ALL supported GPUs, sorted by desirability.
 pub fn detect_all() -> Result<Vec<GpuInfo>> {
     let mut results = Vec::new();
@@ -55,9 +75,10 @@ pub fn detect_all() -> Result<Vec<GpuInfo>> {
         let render_node = find_render_node(&path);
         let name       = gpu_display_name(&path, vendor_id, device_id, &vendor);
         let caps       = probe_caps(&vendor, &driver, &drm_node);
+        let pci_address = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
 
-        debug!(%name, %vendor, %driver, ?drm_node, "GPU found");
-        results.push(GpuInfo { name, vendor, vendor_id, device_id, driver, drm_node, render_node, caps });
+        debug!(%name, %vendor, %driver, ?drm_node, %pci_address, "GPU found");
+        results.push(GpuInfo { name, vendor, vendor_id, device_id, driver, drm_node, render_node, pci_address, caps });
     }
 
     // Sort: AMD open-source first (best legacy support), then Intel, Nvidia
@@ -3,7 +3,7 @@ pub mod detect;
 pub mod intel;
 pub mod nvidia;
 
-pub use detect::{detect_all, detect_primary, GpuInfo};
+pub use detect::{detect_all, detect_primary, find_gpu, GpuInfo};
 
 // ── Vendor enum ───────────────────────────────────────────────────────────────
 
@@ -52,6 +52,7 @@ pub fn print_gpu_info() -> anyhow::Result<()> {
         println!("  GPU     : {}", gpu.name);
         println!("  Vendor  : {}", gpu.vendor);
         println!("  PCI     : {:04x}:{:04x}", gpu.vendor_id, gpu.device_id);
+        println!("  Slot    : {}", gpu.pci_address);
         println!("  Driver  : {}", gpu.driver);
         println!("  DRM node: {}", gpu.drm_node.display());
         println!("  Render  : {}", gpu.render_node.as_deref().unwrap_or("n/a").to_string());
@@ -56,6 +56,32 @@ pub enum BindingAction {
     ScreenshotOutput,
     LaunchApp(String),
     SwitchVt(u8),
+    /// Switch to workspace N (1-based).
+    SwitchWorkspace(u8),
+    /// Change the focused window's opacity by N tenths.
+    AdjustOpacity(i8),
+    /// Split the output between the two topmost windows, or leave split-screen.
+    ToggleSplit,
+    /// Re-read the config file.
+    ReloadConfig,
+    /// Turn the configured sticky/slow/bounce keys off or back on.
+    ToggleAccessibility,
+    /// Zoom in around the pointer, or back out.
+    ToggleMagnifier,
+    /// Switch to the next full-screen colour filter (or off).
+    CycleColorFilter,
+    /// Change display brightness by N configured steps.
+    Brightness(i8),
+    /// Start recording the focused output, or stop recording.
+    ToggleRecording,
+    /// Switch to the next latency / quality preset.
+    CyclePreset,
+    /// Show a calibration pattern on the focused output, or go back.
+    ToggleTestPattern,
+    /// Switch to the next calibration pattern.
+    CycleTestPattern,
+    /// Show or hide the FPS / frame-time counter.
+    ToggleFpsOverlay,
 }
 
 /// Default bindings matching Steam Gaming Mode conventions.
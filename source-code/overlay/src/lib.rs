@@ -68,11 +68,15 @@ impl Overlay {
 
     // ── Frame tick ────────────────────────────────────────────────────────────
 
+    /// Whether `tick` has anything to do: the HUD is shown or a toast is
+    /// still counting down.
+    pub fn is_active(&self) -> bool { self.visible || !self.toasts.is_empty() }
+
     /// Called once per compositor frame.
     /// Decrements toast TTLs and re-renders if visible.
     pub fn tick(&mut self) {
         self.toasts.retain_mut(|t| { t.ttl_frames = t.ttl_frames.saturating_sub(1); t.ttl_frames > 0 });
-        if self.visible || !self.toasts.is_empty() {
+        if self.is_active() {
             self.render();
         }
     }